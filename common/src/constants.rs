@@ -5,3 +5,23 @@ pub const DISCRIMINATOR_BYTES: usize = 8;
 pub const RESCUE_DELAY: u32 = 691200;
 
 pub const BASE_1E7: u64 = 10_000_000;
+
+/// Denominator for shares expressed in basis points
+pub const BASIS_POINTS: u64 = 10_000;
+
+/// Maximum number of parts an order can be split into, bounding the depth of its merkle tree
+pub const MAX_PARTS: u16 = 1024;
+
+/// Maximum number of escrows withdrawn in a single batch, bounded by the transaction size
+/// and the compute budget
+pub const MAX_BATCH_WITHDRAWALS: usize = 5;
+
+/// Maximum length in bytes of a memo attached to a withdrawal
+pub const MAX_MEMO_LEN: usize = 256;
+
+/// Seed of the per-mint account counting the value locked in the escrows of a program
+pub const TVL_STATS_SEED: &[u8] = b"tvl_stats";
+
+/// Hashlock algorithms, selected per order to match the HTLC hash of the counterpart chain
+pub const HASH_ALGO_KECCAK: u8 = 0;
+pub const HASH_ALGO_SHA256: u8 = 1;
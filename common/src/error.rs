@@ -44,4 +44,100 @@ pub enum EscrowError {
     InvalidPartialFill,
     #[msg("Inconsistent merkle proof trait")]
     InconsistentMerkleProofTrait,
+    #[msg("Invalid expiration time")]
+    InvalidExpirationTime,
+    #[msg("Unsupported token program")]
+    UnsupportedTokenProgram,
+    #[msg("Non-monotonic timelocks")]
+    NonMonotonicTimelocks,
+    #[msg("Missing part authorization")]
+    MissingPartAuthorization,
+    #[msg("Unauthorized part fill")]
+    UnauthorizedPartFill,
+    #[msg("Invalid safety deposit split")]
+    InvalidSafetyDepositSplit,
+    #[msg("Order partially filled")]
+    OrderPartiallyFilled,
+    #[msg("Empty merkle proof")]
+    EmptyMerkleProof,
+    #[msg("Dst amount below minimum")]
+    DstAmountBelowMinimum,
+    #[msg("Fill amount below minimum")]
+    FillAmountBelowMinimum,
+    #[msg("Invalid auction curve")]
+    InvalidAuctionCurve,
+    #[msg("Unauthorized lamport recipient")]
+    UnauthorizedLamportRecipient,
+    #[msg("Insufficient escrow balance")]
+    InsufficientEscrowBalance,
+    #[msg("Expiration before cancellation")]
+    ExpirationBeforeCancellation,
+    #[msg("Invalid hash algorithm")]
+    InvalidHashAlgo,
+    #[msg("Insufficient order balance")]
+    InsufficientOrderBalance,
+    #[msg("Timelock duration too large")]
+    TimelockDurationTooLarge,
+    #[msg("Invalid withdrawal batch")]
+    InvalidWithdrawalBatch,
+    #[msg("Fill deadline passed")]
+    FillDeadlinePassed,
+    #[msg("Dst amount overflow")]
+    DstAmountOverflow,
+    #[msg("Taker not allowed")]
+    TakerNotAllowed,
+    #[msg("Premium exceeds balance")]
+    PremiumExceedsBalance,
+    #[msg("Invalid memo")]
+    InvalidMemo,
+    #[msg("Missing memo program")]
+    MissingMemoProgram,
+    #[msg("Timelocks looser than order")]
+    TimelocksLooserThanOrder,
+    #[msg("Escrow ATA frozen")]
+    EscrowAtaFrozen,
+    #[msg("CPI not allowed")]
+    CpiNotAllowed,
+    #[msg("Final fill index mismatch")]
+    FinalFillIndexMismatch,
+    #[msg("Invalid rounding")]
+    InvalidRounding,
+    #[msg("Mint blocked")]
+    MintBlocked,
+    #[msg("Invalid creator ATA")]
+    InvalidCreatorAta,
+    #[msg("Invalid delegate")]
+    InvalidDelegate,
+    #[msg("Src cancellation in past")]
+    SrcCancellationInPast,
+    #[msg("Unauthorized")]
+    Unauthorized,
+    #[msg("Escrow not closed")]
+    EscrowNotClosed,
+    #[msg("Invalid dust amount")]
+    InvalidDustAmount,
+    #[msg("Inconsistent fill mode")]
+    InconsistentFillMode,
+    #[msg("Invalid auction timing")]
+    InvalidAuctionTiming,
+    #[msg("Order params mismatch")]
+    OrderParamsMismatch,
+    #[msg("Order fully filled")]
+    OrderFullyFilled,
+    #[msg("Unsupported mint extension")]
+    UnsupportedMintExtension,
+    #[msg("Maker cosign required")]
+    MakerCosignRequired,
+    #[msg("Withdrawal slots not elapsed")]
+    WithdrawalSlotsNotElapsed,
+    #[msg("Dst amount above maximum")]
+    DstAmountAboveMaximum,
+    #[msg("Missing tvl stats")]
+    MissingTvlStats,
+    #[msg("Tvl accounting error")]
+    TvlAccountingError,
+    #[msg("Withheld fees not harvested")]
+    WithheldFeesNotHarvested,
+    #[msg("Dst params mismatch")]
+    DstParamsMismatch,
 }
@@ -1,19 +1,56 @@
 use anchor_lang::prelude::*;
 use anchor_lang::system_program;
 
+use anchor_spl::memo::{build_memo, BuildMemo, Memo};
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{
+        permanent_delegate::PermanentDelegate,
+        transfer_fee::{TransferFeeAmount, TransferFeeConfig},
+        BaseStateWithExtensions, StateWithExtensions,
+    },
+};
 use anchor_spl::token_interface::{
     close_account, transfer_checked, CloseAccount, Mint, TokenAccount, TokenInterface,
     TransferChecked,
 };
 
+use crate::constants::{BASIS_POINTS, MAX_MEMO_LEN};
 use crate::error::EscrowError;
 use crate::utils;
 
+/// Logs the memo through the SPL Memo program, so that it accompanies the token transfer of the
+/// same instruction. The memo has to be valid UTF-8 of at most `MAX_MEMO_LEN` bytes.
+pub fn attach_memo<'info>(
+    memo: Option<&[u8]>,
+    memo_program: Option<&Program<'info, Memo>>,
+) -> Result<()> {
+    if let Some(memo) = memo {
+        require!(
+            memo.len() <= MAX_MEMO_LEN && std::str::from_utf8(memo).is_ok(),
+            EscrowError::InvalidMemo
+        );
+        let memo_program = memo_program.ok_or(EscrowError::MissingMemoProgram)?;
+
+        build_memo(
+            CpiContext::new(memo_program.to_account_info(), BuildMemo {}),
+            memo,
+        )?;
+    }
+    Ok(())
+}
+
+/// Rescues tokens from the escrow ATA to the recipient ATA. The rent of the ATA, if closed, goes
+/// to the rent recipient when given and to the recipient otherwise.
+///
+/// A frozen escrow ATA fails with `EscrowAtaFrozen`. Only the freeze authority of the mint can
+/// thaw it, so the operator has to ask the issuer to thaw the account and retry the rescue.
 pub fn rescue_funds<'info>(
     escrow: &AccountInfo<'info>,
     rescue_start: Option<u32>,
     escrow_ata: &InterfaceAccount<'info, TokenAccount>,
     recipient: &AccountInfo<'info>,
+    rent_recipient: Option<&AccountInfo<'info>>,
     recipient_ata: &InterfaceAccount<'info, TokenAccount>,
     mint: &InterfaceAccount<'info, Mint>,
     token_program: &Interface<'info, TokenInterface>,
@@ -28,6 +65,9 @@ pub fn rescue_funds<'info>(
         );
     }
 
+    // The token program would reject the transfer with an opaque error
+    require!(!escrow_ata.is_frozen(), EscrowError::EscrowAtaFrozen);
+
     // Transfer tokens from escrow to recipient
     uni_transfer(
         &UniTransferParams::TokenTransfer {
@@ -43,15 +83,13 @@ pub fn rescue_funds<'info>(
 
     if rescue_amount == escrow_ata.amount {
         // Close the escrow_ata account
-        close_account(CpiContext::new_with_signer(
-            token_program.to_account_info(),
-            CloseAccount {
-                account: escrow_ata.to_account_info(),
-                destination: recipient.to_account_info(),
-                authority: escrow.to_account_info(),
-            },
-            &[seeds],
-        ))?;
+        close_token_ata(
+            &escrow_ata.to_account_info(),
+            rent_recipient.unwrap_or(recipient),
+            escrow,
+            token_program,
+            seeds,
+        )?;
     }
 
     Ok(())
@@ -153,18 +191,135 @@ pub fn withdraw_and_close_token_ata<'info>(
     )?;
 
     // Close the escrow_ata account
+    close_token_ata(
+        &escrow_ata.to_account_info(),
+        rent_recipient,
+        authority,
+        token_program,
+        seeds,
+    )
+}
+
+/// Closes a token account owned by a program PDA. Token-2022 accounts holding withheld
+/// transfer fees cannot be closed. Harvesting the fees writes to the mint, so it is left to a
+/// separate, permissionless `HarvestWithheldTokensToMint` instruction of the token program ahead
+/// of the closing one, which keeps the mint read-only for every other escrow transaction.
+pub fn close_token_ata<'info>(
+    token_ata: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    token_program: &Interface<'info, TokenInterface>,
+    seeds: &[&[u8]],
+) -> Result<()> {
+    if token_program.key() == spl_token_2022::ID {
+        require!(
+            get_withheld_fees(token_ata)? == 0,
+            EscrowError::WithheldFeesNotHarvested
+        );
+    }
+
     close_account(CpiContext::new_with_signer(
         token_program.to_account_info(),
         CloseAccount {
-            account: escrow_ata.to_account_info(),
-            destination: rent_recipient.to_account_info(),
+            account: token_ata.clone(),
+            destination: destination.clone(),
             authority: authority.clone(),
         },
         &[seeds],
-    ))?;
+    ))
+}
+
+/// Returns the transfer fees withheld in a Token-2022 account, or 0 if the mint
+/// has no transfer fee extension.
+fn get_withheld_fees(token_ata: &AccountInfo) -> Result<u64> {
+    let data = token_ata.try_borrow_data()?;
+    let account = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&data)?;
+    Ok(account
+        .get_extension::<TransferFeeAmount>()
+        .map_or(0, |fee_amount| u64::from(fee_amount.withheld_amount)))
+}
+
+/// Verifies that the escrow ATA still holds the amount it was funded with, so that an account
+/// drained by an exotic mint fails with a clear error rather than an under-delivered payout or a
+/// failed CPI.
+pub fn verify_escrow_balance(held_amount: u64, funded_amount: u64) -> Result<()> {
+    require!(
+        held_amount >= funded_amount,
+        EscrowError::InsufficientEscrowBalance
+    );
+    Ok(())
+}
+
+/// Counts the amount of a new escrow in the value locked for its mint if the config tracks it.
+/// Returns whether the escrow is counted, so that its resolution is deducted even if the tracking
+/// is disabled meanwhile.
+pub fn lock_tvl(track_tvl: bool, total_locked: Option<&mut u64>, amount: u64) -> Result<bool> {
+    if !track_tvl {
+        return Ok(false);
+    }
+
+    let total_locked = total_locked.ok_or(EscrowError::MissingTvlStats)?;
+    *total_locked = total_locked
+        .checked_add(amount)
+        .ok_or(EscrowError::TvlAccountingError)?;
+    Ok(true)
+}
+
+/// Deducts the amount of a withdrawn or cancelled escrow from the value locked for its mint if
+/// the escrow was counted on creation.
+pub fn unlock_tvl(tvl_tracked: bool, total_locked: Option<&mut u64>, amount: u64) -> Result<()> {
+    if !tvl_tracked {
+        return Ok(());
+    }
+
+    let total_locked = total_locked.ok_or(EscrowError::MissingTvlStats)?;
+    *total_locked = total_locked
+        .checked_sub(amount)
+        .ok_or(EscrowError::TvlAccountingError)?;
     Ok(())
 }
 
+/// Returns the amount a transfer of `amount` credits to the recipient, that is net of the fee
+/// withheld at the current epoch if the mint is a Token-2022 mint with the transfer fee extension.
+pub fn get_amount_net_of_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    let mint = mint.to_account_info();
+    if mint.owner != &spl_token_2022::ID {
+        return Ok(amount);
+    }
+    let data = mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    let Ok(transfer_fee_config) = mint.get_extension::<TransferFeeConfig>() else {
+        return Ok(amount);
+    };
+    let fee = transfer_fee_config
+        .calculate_epoch_fee(Clock::get()?.epoch, amount)
+        .ok_or(EscrowError::InvalidAmount)?;
+    Ok(amount - fee)
+}
+
+/// Returns whether the mint is a Token-2022 mint with the transfer fee extension.
+pub fn has_transfer_fee(mint: &InterfaceAccount<Mint>) -> Result<bool> {
+    let mint = mint.to_account_info();
+    if mint.owner != &spl_token_2022::ID {
+        return Ok(false);
+    }
+    let data = mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    Ok(mint.get_extension::<TransferFeeConfig>().is_ok())
+}
+
+/// Returns whether the mint is a Token-2022 mint with the permanent delegate extension. The
+/// delegate can move tokens out of any account of the mint, escrow ATAs included.
+pub fn has_permanent_delegate(mint: &InterfaceAccount<Mint>) -> Result<bool> {
+    let mint = mint.to_account_info();
+    if mint.owner != &spl_token_2022::ID {
+        return Ok(false);
+    }
+    let data = mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    Ok(mint.get_extension::<PermanentDelegate>().is_ok())
+}
+
 pub fn process_payout<'info>(
     mint: &InterfaceAccount<'info, Mint>,
     asset_is_native: bool,
@@ -181,15 +336,13 @@ pub fn process_payout<'info>(
         // Using escrow pda as an intermediate account to transfer native tokens
         // the leftover lamports from ata's rent will be transferred to the rent recipient
         // after closing the escrow account
-        close_account(CpiContext::new_with_signer(
-            token_program.to_account_info(),
-            CloseAccount {
-                account: escrow_ata.to_account_info(),
-                destination: escrow.to_account_info(),
-                authority: escrow.to_account_info(),
-            },
-            &[&seeds],
-        ))?;
+        close_token_ata(
+            &escrow_ata.to_account_info(),
+            escrow,
+            escrow,
+            token_program,
+            &seeds,
+        )?;
 
         // Transfer the native tokens from escrow pda to recipient
         escrow.sub_lamports(escrow_amount)?;
@@ -210,3 +363,31 @@ pub fn process_payout<'info>(
 
     Ok(())
 }
+
+/// Transfers the safety deposit from the escrow to the recipient. If a secondary recipient is
+/// provided, the recipient receives `split_bps` of the deposit rounded down and the secondary
+/// recipient receives the rest.
+pub fn distribute_safety_deposit<'info>(
+    escrow: &AccountInfo<'info>,
+    safety_deposit: u64,
+    recipient: &AccountInfo<'info>,
+    split: Option<(&AccountInfo<'info>, u16)>,
+) -> Result<()> {
+    escrow.sub_lamports(safety_deposit)?;
+
+    if let Some((secondary_recipient, split_bps)) = split {
+        require!(
+            split_bps as u64 <= BASIS_POINTS,
+            EscrowError::InvalidSafetyDepositSplit
+        );
+        let recipient_share =
+            (safety_deposit as u128 * split_bps as u128 / BASIS_POINTS as u128) as u64;
+
+        recipient.add_lamports(recipient_share)?;
+        secondary_recipient.add_lamports(safety_deposit - recipient_share)?;
+    } else {
+        recipient.add_lamports(safety_deposit)?;
+    }
+
+    Ok(())
+}
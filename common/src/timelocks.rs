@@ -1,9 +1,12 @@
 use anchor_lang::prelude::*;
 use primitive_types::U256;
 
+use crate::error::EscrowError;
+
 #[derive(Clone, Copy)]
 pub struct Timelocks(pub U256);
 
+#[derive(Clone, Copy)]
 #[repr(u8)]
 pub enum Stage {
     SrcWithdrawal = 0,
@@ -13,8 +16,38 @@ pub enum Stage {
     DstWithdrawal = 4,
     DstPublicWithdrawal = 5,
     DstCancellation = 6,
+    /// Dst-only alias of the `SrcPublicCancellation` slot, as all the slots below `deployed_at`
+    /// are taken. It must not be used with the timelocks of src escrows.
+    DstPublicCancellation = 7,
+}
+
+impl Stage {
+    /// Index of the 32-bit slot holding the stage offset. The dst public cancellation is stored
+    /// in the slot of the src public cancellation, which is never used by dst escrows.
+    fn slot(self) -> usize {
+        match self {
+            Stage::DstPublicCancellation => Stage::SrcPublicCancellation as usize,
+            stage => stage as usize,
+        }
+    }
 }
 
+/// Stages of the source chain escrow in the order they must start
+pub const SRC_STAGES: [Stage; 4] = [
+    Stage::SrcWithdrawal,
+    Stage::SrcPublicWithdrawal,
+    Stage::SrcCancellation,
+    Stage::SrcPublicCancellation,
+];
+
+/// Stages of the destination chain escrow in the order they must start
+pub const DST_STAGES: [Stage; 4] = [
+    Stage::DstWithdrawal,
+    Stage::DstPublicWithdrawal,
+    Stage::DstCancellation,
+    Stage::DstPublicCancellation,
+];
+
 const DEPLOYED_AT_OFFSET: usize = 224;
 const STAGE_BIT_SIZE: usize = 32;
 const DEPLOYED_AT_MASK: U256 = U256([0, 0, 0, 0xffffffff00000000]);
@@ -38,12 +71,73 @@ impl Timelocks {
     }
 
     pub fn get(self, stage: Stage) -> std::result::Result<u32, ProgramError> {
-        let shift = (stage as usize) * STAGE_BIT_SIZE;
         let deployed_at = (self.0 >> DEPLOYED_AT_OFFSET).as_u32();
-        let delta = ((self.0 >> shift) & U256::from(u32::MAX)).as_u32();
         let result = deployed_at
-            .checked_add(delta)
+            .checked_add(self.delta(stage))
             .ok_or(ProgramError::ArithmeticOverflow)?;
         Ok(result)
     }
+
+    /// Returns whether the withdrawal, public withdrawal, cancellation and public cancellation
+    /// of an escrow with the given stages (`SRC_STAGES` or `DST_STAGES`) are open at `now`,
+    /// with the same bounds as the escrow instructions use.
+    pub fn get_open_stages(
+        self,
+        stages: &[Stage; 4],
+        now: u32,
+    ) -> std::result::Result<[bool; 4], ProgramError> {
+        let cancellation_start = self.get(stages[2])?;
+        Ok([
+            now >= self.get(stages[0])? && now < cancellation_start,
+            now >= self.get(stages[1])? && now < cancellation_start,
+            now >= cancellation_start,
+            now >= self.get(stages[3])?,
+        ])
+    }
+
+    /// Checks that each of the given stages starts strictly after the previous one.
+    /// Stage offsets are compared relative to `deployed_at`, so this never overflows.
+    pub fn validate_monotonic(self, stages: &[Stage]) -> Result<()> {
+        for pair in stages.windows(2) {
+            require!(
+                self.delta(pair[0]) < self.delta(pair[1]),
+                EscrowError::NonMonotonicTimelocks
+            );
+        }
+        Ok(())
+    }
+
+    /// Checks that each of the given stages can be computed for an escrow deployed as late as
+    /// `latest_deployed_at`, so that overflowing durations are rejected before funds are locked.
+    pub fn validate_durations(self, stages: &[Stage], latest_deployed_at: u32) -> Result<()> {
+        for &stage in stages {
+            require!(
+                latest_deployed_at.checked_add(self.delta(stage)).is_some(),
+                EscrowError::TimelockDurationTooLarge
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns the timelocks with the given stages starting the given durations after
+    /// deployment instead. A stage may only be moved earlier, so that the result is never looser
+    /// than these timelocks.
+    pub fn tighten(self, stages: &[Stage], durations: &[u32]) -> Result<Self> {
+        let mut tightened = self.0;
+        for (&stage, &duration) in stages.iter().zip(durations) {
+            require!(
+                duration <= self.delta(stage),
+                EscrowError::TimelocksLooserThanOrder
+            );
+            let shift = stage.slot() * STAGE_BIT_SIZE;
+            tightened =
+                (tightened & !(U256::from(u32::MAX) << shift)) | (U256::from(duration) << shift);
+        }
+        Ok(Self(tightened))
+    }
+
+    fn delta(self, stage: Stage) -> u32 {
+        let shift = stage.slot() * STAGE_BIT_SIZE;
+        ((self.0 >> shift) & U256::from(u32::MAX)).as_u32()
+    }
 }
@@ -1,4 +1,7 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{hash, keccak};
+
+use crate::constants::{HASH_ALGO_KECCAK, HASH_ALGO_SHA256};
 
 pub fn get_current_timestamp() -> Result<u32> {
     // 'unix_timestamp' has type i64, but the timestamp values
@@ -7,3 +10,39 @@ pub fn get_current_timestamp() -> Result<u32> {
     // will be reached at 2106.
     Ok(Clock::get()?.unix_timestamp as u32)
 }
+
+pub fn is_valid_hash_algo(hash_algo: u8) -> bool {
+    hash_algo == HASH_ALGO_KECCAK || hash_algo == HASH_ALGO_SHA256
+}
+
+/// Hashes the concatenated values with the given hashlock algorithm, keccak unless sha256 is
+/// selected.
+pub fn hashv_with_algo(vals: &[&[u8]], hash_algo: u8) -> [u8; 32] {
+    if hash_algo == HASH_ALGO_SHA256 {
+        hash::hashv(vals).to_bytes()
+    } else {
+        keccak::hashv(vals).to_bytes()
+    }
+}
+
+/// Checks that the hashlock is the hash of the secret under the given algorithm. The secret is
+/// taken as a fixed-size array so that exactly 32 bytes are hashed regardless of how the
+/// instruction received it.
+pub fn is_valid_secret(secret: &[u8; 32], hashlock: &[u8; 32], hash_algo: u8) -> bool {
+    hashv_with_algo(&[secret], hash_algo) == *hashlock
+}
+
+/// Combines the hash of the src order parameters with the terms of the dst escrow committed to
+/// by the maker. The dst program recomputes it to check the escrow against the signed order.
+pub fn get_order_hash_with_dst_terms(
+    order_params_hash: &[u8; 32],
+    dst_token: &[u8; 32],
+    dst_safety_deposit: u128,
+) -> [u8; 32] {
+    keccak::hashv(&[
+        order_params_hash,
+        dst_token,
+        &dst_safety_deposit.to_be_bytes(),
+    ])
+    .to_bytes()
+}
@@ -0,0 +1,77 @@
+use anchor_lang::prelude::AccountInfo;
+use solana_program::{
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program::{get_return_data, invoke, set_return_data},
+    pubkey::Pubkey,
+};
+use solana_program_runtime::invoke_context::BuiltinFunctionWithContext;
+use solana_program_test::processor;
+use solana_sdk::{signer::keypair::Keypair, transaction::Transaction};
+
+use crate::helpers::TestStateBase;
+use crate::wrap_entry;
+
+pub const CPI_WRAPPER_PROGRAM_ID: Pubkey = Pubkey::new_from_array([0xc9; 32]);
+
+pub fn get_program_cpi_wrapper_spec() -> (Pubkey, Option<BuiltinFunctionWithContext>) {
+    (CPI_WRAPPER_PROGRAM_ID, wrap_entry!(cpi_wrapper))
+}
+
+// Invokes the program passed as the first account with the remaining accounts and the
+// instruction data as is, so that the callee runs as an inner instruction. The return data
+// of the callee is read back and returned as the wrapper's own
+fn cpi_wrapper<'a>(_: &Pubkey, accounts: &'a [AccountInfo<'a>], data: &[u8]) -> ProgramResult {
+    let instruction = Instruction {
+        program_id: *accounts[0].key,
+        accounts: accounts[1..]
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: *account.key,
+                is_signer: account.is_signer,
+                is_writable: account.is_writable,
+            })
+            .collect(),
+        data: data.to_vec(),
+    };
+    invoke(&instruction, accounts)?;
+
+    if let Some((program_id, data)) = get_return_data() {
+        if program_id == instruction.program_id {
+            set_return_data(&data);
+        }
+    }
+    Ok(())
+}
+
+/// Rebuilds a single instruction transaction to run its instruction through the CPI wrapper
+pub fn wrap_in_cpi<T, S>(
+    test_state: &TestStateBase<T, S>,
+    transaction: &Transaction,
+    signers: &[&Keypair],
+) -> Transaction {
+    let message = &transaction.message;
+    let instruction = &message.instructions[0];
+    let program_id = message.account_keys[instruction.program_id_index as usize];
+    let accounts = std::iter::once(AccountMeta::new_readonly(program_id, false))
+        .chain(instruction.accounts.iter().map(|&index| {
+            let index = index as usize;
+            AccountMeta {
+                pubkey: message.account_keys[index],
+                is_signer: message.is_signer(index),
+                is_writable: message.is_maybe_writable(index, None),
+            }
+        }))
+        .collect();
+    let wrapped_instruction = Instruction {
+        program_id: CPI_WRAPPER_PROGRAM_ID,
+        accounts,
+        data: instruction.data.clone(),
+    };
+    Transaction::new_signed_with_payer(
+        &[wrapped_instruction],
+        message.account_keys.first(),
+        signers,
+        test_state.context.last_blockhash,
+    )
+}
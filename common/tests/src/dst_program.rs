@@ -1,5 +1,10 @@
-use crate::{helpers::*, whitelist::get_whitelist_access_address};
+use crate::{
+    helpers::*,
+    whitelist::{get_config_address, get_whitelist_access_address, set_track_tvl},
+};
 use anchor_lang::prelude::AccountInfo;
+use anchor_lang::AccountDeserialize;
+use common::utils::get_order_hash_with_dst_terms;
 use solana_program_runtime::invoke_context::BuiltinFunctionWithContext;
 use solana_sdk::{signature::Signer, signer::keypair::Keypair, transaction::Transaction};
 
@@ -7,7 +12,9 @@ use crate::wrap_entry;
 use anchor_lang::InstructionData;
 use solana_program_test::processor;
 
-use anchor_spl::associated_token::ID as spl_associated_token_id;
+use anchor_spl::associated_token::{
+    get_associated_token_address_with_program_id, ID as spl_associated_token_id,
+};
 
 use solana_program::{
     instruction::{AccountMeta, Instruction},
@@ -34,28 +41,45 @@ impl<S: TokenVariant> EscrowVariant<S> for DstProgram {
         escrow_ata: &Pubkey,
         withdrawer: &Keypair,
     ) -> Transaction {
+        let (secondary_recipient, safety_deposit_split_bps) = get_safety_deposit_split_accounts(
+            test_state.test_arguments.safety_deposit_split,
+            cross_chain_escrow_dst::id(),
+        );
+        let reward_recipient = test_state
+            .test_arguments
+            .reward_recipient
+            .unwrap_or(cross_chain_escrow_dst::id());
         let instruction_data =
             InstructionData::data(&cross_chain_escrow_dst::instruction::PublicWithdraw {
                 secret: test_state.secret,
+                safety_deposit_split_bps,
+                memo: test_state.test_arguments.memo.clone(),
             });
 
-        let (_, taker_ata) = find_user_ata(test_state);
+        let recipient_ata = get_recipient_ata(test_state);
         let (whitelist_access, _) = get_whitelist_access_address(&withdrawer.pubkey());
 
         let instruction: Instruction = Instruction {
             program_id: cross_chain_escrow_dst::id(),
             accounts: vec![
                 AccountMeta::new(test_state.maker_wallet.keypair.pubkey(), false),
-                AccountMeta::new(test_state.taker_wallet.keypair.pubkey(), false),
+                AccountMeta::new(get_recipient(test_state), false),
                 AccountMeta::new(withdrawer.pubkey(), true),
                 AccountMeta::new_readonly(whitelist_access, false),
+                secondary_recipient,
+                AccountMeta::new(reward_recipient, false),
                 AccountMeta::new_readonly(test_state.token, false),
                 AccountMeta::new(*escrow, false),
                 AccountMeta::new(*escrow_ata, false),
-                AccountMeta::new(taker_ata, false),
+                AccountMeta::new(recipient_ata, false),
                 AccountMeta::new_readonly(spl_associated_token_id, false),
                 AccountMeta::new_readonly(S::get_token_program_id(), false),
                 AccountMeta::new_readonly(system_program_id, false),
+                get_memo_program_account(
+                    &test_state.test_arguments.memo,
+                    cross_chain_escrow_dst::id(),
+                ),
+                get_tvl_stats_account(test_state, cross_chain_escrow_dst::id()),
             ],
             data: instruction_data,
         };
@@ -76,22 +100,30 @@ impl<S: TokenVariant> EscrowVariant<S> for DstProgram {
         let instruction_data =
             InstructionData::data(&cross_chain_escrow_dst::instruction::Withdraw {
                 secret: test_state.secret,
+                memo: test_state.test_arguments.memo.clone(),
             });
 
-        let (_, taker_ata) = find_user_ata(test_state);
+        let recipient_ata = get_recipient_ata(test_state);
 
         let instruction: Instruction = Instruction {
             program_id: cross_chain_escrow_dst::id(),
             accounts: vec![
                 AccountMeta::new(test_state.maker_wallet.keypair.pubkey(), true),
-                AccountMeta::new(test_state.taker_wallet.keypair.pubkey(), false),
+                AccountMeta::new(get_recipient(test_state), false),
                 AccountMeta::new_readonly(test_state.token, false),
                 AccountMeta::new(*escrow, false),
                 AccountMeta::new(*escrow_ata, false),
-                AccountMeta::new(taker_ata, false),
+                AccountMeta::new(recipient_ata, false),
+                // No destination token account, the tokens are sent to the recipient ATA
+                AccountMeta::new_readonly(cross_chain_escrow_dst::id(), false),
                 AccountMeta::new_readonly(spl_associated_token_id, false),
                 AccountMeta::new_readonly(S::get_token_program_id(), false),
                 AccountMeta::new_readonly(system_program_id, false),
+                get_memo_program_account(
+                    &test_state.test_arguments.memo,
+                    cross_chain_escrow_dst::id(),
+                ),
+                get_tvl_stats_account(test_state, cross_chain_escrow_dst::id()),
             ],
             data: instruction_data,
         };
@@ -122,8 +154,10 @@ impl<S: TokenVariant> EscrowVariant<S> for DstProgram {
                 AccountMeta::new(*escrow, false),
                 AccountMeta::new(*escrow_ata, false),
                 AccountMeta::new(maker_ata, false),
+                AccountMeta::new_readonly(spl_associated_token_id, false),
                 AccountMeta::new_readonly(S::get_token_program_id(), false),
                 AccountMeta::new_readonly(system_program_id, false),
+                get_tvl_stats_account(test_state, cross_chain_escrow_dst::id()),
             ],
             data: instruction_data,
         };
@@ -144,13 +178,14 @@ impl<S: TokenVariant> EscrowVariant<S> for DstProgram {
         let instruction_data =
             InstructionData::data(&cross_chain_escrow_dst::instruction::Create {
                 amount: test_state.test_arguments.escrow_amount,
-                order_hash: test_state.order_hash.to_bytes(),
+                order_hash: <Self as EscrowVariant<S>>::get_escrow_order_hash(test_state),
                 hashlock: test_state.hashlock.to_bytes(),
-                recipient: test_state.taker_wallet.keypair.pubkey(),
+                recipient: get_recipient(test_state),
                 safety_deposit: test_state.test_arguments.safety_deposit,
                 timelocks: test_state.test_arguments.dst_timelocks.get_timelocks(),
                 src_cancellation_timestamp: test_state.test_arguments.src_cancellation_timestamp,
                 asset_is_native: test_state.test_arguments.asset_is_native,
+                order_params_hash: test_state.order_hash.to_bytes(),
             });
 
         let (maker_ata, _) = find_user_ata(test_state);
@@ -160,6 +195,7 @@ impl<S: TokenVariant> EscrowVariant<S> for DstProgram {
             accounts: vec![
                 AccountMeta::new(test_state.maker_wallet.keypair.pubkey(), true),
                 AccountMeta::new_readonly(test_state.token, false),
+                AccountMeta::new_readonly(get_config_address(), false),
                 AccountMeta::new(maker_ata, false),
                 AccountMeta::new(*escrow, false),
                 AccountMeta::new(*escrow_ata, false),
@@ -167,6 +203,7 @@ impl<S: TokenVariant> EscrowVariant<S> for DstProgram {
                 AccountMeta::new_readonly(S::get_token_program_id(), false),
                 AccountMeta::new_readonly(rent_id, false),
                 AccountMeta::new_readonly(system_program_id, false),
+                get_tvl_stats_account(test_state, cross_chain_escrow_dst::id()),
             ],
             data: instruction_data,
         };
@@ -188,11 +225,16 @@ impl<S: TokenVariant> EscrowVariant<S> for DstProgram {
         let instruction_data =
             InstructionData::data(&cross_chain_escrow_dst::instruction::RescueFunds {
                 hashlock: test_state.hashlock.to_bytes(),
-                order_hash: test_state.order_hash.to_bytes(),
+                order_hash: <Self as EscrowVariant<S>>::get_escrow_order_hash(test_state),
                 escrow_amount: test_state.test_arguments.escrow_amount,
                 rescue_amount: test_state.test_arguments.rescue_amount,
             });
 
+        let rent_recipient = test_state
+            .test_arguments
+            .rent_recipient
+            .unwrap_or(cross_chain_escrow_dst::id());
+
         let instruction: Instruction = Instruction {
             program_id: cross_chain_escrow_dst::id(),
             accounts: vec![
@@ -202,6 +244,7 @@ impl<S: TokenVariant> EscrowVariant<S> for DstProgram {
                 AccountMeta::new(*escrow, false),
                 AccountMeta::new(*escrow_ata, false),
                 AccountMeta::new(*taker_ata, false),
+                AccountMeta::new(rent_recipient, false),
                 AccountMeta::new_readonly(S::get_token_program_id(), false),
                 AccountMeta::new_readonly(system_program_id, false),
             ],
@@ -216,6 +259,69 @@ impl<S: TokenVariant> EscrowVariant<S> for DstProgram {
         )
     }
 
+    fn get_escrow_status_tx(test_state: &TestState<S>, escrow: &Pubkey) -> Transaction {
+        let instruction_data =
+            InstructionData::data(&cross_chain_escrow_dst::instruction::EscrowStatus {});
+
+        let instruction: Instruction = Instruction {
+            program_id: cross_chain_escrow_dst::id(),
+            accounts: vec![AccountMeta::new_readonly(*escrow, false)],
+            data: instruction_data,
+        };
+
+        Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&test_state.payer_kp.pubkey()),
+            &[&test_state.payer_kp],
+            test_state.context.last_blockhash,
+        )
+    }
+
+    fn get_derive_escrow_pda_tx(
+        test_state: &TestState<S>,
+        hashlock: [u8; 32],
+        creator: Pubkey,
+    ) -> Transaction {
+        let instruction_data =
+            InstructionData::data(&cross_chain_escrow_dst::instruction::DeriveEscrowPda {
+                order_hash: <Self as EscrowVariant<S>>::get_escrow_order_hash(test_state),
+                hashlock,
+                creator,
+                amount: test_state.test_arguments.escrow_amount,
+            });
+
+        let instruction: Instruction = Instruction {
+            program_id: cross_chain_escrow_dst::id(),
+            accounts: vec![],
+            data: instruction_data,
+        };
+
+        Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&test_state.payer_kp.pubkey()),
+            &[&test_state.payer_kp],
+            test_state.context.last_blockhash,
+        )
+    }
+
+    fn get_escrow_rent_tx(test_state: &TestState<S>) -> Transaction {
+        let instruction_data =
+            InstructionData::data(&cross_chain_escrow_dst::instruction::GetEscrowRent {});
+
+        let instruction: Instruction = Instruction {
+            program_id: cross_chain_escrow_dst::id(),
+            accounts: vec![],
+            data: instruction_data,
+        };
+
+        Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&test_state.payer_kp.pubkey()),
+            &[&test_state.payer_kp],
+            test_state.context.last_blockhash,
+        )
+    }
+
     fn get_escrow_data_len() -> usize {
         DEFAULT_DST_ESCROW_SIZE
     }
@@ -223,4 +329,262 @@ impl<S: TokenVariant> EscrowVariant<S> for DstProgram {
     fn get_escrow_creator_wallet(test_state: &TestState<S>) -> Wallet {
         test_state.maker_wallet.clone()
     }
+
+    // The order hash of the test state stands for the hash of the src order parameters, which
+    // the order hash combines with the dst terms of the escrow
+    fn get_escrow_order_hash(test_state: &TestState<S>) -> [u8; 32] {
+        let (dst_token, dst_safety_deposit) =
+            test_state.test_arguments.committed_dst_terms.unwrap_or((
+                test_state.token,
+                test_state.test_arguments.safety_deposit as u128,
+            ));
+        get_order_hash_with_dst_terms(
+            &test_state.order_hash.to_bytes(),
+            &dst_token.to_bytes(),
+            dst_safety_deposit,
+        )
+    }
+}
+
+fn get_recipient<S: TokenVariant>(test_state: &TestState<S>) -> Pubkey {
+    test_state
+        .test_arguments
+        .dst_recipient
+        .unwrap_or(test_state.taker_wallet.keypair.pubkey())
+}
+
+// Native assets are paid out to the recipient wallet, so the recipient ATA is passed as None
+fn get_recipient_ata<S: TokenVariant>(test_state: &TestState<S>) -> Pubkey {
+    if test_state.test_arguments.asset_is_native {
+        cross_chain_escrow_dst::id()
+    } else if let Some(recipient) = test_state.test_arguments.dst_recipient {
+        get_associated_token_address_with_program_id(
+            &recipient,
+            &test_state.token,
+            &S::get_token_program_id(),
+        )
+    } else {
+        find_user_ata(test_state).1
+    }
+}
+
+/// Builds a withdrawal that transfers the tokens to `destination_token_account` instead of
+/// the recipient ATA
+pub fn get_withdraw_to_destination_tx<S: TokenVariant>(
+    test_state: &TestState<S>,
+    escrow: &Pubkey,
+    escrow_ata: &Pubkey,
+    destination_token_account: &Pubkey,
+) -> Transaction {
+    let instruction_data = InstructionData::data(&cross_chain_escrow_dst::instruction::Withdraw {
+        secret: test_state.secret,
+        memo: test_state.test_arguments.memo.clone(),
+    });
+
+    let instruction: Instruction = Instruction {
+        program_id: cross_chain_escrow_dst::id(),
+        accounts: vec![
+            AccountMeta::new(test_state.maker_wallet.keypair.pubkey(), true),
+            AccountMeta::new(get_recipient(test_state), false),
+            AccountMeta::new_readonly(test_state.token, false),
+            AccountMeta::new(*escrow, false),
+            AccountMeta::new(*escrow_ata, false),
+            // No recipient ATA, the tokens are sent to the destination token account
+            AccountMeta::new_readonly(cross_chain_escrow_dst::id(), false),
+            AccountMeta::new(*destination_token_account, false),
+            AccountMeta::new_readonly(spl_associated_token_id, false),
+            AccountMeta::new_readonly(S::get_token_program_id(), false),
+            AccountMeta::new_readonly(system_program_id, false),
+            get_memo_program_account(
+                &test_state.test_arguments.memo,
+                cross_chain_escrow_dst::id(),
+            ),
+            get_tvl_stats_account(test_state, cross_chain_escrow_dst::id()),
+        ],
+        data: instruction_data,
+    };
+
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.context.payer, &test_state.maker_wallet.keypair],
+        test_state.context.last_blockhash,
+    )
+}
+
+pub fn get_init_tvl_stats_tx<S: TokenVariant>(test_state: &TestState<S>) -> Transaction {
+    let instruction_data =
+        InstructionData::data(&cross_chain_escrow_dst::instruction::InitTvlStats {});
+
+    let instruction: Instruction = Instruction {
+        program_id: cross_chain_escrow_dst::id(),
+        accounts: vec![
+            AccountMeta::new(test_state.payer_kp.pubkey(), true),
+            AccountMeta::new_readonly(test_state.token, false),
+            AccountMeta::new(
+                get_tvl_stats_address(cross_chain_escrow_dst::id(), &test_state.token),
+                false,
+            ),
+            AccountMeta::new_readonly(system_program_id, false),
+        ],
+        data: instruction_data,
+    };
+
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.payer_kp],
+        test_state.context.last_blockhash,
+    )
+}
+
+/// Turns on tvl tracking for the test token and passes its tvl stats in the following
+/// instructions
+pub async fn enable_tvl_tracking<S: TokenVariant>(test_state: &mut TestState<S>) {
+    set_track_tvl(test_state, true).await;
+    let transaction = get_init_tvl_stats_tx(test_state);
+    test_state
+        .client
+        .process_transaction(transaction)
+        .await
+        .expect_success();
+    test_state.test_arguments.pass_tvl_stats = true;
+}
+
+pub async fn get_tvl_total_locked<S: TokenVariant>(test_state: &mut TestState<S>) -> u64 {
+    let tvl_stats = get_tvl_stats_address(cross_chain_escrow_dst::id(), &test_state.token);
+    let account = test_state
+        .client
+        .get_account(tvl_stats)
+        .await
+        .unwrap()
+        .expect("Tvl stats account not found");
+    cross_chain_escrow_dst::TvlStats::try_deserialize(&mut account.data.as_slice())
+        .unwrap()
+        .total_locked
+}
+
+pub fn get_public_cancel_tx<S: TokenVariant>(
+    test_state: &TestState<S>,
+    escrow: &Pubkey,
+    escrow_ata: &Pubkey,
+    canceller: &Keypair,
+) -> Transaction {
+    let instruction_data =
+        InstructionData::data(&cross_chain_escrow_dst::instruction::PublicCancel {});
+
+    let (maker_ata, _) = find_user_ata(test_state);
+    let (whitelist_access, _) = get_whitelist_access_address(&canceller.pubkey());
+
+    let instruction: Instruction = Instruction {
+        program_id: cross_chain_escrow_dst::id(),
+        accounts: vec![
+            AccountMeta::new(test_state.maker_wallet.keypair.pubkey(), false),
+            AccountMeta::new_readonly(test_state.token, false),
+            AccountMeta::new(canceller.pubkey(), true),
+            AccountMeta::new_readonly(whitelist_access, false),
+            AccountMeta::new(*escrow, false),
+            AccountMeta::new(*escrow_ata, false),
+            AccountMeta::new(maker_ata, false),
+            AccountMeta::new_readonly(S::get_token_program_id(), false),
+            AccountMeta::new_readonly(system_program_id, false),
+            get_tvl_stats_account(test_state, cross_chain_escrow_dst::id()),
+        ],
+        data: instruction_data,
+    };
+
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.payer_kp, canceller],
+        test_state.context.last_blockhash,
+    )
+}
+
+pub fn get_reconcile_escrow_tx<S: TokenVariant>(
+    test_state: &TestStateBase<DstProgram, S>,
+    escrow: &Pubkey,
+    escrow_ata: &Pubkey,
+) -> Transaction {
+    let instruction_data =
+        InstructionData::data(&cross_chain_escrow_dst::instruction::ReconcileEscrow {});
+
+    let instruction: Instruction = Instruction {
+        program_id: cross_chain_escrow_dst::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(test_state.token, false),
+            AccountMeta::new_readonly(*escrow, false),
+            AccountMeta::new_readonly(*escrow_ata, false),
+            AccountMeta::new_readonly(S::get_token_program_id(), false),
+        ],
+        data: instruction_data,
+    };
+
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.payer_kp],
+        test_state.context.last_blockhash,
+    )
+}
+
+/// Builds a transaction in which the maker adds `additional` lamports to the safety deposit
+pub fn get_top_up_escrow_safety_deposit_tx<S: TokenVariant>(
+    test_state: &TestStateBase<DstProgram, S>,
+    escrow: &Pubkey,
+    additional: u64,
+) -> Transaction {
+    let instruction_data = InstructionData::data(
+        &cross_chain_escrow_dst::instruction::TopUpEscrowSafetyDeposit { additional },
+    );
+
+    let instruction: Instruction = Instruction {
+        program_id: cross_chain_escrow_dst::id(),
+        accounts: vec![
+            AccountMeta::new(test_state.maker_wallet.keypair.pubkey(), true),
+            AccountMeta::new(*escrow, false),
+            AccountMeta::new_readonly(system_program_id, false),
+        ],
+        data: instruction_data,
+    };
+
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.payer_kp, &test_state.maker_wallet.keypair],
+        test_state.context.last_blockhash,
+    )
+}
+
+/// Builds a transaction that creates the recipient ATA for the escrow token, paid by the taker
+pub fn get_ensure_recipient_ata_tx<S: TokenVariant>(
+    test_state: &TestStateBase<DstProgram, S>,
+    escrow: &Pubkey,
+) -> Transaction {
+    let instruction_data =
+        InstructionData::data(&cross_chain_escrow_dst::instruction::EnsureRecipientAta {});
+
+    let (_, recipient_ata) = find_user_ata(test_state);
+
+    let instruction: Instruction = Instruction {
+        program_id: cross_chain_escrow_dst::id(),
+        accounts: vec![
+            AccountMeta::new(test_state.taker_wallet.keypair.pubkey(), true),
+            AccountMeta::new_readonly(test_state.taker_wallet.keypair.pubkey(), false),
+            AccountMeta::new_readonly(test_state.token, false),
+            AccountMeta::new_readonly(*escrow, false),
+            AccountMeta::new(recipient_ata, false),
+            AccountMeta::new_readonly(spl_associated_token_id, false),
+            AccountMeta::new_readonly(S::get_token_program_id(), false),
+            AccountMeta::new_readonly(system_program_id, false),
+        ],
+        data: instruction_data,
+    };
+
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.payer_kp, &test_state.taker_wallet.keypair],
+        test_state.context.last_blockhash,
+    )
 }
@@ -9,18 +9,25 @@ use anchor_spl::token::spl_token::{
     ID as spl_program_id,
 };
 use anchor_spl::token_2022::spl_token_2022::{
-    extension::ExtensionType, extension::StateWithExtensionsMut,
-    instruction as spl2022_instruction, state::Account as SplToken2022Account,
-    state::Mint as SPL2022_Mint, ID as spl2022_program_id,
+    extension::transfer_fee::{
+        instruction::{harvest_withheld_tokens_to_mint, initialize_transfer_fee_config},
+        TransferFeeConfig,
+    },
+    extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensionsMut},
+    instruction as spl2022_instruction,
+    state::Account as SplToken2022Account,
+    state::Mint as SPL2022_Mint,
+    ID as spl2022_program_id,
 };
 
 use async_trait::async_trait;
+use common::constants::{HASH_ALGO_KECCAK, TVL_STATS_SEED};
 use common::timelocks::{Stage, Timelocks};
 use cross_chain_escrow_src::DstChainParams;
 use cross_chain_escrow_src::{get_escrow_hashlock, merkle_tree::MerkleProof};
 use primitive_types::U256;
 use solana_program::{
-    instruction::Instruction,
+    instruction::{AccountMeta, Instruction},
     keccak::{hashv, Hash},
     program_error::ProgramError,
     program_pack::Pack,
@@ -43,7 +50,8 @@ use std::ops::{Div, Mul};
 use std::time::{SystemTime, UNIX_EPOCH};
 use test_context::AsyncTestContext;
 
-use crate::whitelist::get_program_whitelist_spec;
+use crate::cpi_wrapper::get_program_cpi_wrapper_spec;
+use crate::whitelist::{get_program_whitelist_spec, init_config, init_whitelist};
 
 pub const DEFAULT_FEE_PER_SIGNATURE_LAMPORTS: u64 = 5000;
 
@@ -58,6 +66,7 @@ pub const DEFAULT_DST_ESCROW_AMOUNT: u64 = 1000;
 pub const DEFAULT_RESCUE_AMOUNT: u64 = 100;
 pub const DEFAULT_SAFETY_DEPOSIT: u64 = 25;
 pub const DEFAULT_SALT: u64 = 0xFACE8D00DEADBEEF;
+pub const DEFAULT_TRANSFER_FEE_BASIS_POINTS: u16 = 100; // 1%
 
 pub const DEFAULT_SRC_ESCROW_SIZE: usize = cross_chain_escrow_src::EscrowSrc::INIT_SPACE
     + cross_chain_escrow_src::constants::DISCRIMINATOR_BYTES;
@@ -111,15 +120,57 @@ pub struct TestArgs {
     pub expiration_time: u32,
     pub asset_is_native: bool,
     pub dst_amount: [u64; 4],
+    pub min_dst_amount: [u64; 4],
     pub dutch_auction_data: cross_chain_escrow_src::AuctionData,
     pub max_cancellation_premium: u64,
     pub cancellation_auction_duration: u32,
+    pub public_withdrawal_premium_duration: u32,
     pub reward_limit: u64,
+    /// Receives the maker's lamports on cancellation by resolver instead of the maker if set
+    pub maker_lamport_recipient: Option<Pubkey>,
+    pub safety_deposit_split: Option<(Pubkey, u16)>,
+    /// Receives the safety deposit award of a public withdrawal instead of the withdrawer if set
+    pub reward_recipient: Option<Pubkey>,
+    /// Dst token and safety deposit committed to by the order hash of dst escrows, those of the
+    /// escrow by default
+    pub committed_dst_terms: Option<(Pubkey, u128)>,
+    /// Recipient of the dst escrow instead of the taker if set
+    pub dst_recipient: Option<Pubkey>,
+    /// Affiliate passed on src escrow creation for attribution
+    pub affiliate: Option<Pubkey>,
+    /// Deadline passed on src escrow creation, zero if none
+    pub fill_deadline: u32,
+    /// Src stage durations passed on src escrow creation to tighten the order timelocks if set
+    pub src_timelock_durations: Option<[u32; 4]>,
+    /// Ceiling on the dst amount passed on src escrow creation, no limit if none
+    pub max_dst_amount: Option<[u64; 4]>,
+    /// Passes the tvl stats account of the token on escrow creation, withdrawal and cancellation
+    pub pass_tvl_stats: bool,
+    /// Parts whose authorization PDAs are passed on src escrow creation and order cancellation
+    pub part_authorization_indices: Vec<u64>,
+    /// Receives the rent of the token account closed on rescue instead of the signer if set
+    pub rent_recipient: Option<Pubkey>,
     pub merkle_proof: Option<MerkleProof>,
     pub merkle_root: Hash,
     pub allow_multiple_fills: bool,
+    pub min_fill_amount: u64,
     pub dst_chain_params: DstChainParams,
     pub salt: u64,
+    /// Hashlock algorithm committed in the order, keccak by default
+    pub hash_algo: u8,
+    /// Power of ten the dst amounts of the order are scaled by, zero by default
+    pub dst_decimals_shift: i8,
+    /// Only taker allowed to fill the order, any resolver if default
+    pub allowed_taker: Pubkey,
+    pub simple_parts_amount: u16,
+    /// Taker withdrawal window past the src cancellation start, zero by default
+    pub withdrawal_grace: u32,
+    /// Whether the maker must co-sign every fill, false by default
+    pub require_maker_cosign: bool,
+    /// Slots an escrow must age before withdrawals, zero by default
+    pub min_withdrawal_slots: u32,
+    /// Memo attached to withdrawals, none by default
+    pub memo: Option<Vec<u8>>,
     pub partial_secrets: Vec<[u8; 32]>,
 }
 
@@ -139,11 +190,12 @@ pub fn get_default_testargs(nowsecs: u32) -> TestArgs {
             DEFAULT_PERIOD_DURATION * 3,
             nowsecs,
         ),
+        // The dst public cancellation is stored in the slot of the src public cancellation
         dst_timelocks: init_timelocks(
             0,
             0,
             0,
-            0,
+            DEFAULT_PERIOD_DURATION * 4,
             DEFAULT_PERIOD_DURATION,
             DEFAULT_PERIOD_DURATION * 2,
             DEFAULT_PERIOD_DURATION * 3,
@@ -152,22 +204,47 @@ pub fn get_default_testargs(nowsecs: u32) -> TestArgs {
         src_cancellation_timestamp: nowsecs + 10000,
         init_timestamp: nowsecs,
         rescue_amount: DEFAULT_RESCUE_AMOUNT,
-        expiration_time: nowsecs + DEFAULT_PERIOD_DURATION,
+        // The order must not expire before the src cancellation of an escrow created at once
+        expiration_time: nowsecs + DEFAULT_PERIOD_DURATION * 3,
         asset_is_native: false, // This is set to false by default, will be changed for native tests.
         dst_amount: U256::from(DEFAULT_DST_ESCROW_AMOUNT).0,
+        min_dst_amount: [0; 4],
         dutch_auction_data: cross_chain_escrow_src::AuctionData {
             start_time: nowsecs,
             duration: DEFAULT_PERIOD_DURATION,
             initial_rate_bump: 0.into(),
             points_and_time_deltas: vec![],
+            rounding: cross_chain_escrow_src::auction::ROUNDING_UP,
         },
         max_cancellation_premium: DEFAULT_ESCROW_AMOUNT.mul(50_u64 * 100).div(100_u64 * 100),
         cancellation_auction_duration: DEFAULT_PERIOD_DURATION,
+        public_withdrawal_premium_duration: 0,
         reward_limit: DEFAULT_ESCROW_AMOUNT.mul(50_u64 * 100).div(100_u64 * 100),
+        maker_lamport_recipient: None,
+        safety_deposit_split: None,
+        reward_recipient: None,
+        committed_dst_terms: None,
+        dst_recipient: None,
+        affiliate: None,
+        fill_deadline: 0,
+        src_timelock_durations: None,
+        max_dst_amount: None,
+        pass_tvl_stats: false,
+        part_authorization_indices: vec![],
+        rent_recipient: None,
         merkle_proof: None,
         merkle_root: Hash::default(),
         allow_multiple_fills: false,
+        min_fill_amount: 0,
         salt: DEFAULT_SALT,
+        hash_algo: HASH_ALGO_KECCAK,
+        dst_decimals_shift: 0,
+        allowed_taker: Pubkey::default(),
+        simple_parts_amount: 0,
+        withdrawal_grace: 0,
+        require_maker_cosign: false,
+        min_withdrawal_slots: 0,
+        memo: None,
         dst_chain_params: DstChainParams {
             chain_id: 0u32,
             maker_address: [0u8; 32],
@@ -610,10 +687,19 @@ pub trait EscrowVariant<S: TokenVariant> {
         escrow_ata: &Pubkey,
         taker_ata: &Pubkey,
     ) -> Transaction;
+    fn get_escrow_status_tx(test_state: &TestStateBase<Self, S>, escrow: &Pubkey) -> Transaction;
+    fn get_derive_escrow_pda_tx(
+        test_state: &TestStateBase<Self, S>,
+        hashlock: [u8; 32],
+        creator: Pubkey,
+    ) -> Transaction;
+    fn get_escrow_rent_tx(test_state: &TestStateBase<Self, S>) -> Transaction;
 
     fn get_escrow_data_len() -> usize;
 
     fn get_escrow_creator_wallet(test_state: &TestStateBase<Self, S>) -> Wallet;
+    /// Order hash the escrows of the test state are derived from
+    fn get_escrow_order_hash(test_state: &TestStateBase<Self, S>) -> [u8; 32];
 }
 
 impl<T, S> AsyncTestContext for TestStateBase<T, S>
@@ -627,6 +713,11 @@ where
         add_program_to_test(&mut program_test, "whitelist", || {
             get_program_whitelist_spec()
         });
+        add_program_to_test(
+            &mut program_test,
+            "cpi_wrapper",
+            get_program_cpi_wrapper_spec,
+        );
         let mut context: ProgramTestContext = program_test.start_with_context().await;
         let client: BanksClient = context.banks_client.clone();
         let timestamp: u32 = SystemTime::now()
@@ -665,7 +756,7 @@ where
             WALLET_DEFAULT_TOKENS,
         )
         .await;
-        TestStateBase {
+        let test_state = TestStateBase {
             context,
             client,
             secret,
@@ -679,7 +770,11 @@ where
             init_timestamp: timestamp,
             test_arguments: get_default_testargs(timestamp),
             pd: (PhantomData, PhantomData),
-        }
+        };
+        // The config with supported token programs is required to create orders and escrows
+        init_whitelist(&test_state).await;
+        init_config(&test_state).await;
+        test_state
     }
 }
 
@@ -719,7 +814,7 @@ pub fn get_escrow_addresses<T: EscrowVariant<S>, S: TokenVariant>(
     let (escrow_pda, _) = Pubkey::find_program_address(
         &[
             b"escrow",
-            test_state.order_hash.as_ref(),
+            T::get_escrow_order_hash(test_state).as_ref(),
             hashlock.as_ref(),
             T::get_escrow_creator_wallet(test_state)
                 .keypair
@@ -796,6 +891,21 @@ pub async fn create_wallet<S: TokenVariant>(
     }
 }
 
+/// Creates a wallet funded with the default amounts of lamports and of the test token
+pub async fn create_test_wallet<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &mut TestStateBase<T, S>,
+) -> Wallet {
+    create_wallet::<S>(
+        &mut test_state.context,
+        &test_state.token,
+        &test_state.payer_kp,
+        &test_state.payer_kp,
+        WALLET_DEFAULT_LAMPORTS,
+        WALLET_DEFAULT_TOKENS,
+    )
+    .await
+}
+
 pub async fn sync_native_ata(ctx: &mut ProgramTestContext, ata: &Pubkey) {
     let ix = sync_native(&spl_program_id, ata).unwrap();
 
@@ -827,6 +937,15 @@ pub fn set_time(ctx: &mut ProgramTestContext, timestamp: u32) {
     });
 }
 
+/// Sets the clock to the given time and slot, unlike `set_time` which keeps the slot at zero
+pub fn set_time_and_slot(ctx: &mut ProgramTestContext, timestamp: u32, slot: u64) {
+    ctx.set_sysvar(&Clock {
+        slot,
+        unix_timestamp: timestamp as i64,
+        ..Default::default()
+    });
+}
+
 pub async fn transfer_lamports(
     ctx: &mut ProgramTestContext,
     amount: u64,
@@ -853,6 +972,34 @@ pub async fn transfer_lamports(
         .unwrap();
 }
 
+/// Approves the delegate to transfer up to the given amount from the maker's token account
+pub async fn approve_maker_delegate<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &mut TestStateBase<T, S>,
+    delegate: &Pubkey,
+    amount: u64,
+) {
+    let instruction = spl2022_instruction::approve(
+        &S::get_token_program_id(),
+        &test_state.maker_wallet.token_account,
+        delegate,
+        &test_state.maker_wallet.keypair.pubkey(),
+        &[],
+        amount,
+    )
+    .unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.context.payer, &test_state.maker_wallet.keypair],
+        test_state.context.last_blockhash,
+    );
+    test_state
+        .client
+        .process_transaction(transaction)
+        .await
+        .expect_success();
+}
+
 pub async fn get_token_balance(ctx: &mut ProgramTestContext, account: &Pubkey) -> u64 {
     let client = &mut ctx.banks_client;
     let mut account_data = client.get_account(*account).await.unwrap().unwrap();
@@ -861,6 +1008,274 @@ pub async fn get_token_balance(ctx: &mut ProgramTestContext, account: &Pubkey) -
     state.base.amount
 }
 
+/// Overwrites the balance of a token account, e.g. to emulate an ATA drained by an exotic mint
+pub async fn set_token_balance(ctx: &mut ProgramTestContext, account: &Pubkey, amount: u64) {
+    let mut account_data = ctx
+        .banks_client
+        .get_account(*account)
+        .await
+        .unwrap()
+        .unwrap();
+    let mut state =
+        StateWithExtensionsMut::<SplToken2022Account>::unpack(&mut account_data.data).unwrap();
+    state.base.amount = amount;
+    state.pack_base();
+    ctx.set_account(account, &account_data.into());
+}
+
+/// Creates a token account owned by `owner` that is not an associated token account
+pub async fn create_token_account<S: TokenVariant>(
+    ctx: &mut ProgramTestContext,
+    mint: &Pubkey,
+    owner: &Pubkey,
+) -> Pubkey {
+    let account_keypair = Keypair::new();
+    let create_account_ix = system_instruction::create_account(
+        &ctx.payer.pubkey(),
+        &account_keypair.pubkey(),
+        1_000_000_000, // Some lamports to pay rent
+        SplTokenAccount::LEN as u64,
+        &S::get_token_program_id(),
+    );
+
+    // The Token-2022 instruction builder supports both token programs
+    let initialize_account_ix = spl2022_instruction::initialize_account3(
+        &S::get_token_program_id(),
+        &account_keypair.pubkey(),
+        mint,
+        owner,
+    )
+    .unwrap();
+
+    let signers: Vec<&Keypair> = vec![&ctx.payer, &account_keypair];
+
+    let client = &mut ctx.banks_client;
+    client
+        .process_transaction(Transaction::new_signed_with_payer(
+            &[create_account_ix, initialize_account_ix],
+            Some(&ctx.payer.pubkey()),
+            &signers,
+            ctx.last_blockhash,
+        ))
+        .await
+        .unwrap();
+    account_keypair.pubkey()
+}
+
+/// Deploys a Token-2022 mint with the transfer fee extension, with the payer as the mint and
+/// the transfer fee config authority
+pub async fn deploy_spl_token_with_transfer_fee(
+    ctx: &mut ProgramTestContext,
+    transfer_fee_basis_points: u16,
+) -> Keypair {
+    let mint_keypair = Keypair::new();
+    let account_size = ExtensionType::try_calculate_account_len::<SPL2022_Mint>(&[
+        ExtensionType::TransferFeeConfig,
+    ])
+    .unwrap();
+    let create_mint_acc_ix = system_instruction::create_account(
+        &ctx.payer.pubkey(),
+        &mint_keypair.pubkey(),
+        1_000_000_000,
+        account_size as u64,
+        &spl2022_program_id,
+    );
+
+    // The extension must be initialized before the mint itself
+    let initialize_transfer_fee_ix = initialize_transfer_fee_config(
+        &spl2022_program_id,
+        &mint_keypair.pubkey(),
+        Some(&ctx.payer.pubkey()),
+        Some(&ctx.payer.pubkey()),
+        transfer_fee_basis_points,
+        u64::MAX,
+    )
+    .unwrap();
+
+    let initialize_mint_ix: Instruction = spl2022_instruction::initialize_mint(
+        &spl2022_program_id,
+        &mint_keypair.pubkey(),
+        &ctx.payer.pubkey(),
+        None,
+        8,
+    )
+    .unwrap();
+
+    let signers: Vec<&Keypair> = vec![&ctx.payer, &mint_keypair];
+
+    let client = &mut ctx.banks_client;
+    client
+        .process_transaction(Transaction::new_signed_with_payer(
+            &[
+                create_mint_acc_ix,
+                initialize_transfer_fee_ix,
+                initialize_mint_ix,
+            ],
+            Some(&ctx.payer.pubkey()),
+            &signers,
+            ctx.last_blockhash,
+        ))
+        .await
+        .unwrap();
+    mint_keypair
+}
+
+/// Deploys a Token-2022 mint with the permanent delegate extension, with the payer as the mint
+/// authority
+pub async fn deploy_spl_token_with_permanent_delegate(
+    ctx: &mut ProgramTestContext,
+    delegate: &Pubkey,
+) -> Keypair {
+    let mint_keypair = Keypair::new();
+    let account_size = ExtensionType::try_calculate_account_len::<SPL2022_Mint>(&[
+        ExtensionType::PermanentDelegate,
+    ])
+    .unwrap();
+    let create_mint_acc_ix = system_instruction::create_account(
+        &ctx.payer.pubkey(),
+        &mint_keypair.pubkey(),
+        1_000_000_000,
+        account_size as u64,
+        &spl2022_program_id,
+    );
+
+    let initialize_permanent_delegate_ix = spl2022_instruction::initialize_permanent_delegate(
+        &spl2022_program_id,
+        &mint_keypair.pubkey(),
+        delegate,
+    )
+    .unwrap();
+
+    let initialize_mint_ix: Instruction = spl2022_instruction::initialize_mint(
+        &spl2022_program_id,
+        &mint_keypair.pubkey(),
+        &ctx.payer.pubkey(),
+        None,
+        8,
+    )
+    .unwrap();
+
+    let signers: Vec<&Keypair> = vec![&ctx.payer, &mint_keypair];
+
+    let client = &mut ctx.banks_client;
+    client
+        .process_transaction(Transaction::new_signed_with_payer(
+            &[
+                create_mint_acc_ix,
+                initialize_permanent_delegate_ix,
+                initialize_mint_ix,
+            ],
+            Some(&ctx.payer.pubkey()),
+            &signers,
+            ctx.last_blockhash,
+        ))
+        .await
+        .unwrap();
+    mint_keypair
+}
+
+/// Replaces the test token with a freshly deployed transfer fee mint and funds the maker and
+/// taker token accounts with it
+pub async fn use_transfer_fee_token<T: EscrowVariant<Token2022>>(
+    test_state: &mut TestStateBase<T, Token2022>,
+    transfer_fee_basis_points: u16,
+) {
+    let token =
+        deploy_spl_token_with_transfer_fee(&mut test_state.context, transfer_fee_basis_points)
+            .await
+            .pubkey();
+    use_token(test_state, token).await;
+}
+
+/// Replaces the test token with a freshly deployed mint with a permanent delegate and funds the
+/// maker and taker token accounts with it
+pub async fn use_permanent_delegate_token<T: EscrowVariant<Token2022>>(
+    test_state: &mut TestStateBase<T, Token2022>,
+    delegate: &Pubkey,
+) {
+    let token = deploy_spl_token_with_permanent_delegate(&mut test_state.context, delegate)
+        .await
+        .pubkey();
+    use_token(test_state, token).await;
+}
+
+// Makes the token the test token, funding the maker and taker token accounts with it
+async fn use_token<T: EscrowVariant<Token2022>>(
+    test_state: &mut TestStateBase<T, Token2022>,
+    token: Pubkey,
+) {
+    let payer_kp = test_state.payer_kp.insecure_clone();
+    for wallet in [&mut test_state.maker_wallet, &mut test_state.taker_wallet] {
+        wallet.token_account = Token2022::initialize_spl_associated_account(
+            &mut test_state.context,
+            &token,
+            &wallet.keypair.pubkey(),
+        )
+        .await;
+        Token2022::mint_spl_tokens(
+            &mut test_state.context,
+            &token,
+            &wallet.token_account,
+            &payer_kp.pubkey(),
+            &payer_kp,
+            WALLET_DEFAULT_TOKENS,
+        )
+        .await;
+    }
+    test_state.token = token;
+}
+
+/// Returns the size of an associated token account of a transfer fee mint, which also
+/// stores the withheld fees
+pub fn get_transfer_fee_token_account_size() -> usize {
+    ExtensionType::try_calculate_account_len::<SplToken2022Account>(&[
+        ExtensionType::ImmutableOwner,
+        ExtensionType::TransferFeeAmount,
+    ])
+    .unwrap()
+}
+
+/// Returns the fee withheld when transferring `amount` tokens, rounded up as done by the
+/// Token-2022 program
+pub fn get_transfer_fee(amount: u64, transfer_fee_basis_points: u16) -> u64 {
+    (amount as u128 * transfer_fee_basis_points as u128).div_ceil(10_000) as u64
+}
+
+/// Returns the transfer fees harvested to the mint
+pub async fn get_mint_withheld_fees(ctx: &mut ProgramTestContext, mint: &Pubkey) -> u64 {
+    let client = &mut ctx.banks_client;
+    let mut account_data = client.get_account(*mint).await.unwrap().unwrap();
+    let state = StateWithExtensionsMut::<SPL2022_Mint>::unpack(&mut account_data.data).unwrap();
+    u64::from(
+        state
+            .get_extension::<TransferFeeConfig>()
+            .unwrap()
+            .withheld_amount,
+    )
+}
+
+/// Harvests the transfer fees withheld in `token_accounts` to the mint, as needed before
+/// the escrow programs close these token accounts
+pub async fn harvest_withheld_fees(
+    ctx: &mut ProgramTestContext,
+    mint: &Pubkey,
+    token_accounts: &[&Pubkey],
+) {
+    let harvest_ix =
+        harvest_withheld_tokens_to_mint(&spl2022_program_id, mint, token_accounts).unwrap();
+
+    let client = &mut ctx.banks_client;
+    client
+        .process_transaction(Transaction::new_signed_with_payer(
+            &[harvest_ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        ))
+        .await
+        .unwrap();
+}
+
 #[derive(Clone)]
 pub enum BalanceChange {
     Token(Pubkey, i128),
@@ -1084,3 +1499,42 @@ macro_rules! run_for_tokens {
           })*
     };
 }
+
+/// Returns the secondary safety deposit recipient account of a public withdrawal and the share of
+/// the safety deposit in basis points awarded to the withdrawer
+pub fn get_safety_deposit_split_accounts(
+    split: Option<(Pubkey, u16)>,
+    program_id: Pubkey,
+) -> (AccountMeta, u16) {
+    match split {
+        Some((secondary_recipient, split_bps)) => {
+            (AccountMeta::new(secondary_recipient, false), split_bps)
+        }
+        None => (AccountMeta::new_readonly(program_id, false), 0),
+    }
+}
+
+/// Returns the memo program account of a withdrawal, which is only passed along with a memo
+pub fn get_memo_program_account(memo: &Option<Vec<u8>>, program_id: Pubkey) -> AccountMeta {
+    AccountMeta::new_readonly(
+        memo.as_ref().map_or(program_id, |_| anchor_spl::memo::ID),
+        false,
+    )
+}
+
+pub fn get_tvl_stats_address(program_id: Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[TVL_STATS_SEED, mint.as_ref()], &program_id).0
+}
+
+/// Returns the tvl stats account of the test token if the test passes it, otherwise the program
+/// id standing in for the missing optional account
+pub fn get_tvl_stats_account<T, S>(
+    test_state: &TestStateBase<T, S>,
+    program_id: Pubkey,
+) -> AccountMeta {
+    if test_state.test_arguments.pass_tvl_stats {
+        AccountMeta::new(get_tvl_stats_address(program_id, &test_state.token), false)
+    } else {
+        AccountMeta::new_readonly(program_id, false)
+    }
+}
@@ -1,3 +1,4 @@
+pub mod cpi_wrapper;
 pub mod dst_program;
 pub mod helpers;
 pub mod src_program;
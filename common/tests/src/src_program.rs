@@ -1,11 +1,18 @@
 use crate::helpers::*;
-use crate::whitelist::get_whitelist_access_address;
+use crate::whitelist::{
+    get_blocked_mint_address, get_config_address, get_fill_authority_address,
+    get_whitelist_access_address, get_whitelist_state_address, set_track_tvl,
+};
 use crate::wrap_entry;
 use anchor_lang::prelude::AccountInfo;
+use anchor_lang::AccountDeserialize;
 use anchor_lang::AnchorSerialize;
+use anchor_lang::Discriminator;
 use anchor_lang::InstructionData;
+use cross_chain_escrow_src::merkle_tree::MerkleProof;
+use cross_chain_escrow_src::OrderArgs;
 use solana_program_runtime::invoke_context::BuiltinFunctionWithContext;
-use solana_program_test::processor;
+use solana_program_test::{processor, BanksClientError};
 use solana_sdk::{signature::Signer, signer::keypair::Keypair, transaction::Transaction};
 
 use anchor_spl::associated_token::{spl_associated_token_account, ID as spl_associated_token_id};
@@ -15,7 +22,7 @@ use solana_program::{
     keccak,
     pubkey::Pubkey,
     system_program::ID as system_program_id,
-    sysvar::rent::ID as rent_id,
+    sysvar::{instructions::ID as instructions_sysvar_id, rent::ID as rent_id},
 };
 
 pub struct SrcProgram;
@@ -37,6 +44,7 @@ impl<S: TokenVariant> EscrowVariant<S> for SrcProgram {
         let instruction_data =
             InstructionData::data(&cross_chain_escrow_src::instruction::Withdraw {
                 secret: test_state.secret,
+                memo: test_state.test_arguments.memo.clone(),
             });
 
         let (_, taker_ata) = find_user_ata(test_state);
@@ -51,6 +59,11 @@ impl<S: TokenVariant> EscrowVariant<S> for SrcProgram {
                 AccountMeta::new(taker_ata, false),
                 AccountMeta::new_readonly(S::get_token_program_id(), false),
                 AccountMeta::new_readonly(system_program_id, false),
+                get_memo_program_account(
+                    &test_state.test_arguments.memo,
+                    cross_chain_escrow_src::id(),
+                ),
+                get_tvl_stats_account(test_state, cross_chain_escrow_src::id()),
             ],
             data: instruction_data,
         };
@@ -69,9 +82,19 @@ impl<S: TokenVariant> EscrowVariant<S> for SrcProgram {
         escrow_ata: &Pubkey,
         withdrawer: &Keypair,
     ) -> Transaction {
+        let (secondary_recipient, safety_deposit_split_bps) = get_safety_deposit_split_accounts(
+            test_state.test_arguments.safety_deposit_split,
+            cross_chain_escrow_src::id(),
+        );
+        let reward_recipient = test_state
+            .test_arguments
+            .reward_recipient
+            .unwrap_or(cross_chain_escrow_src::id());
         let instruction_data =
             InstructionData::data(&cross_chain_escrow_src::instruction::PublicWithdraw {
                 secret: test_state.secret,
+                safety_deposit_split_bps,
+                memo: test_state.test_arguments.memo.clone(),
             });
 
         let (_, taker_ata) = find_user_ata(test_state);
@@ -83,12 +106,19 @@ impl<S: TokenVariant> EscrowVariant<S> for SrcProgram {
                 AccountMeta::new(test_state.taker_wallet.keypair.pubkey(), false),
                 AccountMeta::new(withdrawer.pubkey(), true),
                 AccountMeta::new_readonly(whitelist_access, false),
+                secondary_recipient,
+                AccountMeta::new(reward_recipient, false),
                 AccountMeta::new_readonly(test_state.token, false),
                 AccountMeta::new(*escrow, false),
                 AccountMeta::new(*escrow_ata, false),
                 AccountMeta::new(taker_ata, false),
                 AccountMeta::new_readonly(S::get_token_program_id(), false),
                 AccountMeta::new_readonly(system_program_id, false),
+                get_memo_program_account(
+                    &test_state.test_arguments.memo,
+                    cross_chain_escrow_src::id(),
+                ),
+                get_tvl_stats_account(test_state, cross_chain_escrow_src::id()),
             ],
             data: instruction_data,
         };
@@ -122,8 +152,10 @@ impl<S: TokenVariant> EscrowVariant<S> for SrcProgram {
                 AccountMeta::new(*escrow, false),
                 AccountMeta::new(*escrow_ata, false),
                 AccountMeta::new(maker_ata, false),
+                AccountMeta::new_readonly(spl_associated_token_id, false),
                 AccountMeta::new_readonly(S::get_token_program_id(), false),
                 AccountMeta::new_readonly(system_program_id, false),
+                get_tvl_stats_account(test_state, cross_chain_escrow_src::id()),
             ],
             data: instruction_data,
         };
@@ -146,27 +178,40 @@ impl<S: TokenVariant> EscrowVariant<S> for SrcProgram {
                 amount: test_state.test_arguments.escrow_amount,
                 merkle_proof: test_state.test_arguments.merkle_proof.clone(),
                 dutch_auction_data: test_state.test_arguments.dutch_auction_data.clone(),
+                affiliate: test_state.test_arguments.affiliate,
+                deadline: test_state.test_arguments.fill_deadline,
+                src_timelock_durations: test_state.test_arguments.src_timelock_durations,
+                max_dst_amount: test_state.test_arguments.max_dst_amount,
             });
 
         let (order, order_ata) = get_order_addresses(test_state);
         let (whitelist_access, _) =
             get_whitelist_access_address(&test_state.taker_wallet.keypair.pubkey());
 
+        let mut accounts = vec![
+            AccountMeta::new(test_state.taker_wallet.keypair.pubkey(), true),
+            AccountMeta::new(whitelist_access, false),
+            AccountMeta::new_readonly(get_fill_authority_address(), false),
+            AccountMeta::new(test_state.maker_wallet.keypair.pubkey(), false),
+            AccountMeta::new_readonly(test_state.token, false),
+            AccountMeta::new_readonly(get_config_address(), false),
+            AccountMeta::new_readonly(get_blocked_mint_address(&test_state.token), false),
+            AccountMeta::new(order, false),
+            AccountMeta::new(order_ata, false),
+            AccountMeta::new(*escrow, false),
+            AccountMeta::new(*escrow_ata, false),
+            AccountMeta::new_readonly(spl_associated_token_id, false),
+            AccountMeta::new_readonly(S::get_token_program_id(), false),
+            AccountMeta::new_readonly(system_program_id, false),
+            AccountMeta::new_readonly(whitelist::id(), false),
+            AccountMeta::new_readonly(instructions_sysvar_id, false),
+            get_tvl_stats_account(test_state, cross_chain_escrow_src::id()),
+        ];
+        accounts.extend(get_part_authorization_accounts(test_state));
+
         let instruction: Instruction = Instruction {
             program_id: cross_chain_escrow_src::id(),
-            accounts: vec![
-                AccountMeta::new(test_state.taker_wallet.keypair.pubkey(), true),
-                AccountMeta::new_readonly(whitelist_access, false),
-                AccountMeta::new(test_state.maker_wallet.keypair.pubkey(), false),
-                AccountMeta::new_readonly(test_state.token, false),
-                AccountMeta::new(order, false),
-                AccountMeta::new(order_ata, false),
-                AccountMeta::new(*escrow, false),
-                AccountMeta::new(*escrow_ata, false),
-                AccountMeta::new_readonly(spl_associated_token_id, false),
-                AccountMeta::new_readonly(S::get_token_program_id(), false),
-                AccountMeta::new_readonly(system_program_id, false),
-            ],
+            accounts,
             data: instruction_data,
         };
         Transaction::new_signed_with_payer(
@@ -192,6 +237,11 @@ impl<S: TokenVariant> EscrowVariant<S> for SrcProgram {
                 rescue_amount: test_state.test_arguments.rescue_amount,
             });
 
+        let rent_recipient = test_state
+            .test_arguments
+            .rent_recipient
+            .unwrap_or(cross_chain_escrow_src::id());
+
         let instruction: Instruction = Instruction {
             program_id: cross_chain_escrow_src::id(),
             accounts: vec![
@@ -200,6 +250,7 @@ impl<S: TokenVariant> EscrowVariant<S> for SrcProgram {
                 AccountMeta::new(*escrow, false),
                 AccountMeta::new(*escrow_ata, false),
                 AccountMeta::new(*taker_ata, false),
+                AccountMeta::new(rent_recipient, false),
                 AccountMeta::new_readonly(S::get_token_program_id(), false),
                 AccountMeta::new_readonly(system_program_id, false),
             ],
@@ -214,6 +265,69 @@ impl<S: TokenVariant> EscrowVariant<S> for SrcProgram {
         )
     }
 
+    fn get_escrow_status_tx(test_state: &TestState<S>, escrow: &Pubkey) -> Transaction {
+        let instruction_data =
+            InstructionData::data(&cross_chain_escrow_src::instruction::EscrowStatus {});
+
+        let instruction: Instruction = Instruction {
+            program_id: cross_chain_escrow_src::id(),
+            accounts: vec![AccountMeta::new_readonly(*escrow, false)],
+            data: instruction_data,
+        };
+
+        Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&test_state.payer_kp.pubkey()),
+            &[&test_state.payer_kp],
+            test_state.context.last_blockhash,
+        )
+    }
+
+    fn get_derive_escrow_pda_tx(
+        test_state: &TestState<S>,
+        hashlock: [u8; 32],
+        creator: Pubkey,
+    ) -> Transaction {
+        let instruction_data =
+            InstructionData::data(&cross_chain_escrow_src::instruction::DeriveEscrowPda {
+                order_hash: test_state.order_hash.to_bytes(),
+                hashlock,
+                taker: creator,
+                amount: test_state.test_arguments.escrow_amount,
+            });
+
+        let instruction: Instruction = Instruction {
+            program_id: cross_chain_escrow_src::id(),
+            accounts: vec![],
+            data: instruction_data,
+        };
+
+        Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&test_state.payer_kp.pubkey()),
+            &[&test_state.payer_kp],
+            test_state.context.last_blockhash,
+        )
+    }
+
+    fn get_escrow_rent_tx(test_state: &TestState<S>) -> Transaction {
+        let instruction_data =
+            InstructionData::data(&cross_chain_escrow_src::instruction::GetEscrowRent {});
+
+        let instruction: Instruction = Instruction {
+            program_id: cross_chain_escrow_src::id(),
+            accounts: vec![],
+            data: instruction_data,
+        };
+
+        Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&test_state.payer_kp.pubkey()),
+            &[&test_state.payer_kp],
+            test_state.context.last_blockhash,
+        )
+    }
+
     fn get_escrow_data_len() -> usize {
         DEFAULT_SRC_ESCROW_SIZE
     }
@@ -221,6 +335,10 @@ impl<S: TokenVariant> EscrowVariant<S> for SrcProgram {
     fn get_escrow_creator_wallet(test_state: &TestState<S>) -> Wallet {
         test_state.taker_wallet.clone()
     }
+
+    fn get_escrow_order_hash(test_state: &TestState<S>) -> [u8; 32] {
+        test_state.order_hash.to_bytes()
+    }
 }
 
 pub fn create_public_escrow_cancel_tx<S: TokenVariant>(
@@ -248,6 +366,7 @@ pub fn create_public_escrow_cancel_tx<S: TokenVariant>(
             AccountMeta::new(maker_ata, false),
             AccountMeta::new_readonly(S::get_token_program_id(), false),
             AccountMeta::new_readonly(system_program_id, false),
+            get_tvl_stats_account(test_state, cross_chain_escrow_src::id()),
         ],
         data: instruction_data,
     };
@@ -269,30 +388,18 @@ pub fn get_rescue_funds_from_order_tx<S: TokenVariant>(
 ) -> Transaction {
     let instruction_data =
         InstructionData::data(&cross_chain_escrow_src::instruction::RescueFundsForOrder {
-            hashlock: test_state.hashlock.to_bytes(),
             maker: test_state.maker_wallet.keypair.pubkey(),
             token: test_state.token,
-            order_amount: test_state.test_arguments.order_amount,
-            safety_deposit: test_state.test_arguments.safety_deposit,
-            timelocks: test_state.test_arguments.src_timelocks.get_timelocks(),
-            expiration_time: test_state.test_arguments.expiration_time,
-            asset_is_native: test_state.test_arguments.asset_is_native,
-            dst_amount: test_state.test_arguments.dst_amount,
-            dutch_auction_data_hash: keccak::hashv(&[&test_state
-                .test_arguments
-                .dutch_auction_data
-                .try_to_vec()
-                .unwrap()])
-            .to_bytes(),
-            max_cancellation_premium: test_state.test_arguments.max_cancellation_premium,
-            cancellation_auction_duration: test_state.test_arguments.cancellation_auction_duration,
-            allow_multiple_fills: test_state.test_arguments.allow_multiple_fills,
-            salt: test_state.test_arguments.salt,
+            args: get_order_args(test_state),
             rescue_amount: test_state.test_arguments.rescue_amount,
         });
 
     let (whitelist_access, _) =
         get_whitelist_access_address(&test_state.taker_wallet.keypair.pubkey());
+    let rent_recipient = test_state
+        .test_arguments
+        .rent_recipient
+        .unwrap_or(cross_chain_escrow_src::id());
 
     let instruction: Instruction = Instruction {
         program_id: cross_chain_escrow_src::id(),
@@ -303,6 +410,7 @@ pub fn get_rescue_funds_from_order_tx<S: TokenVariant>(
             AccountMeta::new(*order, false),
             AccountMeta::new(*order_ata, false),
             AccountMeta::new(*taker_ata, false),
+            AccountMeta::new(rent_recipient, false),
             AccountMeta::new_readonly(S::get_token_program_id(), false),
             AccountMeta::new_readonly(system_program_id, false),
         ],
@@ -316,8 +424,44 @@ pub fn get_rescue_funds_from_order_tx<S: TokenVariant>(
         test_state.context.last_blockhash,
     )
 }
+/// Builds the order arguments of the instructions that create or derive the order
+pub fn get_order_args<T, S: TokenVariant>(test_state: &TestStateBase<T, S>) -> OrderArgs {
+    OrderArgs {
+        hashlock: test_state.hashlock.to_bytes(),
+        amount: test_state.test_arguments.order_amount,
+        safety_deposit: test_state.test_arguments.safety_deposit,
+        timelocks: test_state.test_arguments.src_timelocks.get_timelocks(),
+        expiration_time: test_state.test_arguments.expiration_time,
+        asset_is_native: test_state.test_arguments.asset_is_native,
+        dst_amount: test_state.test_arguments.dst_amount,
+        min_dst_amount: test_state.test_arguments.min_dst_amount,
+        dutch_auction_data_hash: keccak::hashv(&[&test_state
+            .test_arguments
+            .dutch_auction_data
+            .try_to_vec()
+            .unwrap()])
+        .to_bytes(),
+        max_cancellation_premium: test_state.test_arguments.max_cancellation_premium,
+        cancellation_auction_duration: test_state.test_arguments.cancellation_auction_duration,
+        public_withdrawal_premium_duration: test_state
+            .test_arguments
+            .public_withdrawal_premium_duration,
+        allow_multiple_fills: test_state.test_arguments.allow_multiple_fills,
+        min_fill_amount: test_state.test_arguments.min_fill_amount,
+        salt: test_state.test_arguments.salt,
+        hash_algo: test_state.test_arguments.hash_algo,
+        dst_decimals_shift: test_state.test_arguments.dst_decimals_shift,
+        allowed_taker: test_state.test_arguments.allowed_taker,
+        simple_parts_amount: test_state.test_arguments.simple_parts_amount,
+        withdrawal_grace: test_state.test_arguments.withdrawal_grace,
+        require_maker_cosign: test_state.test_arguments.require_maker_cosign,
+        min_withdrawal_slots: test_state.test_arguments.min_withdrawal_slots,
+        dst_chain_params: test_state.test_arguments.dst_chain_params.clone(),
+    }
+}
+
 pub fn get_order_hash<T, S: TokenVariant>(test_state: &TestStateBase<T, S>) -> keccak::Hash {
-    keccak::hashv(&[
+    let order_params_hash = keccak::hashv(&[
         &test_state.hashlock.to_bytes(),
         test_state.maker_wallet.keypair.pubkey().as_ref(),
         test_state.token.as_ref(),
@@ -335,15 +479,16 @@ pub fn get_order_hash<T, S: TokenVariant>(test_state: &TestStateBase<T, S>) -> k
             .iter()
             .flat_map(|x| x.to_le_bytes())
             .collect::<Vec<u8>>(),
+        &[test_state.test_arguments.asset_is_native as u8],
         test_state
             .test_arguments
-            .expiration_time
-            .to_be_bytes()
+            .dst_amount
+            .try_to_vec()
+            .unwrap()
             .as_ref(),
-        &[test_state.test_arguments.asset_is_native as u8],
         test_state
             .test_arguments
-            .dst_amount
+            .min_dst_amount
             .try_to_vec()
             .unwrap()
             .as_ref(),
@@ -364,8 +509,35 @@ pub fn get_order_hash<T, S: TokenVariant>(test_state: &TestStateBase<T, S>) -> k
             .cancellation_auction_duration
             .to_be_bytes()
             .as_ref(),
+        test_state
+            .test_arguments
+            .public_withdrawal_premium_duration
+            .to_be_bytes()
+            .as_ref(),
         &[test_state.test_arguments.allow_multiple_fills as u8],
+        test_state
+            .test_arguments
+            .min_fill_amount
+            .to_be_bytes()
+            .as_ref(),
         test_state.test_arguments.salt.to_be_bytes().as_ref(),
+        &[test_state.test_arguments.hash_algo],
+        test_state
+            .test_arguments
+            .dst_decimals_shift
+            .to_be_bytes()
+            .as_ref(),
+        test_state.test_arguments.allowed_taker.as_ref(),
+        &test_state.test_arguments.simple_parts_amount.to_be_bytes(),
+        &test_state.test_arguments.withdrawal_grace.to_be_bytes(),
+        &[test_state.test_arguments.require_maker_cosign as u8],
+        &test_state.test_arguments.min_withdrawal_slots.to_be_bytes(),
+    ]);
+    let dst_chain_params = &test_state.test_arguments.dst_chain_params;
+    keccak::hashv(&[
+        order_params_hash.as_ref(),
+        &dst_chain_params.token,
+        &dst_chain_params.safety_deposit.to_be_bytes(),
     ])
 }
 
@@ -384,6 +556,44 @@ pub fn get_order_addresses<S: TokenVariant>(
     (order_pda, order_ata)
 }
 
+/// Part authorization PDAs of the parts listed in the test arguments, passed as remaining accounts
+fn get_part_authorization_accounts<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &TestStateBase<T, S>,
+) -> Vec<AccountMeta> {
+    test_state
+        .test_arguments
+        .part_authorization_indices
+        .iter()
+        .map(|part_index| {
+            let (part_authorization, _) = Pubkey::find_program_address(
+                &[
+                    b"part_authorization",
+                    test_state.order_hash.as_ref(),
+                    &part_index.to_be_bytes(),
+                ],
+                &cross_chain_escrow_src::id(),
+            );
+            AccountMeta::new(part_authorization, false)
+        })
+        .collect()
+}
+
+pub fn get_part_authorization_address<S: TokenVariant>(
+    test_state: &TestStateBase<SrcProgram, S>,
+    part_index: u64,
+) -> Pubkey {
+    let (program_id, _) = <SrcProgram as EscrowVariant<S>>::get_program_spec();
+    let (part_authorization, _) = Pubkey::find_program_address(
+        &[
+            b"part_authorization",
+            test_state.order_hash.as_ref(),
+            &part_index.to_be_bytes(),
+        ],
+        &program_id,
+    );
+    part_authorization
+}
+
 pub fn create_order_data<S: TokenVariant>(
     test_state: &mut TestStateBase<SrcProgram, S>,
 ) -> (Pubkey, Pubkey, Transaction) {
@@ -394,39 +604,41 @@ pub fn create_order_data<S: TokenVariant>(
     (order_pda, order_ata, transaction)
 }
 
-pub fn get_create_order_tx<T: EscrowVariant<S>, S: TokenVariant>(
+pub fn get_create_order_ix<T: EscrowVariant<S>, S: TokenVariant>(
     test_state: &TestStateBase<T, S>,
     order: &Pubkey,
     order_ata: &Pubkey,
-) -> Transaction {
+    opt_delegate: Option<&Pubkey>,
+) -> Instruction {
     let instruction_data = InstructionData::data(&cross_chain_escrow_src::instruction::Create {
-        amount: test_state.test_arguments.order_amount,
-        hashlock: test_state.hashlock.to_bytes(),
-        safety_deposit: test_state.test_arguments.safety_deposit,
-        timelocks: test_state.test_arguments.src_timelocks.get_timelocks(),
-        expiration_time: test_state.test_arguments.expiration_time,
-        asset_is_native: test_state.test_arguments.asset_is_native,
-        dst_amount: test_state.test_arguments.dst_amount,
-        dutch_auction_data_hash: keccak::hashv(&[&test_state
-            .test_arguments
-            .dutch_auction_data
-            .try_to_vec()
-            .unwrap()])
-        .to_bytes(),
-        max_cancellation_premium: test_state.test_arguments.max_cancellation_premium,
-        cancellation_auction_duration: test_state.test_arguments.cancellation_auction_duration,
-        allow_multiple_fills: test_state.test_arguments.allow_multiple_fills,
-        salt: test_state.test_arguments.salt,
-        _dst_chain_params: test_state.test_arguments.dst_chain_params.clone(),
+        args: get_order_args(test_state),
     });
 
     let (maker_ata, _) = find_user_ata(test_state);
+    let maker = test_state.maker_wallet.keypair.pubkey();
+    // The delegate funds the order and pays for its creation in place of the maker
+    let (creator, payer, delegate) = if let Some(delegate) = opt_delegate {
+        (
+            AccountMeta::new(maker, false),
+            AccountMeta::new(*delegate, true),
+            AccountMeta::new_readonly(*delegate, true),
+        )
+    } else {
+        (
+            AccountMeta::new(maker, true),
+            AccountMeta::new(maker, true),
+            AccountMeta::new_readonly(cross_chain_escrow_src::id(), false),
+        )
+    };
 
-    let instruction: Instruction = Instruction {
+    Instruction {
         program_id: cross_chain_escrow_src::id(),
         accounts: vec![
-            AccountMeta::new(test_state.maker_wallet.keypair.pubkey(), true),
+            creator,
+            payer,
             AccountMeta::new_readonly(test_state.token, false),
+            AccountMeta::new_readonly(get_config_address(), false),
+            AccountMeta::new_readonly(get_blocked_mint_address(&test_state.token), false),
             AccountMeta::new(maker_ata, false),
             AccountMeta::new(*order, false),
             AccountMeta::new(*order_ata, false),
@@ -434,9 +646,18 @@ pub fn get_create_order_tx<T: EscrowVariant<S>, S: TokenVariant>(
             AccountMeta::new_readonly(S::get_token_program_id(), false),
             AccountMeta::new_readonly(rent_id, false),
             AccountMeta::new_readonly(system_program_id, false),
+            delegate,
         ],
         data: instruction_data,
-    };
+    }
+}
+
+pub fn get_create_order_tx<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &TestStateBase<T, S>,
+    order: &Pubkey,
+    order_ata: &Pubkey,
+) -> Transaction {
+    let instruction = get_create_order_ix(test_state, order, order_ata, None);
     Transaction::new_signed_with_payer(
         &[instruction],
         Some(&test_state.payer_kp.pubkey()),
@@ -445,56 +666,138 @@ pub fn get_create_order_tx<T: EscrowVariant<S>, S: TokenVariant>(
     )
 }
 
-pub fn get_cancel_order_tx<T: EscrowVariant<S>, S: TokenVariant>(
-    test_state: &TestStateBase<T, S>,
+/// Builds a transaction creating the order through `create_idempotent`, which takes the same
+/// arguments and accounts as `create`
+pub fn get_create_order_idempotent_tx<S: TokenVariant>(
+    test_state: &TestStateBase<SrcProgram, S>,
     order: &Pubkey,
     order_ata: &Pubkey,
-    opt_maker_ata: Option<&Pubkey>,
+) -> Transaction {
+    let mut instruction = get_create_order_ix(test_state, order, order_ata, None);
+    let discriminator = cross_chain_escrow_src::instruction::CreateIdempotent::DISCRIMINATOR;
+    instruction.data[..discriminator.len()].copy_from_slice(discriminator);
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.context.payer, &test_state.maker_wallet.keypair],
+        test_state.context.last_blockhash,
+    )
+}
+
+/// Builds a transaction filling the whole single fill order through `create_escrow_simple`
+pub fn get_create_escrow_simple_tx<S: TokenVariant>(
+    test_state: &TestStateBase<SrcProgram, S>,
+    escrow: &Pubkey,
+    escrow_ata: &Pubkey,
 ) -> Transaction {
     let instruction_data =
-        InstructionData::data(&cross_chain_escrow_src::instruction::CancelOrder {});
+        InstructionData::data(&cross_chain_escrow_src::instruction::CreateEscrowSimple {
+            dutch_auction_data: test_state.test_arguments.dutch_auction_data.clone(),
+            affiliate: test_state.test_arguments.affiliate,
+            deadline: test_state.test_arguments.fill_deadline,
+        });
 
-    let maker_ata = if let Some(ata) = opt_maker_ata {
-        *ata
-    } else {
-        let (maker_ata, _) = find_user_ata(test_state);
-        maker_ata
+    let (order, order_ata) = get_order_addresses(test_state);
+    let (whitelist_access, _) =
+        get_whitelist_access_address(&test_state.taker_wallet.keypair.pubkey());
+
+    let instruction: Instruction = Instruction {
+        program_id: cross_chain_escrow_src::id(),
+        accounts: vec![
+            AccountMeta::new(test_state.taker_wallet.keypair.pubkey(), true),
+            AccountMeta::new(whitelist_access, false),
+            AccountMeta::new_readonly(get_fill_authority_address(), false),
+            AccountMeta::new(test_state.maker_wallet.keypair.pubkey(), false),
+            AccountMeta::new_readonly(test_state.token, false),
+            AccountMeta::new_readonly(get_config_address(), false),
+            AccountMeta::new_readonly(get_blocked_mint_address(&test_state.token), false),
+            AccountMeta::new(order, false),
+            AccountMeta::new(order_ata, false),
+            AccountMeta::new(*escrow, false),
+            AccountMeta::new(*escrow_ata, false),
+            AccountMeta::new_readonly(spl_associated_token_id, false),
+            AccountMeta::new_readonly(S::get_token_program_id(), false),
+            AccountMeta::new_readonly(system_program_id, false),
+            AccountMeta::new_readonly(whitelist::id(), false),
+            AccountMeta::new_readonly(instructions_sysvar_id, false),
+            get_tvl_stats_account(test_state, cross_chain_escrow_src::id()),
+        ],
+        data: instruction_data,
     };
 
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.context.payer, &test_state.taker_wallet.keypair],
+        test_state.context.last_blockhash,
+    )
+}
+
+pub fn get_create_and_fill_tx<S: TokenVariant>(
+    test_state: &TestStateBase<SrcProgram, S>,
+    order: &Pubkey,
+    order_ata: &Pubkey,
+    escrow: &Pubkey,
+    escrow_ata: &Pubkey,
+) -> Transaction {
+    let instruction_data =
+        InstructionData::data(&cross_chain_escrow_src::instruction::CreateAndFill {
+            order_hash: test_state.order_hash.to_bytes(),
+            args: get_order_args(test_state),
+            fill_amount: test_state.test_arguments.escrow_amount,
+            merkle_proof: test_state.test_arguments.merkle_proof.clone(),
+            dutch_auction_data: test_state.test_arguments.dutch_auction_data.clone(),
+            affiliate: test_state.test_arguments.affiliate,
+        });
+
+    let (maker_ata, _) = find_user_ata(test_state);
+    let (whitelist_access, _) =
+        get_whitelist_access_address(&test_state.taker_wallet.keypair.pubkey());
+
     let instruction: Instruction = Instruction {
         program_id: cross_chain_escrow_src::id(),
         accounts: vec![
             AccountMeta::new(test_state.maker_wallet.keypair.pubkey(), true),
+            AccountMeta::new(test_state.taker_wallet.keypair.pubkey(), true),
+            AccountMeta::new(whitelist_access, false),
+            AccountMeta::new_readonly(get_fill_authority_address(), false),
             AccountMeta::new_readonly(test_state.token, false),
+            AccountMeta::new_readonly(get_config_address(), false),
+            AccountMeta::new_readonly(get_blocked_mint_address(&test_state.token), false),
+            AccountMeta::new(maker_ata, false),
             AccountMeta::new(*order, false),
             AccountMeta::new(*order_ata, false),
-            AccountMeta::new(maker_ata, false),
+            AccountMeta::new(*escrow, false),
+            AccountMeta::new(*escrow_ata, false),
+            AccountMeta::new_readonly(spl_associated_token_id, false),
             AccountMeta::new_readonly(S::get_token_program_id(), false),
             AccountMeta::new_readonly(system_program_id, false),
+            AccountMeta::new_readonly(whitelist::id(), false),
+            AccountMeta::new_readonly(instructions_sysvar_id, false),
+            get_tvl_stats_account(test_state, cross_chain_escrow_src::id()),
         ],
         data: instruction_data,
     };
-
     Transaction::new_signed_with_payer(
         &[instruction],
         Some(&test_state.payer_kp.pubkey()),
-        &[&test_state.payer_kp, &test_state.maker_wallet.keypair],
+        &[
+            &test_state.context.payer,
+            &test_state.maker_wallet.keypair,
+            &test_state.taker_wallet.keypair,
+        ],
         test_state.context.last_blockhash,
     )
 }
 
-pub fn get_cancel_order_by_resolver_tx<T: EscrowVariant<S>, S: TokenVariant>(
+pub fn get_cancel_order_tx<T: EscrowVariant<S>, S: TokenVariant>(
     test_state: &TestStateBase<T, S>,
     order: &Pubkey,
     order_ata: &Pubkey,
     opt_maker_ata: Option<&Pubkey>,
 ) -> Transaction {
-    let reward_limit = test_state.test_arguments.reward_limit;
-    let instruction_data = InstructionData::data(
-        &cross_chain_escrow_src::instruction::CancelOrderByResolver { reward_limit },
-    );
-    let (whitelist_access, _) =
-        get_whitelist_access_address(&test_state.taker_wallet.keypair.pubkey());
+    let instruction_data =
+        InstructionData::data(&cross_chain_escrow_src::instruction::CancelOrder {});
 
     let maker_ata = if let Some(ata) = opt_maker_ata {
         *ata
@@ -503,26 +806,428 @@ pub fn get_cancel_order_by_resolver_tx<T: EscrowVariant<S>, S: TokenVariant>(
         maker_ata
     };
 
+    let mut accounts = vec![
+        AccountMeta::new(test_state.maker_wallet.keypair.pubkey(), true),
+        AccountMeta::new_readonly(test_state.token, false),
+        AccountMeta::new(*order, false),
+        AccountMeta::new(*order_ata, false),
+        AccountMeta::new(maker_ata, false),
+        AccountMeta::new_readonly(S::get_token_program_id(), false),
+        AccountMeta::new_readonly(system_program_id, false),
+    ];
+    accounts.extend(get_part_authorization_accounts(test_state));
+
     let instruction: Instruction = Instruction {
         program_id: cross_chain_escrow_src::id(),
-        accounts: vec![
-            AccountMeta::new(test_state.taker_wallet.keypair.pubkey(), true),
-            AccountMeta::new_readonly(whitelist_access, false),
-            AccountMeta::new(test_state.maker_wallet.keypair.pubkey(), false),
-            AccountMeta::new_readonly(test_state.token, false),
-            AccountMeta::new(*order, false),
-            AccountMeta::new(*order_ata, false),
-            AccountMeta::new(maker_ata, false),
-            AccountMeta::new_readonly(S::get_token_program_id(), false),
-            AccountMeta::new_readonly(system_program_id, false),
-        ],
+        accounts,
         data: instruction_data,
     };
 
     Transaction::new_signed_with_payer(
         &[instruction],
         Some(&test_state.payer_kp.pubkey()),
-        &[&test_state.payer_kp, &test_state.taker_wallet.keypair],
+        &[&test_state.payer_kp, &test_state.maker_wallet.keypair],
+        test_state.context.last_blockhash,
+    )
+}
+
+pub fn get_withdraw_many_tx<S: TokenVariant>(
+    test_state: &TestStateBase<SrcProgram, S>,
+    escrows: &[(Pubkey, Pubkey, [u8; 32])],
+) -> Transaction {
+    let instruction_data =
+        InstructionData::data(&cross_chain_escrow_src::instruction::WithdrawMany {
+            secrets: escrows.iter().map(|(_, _, secret)| *secret).collect(),
+        });
+
+    let (_, taker_ata) = find_user_ata(test_state);
+
+    let mut accounts = vec![
+        AccountMeta::new(test_state.taker_wallet.keypair.pubkey(), true),
+        AccountMeta::new_readonly(test_state.token, false),
+        AccountMeta::new_readonly(S::get_token_program_id(), false),
+        AccountMeta::new_readonly(system_program_id, false),
+        get_tvl_stats_account(test_state, cross_chain_escrow_src::id()),
+    ];
+    for (escrow, escrow_ata, _) in escrows {
+        accounts.push(AccountMeta::new(*escrow, false));
+        accounts.push(AccountMeta::new(*escrow_ata, false));
+        accounts.push(AccountMeta::new(taker_ata, false));
+    }
+
+    let instruction: Instruction = Instruction {
+        program_id: cross_chain_escrow_src::id(),
+        accounts,
+        data: instruction_data,
+    };
+
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.payer_kp, &test_state.taker_wallet.keypair],
+        test_state.context.last_blockhash,
+    )
+}
+
+pub fn get_extend_order_tx<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &TestStateBase<T, S>,
+    order: &Pubkey,
+    new_expiration_time: u32,
+    signer: &Keypair,
+) -> Transaction {
+    let instruction_data =
+        InstructionData::data(&cross_chain_escrow_src::instruction::ExtendOrder {
+            new_expiration_time,
+        });
+
+    let instruction: Instruction = Instruction {
+        program_id: cross_chain_escrow_src::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(signer.pubkey(), true),
+            AccountMeta::new(*order, false),
+        ],
+        data: instruction_data,
+    };
+
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.payer_kp, signer],
+        test_state.context.last_blockhash,
+    )
+}
+
+pub fn get_transfer_order_ownership_tx<S: TokenVariant>(
+    test_state: &TestStateBase<SrcProgram, S>,
+    order: &Pubkey,
+    new_creator: &Pubkey,
+    signer: &Keypair,
+) -> Transaction {
+    let instruction_data = InstructionData::data(
+        &cross_chain_escrow_src::instruction::TransferOrderOwnership {
+            new_creator: *new_creator,
+        },
+    );
+
+    let instruction: Instruction = Instruction {
+        program_id: cross_chain_escrow_src::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(signer.pubkey(), true),
+            AccountMeta::new(*order, false),
+        ],
+        data: instruction_data,
+    };
+
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.payer_kp, signer],
+        test_state.context.last_blockhash,
+    )
+}
+
+pub fn get_update_cancellation_auction_tx<S: TokenVariant>(
+    test_state: &TestStateBase<SrcProgram, S>,
+    new_max_premium: u64,
+    new_duration: u32,
+    signer: &Keypair,
+) -> Transaction {
+    let instruction_data = InstructionData::data(
+        &cross_chain_escrow_src::instruction::UpdateCancellationAuction {
+            new_max_premium,
+            new_duration,
+        },
+    );
+
+    let (order, order_ata) = get_order_addresses(test_state);
+
+    let instruction: Instruction = Instruction {
+        program_id: cross_chain_escrow_src::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(signer.pubkey(), true),
+            AccountMeta::new_readonly(test_state.token, false),
+            AccountMeta::new(order, false),
+            AccountMeta::new_readonly(order_ata, false),
+            AccountMeta::new_readonly(S::get_token_program_id(), false),
+        ],
+        data: instruction_data,
+    };
+
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.payer_kp, signer],
+        test_state.context.last_blockhash,
+    )
+}
+
+pub fn get_increase_order_tx<S: TokenVariant>(
+    test_state: &TestStateBase<SrcProgram, S>,
+    additional_amount: u64,
+    opt_maker_ata: Option<&Pubkey>,
+    signer: &Keypair,
+) -> Transaction {
+    let instruction_data =
+        InstructionData::data(&cross_chain_escrow_src::instruction::IncreaseOrder {
+            additional_amount,
+        });
+
+    let (order, order_ata) = get_order_addresses(test_state);
+    let maker_ata = if let Some(ata) = opt_maker_ata {
+        *ata
+    } else {
+        let (maker_ata, _) = find_user_ata(test_state);
+        maker_ata
+    };
+
+    let instruction: Instruction = Instruction {
+        program_id: cross_chain_escrow_src::id(),
+        accounts: vec![
+            AccountMeta::new(signer.pubkey(), true),
+            AccountMeta::new_readonly(test_state.token, false),
+            AccountMeta::new(order, false),
+            AccountMeta::new(order_ata, false),
+            AccountMeta::new(maker_ata, false),
+            AccountMeta::new_readonly(S::get_token_program_id(), false),
+            AccountMeta::new_readonly(system_program_id, false),
+        ],
+        data: instruction_data,
+    };
+
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.payer_kp, signer],
+        test_state.context.last_blockhash,
+    )
+}
+
+pub fn get_authorize_part_fill_tx<S: TokenVariant>(
+    test_state: &TestStateBase<SrcProgram, S>,
+    order: &Pubkey,
+    part_index: u64,
+    resolver: &Pubkey,
+    signer: &Keypair,
+) -> Transaction {
+    let instruction_data =
+        InstructionData::data(&cross_chain_escrow_src::instruction::AuthorizePartFill {
+            part_index,
+            resolver: *resolver,
+        });
+
+    let instruction: Instruction = Instruction {
+        program_id: cross_chain_escrow_src::id(),
+        accounts: vec![
+            AccountMeta::new(signer.pubkey(), true),
+            AccountMeta::new(*order, false),
+            AccountMeta::new(
+                get_part_authorization_address(test_state, part_index),
+                false,
+            ),
+            AccountMeta::new_readonly(system_program_id, false),
+        ],
+        data: instruction_data,
+    };
+
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.payer_kp, signer],
+        test_state.context.last_blockhash,
+    )
+}
+
+pub fn get_revoke_part_fill_tx<S: TokenVariant>(
+    test_state: &TestStateBase<SrcProgram, S>,
+    order: &Pubkey,
+    part_index: u64,
+    signer: &Keypair,
+) -> Transaction {
+    let instruction_data =
+        InstructionData::data(&cross_chain_escrow_src::instruction::RevokePartFill { part_index });
+
+    let instruction: Instruction = Instruction {
+        program_id: cross_chain_escrow_src::id(),
+        accounts: vec![
+            AccountMeta::new(signer.pubkey(), true),
+            AccountMeta::new(*order, false),
+            AccountMeta::new(
+                get_part_authorization_address(test_state, part_index),
+                false,
+            ),
+        ],
+        data: instruction_data,
+    };
+
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.payer_kp, signer],
+        test_state.context.last_blockhash,
+    )
+}
+
+pub fn get_preview_cancellation_premium_tx<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &TestStateBase<T, S>,
+    order: &Pubkey,
+    now: Option<u32>,
+) -> Transaction {
+    let instruction_data = InstructionData::data(
+        &cross_chain_escrow_src::instruction::PreviewCancellationPremium { now },
+    );
+
+    let instruction: Instruction = Instruction {
+        program_id: cross_chain_escrow_src::id(),
+        accounts: vec![AccountMeta::new_readonly(*order, false)],
+        data: instruction_data,
+    };
+
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.payer_kp],
+        test_state.context.last_blockhash,
+    )
+}
+
+pub fn get_order_fill_state_tx<S: TokenVariant>(
+    test_state: &TestStateBase<SrcProgram, S>,
+    order: &Pubkey,
+) -> Transaction {
+    let instruction_data =
+        InstructionData::data(&cross_chain_escrow_src::instruction::GetOrderFillState {});
+
+    let instruction: Instruction = Instruction {
+        program_id: cross_chain_escrow_src::id(),
+        accounts: vec![AccountMeta::new_readonly(*order, false)],
+        data: instruction_data,
+    };
+
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.payer_kp],
+        test_state.context.last_blockhash,
+    )
+}
+
+pub fn get_verify_order_root_tx<S: TokenVariant>(
+    test_state: &TestStateBase<SrcProgram, S>,
+    order: &Pubkey,
+    sample_proof: MerkleProof,
+) -> Transaction {
+    let instruction_data =
+        InstructionData::data(&cross_chain_escrow_src::instruction::VerifyOrderRoot {
+            sample_proof,
+        });
+
+    let instruction: Instruction = Instruction {
+        program_id: cross_chain_escrow_src::id(),
+        accounts: vec![AccountMeta::new_readonly(*order, false)],
+        data: instruction_data,
+    };
+
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.payer_kp],
+        test_state.context.last_blockhash,
+    )
+}
+
+pub fn get_preview_dst_amount_tx<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &TestStateBase<T, S>,
+    order: &Pubkey,
+    now: Option<u32>,
+) -> Transaction {
+    let instruction_data =
+        InstructionData::data(&cross_chain_escrow_src::instruction::PreviewDstAmount {
+            fill_amount: test_state.test_arguments.escrow_amount,
+            dutch_auction_data: test_state.test_arguments.dutch_auction_data.clone(),
+            now,
+        });
+
+    let instruction: Instruction = Instruction {
+        program_id: cross_chain_escrow_src::id(),
+        accounts: vec![AccountMeta::new_readonly(*order, false)],
+        data: instruction_data,
+    };
+
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.payer_kp],
+        test_state.context.last_blockhash,
+    )
+}
+
+pub fn get_cancel_order_by_resolver_ix<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &TestStateBase<T, S>,
+    order: &Pubkey,
+    order_ata: &Pubkey,
+    opt_maker_ata: Option<&Pubkey>,
+) -> Instruction {
+    let reward_limit = test_state.test_arguments.reward_limit;
+    let instruction_data = InstructionData::data(
+        &cross_chain_escrow_src::instruction::CancelOrderByResolver { reward_limit },
+    );
+    let (whitelist_access, _) =
+        get_whitelist_access_address(&test_state.taker_wallet.keypair.pubkey());
+
+    let maker_ata = if let Some(ata) = opt_maker_ata {
+        *ata
+    } else {
+        let (maker_ata, _) = find_user_ata(test_state);
+        maker_ata
+    };
+
+    let maker_lamport_recipient = test_state
+        .test_arguments
+        .maker_lamport_recipient
+        .unwrap_or(cross_chain_escrow_src::id());
+
+    let mut accounts = vec![
+        AccountMeta::new(test_state.taker_wallet.keypair.pubkey(), true),
+        AccountMeta::new_readonly(whitelist_access, false),
+        AccountMeta::new(
+            test_state.maker_wallet.keypair.pubkey(),
+            test_state.test_arguments.maker_lamport_recipient.is_some(),
+        ),
+        AccountMeta::new_readonly(test_state.token, false),
+        AccountMeta::new(*order, false),
+        AccountMeta::new(*order_ata, false),
+        AccountMeta::new(maker_ata, false),
+        AccountMeta::new(maker_lamport_recipient, false),
+        AccountMeta::new_readonly(S::get_token_program_id(), false),
+        AccountMeta::new_readonly(system_program_id, false),
+    ];
+    accounts.extend(get_part_authorization_accounts(test_state));
+
+    Instruction {
+        program_id: cross_chain_escrow_src::id(),
+        accounts,
+        data: instruction_data,
+    }
+}
+
+pub fn get_cancel_order_by_resolver_tx<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &TestStateBase<T, S>,
+    order: &Pubkey,
+    order_ata: &Pubkey,
+    opt_maker_ata: Option<&Pubkey>,
+) -> Transaction {
+    let instruction = get_cancel_order_by_resolver_ix(test_state, order, order_ata, opt_maker_ata);
+
+    // The maker has to approve a separate lamport recipient
+    let mut signers = vec![&test_state.payer_kp, &test_state.taker_wallet.keypair];
+    if test_state.test_arguments.maker_lamport_recipient.is_some() {
+        signers.push(&test_state.maker_wallet.keypair);
+    }
+
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &signers,
         test_state.context.last_blockhash,
     )
 }
@@ -542,3 +1247,207 @@ pub async fn create_order<S: TokenVariant>(
 
     (order_pda, order_ata)
 }
+
+/// Creates the order through `create_idempotent` and returns the result, so that the creation
+/// can be retried
+pub async fn create_order_idempotent<S: TokenVariant>(
+    test_state: &mut TestStateBase<SrcProgram, S>,
+) -> Result<(), BanksClientError> {
+    test_state.order_hash = get_order_hash(test_state);
+    let (order_pda, order_ata) = get_order_addresses(test_state);
+    let transaction = get_create_order_idempotent_tx(test_state, &order_pda, &order_ata);
+    test_state.client.process_transaction(transaction).await
+}
+
+pub fn get_reconcile_escrow_tx<S: TokenVariant>(
+    test_state: &TestStateBase<SrcProgram, S>,
+    escrow: &Pubkey,
+    escrow_ata: &Pubkey,
+) -> Transaction {
+    let instruction_data =
+        InstructionData::data(&cross_chain_escrow_src::instruction::ReconcileEscrow {});
+
+    let instruction: Instruction = Instruction {
+        program_id: cross_chain_escrow_src::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(test_state.token, false),
+            AccountMeta::new_readonly(*escrow, false),
+            AccountMeta::new_readonly(*escrow_ata, false),
+            AccountMeta::new_readonly(S::get_token_program_id(), false),
+        ],
+        data: instruction_data,
+    };
+
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.payer_kp],
+        test_state.context.last_blockhash,
+    )
+}
+
+pub fn get_sweep_dust_tx<S: TokenVariant>(
+    test_state: &TestStateBase<SrcProgram, S>,
+    escrow: &Pubkey,
+    treasury: &Pubkey,
+) -> Transaction {
+    let instruction_data = InstructionData::data(&cross_chain_escrow_src::instruction::SweepDust {
+        order_hash: test_state.order_hash.to_bytes(),
+        hashlock: cross_chain_escrow_src::get_escrow_hashlock(
+            test_state.hashlock.to_bytes(),
+            test_state.test_arguments.merkle_proof.clone(),
+        ),
+        taker: test_state.taker_wallet.keypair.pubkey(),
+        amount: test_state.test_arguments.escrow_amount,
+    });
+
+    let (whitelist_state, _) = get_whitelist_state_address();
+
+    let instruction: Instruction = Instruction {
+        program_id: cross_chain_escrow_src::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(test_state.authority_whitelist_kp.pubkey(), true),
+            AccountMeta::new_readonly(whitelist_state, false),
+            AccountMeta::new(*escrow, false),
+            AccountMeta::new(*treasury, false),
+            AccountMeta::new_readonly(system_program_id, false),
+        ],
+        data: instruction_data,
+    };
+
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[
+            &test_state.context.payer,
+            &test_state.authority_whitelist_kp,
+        ],
+        test_state.context.last_blockhash,
+    )
+}
+
+pub fn get_reveal_secret_tx<S: TokenVariant>(
+    test_state: &TestStateBase<SrcProgram, S>,
+    escrow: &Pubkey,
+    secret: [u8; 32],
+) -> Transaction {
+    let instruction_data =
+        InstructionData::data(&cross_chain_escrow_src::instruction::RevealSecret { secret });
+
+    let instruction: Instruction = Instruction {
+        program_id: cross_chain_escrow_src::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(test_state.taker_wallet.keypair.pubkey(), true),
+            AccountMeta::new_readonly(*escrow, false),
+        ],
+        data: instruction_data,
+    };
+
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.payer_kp, &test_state.taker_wallet.keypair],
+        test_state.context.last_blockhash,
+    )
+}
+
+pub fn get_init_tvl_stats_tx<S: TokenVariant>(
+    test_state: &TestStateBase<SrcProgram, S>,
+) -> Transaction {
+    let instruction_data =
+        InstructionData::data(&cross_chain_escrow_src::instruction::InitTvlStats {});
+
+    let instruction: Instruction = Instruction {
+        program_id: cross_chain_escrow_src::id(),
+        accounts: vec![
+            AccountMeta::new(test_state.payer_kp.pubkey(), true),
+            AccountMeta::new_readonly(test_state.token, false),
+            AccountMeta::new(
+                get_tvl_stats_address(cross_chain_escrow_src::id(), &test_state.token),
+                false,
+            ),
+            AccountMeta::new_readonly(system_program_id, false),
+        ],
+        data: instruction_data,
+    };
+
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.payer_kp],
+        test_state.context.last_blockhash,
+    )
+}
+
+/// Turns on tvl tracking for the test token and passes its tvl stats in the following
+/// instructions
+pub async fn enable_tvl_tracking<S: TokenVariant>(test_state: &mut TestStateBase<SrcProgram, S>) {
+    set_track_tvl(test_state, true).await;
+    let transaction = get_init_tvl_stats_tx(test_state);
+    test_state
+        .client
+        .process_transaction(transaction)
+        .await
+        .expect_success();
+    test_state.test_arguments.pass_tvl_stats = true;
+}
+
+pub async fn get_tvl_total_locked<S: TokenVariant>(
+    test_state: &mut TestStateBase<SrcProgram, S>,
+) -> u64 {
+    let tvl_stats = get_tvl_stats_address(cross_chain_escrow_src::id(), &test_state.token);
+    let account = test_state
+        .client
+        .get_account(tvl_stats)
+        .await
+        .unwrap()
+        .expect("Tvl stats account not found");
+    cross_chain_escrow_src::TvlStats::try_deserialize(&mut account.data.as_slice())
+        .unwrap()
+        .total_locked
+}
+
+pub fn get_decode_timelocks_tx<S: TokenVariant>(
+    test_state: &TestStateBase<SrcProgram, S>,
+    timelocks: [u64; 4],
+    deployed_at: u32,
+) -> Transaction {
+    let instruction_data =
+        InstructionData::data(&cross_chain_escrow_src::instruction::DecodeTimelocks {
+            timelocks,
+            deployed_at,
+        });
+
+    let instruction: Instruction = Instruction {
+        program_id: cross_chain_escrow_src::id(),
+        accounts: vec![],
+        data: instruction_data,
+    };
+
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.payer_kp],
+        test_state.context.last_blockhash,
+    )
+}
+
+pub fn get_order_rent_tx<S: TokenVariant>(
+    test_state: &TestStateBase<SrcProgram, S>,
+) -> Transaction {
+    let instruction_data =
+        InstructionData::data(&cross_chain_escrow_src::instruction::GetOrderRent {});
+
+    let instruction: Instruction = Instruction {
+        program_id: cross_chain_escrow_src::id(),
+        accounts: vec![],
+        data: instruction_data,
+    };
+
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.payer_kp.pubkey()),
+        &[&test_state.payer_kp],
+        test_state.context.last_blockhash,
+    )
+}
@@ -1,15 +1,28 @@
 use std::any::TypeId;
 
 use crate::{
+    cpi_wrapper::{wrap_in_cpi, CPI_WRAPPER_PROGRAM_ID},
     helpers::*,
     src_program::{get_order_hash, SrcProgram},
 };
-use anchor_lang::error::ErrorCode;
-use anchor_spl::token::spl_token::{error::TokenError, native_mint::ID as NATIVE_MINT};
-use common::{constants::RESCUE_DELAY, error::EscrowError, timelocks::Stage};
+use anchor_lang::{error::ErrorCode, AnchorDeserialize, Discriminator};
+use anchor_spl::token::spl_token::{
+    error::TokenError, native_mint::ID as NATIVE_MINT, state::AccountState,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine};
+use common::{
+    constants::{BASIS_POINTS, MAX_MEMO_LEN, RESCUE_DELAY},
+    error::EscrowError,
+    timelocks::Stage,
+};
+use cross_chain_escrow_src::get_escrow_hashlock;
 use solana_program::{keccak::hash, program_error::ProgramError};
+use solana_program_test::BanksClient;
 use solana_sdk::{
-    pubkey::Pubkey, signature::Signer, system_instruction::SystemError, transaction::Transaction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction::SystemError,
+    transaction::Transaction,
 };
 
 pub async fn test_escrow_creation_tx_cost<T: EscrowVariant<S>, S: TokenVariant>(
@@ -26,7 +39,7 @@ pub async fn test_escrow_creation_tx_cost<T: EscrowVariant<S>, S: TokenVariant>(
     );
 }
 
-async fn measure_tx_compute_units<T, S>(
+pub async fn measure_tx_compute_units<T, S>(
     test_state: &mut TestStateBase<T, S>,
     tx: Transaction,
 ) -> u64 {
@@ -46,6 +59,59 @@ async fn measure_tx_compute_units<T, S>(
     sim_details.units_consumed
 }
 
+pub async fn simulate_tx_return_data<T, S>(
+    test_state: &mut TestStateBase<T, S>,
+    tx: Transaction,
+) -> Vec<u8> {
+    let result = test_state
+        .client
+        .simulate_transaction(tx)
+        .await
+        .expect("Simulation RPC failed");
+
+    let sim_details = result
+        .simulation_details
+        .expect("Simulation details not found");
+
+    sim_details.return_data.expect("Return data not found").data
+}
+
+/// Simulates the transaction and returns the Anchor events of type `E` it emits
+pub async fn simulate_tx_events<T, S, E: Discriminator + AnchorDeserialize>(
+    test_state: &mut TestStateBase<T, S>,
+    tx: Transaction,
+) -> Vec<E> {
+    simulate_client_tx_events(&mut test_state.client, tx).await
+}
+
+/// Same as `simulate_tx_events`, for test states not built on `TestStateBase`
+pub async fn simulate_client_tx_events<E: Discriminator + AnchorDeserialize>(
+    client: &mut BanksClient,
+    tx: Transaction,
+) -> Vec<E> {
+    let result = client
+        .simulate_transaction(tx)
+        .await
+        .expect("Simulation RPC failed");
+
+    let sim_details = result
+        .simulation_details
+        .expect("Simulation details not found");
+
+    sim_details
+        .logs
+        .iter()
+        .filter_map(|log| log.strip_prefix("Program data: "))
+        .map(|data| {
+            BASE64_STANDARD
+                .decode(data)
+                .expect("Invalid event encoding")
+        })
+        .filter(|data| data.starts_with(E::DISCRIMINATOR))
+        .map(|data| E::try_from_slice(&data[E::DISCRIMINATOR.len()..]).unwrap())
+        .collect()
+}
+
 pub async fn test_escrow_creation<T: EscrowVariant<S>, S: TokenVariant>(
     test_state: &mut TestStateBase<T, S>,
 ) {
@@ -247,6 +313,196 @@ pub async fn test_withdraw_does_not_work_with_wrong_secret<T: EscrowVariant<S>,
     );
 }
 
+pub async fn test_withdraw_with_memo<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &mut TestStateBase<T, S>,
+) {
+    let (escrow, escrow_ata) = create_escrow(test_state).await;
+
+    let memo = "Compliance reference 42";
+    test_state.test_arguments.memo = Some(memo.as_bytes().to_vec());
+    let transaction = T::get_withdraw_tx(test_state, &escrow, &escrow_ata);
+
+    set_time(
+        &mut test_state.context,
+        test_state
+            .test_arguments
+            .src_timelocks
+            .get(Stage::SrcWithdrawal)
+            .unwrap(),
+    );
+
+    let result = test_state
+        .client
+        .simulate_transaction(transaction.clone())
+        .await
+        .expect("Simulation RPC failed");
+    assert!(result
+        .simulation_details
+        .expect("Simulation details not found")
+        .logs
+        .iter()
+        .any(|log| log.contains(memo)));
+
+    test_state
+        .client
+        .process_transaction(transaction)
+        .await
+        .expect_success();
+
+    // Assert escrow_ata was closed
+    assert!(test_state
+        .client
+        .get_account(escrow_ata)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+pub async fn test_withdraw_fails_with_invalid_memo<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &mut TestStateBase<T, S>,
+) {
+    let (escrow, escrow_ata) = create_escrow(test_state).await;
+
+    set_time(
+        &mut test_state.context,
+        test_state
+            .test_arguments
+            .src_timelocks
+            .get(Stage::SrcWithdrawal)
+            .unwrap(),
+    );
+
+    // Neither an overlong memo nor a memo that is not valid UTF-8 is accepted
+    for memo in [vec![b'a'; MAX_MEMO_LEN + 1], vec![0xff]] {
+        test_state.test_arguments.memo = Some(memo);
+        let transaction = T::get_withdraw_tx(test_state, &escrow, &escrow_ata);
+        test_state
+            .client
+            .process_transaction(transaction)
+            .await
+            .expect_error(ProgramError::Custom(EscrowError::InvalidMemo.into()));
+    }
+
+    // A memo of the maximum length is accepted
+    test_state.test_arguments.memo = Some(vec![b'a'; MAX_MEMO_LEN]);
+    let transaction = T::get_withdraw_tx(test_state, &escrow, &escrow_ata);
+    test_state
+        .client
+        .process_transaction(transaction)
+        .await
+        .expect_success();
+}
+
+pub async fn test_withdraw_through_cpi_returns_secret<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &mut TestStateBase<T, S>,
+) {
+    let (escrow, escrow_ata) = create_escrow(test_state).await;
+    let transaction = T::get_withdraw_tx(test_state, &escrow, &escrow_ata);
+    let creator = T::get_escrow_creator_wallet(test_state);
+    let transaction = wrap_in_cpi(
+        test_state,
+        &transaction,
+        &[&test_state.context.payer, &creator.keypair],
+    );
+
+    set_time(
+        &mut test_state.context,
+        test_state
+            .test_arguments
+            .src_timelocks
+            .get(Stage::SrcWithdrawal)
+            .unwrap(),
+    );
+
+    // The wrapper passes on the return data it read after invoking the withdrawal. Trailing
+    // zero bytes of the return data are trimmed by the runtime
+    let mut return_data = test_state
+        .client
+        .simulate_transaction(transaction.clone())
+        .await
+        .expect("Simulation RPC failed")
+        .simulation_details
+        .expect("Simulation details not found")
+        .return_data
+        .expect("Return data not found");
+    assert_eq!(return_data.program_id, CPI_WRAPPER_PROGRAM_ID);
+    return_data.data.resize(32, 0);
+    assert_eq!(return_data.data, test_state.secret);
+
+    test_state
+        .client
+        .process_transaction(transaction)
+        .await
+        .expect_success();
+}
+
+pub async fn test_withdraw_fails_with_insufficient_escrow_balance<
+    T: EscrowVariant<S>,
+    S: TokenVariant,
+>(
+    test_state: &mut TestStateBase<T, S>,
+) {
+    let (escrow, escrow_ata) = create_escrow(test_state).await;
+    set_token_balance(
+        &mut test_state.context,
+        &escrow_ata,
+        test_state.test_arguments.escrow_amount - 1,
+    )
+    .await;
+
+    let transaction = T::get_withdraw_tx(test_state, &escrow, &escrow_ata);
+
+    set_time(
+        &mut test_state.context,
+        test_state
+            .test_arguments
+            .src_timelocks
+            .get(Stage::SrcWithdrawal)
+            .unwrap(),
+    );
+
+    test_state
+        .client
+        .process_transaction(transaction)
+        .await
+        .expect_error(ProgramError::Custom(
+            EscrowError::InsufficientEscrowBalance.into(),
+        ));
+}
+
+// Expects the caller to set `test_state.hashlock` to the hash of a preimage that is not exactly
+// `test_state.secret` (e.g. a truncated or extended version of it) before the escrow is created.
+pub async fn test_withdraw_does_not_work_with_non_32_byte_preimage<
+    T: EscrowVariant<S>,
+    S: TokenVariant,
+>(
+    test_state: &mut TestStateBase<T, S>,
+) {
+    let (escrow, escrow_ata) = create_escrow(test_state).await;
+
+    let transaction = T::get_withdraw_tx(test_state, &escrow, &escrow_ata);
+
+    set_time(
+        &mut test_state.context,
+        test_state
+            .test_arguments
+            .src_timelocks
+            .get(Stage::SrcWithdrawal)
+            .unwrap(),
+    );
+
+    test_state
+        .client
+        .process_transaction(transaction)
+        .await
+        .expect_error(ProgramError::Custom(EscrowError::InvalidSecret.into()));
+
+    assert_eq!(
+        get_token_balance(&mut test_state.context, &escrow_ata).await,
+        DEFAULT_ESCROW_AMOUNT
+    );
+}
+
 pub async fn test_withdraw_does_not_work_with_non_recipient<
     T: EscrowVariant<S>,
     S: TokenVariant,
@@ -348,6 +604,215 @@ pub async fn test_withdraw_does_not_work_after_cancellation_start<
         .expect_error(ProgramError::Custom(EscrowError::InvalidTime.into()))
 }
 
+// Checks that the withdrawer receives `split_bps` of the safety deposit rounded down
+// and the secondary recipient receives the rest, so no lamports are lost or created
+pub async fn test_public_withdraw_with_safety_deposit_split<
+    T: EscrowVariant<S>,
+    S: TokenVariant,
+>(
+    test_state: &mut TestStateBase<T, S>,
+    withdrawer: &Keypair,
+    split_bps: u16,
+) {
+    let secondary_recipient = Keypair::new();
+    // Fund the secondary recipient so that it stays rent exempt after receiving its share
+    transfer_lamports(
+        &mut test_state.context,
+        WALLET_DEFAULT_LAMPORTS,
+        &test_state.payer_kp,
+        &secondary_recipient.pubkey(),
+    )
+    .await;
+    test_state.test_arguments.safety_deposit_split =
+        Some((secondary_recipient.pubkey(), split_bps));
+
+    let (escrow, escrow_ata) = create_escrow(test_state).await;
+    let transaction = T::get_public_withdraw_tx(test_state, &escrow, &escrow_ata, withdrawer);
+
+    set_time(
+        &mut test_state.context,
+        test_state
+            .test_arguments
+            .src_timelocks
+            .get(Stage::SrcPublicWithdrawal)
+            .unwrap(),
+    );
+
+    let escrow_rent = get_min_rent_for_size(&mut test_state.client, T::get_escrow_data_len()).await;
+    let token_account_rent =
+        get_min_rent_for_size(&mut test_state.client, S::get_token_account_size()).await;
+
+    let safety_deposit = test_state.test_arguments.safety_deposit;
+    let withdrawer_share = safety_deposit * split_bps as u64 / BASIS_POINTS;
+    let secondary_share = safety_deposit - withdrawer_share;
+
+    let (_, taker_ata) = find_user_ata(test_state);
+    let rent_recipient = T::get_escrow_creator_wallet(test_state).keypair.pubkey();
+
+    test_state
+        .expect_state_change(
+            transaction,
+            &[
+                native_change(withdrawer.pubkey(), withdrawer_share),
+                native_change(secondary_recipient.pubkey(), secondary_share),
+                native_change(
+                    rent_recipient,
+                    escrow_rent + token_account_rent - safety_deposit,
+                ),
+                token_change(taker_ata, test_state.test_arguments.escrow_amount),
+                account_closure(escrow, true),
+                account_closure(escrow_ata, true),
+            ],
+        )
+        .await;
+}
+
+// Checks that the secondary recipient receives its share of the safety deposit when the
+// withdrawer is also the rent recipient, who then keeps the rest along with the rent
+pub async fn test_public_withdraw_by_rent_recipient_with_split<
+    T: EscrowVariant<S>,
+    S: TokenVariant,
+>(
+    test_state: &mut TestStateBase<T, S>,
+    split_bps: u16,
+) {
+    let secondary_recipient = Keypair::new();
+    // Fund the secondary recipient so that it stays rent exempt after receiving its share
+    transfer_lamports(
+        &mut test_state.context,
+        WALLET_DEFAULT_LAMPORTS,
+        &test_state.payer_kp,
+        &secondary_recipient.pubkey(),
+    )
+    .await;
+    test_state.test_arguments.safety_deposit_split =
+        Some((secondary_recipient.pubkey(), split_bps));
+
+    let withdrawer = T::get_escrow_creator_wallet(test_state).keypair;
+    let (escrow, escrow_ata) = create_escrow(test_state).await;
+    let transaction = T::get_public_withdraw_tx(test_state, &escrow, &escrow_ata, &withdrawer);
+
+    set_time(
+        &mut test_state.context,
+        test_state
+            .test_arguments
+            .src_timelocks
+            .get(Stage::SrcPublicWithdrawal)
+            .unwrap(),
+    );
+
+    let escrow_rent = get_min_rent_for_size(&mut test_state.client, T::get_escrow_data_len()).await;
+    let token_account_rent =
+        get_min_rent_for_size(&mut test_state.client, S::get_token_account_size()).await;
+
+    let safety_deposit = test_state.test_arguments.safety_deposit;
+    let secondary_share = safety_deposit - safety_deposit * split_bps as u64 / BASIS_POINTS;
+
+    let (_, taker_ata) = find_user_ata(test_state);
+
+    test_state
+        .expect_state_change(
+            transaction,
+            &[
+                native_change(secondary_recipient.pubkey(), secondary_share),
+                native_change(
+                    withdrawer.pubkey(),
+                    escrow_rent + token_account_rent - secondary_share,
+                ),
+                token_change(taker_ata, test_state.test_arguments.escrow_amount),
+                account_closure(escrow, true),
+                account_closure(escrow_ata, true),
+            ],
+        )
+        .await;
+}
+
+// Checks that the safety deposit award goes to the reward recipient instead of the withdrawer
+// while the tokens are still paid out as in a regular public withdrawal
+pub async fn test_public_withdraw_with_reward_recipient<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &mut TestStateBase<T, S>,
+    withdrawer: &Keypair,
+) {
+    let reward_recipient = Keypair::new();
+    // Fund the reward recipient so that it stays rent exempt after receiving the award
+    transfer_lamports(
+        &mut test_state.context,
+        WALLET_DEFAULT_LAMPORTS,
+        &test_state.payer_kp,
+        &reward_recipient.pubkey(),
+    )
+    .await;
+    test_state.test_arguments.reward_recipient = Some(reward_recipient.pubkey());
+
+    let (escrow, escrow_ata) = create_escrow(test_state).await;
+    let transaction = T::get_public_withdraw_tx(test_state, &escrow, &escrow_ata, withdrawer);
+
+    set_time(
+        &mut test_state.context,
+        test_state
+            .test_arguments
+            .src_timelocks
+            .get(Stage::SrcPublicWithdrawal)
+            .unwrap(),
+    );
+
+    let escrow_rent = get_min_rent_for_size(&mut test_state.client, T::get_escrow_data_len()).await;
+    let token_account_rent =
+        get_min_rent_for_size(&mut test_state.client, S::get_token_account_size()).await;
+
+    let safety_deposit = test_state.test_arguments.safety_deposit;
+    let (_, taker_ata) = find_user_ata(test_state);
+    let rent_recipient = T::get_escrow_creator_wallet(test_state).keypair.pubkey();
+
+    test_state
+        .expect_state_change(
+            transaction,
+            &[
+                native_change(withdrawer.pubkey(), 0),
+                native_change(reward_recipient.pubkey(), safety_deposit),
+                native_change(
+                    rent_recipient,
+                    escrow_rent + token_account_rent - safety_deposit,
+                ),
+                token_change(taker_ata, test_state.test_arguments.escrow_amount),
+                account_closure(escrow, true),
+                account_closure(escrow_ata, true),
+            ],
+        )
+        .await;
+}
+
+pub async fn test_public_withdraw_fails_with_invalid_safety_deposit_split<
+    T: EscrowVariant<S>,
+    S: TokenVariant,
+>(
+    test_state: &mut TestStateBase<T, S>,
+    withdrawer: &Keypair,
+) {
+    test_state.test_arguments.safety_deposit_split =
+        Some((test_state.payer_kp.pubkey(), BASIS_POINTS as u16 + 1));
+
+    let (escrow, escrow_ata) = create_escrow(test_state).await;
+    let transaction = T::get_public_withdraw_tx(test_state, &escrow, &escrow_ata, withdrawer);
+
+    set_time(
+        &mut test_state.context,
+        test_state
+            .test_arguments
+            .src_timelocks
+            .get(Stage::SrcPublicWithdrawal)
+            .unwrap(),
+    );
+
+    test_state
+        .client
+        .process_transaction(transaction)
+        .await
+        .expect_error(ProgramError::Custom(
+            EscrowError::InvalidSafetyDepositSplit.into(),
+        ));
+}
+
 pub async fn test_public_withdraw_fails_with_wrong_secret<T: EscrowVariant<S>, S: TokenVariant>(
     test_state: &mut TestStateBase<T, S>,
 ) {
@@ -462,30 +927,145 @@ pub async fn test_public_withdraw_fails_before_start_of_public_withdraw<
         .expect_error(ProgramError::Custom(EscrowError::InvalidTime.into()))
 }
 
-pub async fn test_public_withdraw_fails_after_cancellation_start<
-    T: EscrowVariant<S>,
-    S: TokenVariant,
->(
+pub async fn test_public_withdraw_fails_after_cancellation_start<
+    T: EscrowVariant<S>,
+    S: TokenVariant,
+>(
+    test_state: &mut TestStateBase<T, S>,
+) {
+    let (escrow, escrow_ata) = create_escrow(test_state).await;
+    let transaction =
+        T::get_public_withdraw_tx(test_state, &escrow, &escrow_ata, &test_state.context.payer);
+
+    set_time(
+        &mut test_state.context,
+        test_state
+            .test_arguments
+            .src_timelocks
+            .get(Stage::SrcCancellation)
+            .unwrap(),
+    );
+
+    test_state
+        .client
+        .process_transaction(transaction)
+        .await
+        .expect_error(ProgramError::Custom(EscrowError::InvalidTime.into()))
+}
+
+// Checks the flags returned by `escrow_status` at the start of each stage, in the order
+// withdrawable, public withdrawable, cancellable and public cancellable
+pub async fn test_derive_escrow_pda_matches_created_escrow<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &mut TestStateBase<T, S>,
+) {
+    let (escrow, _) = create_escrow(test_state).await;
+
+    let hashlock = get_escrow_hashlock(
+        test_state.hashlock.to_bytes(),
+        test_state.test_arguments.merkle_proof.clone(),
+    );
+    let creator = T::get_escrow_creator_wallet(test_state).keypair.pubkey();
+    let transaction = T::get_derive_escrow_pda_tx(test_state, hashlock, creator);
+    // Both programs return the address followed by the bump. Trailing zero bytes of the return
+    // data are trimmed, so it may come back shorter
+    let mut return_data = test_state
+        .client
+        .simulate_transaction(transaction)
+        .await
+        .expect("Simulation RPC failed")
+        .simulation_details
+        .expect("Simulation details not found")
+        .return_data
+        .map(|return_data| return_data.data)
+        .unwrap_or_default();
+    return_data.resize(33, 0);
+    let (address, bump) = <(Pubkey, u8)>::try_from_slice(&return_data).unwrap();
+
+    assert_eq!(address, escrow);
+    // Both escrow layouts end with the bump the escrow was created with
+    let escrow_data = test_state
+        .client
+        .get_account(escrow)
+        .await
+        .unwrap()
+        .unwrap()
+        .data;
+    assert_eq!(Some(&bump), escrow_data.last());
+}
+
+pub async fn test_escrow_rent_matches_escrow_creation<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &mut TestStateBase<T, S>,
+) {
+    let transaction = T::get_escrow_rent_tx(test_state);
+    let mut return_data = simulate_tx_return_data(test_state, transaction).await;
+    // Trailing zero bytes of the return data, such as the high bytes of the rent, are trimmed
+    return_data.resize(8, 0);
+    let escrow_rent = u64::try_from_slice(&return_data).unwrap();
+
+    // The escrow holds exactly the rent its creation required
+    let (escrow, _) = create_escrow(test_state).await;
+    assert_eq!(
+        test_state.client.get_balance(escrow).await.unwrap(),
+        escrow_rent
+    );
+}
+
+pub async fn test_escrow_status_follows_timelocks<T: EscrowVariant<S>, S: TokenVariant>(
     test_state: &mut TestStateBase<T, S>,
 ) {
     let (escrow, escrow_ata) = create_escrow(test_state).await;
-    let transaction =
-        T::get_public_withdraw_tx(test_state, &escrow, &escrow_ata, &test_state.context.payer);
-
-    set_time(
-        &mut test_state.context,
-        test_state
-            .test_arguments
-            .src_timelocks
-            .get(Stage::SrcCancellation)
-            .unwrap(),
-    );
+    let timelocks = test_state.test_arguments.src_timelocks;
+
+    let expected_statuses = [
+        (test_state.init_timestamp, [false, false, false, false]),
+        (
+            timelocks.get(Stage::SrcWithdrawal).unwrap(),
+            [true, false, false, false],
+        ),
+        (
+            timelocks.get(Stage::SrcPublicWithdrawal).unwrap(),
+            [true, true, false, false],
+        ),
+        (
+            timelocks.get(Stage::SrcCancellation).unwrap() - 1,
+            [true, true, false, false],
+        ),
+        (
+            timelocks.get(Stage::SrcCancellation).unwrap(),
+            [false, false, true, false],
+        ),
+        (
+            timelocks.get(Stage::SrcPublicCancellation).unwrap(),
+            [false, false, true, true],
+        ),
+    ];
+    for (time, expected_status) in expected_statuses {
+        set_time(&mut test_state.context, time);
+        let transaction = T::get_escrow_status_tx(test_state, &escrow);
+        // Both programs return the flags as four bools. Trailing zero bytes of the return data
+        // are trimmed, so it may come back shorter or empty
+        let mut return_data = test_state
+            .client
+            .simulate_transaction(transaction)
+            .await
+            .expect("Simulation RPC failed")
+            .simulation_details
+            .expect("Simulation details not found")
+            .return_data
+            .map(|return_data| return_data.data)
+            .unwrap_or_default();
+        return_data.resize(4, 0);
+        let status = <[bool; 4]>::try_from_slice(&return_data).unwrap();
+        assert_eq!(status, expected_status, "unexpected status at {time}");
+    }
 
+    // The escrow is indeed cancellable once the status says so
+    let transaction = T::get_cancel_tx(test_state, &escrow, &escrow_ata);
     test_state
         .client
         .process_transaction(transaction)
         .await
-        .expect_error(ProgramError::Custom(EscrowError::InvalidTime.into()))
+        .expect_success();
 }
 
 pub async fn test_cancel<T: EscrowVariant<S> + 'static, S: TokenVariant>(
@@ -602,6 +1182,40 @@ pub async fn test_cannot_cancel_before_cancellation_start<T: EscrowVariant<S>, S
         .expect_error(ProgramError::Custom(EscrowError::InvalidTime.into()))
 }
 
+pub async fn test_cancel_fails_with_insufficient_escrow_balance<
+    T: EscrowVariant<S>,
+    S: TokenVariant,
+>(
+    test_state: &mut TestStateBase<T, S>,
+) {
+    let (escrow, escrow_ata) = create_escrow(test_state).await;
+    set_token_balance(
+        &mut test_state.context,
+        &escrow_ata,
+        test_state.test_arguments.escrow_amount - 1,
+    )
+    .await;
+
+    let transaction = T::get_cancel_tx(test_state, &escrow, &escrow_ata);
+
+    set_time(
+        &mut test_state.context,
+        test_state
+            .test_arguments
+            .src_timelocks
+            .get(Stage::SrcCancellation)
+            .unwrap(),
+    );
+
+    test_state
+        .client
+        .process_transaction(transaction)
+        .await
+        .expect_error(ProgramError::Custom(
+            EscrowError::InsufficientEscrowBalance.into(),
+        ));
+}
+
 pub async fn test_rescue_all_tokens_and_close_ata<
     T: EscrowVariant<S> + 'static,
     S: TokenVariant,
@@ -668,6 +1282,196 @@ pub async fn test_rescue_all_tokens_and_close_ata<
         .is_none());
 }
 
+pub async fn test_rescue_fails_with_frozen_escrow_ata<
+    T: EscrowVariant<S> + 'static,
+    S: TokenVariant,
+>(
+    test_state: &mut TestStateBase<T, S>,
+) {
+    let (escrow, _) = create_escrow(test_state).await;
+
+    let token_to_rescue = S::deploy_spl_token(&mut test_state.context).await.pubkey();
+    let escrow_ata =
+        S::initialize_spl_associated_account(&mut test_state.context, &token_to_rescue, &escrow)
+            .await;
+
+    S::mint_spl_tokens(
+        &mut test_state.context,
+        &token_to_rescue,
+        &escrow_ata,
+        &test_state.payer_kp.pubkey(),
+        &test_state.payer_kp,
+        test_state.test_arguments.rescue_amount,
+    )
+    .await;
+
+    // The test mints have no freeze authority, so set the frozen state of the account directly.
+    // The state follows the mint, owner, amount and delegate in both token program layouts
+    let mut escrow_ata_account = test_state
+        .client
+        .get_account(escrow_ata)
+        .await
+        .unwrap()
+        .unwrap();
+    escrow_ata_account.data[108] = AccountState::Frozen as u8;
+    test_state
+        .context
+        .set_account(&escrow_ata, &escrow_ata_account.into());
+
+    let wallet = if TypeId::of::<T>() == TypeId::of::<SrcProgram>() {
+        test_state.taker_wallet.keypair.pubkey()
+    } else {
+        test_state.maker_wallet.keypair.pubkey()
+    };
+
+    let taker_ata =
+        S::initialize_spl_associated_account(&mut test_state.context, &token_to_rescue, &wallet)
+            .await;
+
+    let transaction = T::get_rescue_funds_tx(
+        test_state,
+        &escrow,
+        &token_to_rescue,
+        &escrow_ata,
+        &taker_ata,
+    );
+
+    set_time(
+        &mut test_state.context,
+        test_state.init_timestamp + RESCUE_DELAY + 100,
+    );
+    test_state
+        .client
+        .process_transaction(transaction)
+        .await
+        .expect_error(ProgramError::Custom(EscrowError::EscrowAtaFrozen.into()));
+}
+
+pub async fn test_rescue_all_tokens_with_rent_recipient<
+    T: EscrowVariant<S> + 'static,
+    S: TokenVariant,
+>(
+    test_state: &mut TestStateBase<T, S>,
+) {
+    let rent_recipient = Keypair::new().pubkey();
+    test_state.test_arguments.rent_recipient = Some(rent_recipient);
+    let (escrow, _) = create_escrow(test_state).await;
+
+    let token_to_rescue = S::deploy_spl_token(&mut test_state.context).await.pubkey();
+    let escrow_ata =
+        S::initialize_spl_associated_account(&mut test_state.context, &token_to_rescue, &escrow)
+            .await;
+
+    S::mint_spl_tokens(
+        &mut test_state.context,
+        &token_to_rescue,
+        &escrow_ata,
+        &test_state.payer_kp.pubkey(),
+        &test_state.payer_kp,
+        test_state.test_arguments.rescue_amount,
+    )
+    .await;
+
+    let wallet = if TypeId::of::<T>() == TypeId::of::<SrcProgram>() {
+        test_state.taker_wallet.keypair.pubkey()
+    } else {
+        test_state.maker_wallet.keypair.pubkey()
+    };
+
+    let taker_ata =
+        S::initialize_spl_associated_account(&mut test_state.context, &token_to_rescue, &wallet)
+            .await;
+
+    let transaction = T::get_rescue_funds_tx(
+        test_state,
+        &escrow,
+        &token_to_rescue,
+        &escrow_ata,
+        &taker_ata,
+    );
+    let token_account_rent =
+        get_min_rent_for_size(&mut test_state.client, S::get_token_account_size()).await;
+
+    set_time(
+        &mut test_state.context,
+        test_state.init_timestamp + RESCUE_DELAY + 100,
+    );
+    test_state
+        .expect_state_change(
+            transaction,
+            &[
+                native_change(rent_recipient, token_account_rent),
+                native_change(wallet, 0),
+                token_change(taker_ata, test_state.test_arguments.rescue_amount),
+            ],
+        )
+        .await;
+
+    // Assert escrow_ata was closed
+    assert!(test_state
+        .client
+        .get_account(escrow_ata)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+pub async fn test_rescue_fails_with_non_system_rent_recipient<
+    T: EscrowVariant<S> + 'static,
+    S: TokenVariant,
+>(
+    test_state: &mut TestStateBase<T, S>,
+) {
+    // The mint is owned by the token program
+    test_state.test_arguments.rent_recipient = Some(test_state.token);
+    let (escrow, _) = create_escrow(test_state).await;
+
+    let token_to_rescue = S::deploy_spl_token(&mut test_state.context).await.pubkey();
+    let escrow_ata =
+        S::initialize_spl_associated_account(&mut test_state.context, &token_to_rescue, &escrow)
+            .await;
+
+    S::mint_spl_tokens(
+        &mut test_state.context,
+        &token_to_rescue,
+        &escrow_ata,
+        &test_state.payer_kp.pubkey(),
+        &test_state.payer_kp,
+        test_state.test_arguments.rescue_amount,
+    )
+    .await;
+
+    let wallet = if TypeId::of::<T>() == TypeId::of::<SrcProgram>() {
+        test_state.taker_wallet.keypair.pubkey()
+    } else {
+        test_state.maker_wallet.keypair.pubkey()
+    };
+
+    let taker_ata =
+        S::initialize_spl_associated_account(&mut test_state.context, &token_to_rescue, &wallet)
+            .await;
+
+    let transaction = T::get_rescue_funds_tx(
+        test_state,
+        &escrow,
+        &token_to_rescue,
+        &escrow_ata,
+        &taker_ata,
+    );
+
+    set_time(
+        &mut test_state.context,
+        test_state.init_timestamp + RESCUE_DELAY + 100,
+    );
+    test_state
+        .client
+        .process_transaction(transaction)
+        .await
+        .expect_error(ProgramError::Custom(
+            ErrorCode::AccountNotSystemOwned.into(),
+        ));
+}
+
 pub async fn test_rescue_part_of_tokens_and_not_close_ata<
     T: EscrowVariant<S> + 'static,
     S: TokenVariant,
@@ -1016,7 +1820,9 @@ pub async fn test_escrow_creation_fails_if_token_is_not_native<
 mod test {
     use crate::helpers::*;
     use crate::wrap_entry;
+    use common::error::EscrowError;
     use common::escrow::{uni_transfer, UniTransferParams};
+    use common::timelocks::{Stage, DST_STAGES, SRC_STAGES};
     use solana_program_test::tokio;
     use solana_sdk::{signature::Signer, transaction::Transaction};
 
@@ -1025,7 +1831,7 @@ mod test {
         prelude::{AccountInfo, AccountMeta, Interface, Pubkey},
     };
     use anchor_spl::token::spl_token::{native_mint::ID as NATIVE_MINT, ID as spl_program_id};
-    use solana_program::instruction::Instruction;
+    use solana_program::{instruction::Instruction, program_error::ProgramError};
     use solana_program_test::{processor, BanksClient, ProgramTest, ProgramTestContext};
     use solana_sdk::{entrypoint::ProgramResult, system_program::ID as system_program_id};
 
@@ -1124,4 +1930,108 @@ mod test {
             .await
             .expect_success();
     }
+
+    fn expect_non_monotonic(result: anchor_lang::Result<()>) {
+        assert_eq!(
+            ProgramError::from(result.expect_err("Expected timelocks to be rejected")),
+            ProgramError::Custom(EscrowError::NonMonotonicTimelocks.into())
+        );
+    }
+
+    #[test]
+    fn test_timelocks_validate_monotonic_accepts_default_timelocks() {
+        let timelocks = init_timelocks(10, 20, 30, 40, 10, 20, 30, 1_000);
+        timelocks.validate_monotonic(&SRC_STAGES).unwrap();
+        timelocks.validate_monotonic(&DST_STAGES).unwrap();
+    }
+
+    // Dst escrows only carry dst stages, with the dst public cancellation in the slot of the src
+    // public cancellation, so zeroed src stages must not affect the dst check
+    #[test]
+    fn test_timelocks_validate_monotonic_checks_only_given_stages() {
+        let timelocks = init_timelocks(0, 0, 0, 40, 10, 20, 30, 0);
+        timelocks.validate_monotonic(&DST_STAGES).unwrap();
+        expect_non_monotonic(timelocks.validate_monotonic(&SRC_STAGES));
+    }
+
+    #[test]
+    fn test_timelocks_tighten_moves_only_given_stages() {
+        let timelocks = init_timelocks(10, 20, 30, 40, 10, 20, 30, 1_000)
+            .tighten(&SRC_STAGES, &[5, 20, 25, 40])
+            .unwrap();
+        assert_eq!(timelocks.get(Stage::SrcWithdrawal).unwrap(), 1_005);
+        assert_eq!(timelocks.get(Stage::SrcPublicWithdrawal).unwrap(), 1_020);
+        assert_eq!(timelocks.get(Stage::SrcCancellation).unwrap(), 1_025);
+        assert_eq!(timelocks.get(Stage::SrcPublicCancellation).unwrap(), 1_040);
+        assert_eq!(timelocks.get(Stage::DstWithdrawal).unwrap(), 1_010);
+        assert_eq!(timelocks.get(Stage::DstCancellation).unwrap(), 1_030);
+    }
+
+    #[test]
+    fn test_timelocks_tighten_rejects_later_stage() {
+        let result = init_timelocks(10, 20, 30, 40, 10, 20, 30, 1_000)
+            .tighten(&SRC_STAGES, &[10, 21, 30, 40]);
+        assert_eq!(
+            ProgramError::from(result.err().expect("Expected timelocks to be rejected")),
+            ProgramError::Custom(EscrowError::TimelocksLooserThanOrder.into())
+        );
+    }
+
+    #[test]
+    fn test_timelocks_dst_public_cancellation_is_stored_in_src_public_cancellation_slot() {
+        let timelocks = init_timelocks(0, 0, 0, 40, 10, 20, 30, 1_000);
+        assert_eq!(timelocks.get(Stage::DstPublicCancellation).unwrap(), 1_040);
+        assert_eq!(timelocks.get(Stage::SrcPublicCancellation).unwrap(), 1_040);
+    }
+
+    #[test]
+    fn test_timelocks_validate_monotonic_ignores_deployed_at() {
+        // Absolute stage times would overflow, but the offsets are still ordered
+        let timelocks = init_timelocks(10, 20, 30, 40, 10, 20, 30, u32::MAX);
+        timelocks.validate_monotonic(&SRC_STAGES).unwrap();
+        timelocks.validate_monotonic(&DST_STAGES).unwrap();
+    }
+
+    #[test]
+    fn test_timelocks_validate_monotonic_rejects_public_withdrawal_after_cancellation() {
+        expect_non_monotonic(
+            init_timelocks(10, 40, 30, 50, 0, 0, 0, 0).validate_monotonic(&SRC_STAGES),
+        );
+        expect_non_monotonic(
+            init_timelocks(0, 0, 0, 50, 10, 40, 30, 0).validate_monotonic(&DST_STAGES),
+        );
+    }
+
+    #[test]
+    fn test_timelocks_validate_monotonic_rejects_withdrawal_after_public_withdrawal() {
+        expect_non_monotonic(
+            init_timelocks(25, 20, 30, 40, 0, 0, 0, 0).validate_monotonic(&SRC_STAGES),
+        );
+        expect_non_monotonic(
+            init_timelocks(0, 0, 0, 40, 25, 20, 30, 0).validate_monotonic(&DST_STAGES),
+        );
+    }
+
+    #[test]
+    fn test_timelocks_validate_monotonic_rejects_public_cancellation_before_cancellation() {
+        expect_non_monotonic(
+            init_timelocks(10, 20, 30, 25, 0, 0, 0, 0).validate_monotonic(&SRC_STAGES),
+        );
+        expect_non_monotonic(
+            init_timelocks(0, 0, 0, 25, 10, 20, 30, 0).validate_monotonic(&DST_STAGES),
+        );
+    }
+
+    #[test]
+    fn test_timelocks_validate_monotonic_rejects_equal_stages() {
+        expect_non_monotonic(
+            init_timelocks(10, 20, 20, 40, 0, 0, 0, 0).validate_monotonic(&SRC_STAGES),
+        );
+        expect_non_monotonic(
+            init_timelocks(0, 0, 0, 40, 10, 10, 30, 0).validate_monotonic(&DST_STAGES),
+        );
+        expect_non_monotonic(
+            init_timelocks(0, 0, 0, 0, 0, 0, 0, 0).validate_monotonic(&DST_STAGES),
+        );
+    }
 }
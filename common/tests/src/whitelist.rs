@@ -1,5 +1,6 @@
 use anchor_lang::prelude::{AccountInfo, AccountMeta};
-use anchor_lang::InstructionData;
+use anchor_lang::{AccountDeserialize, InstructionData};
+use anchor_spl::{token::ID as spl_program_id, token_2022::ID as spl2022_program_id};
 use solana_program::{instruction::Instruction, pubkey::Pubkey};
 use solana_program_test::processor;
 
@@ -22,6 +23,11 @@ pub fn get_whitelist_state_address() -> (Pubkey, Pubkey) {
     (whitelist_state, program_id)
 }
 
+pub fn get_config_address() -> Pubkey {
+    let (config, _) = Pubkey::find_program_address(&[b"config"], &whitelist::id());
+    config
+}
+
 pub fn get_whitelist_access_address(user: &Pubkey) -> (Pubkey, u8) {
     let program_id = whitelist::id();
     let (whitelist_access, bump) =
@@ -29,6 +35,18 @@ pub fn get_whitelist_access_address(user: &Pubkey) -> (Pubkey, u8) {
     (whitelist_access, bump)
 }
 
+pub fn get_blocked_mint_address(mint: &Pubkey) -> Pubkey {
+    let (blocked_mint, _) =
+        Pubkey::find_program_address(&[b"blocked_mint", mint.as_ref()], &whitelist::id());
+    blocked_mint
+}
+
+pub fn get_fill_authority_address() -> Pubkey {
+    let (fill_authority, _) =
+        Pubkey::find_program_address(&[b"fill_authority"], &whitelist::ESCROW_SRC_PROGRAM_ID);
+    fill_authority
+}
+
 pub fn init_whitelist_data<T: EscrowVariant<S>, S: TokenVariant>(
     test_state: &TestStateBase<T, S>,
 ) -> (Pubkey, Transaction) {
@@ -67,6 +85,91 @@ pub async fn init_whitelist<T: EscrowVariant<S>, S: TokenVariant>(
     whitelist_state
 }
 
+pub fn init_config_data<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &TestStateBase<T, S>,
+) -> (Pubkey, Transaction) {
+    let (whitelist_state, program_id) = get_whitelist_state_address();
+    let config = get_config_address();
+
+    let instruction_data = InstructionData::data(&whitelist::instruction::InitializeConfig {});
+
+    let instruction: Instruction = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(test_state.authority_whitelist_kp.pubkey(), true),
+            AccountMeta::new_readonly(whitelist_state, false),
+            AccountMeta::new(config, false),
+            AccountMeta::new_readonly(system_program_id, false),
+        ],
+        data: instruction_data,
+    };
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.authority_whitelist_kp.pubkey()),
+        &[&test_state.authority_whitelist_kp],
+        test_state.context.last_blockhash,
+    );
+
+    (config, transaction)
+}
+
+pub async fn init_config<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &TestStateBase<T, S>,
+) -> Pubkey {
+    let (config, tx) = init_config_data(test_state);
+    test_state
+        .client
+        .process_transaction(tx)
+        .await
+        .expect_success();
+    config
+}
+
+pub async fn set_supported_token_programs<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &TestStateBase<T, S>,
+    token_programs: Vec<Pubkey>,
+) {
+    let (whitelist_state, program_id) = get_whitelist_state_address();
+
+    let instruction_data =
+        InstructionData::data(&whitelist::instruction::SetSupportedTokenPrograms {
+            token_programs,
+        });
+
+    let instruction: Instruction = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(test_state.authority_whitelist_kp.pubkey(), true),
+            AccountMeta::new_readonly(whitelist_state, false),
+            AccountMeta::new(get_config_address(), false),
+        ],
+        data: instruction_data,
+    };
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.authority_whitelist_kp.pubkey()),
+        &[&test_state.authority_whitelist_kp],
+        test_state.context.last_blockhash,
+    );
+    test_state
+        .client
+        .process_transaction(transaction)
+        .await
+        .expect_success();
+}
+
+/// Leaves every default token program supported except the given one.
+pub async fn unlist_token_program<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &TestStateBase<T, S>,
+    token_program: &Pubkey,
+) {
+    let token_programs = [spl_program_id, spl2022_program_id]
+        .into_iter()
+        .filter(|program| program != token_program)
+        .collect();
+    set_supported_token_programs(test_state, token_programs).await;
+}
+
 pub fn register_deregister_data<T: EscrowVariant<S>, S: TokenVariant>(
     test_state: &TestStateBase<T, S>,
     whitelisted_account: Pubkey,
@@ -99,7 +202,8 @@ pub async fn register<T: EscrowVariant<S>, S: TokenVariant>(
     whitelisted_account: Pubkey,
 ) -> Pubkey {
     let instruction_data = InstructionData::data(&whitelist::instruction::Register {
-        _user: whitelisted_account,
+        user: whitelisted_account,
+        metadata: None,
     });
 
     let (whitelist_access, tx) =
@@ -134,9 +238,164 @@ pub async fn prepare_resolvers<T: EscrowVariant<S>, S: TokenVariant>(
     test_state: &TestStateBase<T, S>,
     resolvers: &[Pubkey],
 ) {
-    init_whitelist(test_state).await;
-
+    // The whitelist itself is initialized during the test state setup
     for resolver in resolvers {
         register(test_state, *resolver).await;
     }
 }
+
+pub async fn block_mint<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &TestStateBase<T, S>,
+    mint: Pubkey,
+) {
+    let instruction_data =
+        InstructionData::data(&whitelist::instruction::BlockMint { _mint: mint });
+    process_blocked_mint_tx(test_state, mint, instruction_data).await;
+}
+
+pub async fn unblock_mint<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &TestStateBase<T, S>,
+    mint: Pubkey,
+) {
+    let instruction_data =
+        InstructionData::data(&whitelist::instruction::UnblockMint { _mint: mint });
+    process_blocked_mint_tx(test_state, mint, instruction_data).await;
+}
+
+async fn process_blocked_mint_tx<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &TestStateBase<T, S>,
+    mint: Pubkey,
+    instruction_data: Vec<u8>,
+) {
+    let (whitelist_state, program_id) = get_whitelist_state_address();
+
+    let instruction: Instruction = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(test_state.authority_whitelist_kp.pubkey(), true),
+            AccountMeta::new_readonly(whitelist_state, false),
+            AccountMeta::new(get_blocked_mint_address(&mint), false),
+            AccountMeta::new_readonly(system_program_id, false),
+        ],
+        data: instruction_data,
+    };
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.authority_whitelist_kp.pubkey()),
+        &[&test_state.authority_whitelist_kp],
+        test_state.context.last_blockhash,
+    );
+    test_state
+        .client
+        .process_transaction(transaction)
+        .await
+        .expect_success();
+}
+
+pub async fn set_require_top_level<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &TestStateBase<T, S>,
+    require_top_level: bool,
+) {
+    let (whitelist_state, program_id) = get_whitelist_state_address();
+    let instruction_data =
+        InstructionData::data(&whitelist::instruction::SetRequireTopLevel { require_top_level });
+
+    let instruction: Instruction = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(test_state.authority_whitelist_kp.pubkey(), true),
+            AccountMeta::new_readonly(whitelist_state, false),
+            AccountMeta::new(get_config_address(), false),
+        ],
+        data: instruction_data,
+    };
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.authority_whitelist_kp.pubkey()),
+        &[&test_state.authority_whitelist_kp],
+        test_state.context.last_blockhash,
+    );
+    test_state
+        .client
+        .process_transaction(transaction)
+        .await
+        .expect_success();
+}
+
+pub async fn set_track_tvl<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &TestStateBase<T, S>,
+    track_tvl: bool,
+) {
+    let (whitelist_state, program_id) = get_whitelist_state_address();
+    let instruction_data =
+        InstructionData::data(&whitelist::instruction::SetTrackTvl { track_tvl });
+
+    let instruction: Instruction = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(test_state.authority_whitelist_kp.pubkey(), true),
+            AccountMeta::new_readonly(whitelist_state, false),
+            AccountMeta::new(get_config_address(), false),
+        ],
+        data: instruction_data,
+    };
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.authority_whitelist_kp.pubkey()),
+        &[&test_state.authority_whitelist_kp],
+        test_state.context.last_blockhash,
+    );
+    test_state
+        .client
+        .process_transaction(transaction)
+        .await
+        .expect_success();
+}
+
+pub async fn set_max_window_volume<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &TestStateBase<T, S>,
+    resolver: Pubkey,
+    max_window_volume: u64,
+) {
+    let (whitelist_state, program_id) = get_whitelist_state_address();
+    let (whitelist_access, _) = get_whitelist_access_address(&resolver);
+    let instruction_data = InstructionData::data(&whitelist::instruction::SetMaxWindowVolume {
+        _user: resolver,
+        max_window_volume,
+    });
+
+    let instruction: Instruction = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(test_state.authority_whitelist_kp.pubkey(), true),
+            AccountMeta::new_readonly(whitelist_state, false),
+            AccountMeta::new(whitelist_access, false),
+        ],
+        data: instruction_data,
+    };
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.authority_whitelist_kp.pubkey()),
+        &[&test_state.authority_whitelist_kp],
+        test_state.context.last_blockhash,
+    );
+    test_state
+        .client
+        .process_transaction(transaction)
+        .await
+        .expect_success();
+}
+
+pub async fn get_resolver_access<T: EscrowVariant<S>, S: TokenVariant>(
+    test_state: &mut TestStateBase<T, S>,
+    resolver: &Pubkey,
+) -> whitelist::ResolverAccess {
+    let (resolver_access, _) = get_whitelist_access_address(resolver);
+    let account = test_state
+        .client
+        .get_account(resolver_access)
+        .await
+        .unwrap()
+        .unwrap();
+    whitelist::ResolverAccess::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
@@ -1,13 +1,17 @@
 use anchor_lang::prelude::*;
 use anchor_spl::associated_token::{AssociatedToken, ID as ASSOCIATED_TOKEN_PROGRAM_ID};
+use anchor_spl::memo::Memo;
 use anchor_spl::token::spl_token::native_mint::ID as NATIVE_MINT;
 use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 pub use common::constants;
 use common::{
     error::EscrowError,
-    escrow::{process_payout, uni_transfer, UniTransferParams},
-    timelocks::{Stage, Timelocks},
-    utils::get_current_timestamp,
+    escrow::{
+        attach_memo, get_amount_net_of_fee, has_permanent_delegate, lock_tvl, uni_transfer,
+        unlock_tvl, UniTransferParams,
+    },
+    timelocks::{Stage, Timelocks, DST_STAGES},
+    utils::{get_current_timestamp, get_order_hash_with_dst_terms},
 };
 use primitive_types::U256;
 
@@ -30,9 +34,35 @@ pub mod cross_chain_escrow_dst {
         timelocks: [u64; 4],
         src_cancellation_timestamp: u32,
         asset_is_native: bool,
+        order_params_hash: [u8; 32], // Hash of the src order parameters other than the dst terms
     ) -> Result<()> {
-        let updated_timelocks =
-            Timelocks(U256(timelocks)).set_deployed_at(get_current_timestamp()?);
+        // The order hash commits to the dst terms signed by the maker, which the escrow must match
+        require!(
+            get_order_hash_with_dst_terms(
+                &order_params_hash,
+                &ctx.accounts.mint.key().to_bytes(),
+                safety_deposit as u128,
+            ) == order_hash,
+            EscrowError::DstParamsMismatch
+        );
+        // Tokens of a mint with a permanent delegate can be moved out of the escrow ATA
+        require!(
+            !has_permanent_delegate(&ctx.accounts.mint)?,
+            EscrowError::UnsupportedMintExtension
+        );
+
+        let now = get_current_timestamp()?;
+        // An escrow referencing a src side that is already cancellable leaves the recipient
+        // no window to claim
+        require!(
+            src_cancellation_timestamp > now,
+            EscrowError::SrcCancellationInPast
+        );
+
+        let updated_timelocks = Timelocks(U256(timelocks)).set_deployed_at(now);
+        updated_timelocks.validate_durations(&DST_STAGES, now)?;
+        updated_timelocks.validate_monotonic(&DST_STAGES)?;
+
         let cancellation_start = updated_timelocks.get(Stage::DstCancellation)?;
 
         require!(
@@ -46,13 +76,18 @@ pub mod cross_chain_escrow_dst {
             EscrowError::ZeroAmountOrDeposit
         );
 
-        // Verify that safety_deposit is less than escrow rent_exempt_reserve
+        // Verify that safety_deposit is less than escrow rent_exempt_reserve. The reserve depends
+        // on the escrow layout, so both values are logged for integrators to adjust the deposit
         let rent_exempt_reserve =
             Rent::get()?.minimum_balance(EscrowDst::INIT_SPACE + constants::DISCRIMINATOR_BYTES);
-        require!(
-            safety_deposit <= rent_exempt_reserve,
-            EscrowError::SafetyDepositTooLarge
-        );
+        if safety_deposit > rent_exempt_reserve {
+            msg!(
+                "Safety deposit: {}, rent-exempt reserve: {}",
+                safety_deposit,
+                rent_exempt_reserve
+            );
+            return err!(EscrowError::SafetyDepositTooLarge);
+        }
 
         require!(
             ctx.accounts.mint.key() == NATIVE_MINT || !asset_is_native,
@@ -61,9 +96,9 @@ pub mod cross_chain_escrow_dst {
 
         // Check if token is native (SOL)
         if asset_is_native {
-            // Transfer native tokens from creator to escrow_ata. Wrapping is not required
-            // because the protocol must return the tokens in the same form in which they were received.
-            // Therefore, there is no point in performing unnecessary wrapping/unwrapping of tokens.
+            // Transfer native tokens from creator to escrow_ata and wrap them, so that the token
+            // balance of the escrow ATA reflects the deposit. The tokens are still paid out as
+            // lamports, as closing the escrow ATA unwraps them.
             uni_transfer(
                 &UniTransferParams::NativeTransfer {
                     from: ctx.accounts.creator.to_account_info(),
@@ -73,6 +108,13 @@ pub mod cross_chain_escrow_dst {
                 },
                 None,
             )?;
+
+            anchor_spl::token::sync_native(CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token::SyncNative {
+                    account: ctx.accounts.escrow_ata.to_account_info(),
+                },
+            ))?;
         } else {
             // Transfer SPL tokens (WSOL included)
             uni_transfer(
@@ -93,6 +135,15 @@ pub mod cross_chain_escrow_dst {
             )?;
         }
 
+        let tvl_tracked = lock_tvl(
+            ctx.accounts.config.track_tvl,
+            ctx.accounts
+                .tvl_stats
+                .as_deref_mut()
+                .map(|tvl_stats| &mut tvl_stats.total_locked),
+            amount,
+        )?;
+
         ctx.accounts.escrow.set_inner(EscrowDst {
             order_hash,
             hashlock,
@@ -103,13 +154,20 @@ pub mod cross_chain_escrow_dst {
             safety_deposit,
             timelocks: updated_timelocks.get_timelocks(),
             asset_is_native,
+            tvl_tracked,
+            funded_amount: get_amount_net_of_fee(&ctx.accounts.mint, amount)?,
             bump: ctx.bumps.escrow,
         });
 
         Ok(())
     }
 
-    pub fn withdraw(ctx: Context<Withdraw>, secret: [u8; 32]) -> Result<()> {
+    /// The secret is set as return data for callers composing on the reveal through CPI
+    pub fn withdraw(
+        ctx: Context<Withdraw>,
+        secret: [u8; 32],
+        memo: Option<Vec<u8>>,
+    ) -> Result<[u8; 32]> {
         let now = get_current_timestamp()?;
         let timelocks = Timelocks(U256(ctx.accounts.escrow.timelocks));
         require!(
@@ -121,21 +179,48 @@ pub mod cross_chain_escrow_dst {
         // In a standard withdrawal, the creator receives the entire rent amount, including the safety deposit,
         // because they initially covered the entire rent during escrow creation.
 
+        // The tokens may be routed to another token account of the recipient instead of
+        // their ATA, which is only possible for non-native assets
+        let recipient_ata = ctx
+            .accounts
+            .destination_token_account
+            .as_deref()
+            .or(ctx.accounts.recipient_ata.as_deref());
+
+        // The memo is logged ahead of the token transfer it accompanies
+        attach_memo(memo.as_deref(), ctx.accounts.memo_program.as_ref())?;
+
         utils::withdraw(
             &ctx.accounts.escrow,
             ctx.accounts.escrow.bump,
             &ctx.accounts.escrow_ata,
             &ctx.accounts.recipient,
-            ctx.accounts.recipient_ata.as_deref(),
+            recipient_ata,
             &ctx.accounts.mint,
             &ctx.accounts.token_program,
             &ctx.accounts.creator,
             &ctx.accounts.creator,
+            None,
             secret,
-        )
+        )?;
+        unlock_tvl(
+            ctx.accounts.escrow.tvl_tracked,
+            ctx.accounts
+                .tvl_stats
+                .as_deref_mut()
+                .map(|tvl_stats| &mut tvl_stats.total_locked),
+            ctx.accounts.escrow.amount,
+        )?;
+
+        Ok(secret)
     }
 
-    pub fn public_withdraw(ctx: Context<PublicWithdraw>, secret: [u8; 32]) -> Result<()> {
+    pub fn public_withdraw(
+        ctx: Context<PublicWithdraw>,
+        secret: [u8; 32],
+        safety_deposit_split_bps: u16,
+        memo: Option<Vec<u8>>,
+    ) -> Result<[u8; 32]> {
         let now = get_current_timestamp()?;
         let timelocks = Timelocks(U256(ctx.accounts.escrow.timelocks));
         require!(
@@ -146,6 +231,10 @@ pub mod cross_chain_escrow_dst {
 
         // In a public withdrawal, the creator receives the rent minus the safety deposit
         // while the safety deposit is awarded to the payer who executed the public withdrawal
+        // (or to the reward recipient they specify), optionally shared with a secondary recipient
+
+        // The memo is logged ahead of the token transfer it accompanies
+        attach_memo(memo.as_deref(), ctx.accounts.memo_program.as_ref())?;
 
         utils::withdraw(
             &ctx.accounts.escrow,
@@ -156,9 +245,26 @@ pub mod cross_chain_escrow_dst {
             &ctx.accounts.mint,
             &ctx.accounts.token_program,
             &ctx.accounts.creator,
-            &ctx.accounts.payer,
+            ctx.accounts
+                .reward_recipient
+                .as_deref()
+                .unwrap_or(&ctx.accounts.payer),
+            ctx.accounts
+                .safety_deposit_secondary_recipient
+                .as_deref()
+                .map(|recipient| (recipient, safety_deposit_split_bps)),
             secret,
-        )
+        )?;
+        unlock_tvl(
+            ctx.accounts.escrow.tvl_tracked,
+            ctx.accounts
+                .tvl_stats
+                .as_deref_mut()
+                .map(|tvl_stats| &mut tvl_stats.total_locked),
+            ctx.accounts.escrow.amount,
+        )?;
+
+        Ok(secret)
     }
 
     pub fn cancel(ctx: Context<Cancel>) -> Result<()> {
@@ -169,31 +275,195 @@ pub mod cross_chain_escrow_dst {
             EscrowError::InvalidTime
         );
 
-        let seeds = [
-            "escrow".as_bytes(),
-            &ctx.accounts.escrow.order_hash,
-            &ctx.accounts.escrow.hashlock,
-            ctx.accounts.escrow.creator.as_ref(),
-            &ctx.accounts.escrow.amount.to_be_bytes(),
-            &[ctx.accounts.escrow.bump],
-        ];
+        // In a standard cancel, the creator receives the entire rent amount, including the safety
+        // deposit, because they initially covered the entire rent during escrow creation, while
+        // also receiving their tokens back to their initial ATA or wallet if the token is native.
 
-        process_payout(
+        utils::cancel(
+            &ctx.accounts.escrow,
+            ctx.accounts.escrow.bump,
+            &ctx.accounts.escrow_ata,
+            ctx.accounts.creator_ata.as_deref(),
             &ctx.accounts.mint,
-            ctx.accounts.escrow.asset_is_native,
+            &ctx.accounts.token_program,
+            &ctx.accounts.creator,
+            &ctx.accounts.creator, // safety deposit recipient
+        )?;
+        unlock_tvl(
+            ctx.accounts.escrow.tvl_tracked,
+            ctx.accounts
+                .tvl_stats
+                .as_deref_mut()
+                .map(|tvl_stats| &mut tvl_stats.total_locked),
             ctx.accounts.escrow.amount,
-            &ctx.accounts.escrow.to_account_info(),
+        )?;
+
+        emit!(EscrowCancelled {
+            order_hash: ctx.accounts.escrow.order_hash,
+            hashlock: ctx.accounts.escrow.hashlock,
+            amount: ctx.accounts.escrow.amount,
+        });
+        Ok(())
+    }
+
+    pub fn public_cancel(ctx: Context<PublicCancel>) -> Result<()> {
+        let now = get_current_timestamp()?;
+        let timelocks = Timelocks(U256(ctx.accounts.escrow.timelocks));
+        require!(
+            now >= timelocks.get(Stage::DstPublicCancellation)?,
+            EscrowError::InvalidTime
+        );
+
+        // In a public cancel, the creator receives the entire rent amount minus the safety deposit,
+        // which is awarded to the payer who executed the public cancellation, while also
+        // receiving their tokens back to their initial ATA or wallet if the token is native.
+
+        utils::cancel(
+            &ctx.accounts.escrow,
+            ctx.accounts.escrow.bump,
             &ctx.accounts.escrow_ata,
-            &ctx.accounts.creator,
             ctx.accounts.creator_ata.as_deref(),
-            &ctx.accounts.creator,
-            seeds,
+            &ctx.accounts.mint,
             &ctx.accounts.token_program,
+            &ctx.accounts.creator,
+            &ctx.accounts.payer, // safety deposit recipient
+        )?;
+        unlock_tvl(
+            ctx.accounts.escrow.tvl_tracked,
+            ctx.accounts
+                .tvl_stats
+                .as_deref_mut()
+                .map(|tvl_stats| &mut tvl_stats.total_locked),
+            ctx.accounts.escrow.amount,
+        )?;
+
+        emit!(EscrowPublicCancelled {
+            order_hash: ctx.accounts.escrow.order_hash,
+            hashlock: ctx.accounts.escrow.hashlock,
+            amount: ctx.accounts.escrow.amount,
+            canceller: ctx.accounts.payer.key(),
+        });
+        Ok(())
+    }
+
+    /// Raises the safety deposit awarded on a public withdrawal or cancellation by `additional`
+    /// lamports paid by the creator, e.g. to attract a caller when liquidity is scarce. Only
+    /// possible before the withdrawal window opens.
+    pub fn top_up_escrow_safety_deposit(
+        ctx: Context<TopUpEscrowSafetyDeposit>,
+        additional: u64,
+    ) -> Result<()> {
+        let now = get_current_timestamp()?;
+        let timelocks = Timelocks(U256(ctx.accounts.escrow.timelocks));
+        require!(
+            now < timelocks.get(Stage::DstWithdrawal)?,
+            EscrowError::InvalidTime
+        );
+
+        // The deposit is subject to the same cap as on escrow creation
+        let safety_deposit = ctx
+            .accounts
+            .escrow
+            .safety_deposit
+            .checked_add(additional)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        let rent_exempt_reserve =
+            Rent::get()?.minimum_balance(EscrowDst::INIT_SPACE + constants::DISCRIMINATOR_BYTES);
+        require!(
+            safety_deposit <= rent_exempt_reserve,
+            EscrowError::SafetyDepositTooLarge
+        );
+
+        uni_transfer(
+            &UniTransferParams::NativeTransfer {
+                from: ctx.accounts.creator.to_account_info(),
+                to: ctx.accounts.escrow.to_account_info(),
+                amount: additional,
+                program: ctx.accounts.system_program.clone(),
+            },
+            None,
         )?;
+        ctx.accounts.escrow.safety_deposit = safety_deposit;
 
         Ok(())
     }
 
+    /// Returns the stored escrow amount, the actual escrow ATA balance and their difference,
+    /// which may diverge due to excess transfers or Token-2022 transfer fees.
+    pub fn reconcile_escrow(ctx: Context<ReconcileEscrow>) -> Result<EscrowReconciliation> {
+        let stored_amount = ctx.accounts.escrow.amount;
+        let actual_amount = utils::get_held_amount(&ctx.accounts.escrow, &ctx.accounts.escrow_ata)?;
+
+        Ok(EscrowReconciliation {
+            stored_amount,
+            actual_amount,
+            difference: actual_amount as i128 - stored_amount as i128,
+        })
+    }
+
+    /// Returns which actions the timelocks of the escrow allow at the current time, so that
+    /// keepers do not have to replicate the stage arithmetic. Only the timing is checked,
+    /// other requirements of the instructions such as the resolver access are not.
+    pub fn escrow_status(ctx: Context<GetEscrowStatus>) -> Result<EscrowStatus> {
+        let now = get_current_timestamp()?;
+        let [withdrawable, public_withdrawable, cancellable, public_cancellable] =
+            Timelocks(U256(ctx.accounts.escrow.timelocks)).get_open_stages(&DST_STAGES, now)?;
+
+        Ok(EscrowStatus {
+            withdrawable,
+            public_withdrawable,
+            cancellable,
+            public_cancellable,
+        })
+    }
+
+    /// Returns the address and bump of the escrow of `creator` for the given parameters, using
+    /// the seeds of the escrow account constraints.
+    pub fn derive_escrow_pda(
+        _ctx: Context<DeriveEscrowPda>,
+        order_hash: [u8; 32],
+        hashlock: [u8; 32],
+        creator: Pubkey,
+        amount: u64,
+    ) -> Result<EscrowPda> {
+        let (address, bump) = Pubkey::find_program_address(
+            &[
+                "escrow".as_bytes(),
+                order_hash.as_ref(),
+                hashlock.as_ref(),
+                creator.as_ref(),
+                amount.to_be_bytes().as_ref(),
+            ],
+            &crate::ID,
+        );
+
+        Ok(EscrowPda { address, bump })
+    }
+
+    /// Returns the lamports the creator locks in a new escrow account, i.e. its rent-exempt
+    /// minimum. The safety deposit is paid out of these lamports, so it can't exceed them.
+    pub fn get_escrow_rent(_ctx: Context<GetRent>) -> Result<u64> {
+        Ok(Rent::get()?.minimum_balance(constants::DISCRIMINATOR_BYTES + EscrowDst::INIT_SPACE))
+    }
+
+    /// Creates the account counting the value locked in the escrows of the mint, which escrow
+    /// creation requires while the whitelist config tracks it. Anyone can pay for it.
+    pub fn init_tvl_stats(ctx: Context<InitTvlStats>) -> Result<()> {
+        ctx.accounts.tvl_stats.set_inner(TvlStats {
+            mint: ctx.accounts.mint.key(),
+            total_locked: 0,
+            bump: ctx.bumps.tvl_stats,
+        });
+        Ok(())
+    }
+
+    /// Creates the recipient ATA for the escrow token if it does not exist yet, so that
+    /// the rent is not charged to whoever executes the withdrawal.
+    pub fn ensure_recipient_ata(_ctx: Context<EnsureRecipientAta>) -> Result<()> {
+        // The account is created by the `init_if_needed` constraint
+        Ok(())
+    }
+
     pub fn rescue_funds(
         ctx: Context<RescueFunds>,
         order_hash: [u8; 32],
@@ -225,6 +495,10 @@ pub mod cross_chain_escrow_dst {
             rescue_start,
             &ctx.accounts.escrow_ata,
             &ctx.accounts.creator,
+            ctx.accounts
+                .rent_recipient
+                .as_ref()
+                .map(|rent_recipient| rent_recipient.as_ref()),
             &ctx.accounts.creator_ata,
             &ctx.accounts.mint,
             &ctx.accounts.token_program,
@@ -244,6 +518,14 @@ pub struct Create<'info> {
     creator: Signer<'info>,
     /// CHECK: check is not necessary as token is only used as a constraint to creator_ata and escrow_ata
     mint: Box<InterfaceAccount<'info, Mint>>,
+    /// Config with the token programs supported by the protocol
+    #[account(
+        seeds = [whitelist::CONFIG_SEED],
+        bump = config.bump,
+        seeds::program = whitelist::ID,
+        constraint = config.supported_token_programs.contains(&token_program.key()) @ EscrowError::UnsupportedTokenProgram,
+    )]
+    config: Box<Account<'info, whitelist::Config>>,
     #[account(
         mut,
         associated_token::mint = mint,
@@ -282,6 +564,13 @@ pub struct Create<'info> {
     token_program: Interface<'info, TokenInterface>,
     rent: Sysvar<'info, Rent>,
     system_program: Program<'info, System>,
+    /// Required only if the config tracks the value locked in escrows
+    #[account(
+        mut,
+        seeds = [constants::TVL_STATS_SEED, mint.key().as_ref()],
+        bump = tvl_stats.bump,
+    )]
+    tvl_stats: Option<Box<Account<'info, TvlStats>>>,
 }
 
 #[derive(Accounts)]
@@ -296,7 +585,9 @@ pub struct Withdraw<'info> {
         mut, // Needed because this account receives lamports if asset is native
         constraint = recipient.key() == escrow.recipient @ EscrowError::InvalidAccount)]
     recipient: AccountInfo<'info>,
-    #[account(constraint = mint.key() == escrow.token @ EscrowError::InvalidMint)]
+    #[account(
+        constraint = mint.key() == escrow.token @ EscrowError::InvalidMint
+    )]
     mint: Box<InterfaceAccount<'info, Mint>>,
     #[account(
         mut,
@@ -325,11 +616,28 @@ pub struct Withdraw<'info> {
         associated_token::authority = recipient,
         associated_token::token_program = token_program
     )]
-    /// Optional if the token is native
+    /// Must be omitted if the token is native, optional if the destination token account is provided
     recipient_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = recipient,
+        token::token_program = token_program
+    )]
+    /// Optional token account of the recipient that receives the tokens instead of their ATA
+    destination_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
     associated_token_program: Program<'info, AssociatedToken>,
     token_program: Interface<'info, TokenInterface>,
     system_program: Program<'info, System>,
+    /// Required only when a memo is attached to the withdrawal
+    memo_program: Option<Program<'info, Memo>>,
+    /// Required only if the escrow is counted in the value locked
+    #[account(
+        mut,
+        seeds = [constants::TVL_STATS_SEED, mint.key().as_ref()],
+        bump = tvl_stats.bump,
+    )]
+    tvl_stats: Option<Box<Account<'info, TvlStats>>>,
 }
 
 #[derive(Accounts)]
@@ -353,7 +661,15 @@ pub struct PublicWithdraw<'info> {
         seeds::program = whitelist::ID,
     )]
     resolver_access: Account<'info, whitelist::ResolverAccess>,
-    #[account(constraint = mint.key() == escrow.token @ EscrowError::InvalidMint)]
+    /// CHECK: Optional account that receives the part of the safety deposit not awarded to the payer
+    #[account(mut)]
+    safety_deposit_secondary_recipient: Option<UncheckedAccount<'info>>,
+    /// CHECK: Optional account that receives the safety deposit award instead of the payer
+    #[account(mut)]
+    reward_recipient: Option<UncheckedAccount<'info>>,
+    #[account(
+        constraint = mint.key() == escrow.token @ EscrowError::InvalidMint
+    )]
     mint: Box<InterfaceAccount<'info, Mint>>,
     #[account(
         mut,
@@ -382,11 +698,20 @@ pub struct PublicWithdraw<'info> {
         associated_token::authority = recipient,
         associated_token::token_program = token_program
     )]
-    /// Optional if the token is native
+    /// Must be omitted if the token is native
     recipient_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
     associated_token_program: Program<'info, AssociatedToken>,
     token_program: Interface<'info, TokenInterface>,
     system_program: Program<'info, System>,
+    /// Required only when a memo is attached to the withdrawal
+    memo_program: Option<Program<'info, Memo>>,
+    /// Required only if the escrow is counted in the value locked
+    #[account(
+        mut,
+        seeds = [constants::TVL_STATS_SEED, mint.key().as_ref()],
+        bump = tvl_stats.bump,
+    )]
+    tvl_stats: Option<Box<Account<'info, TvlStats>>>,
 }
 
 #[derive(Accounts)]
@@ -396,7 +721,9 @@ pub struct Cancel<'info> {
         constraint = creator.key() == escrow.creator @ EscrowError::InvalidAccount
     )]
     creator: Signer<'info>,
-    #[account(constraint = mint.key() == escrow.token @ EscrowError::InvalidMint)]
+    #[account(
+        constraint = mint.key() == escrow.token @ EscrowError::InvalidMint
+    )]
     mint: Box<InterfaceAccount<'info, Mint>>,
     #[account(
         mut,
@@ -418,16 +745,87 @@ pub struct Cancel<'info> {
         associated_token::token_program = token_program
     )]
     escrow_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    // The creator ATA is recreated at the creator's expense if the creator has closed it,
+    // so that the refund is not stranded
     #[account(
-        mut,
+        init_if_needed,
+        payer = creator,
         associated_token::mint = mint,
         associated_token::authority = creator,
         associated_token::token_program = token_program
     )]
     // Optional if the token is native
     creator_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(address = ASSOCIATED_TOKEN_PROGRAM_ID)]
+    associated_token_program: Program<'info, AssociatedToken>,
+    token_program: Interface<'info, TokenInterface>,
+    system_program: Program<'info, System>,
+    /// Required only if the escrow is counted in the value locked
+    #[account(
+        mut,
+        seeds = [constants::TVL_STATS_SEED, mint.key().as_ref()],
+        bump = tvl_stats.bump,
+    )]
+    tvl_stats: Option<Box<Account<'info, TvlStats>>>,
+}
+
+#[derive(Accounts)]
+pub struct PublicCancel<'info> {
+    /// CHECK: This account is used as a destination for rent, and its key is verified against the escrow.creator field
+    #[account(
+        mut, // Needed because this account receives lamports (rent and native tokens)
+        constraint = creator.key() == escrow.creator @ EscrowError::InvalidAccount
+    )]
+    creator: AccountInfo<'info>,
+    #[account(
+        constraint = mint.key() == escrow.token @ EscrowError::InvalidMint
+    )]
+    mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mut)]
+    payer: Signer<'info>,
+    #[account(
+        seeds = [whitelist::RESOLVER_ACCESS_SEED, payer.key().as_ref()],
+        bump = resolver_access.bump,
+        seeds::program = whitelist::ID,
+    )]
+    resolver_access: Account<'info, whitelist::ResolverAccess>,
+    #[account(
+        mut,
+        close = creator,
+        seeds = [
+            "escrow".as_bytes(),
+            escrow.order_hash.as_ref(),
+            escrow.hashlock.as_ref(),
+            escrow.creator.key().as_ref(),
+            escrow.amount.to_be_bytes().as_ref(),
+        ],
+        bump = escrow.bump,
+    )]
+    escrow: Box<Account<'info, EscrowDst>>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program
+    )]
+    escrow_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = escrow.creator,
+        associated_token::token_program = token_program
+    )]
+    // Optional if the token is native
+    creator_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
     token_program: Interface<'info, TokenInterface>,
     system_program: Program<'info, System>,
+    /// Required only if the escrow is counted in the value locked
+    #[account(
+        mut,
+        seeds = [constants::TVL_STATS_SEED, mint.key().as_ref()],
+        bump = tvl_stats.bump,
+    )]
+    tvl_stats: Option<Box<Account<'info, TvlStats>>>,
 }
 
 #[derive(Accounts)]
@@ -466,10 +864,130 @@ pub struct RescueFunds<'info> {
         associated_token::token_program = token_program
     )]
     creator_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// Receives the rent of the closed token account instead of the signer if set
+    #[account(mut)]
+    rent_recipient: Option<SystemAccount<'info>>,
+    token_program: Interface<'info, TokenInterface>,
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReconcileEscrow<'info> {
+    #[account(constraint = mint.key() == escrow.token @ EscrowError::InvalidMint)]
+    mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        seeds = [
+            "escrow".as_bytes(),
+            escrow.order_hash.as_ref(),
+            escrow.hashlock.as_ref(),
+            escrow.creator.key().as_ref(),
+            escrow.amount.to_be_bytes().as_ref(),
+        ],
+        bump = escrow.bump,
+    )]
+    escrow: Box<Account<'info, EscrowDst>>,
+    #[account(
+        associated_token::mint = mint,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program
+    )]
+    escrow_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct GetEscrowStatus<'info> {
+    #[account(
+        seeds = [
+            "escrow".as_bytes(),
+            escrow.order_hash.as_ref(),
+            escrow.hashlock.as_ref(),
+            escrow.creator.key().as_ref(),
+            escrow.amount.to_be_bytes().as_ref(),
+        ],
+        bump = escrow.bump,
+    )]
+    escrow: Box<Account<'info, EscrowDst>>,
+}
+
+#[derive(Accounts)]
+pub struct DeriveEscrowPda {}
+
+#[derive(Accounts)]
+pub struct GetRent {}
+
+#[derive(Accounts)]
+pub struct InitTvlStats<'info> {
+    #[account(mut)]
+    payer: Signer<'info>,
+    mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        init,
+        payer = payer,
+        space = constants::DISCRIMINATOR_BYTES + TvlStats::INIT_SPACE,
+        seeds = [constants::TVL_STATS_SEED, mint.key().as_ref()],
+        bump,
+    )]
+    tvl_stats: Box<Account<'info, TvlStats>>,
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct EnsureRecipientAta<'info> {
+    /// Pays for the recipient ATA if it has to be created
+    #[account(mut)]
+    payer: Signer<'info>,
+    /// CHECK: This account is used to check its pubkey to match the one stored in the escrow account
+    #[account(constraint = recipient.key() == escrow.recipient @ EscrowError::InvalidAccount)]
+    recipient: AccountInfo<'info>,
+    #[account(constraint = mint.key() == escrow.token @ EscrowError::InvalidMint)]
+    mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        seeds = [
+            "escrow".as_bytes(),
+            escrow.order_hash.as_ref(),
+            escrow.hashlock.as_ref(),
+            escrow.creator.key().as_ref(),
+            escrow.amount.to_be_bytes().as_ref(),
+        ],
+        bump = escrow.bump,
+    )]
+    escrow: Box<Account<'info, EscrowDst>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = mint,
+        associated_token::authority = recipient,
+        associated_token::token_program = token_program
+    )]
+    recipient_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    associated_token_program: Program<'info, AssociatedToken>,
     token_program: Interface<'info, TokenInterface>,
     system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct TopUpEscrowSafetyDeposit<'info> {
+    #[account(
+        mut, // Pays the additional safety deposit
+        constraint = creator.key() == escrow.creator @ EscrowError::InvalidAccount
+    )]
+    creator: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            "escrow".as_bytes(),
+            escrow.order_hash.as_ref(),
+            escrow.hashlock.as_ref(),
+            escrow.creator.key().as_ref(),
+            escrow.amount.to_be_bytes().as_ref(),
+        ],
+        bump = escrow.bump,
+    )]
+    escrow: Box<Account<'info, EscrowDst>>,
+    system_program: Program<'info, System>,
+}
+
 #[account]
 #[derive(InitSpace)]
 pub struct EscrowDst {
@@ -482,5 +1000,72 @@ pub struct EscrowDst {
     pub amount: u64,
     pub safety_deposit: u64,
     pub timelocks: [u64; 4],
+    /// Whether the amount is counted in the `TvlStats` of the mint, which its withdrawal or
+    /// cancellation then updates as well
+    pub tvl_tracked: bool,
+    /// Balance the escrow ATA was funded with, net of the transfer fee of the mint, which it must
+    /// still hold when the escrow is withdrawn or cancelled
+    pub funded_amount: u64,
+    pub bump: u8,
+}
+
+/// Value locked in the escrows of the mint, counted while the whitelist config tracks it.
+/// Native escrows are counted under the native mint.
+#[account]
+#[derive(InitSpace)]
+pub struct TvlStats {
+    pub mint: Pubkey,
+    /// Sum of the amounts of the counted escrows not yet withdrawn or cancelled
+    pub total_locked: u64,
+    pub bump: u8,
+}
+
+/// Emitted on withdrawal once the secret has been validated against the escrow hashlock
+#[event]
+pub struct SecretRevealed {
+    pub order_hash: [u8; 32],
+    pub hashlock: [u8; 32],
+    pub secret: [u8; 32],
+}
+
+/// Emitted when the creator cancels an escrow, returning its amount to them
+#[event]
+pub struct EscrowCancelled {
+    pub order_hash: [u8; 32],
+    pub hashlock: [u8; 32],
+    pub amount: u64,
+}
+
+/// Emitted when an escrow is cancelled in the public cancellation period
+#[event]
+pub struct EscrowPublicCancelled {
+    pub order_hash: [u8; 32],
+    pub hashlock: [u8; 32],
+    pub amount: u64,
+    /// Account that executed the cancellation and received the safety deposit
+    pub canceller: Pubkey,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EscrowReconciliation {
+    pub stored_amount: u64,
+    pub actual_amount: u64,
+    /// `actual_amount - stored_amount`, negative if the escrow ATA holds less than stored
+    pub difference: i128,
+}
+
+/// Actions the timelocks of an escrow allow at a given time, see `escrow_status`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EscrowStatus {
+    pub withdrawable: bool,
+    pub public_withdrawable: bool,
+    pub cancellable: bool,
+    pub public_cancellable: bool,
+}
+
+/// Address and bump of an escrow PDA, see `derive_escrow_pda`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EscrowPda {
+    pub address: Pubkey,
     pub bump: u8,
 }
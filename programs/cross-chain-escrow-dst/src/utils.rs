@@ -1,8 +1,13 @@
-use anchor_lang::{prelude::*, solana_program::keccak::hash};
+use anchor_lang::prelude::*;
 use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
-use common::{error::EscrowError, escrow::process_payout};
+use common::{
+    constants::HASH_ALGO_KECCAK,
+    error::EscrowError,
+    escrow::{distribute_safety_deposit, process_payout, verify_escrow_balance},
+    utils::is_valid_secret,
+};
 
-use crate::EscrowDst;
+use crate::{EscrowDst, SecretRevealed};
 
 pub fn withdraw<'info>(
     escrow: &Account<'info, EscrowDst>,
@@ -14,14 +19,30 @@ pub fn withdraw<'info>(
     token_program: &Interface<'info, TokenInterface>,
     rent_recipient: &AccountInfo<'info>,
     safety_deposit_recipient: &AccountInfo<'info>,
+    safety_deposit_split: Option<(&AccountInfo<'info>, u16)>,
     secret: [u8; 32],
 ) -> Result<()> {
+    // Native assets are paid out to the recipient wallet directly, without a recipient ATA
+    require!(
+        escrow.asset_is_native == recipient_ata.is_none(),
+        EscrowError::InconsistentNativeTrait
+    );
+
+    verify_escrow_balance(get_held_amount(escrow, escrow_ata)?, escrow.funded_amount)?;
+
     // Verify that the secret matches the hashlock
     require!(
-        hash(&secret).to_bytes() == escrow.hashlock,
+        is_valid_secret(&secret, &escrow.hashlock, HASH_ALGO_KECCAK),
         EscrowError::InvalidSecret
     );
 
+    // Publish the secret only once validated, so that it can be used on the other chain
+    emit!(SecretRevealed {
+        order_hash: escrow.order_hash,
+        hashlock: escrow.hashlock,
+        secret,
+    });
+
     let seeds = [
         "escrow".as_bytes(),
         &escrow.order_hash,
@@ -44,11 +65,76 @@ pub fn withdraw<'info>(
         token_program,
     )?;
 
+    // Disrtibute the safety deposit if needed. A secondary recipient takes its share even if the
+    // rent recipient gets the rest.
+    if rent_recipient.key() != safety_deposit_recipient.key() || safety_deposit_split.is_some() {
+        distribute_safety_deposit(
+            &escrow.to_account_info(),
+            escrow.safety_deposit,
+            safety_deposit_recipient,
+            safety_deposit_split,
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn cancel<'info>(
+    escrow: &Account<'info, EscrowDst>,
+    escrow_bump: u8,
+    escrow_ata: &InterfaceAccount<'info, TokenAccount>,
+    creator_ata: Option<&InterfaceAccount<'info, TokenAccount>>,
+    mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    creator: &AccountInfo<'info>,
+    safety_deposit_recipient: &AccountInfo<'info>,
+) -> Result<()> {
+    verify_escrow_balance(get_held_amount(escrow, escrow_ata)?, escrow.funded_amount)?;
+
+    let seeds = [
+        "escrow".as_bytes(),
+        &escrow.order_hash,
+        &escrow.hashlock,
+        escrow.creator.as_ref(),
+        &escrow.amount.to_be_bytes(),
+        &[escrow_bump],
+    ];
+
+    process_payout(
+        mint,
+        escrow.asset_is_native,
+        escrow.amount,
+        &escrow.to_account_info(),
+        escrow_ata,
+        creator,
+        creator_ata,
+        creator,
+        seeds,
+        token_program,
+    )?;
+
     // Disrtibute the safety deposit if needed
-    if rent_recipient.key() != safety_deposit_recipient.key() {
+    if creator.key() != safety_deposit_recipient.key() {
         escrow.sub_lamports(escrow.safety_deposit)?;
         safety_deposit_recipient.add_lamports(escrow.safety_deposit)?;
     }
 
     Ok(())
 }
+
+/// Returns the amount of tokens held by the escrow ATA
+pub fn get_held_amount(
+    escrow: &EscrowDst,
+    escrow_ata: &InterfaceAccount<TokenAccount>,
+) -> Result<u64> {
+    if escrow.asset_is_native {
+        // Native tokens are wrapped on deposit, but escrows created before that hold them as
+        // unwrapped lamports of the escrow ATA, so the lamports are counted for both
+        let escrow_ata = escrow_ata.to_account_info();
+        Ok(escrow_ata
+            .lamports()
+            .saturating_sub(Rent::get()?.minimum_balance(escrow_ata.data_len())))
+    } else {
+        Ok(escrow_ata.amount)
+    }
+}
@@ -1,6 +1,6 @@
 use anchor_spl::token::spl_token::native_mint::ID as NATIVE_MINT;
 use common::{error::EscrowError, timelocks::Stage};
-use common_tests::dst_program::DstProgram;
+use common_tests::dst_program::{get_public_cancel_tx, DstProgram};
 use common_tests::helpers::*;
 use common_tests::tests as common_escrow_tests;
 use common_tests::whitelist::prepare_resolvers;
@@ -54,6 +54,19 @@ mod test_escrow_native {
         );
     }
 
+    #[test_context(TestState)]
+    #[tokio::test]
+    async fn test_escrow_creation_wraps_native_deposit(test_state: &mut TestState) {
+        test_state.token = NATIVE_MINT;
+        test_state.test_arguments.asset_is_native = true;
+        let (_, escrow_ata) = create_escrow(test_state).await;
+
+        assert_eq!(
+            get_token_balance(&mut test_state.context, &escrow_ata).await,
+            test_state.test_arguments.escrow_amount
+        );
+    }
+
     #[test_context(TestState)]
     #[tokio::test]
     async fn test_escrow_creation_fails_if_token_is_not_native(test_state: &mut TestState) {
@@ -120,6 +133,35 @@ mod test_escrow_native {
             .is_none());
     }
 
+    #[test_context(TestState)]
+    #[tokio::test]
+    async fn test_withdraw_fails_with_recipient_ata(test_state: &mut TestState) {
+        test_state.token = NATIVE_MINT;
+        test_state.test_arguments.asset_is_native = true;
+        let (escrow, escrow_ata) = create_escrow(test_state).await;
+
+        // Build the transaction as for wrapped SOL, which passes the recipient ATA
+        test_state.test_arguments.asset_is_native = false;
+        let transaction = DstProgram::get_withdraw_tx(test_state, &escrow, &escrow_ata);
+
+        set_time(
+            &mut test_state.context,
+            test_state
+                .test_arguments
+                .dst_timelocks
+                .get(Stage::DstWithdrawal)
+                .unwrap(),
+        );
+
+        test_state
+            .client
+            .process_transaction(transaction)
+            .await
+            .expect_error(ProgramError::Custom(
+                EscrowError::InconsistentNativeTrait.into(),
+            ));
+    }
+
     #[test_context(TestState)]
     #[tokio::test]
     async fn test_public_withdraw_by_maker(test_state: &mut TestState) {
@@ -261,6 +303,38 @@ mod test_escrow_native {
             .is_none());
     }
 
+    #[test_context(TestState)]
+    #[tokio::test]
+    async fn test_public_withdraw_fails_with_recipient_ata(test_state: &mut TestState) {
+        test_state.token = NATIVE_MINT;
+        test_state.test_arguments.asset_is_native = true;
+        let withdrawer = test_state.maker_wallet.keypair.insecure_clone();
+        prepare_resolvers(test_state, &[withdrawer.pubkey()]).await;
+        let (escrow, escrow_ata) = create_escrow(test_state).await;
+
+        // Build the transaction as for wrapped SOL, which passes the recipient ATA
+        test_state.test_arguments.asset_is_native = false;
+        let transaction =
+            DstProgram::get_public_withdraw_tx(test_state, &escrow, &escrow_ata, &withdrawer);
+
+        set_time(
+            &mut test_state.context,
+            test_state
+                .test_arguments
+                .dst_timelocks
+                .get(Stage::DstPublicWithdrawal)
+                .unwrap(),
+        );
+
+        test_state
+            .client
+            .process_transaction(transaction)
+            .await
+            .expect_error(ProgramError::Custom(
+                EscrowError::InconsistentNativeTrait.into(),
+            ));
+    }
+
     #[test_context(TestState)]
     #[tokio::test]
     async fn test_cancel(test_state: &mut TestState) {
@@ -298,6 +372,54 @@ mod test_escrow_native {
             .await;
     }
 
+    #[test_context(TestState)]
+    #[tokio::test]
+    async fn test_public_cancel(test_state: &mut TestState) {
+        test_state.token = NATIVE_MINT;
+        test_state.test_arguments.asset_is_native = true;
+        let canceller = Keypair::new();
+        transfer_lamports(
+            &mut test_state.context,
+            WALLET_DEFAULT_LAMPORTS,
+            &test_state.payer_kp,
+            &canceller.pubkey(),
+        )
+        .await;
+        prepare_resolvers(test_state, &[canceller.pubkey()]).await;
+        let (escrow, escrow_ata) = create_escrow(test_state).await;
+        let transaction = get_public_cancel_tx(test_state, &escrow, &escrow_ata, &canceller);
+
+        set_time(
+            &mut test_state.context,
+            test_state
+                .test_arguments
+                .dst_timelocks
+                .get(Stage::DstPublicCancellation)
+                .unwrap(),
+        );
+
+        let token_account_rent =
+            get_min_rent_for_size(&mut test_state.client, TokenSPL::get_token_account_size()).await;
+        let escrow_rent =
+            get_min_rent_for_size(&mut test_state.client, DEFAULT_DST_ESCROW_SIZE).await;
+
+        test_state
+            .expect_state_change(
+                transaction,
+                &[
+                    native_change(
+                        test_state.maker_wallet.keypair.pubkey(),
+                        test_state.test_arguments.escrow_amount + escrow_rent + token_account_rent
+                            - test_state.test_arguments.safety_deposit,
+                    ),
+                    native_change(canceller.pubkey(), test_state.test_arguments.safety_deposit),
+                    account_closure(escrow_ata, true),
+                    account_closure(escrow, true),
+                ],
+            )
+            .await;
+    }
+
     #[test_context(TestState)]
     #[tokio::test]
     async fn test_rescue_all_tokens_and_close_ata(test_state: &mut TestState) {
@@ -412,7 +534,7 @@ mod test_escrow_wrapped_native {
             .process_transaction(transaction)
             .await
             .expect_error(ProgramError::Custom(
-                EscrowError::MissingRecipientAta.into(),
+                EscrowError::InconsistentNativeTrait.into(),
             ));
     }
 
@@ -602,7 +724,7 @@ mod test_escrow_wrapped_native {
             .process_transaction(transaction)
             .await
             .expect_error(ProgramError::Custom(
-                EscrowError::MissingRecipientAta.into(),
+                EscrowError::InconsistentNativeTrait.into(),
             ));
     }
 
@@ -1,11 +1,20 @@
 use anchor_lang::error::ErrorCode;
+use anchor_lang::{AccountDeserialize, AnchorDeserialize};
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
 use common::{error::EscrowError, timelocks::Stage};
-use common_tests::dst_program::DstProgram;
+use common_tests::dst_program::{
+    enable_tvl_tracking, get_ensure_recipient_ata_tx, get_public_cancel_tx,
+    get_reconcile_escrow_tx, get_top_up_escrow_safety_deposit_tx, get_tvl_total_locked,
+    get_withdraw_to_destination_tx, DstProgram,
+};
 use common_tests::helpers::*;
 use common_tests::run_for_tokens;
 use common_tests::tests as common_escrow_tests;
-use common_tests::whitelist::prepare_resolvers;
+use common_tests::whitelist::{prepare_resolvers, unlist_token_program};
+use cross_chain_escrow_dst::{EscrowCancelled, EscrowPublicCancelled, SecretRevealed};
+use solana_program::keccak::hashv;
 use solana_program::program_error::ProgramError;
+use solana_program::pubkey::Pubkey;
 use solana_program_test::tokio;
 use solana_sdk::{signature::Signer, signer::keypair::Keypair, sysvar::clock::Clock};
 
@@ -42,6 +51,95 @@ run_for_tokens!(
                 common_escrow_tests::test_escrow_creation(test_state).await
             }
 
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_with_committed_dst_safety_deposit(
+                test_state: &mut TestState,
+            ) {
+                // The resolver funds the dst escrow with the deposit committed by the maker on the src chain
+                let committed_safety_deposit =
+                    test_state.test_arguments.dst_chain_params.safety_deposit;
+                test_state.test_arguments.committed_dst_terms =
+                    Some((test_state.token, committed_safety_deposit));
+                test_state.test_arguments.safety_deposit = committed_safety_deposit as u64;
+
+                let (escrow, _) = create_escrow(test_state).await;
+
+                let escrow_account = test_state
+                    .client
+                    .get_account(escrow)
+                    .await
+                    .unwrap()
+                    .unwrap();
+                let escrow_data = cross_chain_escrow_dst::EscrowDst::try_deserialize(
+                    &mut escrow_account.data.as_slice(),
+                )
+                .unwrap();
+                assert_eq!(escrow_data.safety_deposit as u128, committed_safety_deposit);
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_fails_with_uncommitted_dst_safety_deposit(
+                test_state: &mut TestState,
+            ) {
+                let committed_safety_deposit =
+                    test_state.test_arguments.dst_chain_params.safety_deposit;
+                test_state.test_arguments.committed_dst_terms =
+                    Some((test_state.token, committed_safety_deposit));
+                test_state.test_arguments.safety_deposit = committed_safety_deposit as u64 - 1;
+                let (escrow, _, transaction) = create_escrow_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::DstParamsMismatch.into()));
+
+                assert!(test_state
+                    .client
+                    .get_account(escrow)
+                    .await
+                    .unwrap()
+                    .is_none());
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_with_matching_dst_token(test_state: &mut TestState) {
+                test_state.test_arguments.committed_dst_terms = Some((
+                    test_state.token,
+                    test_state.test_arguments.safety_deposit as u128,
+                ));
+                common_escrow_tests::test_escrow_creation(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_fails_with_dst_token_mismatch(
+                test_state: &mut TestState,
+            ) {
+                // The escrow is created in another token than the one signed by the maker
+                test_state.test_arguments.committed_dst_terms = Some((
+                    Pubkey::new_unique(),
+                    test_state.test_arguments.safety_deposit as u128,
+                ));
+                let (escrow, _, transaction) = create_escrow_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::DstParamsMismatch.into()));
+
+                assert!(test_state
+                    .client
+                    .get_account(escrow)
+                    .await
+                    .unwrap()
+                    .is_none());
+            }
+
             #[test_context(TestState)]
             #[tokio::test]
             async fn test_escrow_creation_fails_with_insufficient_funds(
@@ -51,6 +149,45 @@ run_for_tokens!(
                     .await
             }
 
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_with_safety_deposit_at_rent_exempt_reserve(
+                test_state: &mut TestState,
+            ) {
+                // Changing the escrow layout shifts the maximum safety deposit
+                assert_eq!(DEFAULT_DST_ESCROW_SIZE, 218);
+                let rent_exempt_reserve =
+                    get_min_rent_for_size(&mut test_state.client, DEFAULT_DST_ESCROW_SIZE).await;
+
+                test_state.test_arguments.safety_deposit = rent_exempt_reserve + 1;
+                let (_, _, transaction) = create_escrow_data(test_state);
+                let result = test_state
+                    .client
+                    .simulate_transaction(transaction.clone())
+                    .await
+                    .expect("Simulation RPC failed");
+                let expected_log = format!(
+                    "Program log: Safety deposit: {}, rent-exempt reserve: {}",
+                    rent_exempt_reserve + 1,
+                    rent_exempt_reserve
+                );
+                assert!(result
+                    .simulation_details
+                    .expect("Simulation details not found")
+                    .logs
+                    .contains(&expected_log));
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::SafetyDepositTooLarge.into(),
+                    ));
+
+                test_state.test_arguments.safety_deposit = rent_exempt_reserve;
+                create_escrow(test_state).await;
+            }
+
             #[test_context(TestState)]
             #[tokio::test]
             async fn test_escrow_creation_fails_with_zero_safety_deposit(
@@ -95,6 +232,161 @@ run_for_tokens!(
                         EscrowError::InvalidCreationTime.into(),
                     ))
             }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_fails_with_src_cancellation_timestamp_in_past(
+                test_state: &mut TestState,
+            ) {
+                let c: Clock = test_state.client.get_sysvar().await.unwrap();
+                test_state.test_arguments.src_cancellation_timestamp = c.unix_timestamp as u32 - 1;
+                let (_, _, transaction) = create_escrow_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::SrcCancellationInPast.into(),
+                    ))
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_fails_with_src_cancellation_timestamp_now(
+                test_state: &mut TestState,
+            ) {
+                let c: Clock = test_state.client.get_sysvar().await.unwrap();
+                test_state.test_arguments.src_cancellation_timestamp = c.unix_timestamp as u32;
+                let (_, _, transaction) = create_escrow_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::SrcCancellationInPast.into(),
+                    ))
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_with_src_cancellation_at_dst_cancellation_start(
+                test_state: &mut TestState,
+            ) {
+                let c: Clock = test_state.client.get_sysvar().await.unwrap();
+                test_state.test_arguments.src_cancellation_timestamp =
+                    c.unix_timestamp as u32 + DEFAULT_PERIOD_DURATION * 2;
+                let (_, _, transaction) = create_escrow_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success()
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_fails_with_non_monotonic_timelocks(
+                test_state: &mut TestState,
+            ) {
+                // Public withdrawal starts after cancellation
+                test_state.test_arguments.dst_timelocks = init_timelocks(
+                    0,
+                    0,
+                    0,
+                    0,
+                    DEFAULT_PERIOD_DURATION,
+                    DEFAULT_PERIOD_DURATION * 3,
+                    DEFAULT_PERIOD_DURATION * 2,
+                    0,
+                );
+                let (_, escrow_ata, transaction) = create_escrow_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::NonMonotonicTimelocks.into(),
+                    ));
+
+                let acc_lookup_result = test_state.client.get_account(escrow_ata).await.unwrap();
+                assert!(acc_lookup_result.is_none());
+            }
+
+            // Overflowing stage durations are rejected before any funds are locked
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_fails_if_stage_duration_overflows(
+                test_state: &mut TestState,
+            ) {
+                test_state.test_arguments.dst_timelocks =
+                    init_timelocks(0, 0, 0, 0, u32::MAX - 2, u32::MAX - 1, u32::MAX, 0);
+                let (_, _, transaction) = create_escrow_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::TimelockDurationTooLarge.into(),
+                    ));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_reconcile_escrow_with_excess_tokens(test_state: &mut TestState) {
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+                let excess_amount = 1000;
+                // Send excess tokens to the escrow account
+                mint_excess_tokens(test_state, &escrow_ata, excess_amount).await;
+
+                let transaction = get_reconcile_escrow_tx(test_state, &escrow, &escrow_ata);
+                let return_data =
+                    common_escrow_tests::simulate_tx_return_data(test_state, transaction).await;
+                let reconciliation =
+                    cross_chain_escrow_dst::EscrowReconciliation::try_from_slice(&return_data)
+                        .unwrap();
+
+                assert_eq!(
+                    reconciliation,
+                    cross_chain_escrow_dst::EscrowReconciliation {
+                        stored_amount: test_state.test_arguments.escrow_amount,
+                        actual_amount: test_state.test_arguments.escrow_amount + excess_amount,
+                        difference: excess_amount as i128,
+                    }
+                );
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_fails_with_unsupported_token_program(
+                test_state: &mut TestState,
+            ) {
+                unlist_token_program(
+                    test_state,
+                    &<TestState as HasTokenVariant>::Token::get_token_program_id(),
+                )
+                .await;
+                let (escrow, escrow_ata, transaction) = create_escrow_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::UnsupportedTokenProgram.into(),
+                    ));
+
+                // Check that the escrow accounts have not been created.
+                let acc_lookup_result = test_state.client.get_account(escrow).await.unwrap();
+                assert!(acc_lookup_result.is_none());
+
+                let acc_lookup_result = test_state.client.get_account(escrow_ata).await.unwrap();
+                assert!(acc_lookup_result.is_none());
+            }
         }
         mod test_escrow_withdraw {
             use super::*;
@@ -153,6 +445,35 @@ run_for_tokens!(
                     .is_none());
             }
 
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_withdraw_emits_secret_revealed(test_state: &mut TestState) {
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+                let transaction = DstProgram::get_withdraw_tx(test_state, &escrow, &escrow_ata);
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .dst_timelocks
+                        .get(Stage::DstWithdrawal)
+                        .unwrap(),
+                );
+
+                let events = common_escrow_tests::simulate_tx_events::<_, _, SecretRevealed>(
+                    test_state,
+                    transaction,
+                )
+                .await;
+                assert_eq!(events.len(), 1);
+                assert_eq!(
+                    events[0].order_hash,
+                    DstProgram::get_escrow_order_hash(test_state)
+                );
+                assert_eq!(events[0].hashlock, test_state.hashlock.to_bytes());
+                assert_eq!(events[0].secret, test_state.secret);
+            }
+
             #[test_context(TestState)]
             #[tokio::test]
             async fn test_withdraw_without_recipient_ata(test_state: &mut TestState) {
@@ -211,8 +532,15 @@ run_for_tokens!(
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_withdraw_with_excess_tokens(test_state: &mut TestState) {
+            async fn test_withdraw_to_pda_recipient(test_state: &mut TestState) {
+                // A vault PDA of another program, which is off curve and can't sign
+                let (recipient, _) =
+                    Pubkey::find_program_address(&[b"vault"], &Pubkey::new_unique());
+                assert!(!recipient.is_on_curve());
+                test_state.test_arguments.dst_recipient = Some(recipient);
+
                 let (escrow, escrow_ata) = create_escrow(test_state).await;
+
                 let transaction = DstProgram::get_withdraw_tx(test_state, &escrow, &escrow_ata);
 
                 set_time(
@@ -224,54 +552,350 @@ run_for_tokens!(
                         .unwrap(),
                 );
 
-                let (_, taker_ata) = find_user_ata(test_state);
-
-                let excess_amount = 1000;
-                // Send excess tokens to the escrow account
-                mint_excess_tokens(test_state, &escrow_ata, excess_amount).await;
                 test_state
-                    .expect_state_change(
-                        transaction,
-                        &[token_change(
-                            taker_ata,
-                            test_state.test_arguments.escrow_amount + excess_amount,
-                        )],
-                    )
-                    .await;
-
-                // Assert escrow was closed
-                assert!(test_state
                     .client
-                    .get_account(escrow)
+                    .process_transaction(transaction)
                     .await
-                    .unwrap()
-                    .is_none());
+                    .expect_success();
 
-                // Assert escrow_ata was closed
-                assert!(test_state
-                    .client
-                    .get_account(escrow_ata)
-                    .await
-                    .unwrap()
-                    .is_none());
+                let recipient_ata = get_associated_token_address_with_program_id(
+                    &recipient,
+                    &test_state.token,
+                    &<TestState as HasTokenVariant>::Token::get_token_program_id(),
+                );
+                assert_eq!(
+                    get_token_balance(&mut test_state.context, &recipient_ata).await,
+                    test_state.test_arguments.escrow_amount
+                );
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_withdraw_does_not_work_with_wrong_secret(test_state: &mut TestState) {
-                common_escrow_tests::test_withdraw_does_not_work_with_wrong_secret(test_state).await
-            }
+            async fn test_withdraw_with_pre_created_recipient_ata(test_state: &mut TestState) {
+                type S = <TestState as HasTokenVariant>::Token;
+                let rent_recipient = test_state.maker_wallet.keypair.pubkey();
+                let taker_ata = test_state.taker_wallet.token_account;
+                S::close_ata(
+                    &mut test_state.context,
+                    &taker_ata,
+                    &test_state.taker_wallet.keypair.pubkey(),
+                    &test_state.taker_wallet.keypair.pubkey(),
+                    &test_state.taker_wallet.keypair,
+                )
+                .await;
 
-            #[test_context(TestState)]
-            #[tokio::test]
-            async fn test_withdraw_does_not_work_with_non_recipient(test_state: &mut TestState) {
-                common_escrow_tests::test_withdraw_does_not_work_with_non_recipient(test_state)
-                    .await
-            }
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
 
-            #[test_context(TestState)]
-            #[tokio::test]
-            async fn test_withdraw_does_not_work_with_wrong_taker_ata(test_state: &mut TestState) {
+                let transaction = get_ensure_recipient_ata_tx(test_state, &escrow);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success();
+                assert_eq!(
+                    get_token_balance(&mut test_state.context, &taker_ata).await,
+                    0
+                );
+
+                let token_account_rent =
+                    get_min_rent_for_size(&mut test_state.client, S::get_token_account_size())
+                        .await;
+                let escrow_rent =
+                    get_min_rent_for_size(&mut test_state.client, DEFAULT_DST_ESCROW_SIZE).await;
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .dst_timelocks
+                        .get(Stage::DstWithdrawal)
+                        .unwrap(),
+                );
+
+                // The creator receives the whole rent back as it does not pay for the recipient ATA
+                let transaction = DstProgram::get_withdraw_tx(test_state, &escrow, &escrow_ata);
+                test_state
+                    .expect_state_change(
+                        transaction,
+                        &[
+                            native_change(rent_recipient, token_account_rent + escrow_rent),
+                            token_change(taker_ata, test_state.test_arguments.escrow_amount),
+                            account_closure(escrow, true),
+                            account_closure(escrow_ata, true),
+                        ],
+                    )
+                    .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_ensure_recipient_ata_fails_with_wrong_mint(test_state: &mut TestState) {
+                let (escrow, _) = create_escrow(test_state).await;
+
+                type S = <TestState as HasTokenVariant>::Token;
+                test_state.token = S::deploy_spl_token(&mut test_state.context).await.pubkey();
+                test_state.taker_wallet.token_account =
+                    get_associated_token_address_with_program_id(
+                        &test_state.taker_wallet.keypair.pubkey(),
+                        &test_state.token,
+                        &S::get_token_program_id(),
+                    );
+
+                let transaction = get_ensure_recipient_ata_tx(test_state, &escrow);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidMint.into()));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_withdraw_to_destination_token_account(test_state: &mut TestState) {
+                type S = <TestState as HasTokenVariant>::Token;
+                let rent_recipient = test_state.maker_wallet.keypair.pubkey();
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+
+                // A token account of the recipient that is not their associated token account
+                let destination_token_account = create_token_account::<S>(
+                    &mut test_state.context,
+                    &test_state.token,
+                    &test_state.taker_wallet.keypair.pubkey(),
+                )
+                .await;
+                let transaction = get_withdraw_to_destination_tx(
+                    test_state,
+                    &escrow,
+                    &escrow_ata,
+                    &destination_token_account,
+                );
+
+                let token_account_rent =
+                    get_min_rent_for_size(&mut test_state.client, S::get_token_account_size())
+                        .await;
+                let escrow_rent =
+                    get_min_rent_for_size(&mut test_state.client, DEFAULT_DST_ESCROW_SIZE).await;
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .dst_timelocks
+                        .get(Stage::DstWithdrawal)
+                        .unwrap(),
+                );
+
+                let (_, taker_ata) = find_user_ata(test_state);
+
+                test_state
+                    .expect_state_change(
+                        transaction,
+                        &[
+                            native_change(rent_recipient, token_account_rent + escrow_rent),
+                            token_change(
+                                destination_token_account,
+                                test_state.test_arguments.escrow_amount,
+                            ),
+                            token_change(taker_ata, 0),
+                            account_closure(escrow, true),
+                            account_closure(escrow_ata, true),
+                        ],
+                    )
+                    .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_withdraw_to_destination_token_account_fails_with_other_owner(
+                test_state: &mut TestState,
+            ) {
+                type S = <TestState as HasTokenVariant>::Token;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+
+                // The escrow creator cannot redirect the tokens to an account of their own
+                let destination_token_account = create_token_account::<S>(
+                    &mut test_state.context,
+                    &test_state.token,
+                    &test_state.maker_wallet.keypair.pubkey(),
+                )
+                .await;
+                let transaction = get_withdraw_to_destination_tx(
+                    test_state,
+                    &escrow,
+                    &escrow_ata,
+                    &destination_token_account,
+                );
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .dst_timelocks
+                        .get(Stage::DstWithdrawal)
+                        .unwrap(),
+                );
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(ErrorCode::ConstraintTokenOwner.into()));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_withdraw_to_destination_token_account_fails_with_wrong_mint(
+                test_state: &mut TestState,
+            ) {
+                type S = <TestState as HasTokenVariant>::Token;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+
+                let other_token = S::deploy_spl_token(&mut test_state.context).await.pubkey();
+                let destination_token_account = create_token_account::<S>(
+                    &mut test_state.context,
+                    &other_token,
+                    &Keypair::new().pubkey(),
+                )
+                .await;
+                let transaction = get_withdraw_to_destination_tx(
+                    test_state,
+                    &escrow,
+                    &escrow_ata,
+                    &destination_token_account,
+                );
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .dst_timelocks
+                        .get(Stage::DstWithdrawal)
+                        .unwrap(),
+                );
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(ErrorCode::ConstraintTokenMint.into()));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_withdraw_with_excess_tokens(test_state: &mut TestState) {
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+                let transaction = DstProgram::get_withdraw_tx(test_state, &escrow, &escrow_ata);
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .dst_timelocks
+                        .get(Stage::DstWithdrawal)
+                        .unwrap(),
+                );
+
+                let (_, taker_ata) = find_user_ata(test_state);
+
+                let excess_amount = 1000;
+                // Send excess tokens to the escrow account
+                mint_excess_tokens(test_state, &escrow_ata, excess_amount).await;
+                test_state
+                    .expect_state_change(
+                        transaction,
+                        &[token_change(
+                            taker_ata,
+                            test_state.test_arguments.escrow_amount + excess_amount,
+                        )],
+                    )
+                    .await;
+
+                // Assert escrow was closed
+                assert!(test_state
+                    .client
+                    .get_account(escrow)
+                    .await
+                    .unwrap()
+                    .is_none());
+
+                // Assert escrow_ata was closed
+                assert!(test_state
+                    .client
+                    .get_account(escrow_ata)
+                    .await
+                    .unwrap()
+                    .is_none());
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_withdraw_does_not_work_with_wrong_secret(test_state: &mut TestState) {
+                common_escrow_tests::test_withdraw_does_not_work_with_wrong_secret(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_withdraw_with_memo(test_state: &mut TestState) {
+                common_escrow_tests::test_withdraw_with_memo(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_withdraw_fails_with_invalid_memo(test_state: &mut TestState) {
+                common_escrow_tests::test_withdraw_fails_with_invalid_memo(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_withdraw_through_cpi_returns_secret(test_state: &mut TestState) {
+                common_escrow_tests::test_withdraw_through_cpi_returns_secret(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_withdraw_fails_with_insufficient_escrow_balance(
+                test_state: &mut TestState,
+            ) {
+                common_escrow_tests::test_withdraw_fails_with_insufficient_escrow_balance(
+                    test_state,
+                )
+                .await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_withdraw_does_not_work_with_truncated_preimage(
+                test_state: &mut TestState,
+            ) {
+                test_state.hashlock = hashv(&[&test_state.secret[..31]]);
+                common_escrow_tests::test_withdraw_does_not_work_with_non_32_byte_preimage(
+                    test_state,
+                )
+                .await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_withdraw_does_not_work_with_extended_preimage(
+                test_state: &mut TestState,
+            ) {
+                test_state.hashlock = hashv(&[&test_state.secret, &[0u8]]);
+                common_escrow_tests::test_withdraw_does_not_work_with_non_32_byte_preimage(
+                    test_state,
+                )
+                .await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_withdraw_does_not_work_with_non_recipient(test_state: &mut TestState) {
+                common_escrow_tests::test_withdraw_does_not_work_with_non_recipient(test_state)
+                    .await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_withdraw_does_not_work_with_wrong_taker_ata(test_state: &mut TestState) {
                 common_escrow_tests::test_withdraw_does_not_work_with_wrong_taker_ata(test_state)
                     .await
             }
@@ -362,24 +986,6 @@ run_for_tokens!(
                     .await
                     .expect_error(ProgramError::Custom(EscrowError::InvalidMint.into()));
             }
-
-            #[test_context(TestState)]
-            #[tokio::test]
-            async fn test_withdraw_fails_if_withdrawal_duration_overflows(
-                test_state: &mut TestState,
-            ) {
-                test_state.test_arguments.dst_timelocks =
-                    init_timelocks(0, 0, 0, 0, u32::MAX, 0, 0, 0);
-                let (escrow, escrow_ata) = create_escrow(test_state).await;
-
-                let transaction = DstProgram::get_withdraw_tx(test_state, &escrow, &escrow_ata);
-
-                test_state
-                    .client
-                    .process_transaction(transaction)
-                    .await
-                    .expect_error(ProgramError::ArithmeticOverflow);
-            }
         }
 
         mod test_escrow_public_withdraw {
@@ -478,6 +1084,41 @@ run_for_tokens!(
                     .is_none());
             }
 
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_withdraw_emits_secret_revealed(test_state: &mut TestState) {
+                prepare_resolvers(test_state, &[test_state.maker_wallet.keypair.pubkey()]).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+                let transaction = DstProgram::get_public_withdraw_tx(
+                    test_state,
+                    &escrow,
+                    &escrow_ata,
+                    &test_state.maker_wallet.keypair,
+                );
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .dst_timelocks
+                        .get(Stage::DstPublicWithdrawal)
+                        .unwrap(),
+                );
+
+                let events = common_escrow_tests::simulate_tx_events::<_, _, SecretRevealed>(
+                    test_state,
+                    transaction,
+                )
+                .await;
+                assert_eq!(events.len(), 1);
+                assert_eq!(
+                    events[0].order_hash,
+                    DstProgram::get_escrow_order_hash(test_state)
+                );
+                assert_eq!(events[0].hashlock, test_state.hashlock.to_bytes());
+                assert_eq!(events[0].secret, test_state.secret);
+            }
+
             #[test_context(TestState)]
             #[tokio::test]
             async fn test_public_withdraw_tokens_by_any_resolver(test_state: &mut TestState) {
@@ -493,46 +1134,138 @@ run_for_tokens!(
 
                 let (escrow, escrow_ata) = create_escrow(test_state).await;
 
-                let transaction = DstProgram::get_public_withdraw_tx(
-                    test_state,
-                    &escrow,
-                    &escrow_ata,
-                    &withdrawer,
-                );
-
-                set_time(
-                    &mut test_state.context,
-                    test_state
-                        .test_arguments
-                        .dst_timelocks
-                        .get(Stage::DstPublicWithdrawal)
-                        .unwrap(),
-                );
+                let transaction = DstProgram::get_public_withdraw_tx(
+                    test_state,
+                    &escrow,
+                    &escrow_ata,
+                    &withdrawer,
+                );
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .dst_timelocks
+                        .get(Stage::DstPublicWithdrawal)
+                        .unwrap(),
+                );
+
+                // Check that the escrow balance is correct
+                assert_eq!(
+                    get_token_balance(&mut test_state.context, &escrow_ata).await,
+                    test_state.test_arguments.escrow_amount
+                );
+
+                let escrow_data_len = DEFAULT_DST_ESCROW_SIZE;
+
+                let rent_lamports =
+                    get_min_rent_for_size(&mut test_state.client, escrow_data_len).await;
+
+                let token_account_rent = get_min_rent_for_size(
+                    &mut test_state.client,
+                    <TestState as HasTokenVariant>::Token::get_token_account_size(),
+                )
+                .await;
+
+                assert_eq!(
+                    rent_lamports,
+                    test_state.client.get_balance(escrow).await.unwrap()
+                );
+
+                let (_, taker_ata) = find_user_ata(test_state);
+
+                test_state
+                    .expect_state_change(
+                        transaction,
+                        &[
+                            native_change(
+                                test_state.maker_wallet.keypair.pubkey(),
+                                token_account_rent + rent_lamports
+                                    - test_state.test_arguments.safety_deposit,
+                            ),
+                            native_change(
+                                withdrawer.pubkey(),
+                                test_state.test_arguments.safety_deposit,
+                            ),
+                            token_change(taker_ata, test_state.test_arguments.escrow_amount),
+                        ],
+                    )
+                    .await;
+
+                // Assert accounts were closed
+                assert!(test_state
+                    .client
+                    .get_account(escrow)
+                    .await
+                    .unwrap()
+                    .is_none());
+
+                // Assert escrow_ata was closed
+                assert!(test_state
+                    .client
+                    .get_account(escrow_ata)
+                    .await
+                    .unwrap()
+                    .is_none());
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_withdraw_pays_topped_up_safety_deposit(
+                test_state: &mut TestState,
+            ) {
+                let withdrawer = Keypair::new();
+                prepare_resolvers(test_state, &[withdrawer.pubkey()]).await;
+                transfer_lamports(
+                    &mut test_state.context,
+                    WALLET_DEFAULT_LAMPORTS,
+                    &test_state.payer_kp,
+                    &withdrawer.pubkey(),
+                )
+                .await;
+
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+
+                let additional = test_state.test_arguments.safety_deposit;
+                let transaction =
+                    get_top_up_escrow_safety_deposit_tx(test_state, &escrow, additional);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success();
 
-                // Check that the escrow balance is correct
+                let rent_lamports =
+                    get_min_rent_for_size(&mut test_state.client, DEFAULT_DST_ESCROW_SIZE).await;
                 assert_eq!(
-                    get_token_balance(&mut test_state.context, &escrow_ata).await,
-                    test_state.test_arguments.escrow_amount
+                    rent_lamports + additional,
+                    test_state.client.get_balance(escrow).await.unwrap()
                 );
 
-                let escrow_data_len = DEFAULT_DST_ESCROW_SIZE;
-
-                let rent_lamports =
-                    get_min_rent_for_size(&mut test_state.client, escrow_data_len).await;
-
                 let token_account_rent = get_min_rent_for_size(
                     &mut test_state.client,
                     <TestState as HasTokenVariant>::Token::get_token_account_size(),
                 )
                 .await;
 
-                assert_eq!(
-                    rent_lamports,
-                    test_state.client.get_balance(escrow).await.unwrap()
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .dst_timelocks
+                        .get(Stage::DstPublicWithdrawal)
+                        .unwrap(),
                 );
 
                 let (_, taker_ata) = find_user_ata(test_state);
+                let transaction = DstProgram::get_public_withdraw_tx(
+                    test_state,
+                    &escrow,
+                    &escrow_ata,
+                    &withdrawer,
+                );
 
+                // The maker gets back the same rent as without the top-up
                 test_state
                     .expect_state_change(
                         transaction,
@@ -544,28 +1277,128 @@ run_for_tokens!(
                             ),
                             native_change(
                                 withdrawer.pubkey(),
-                                test_state.test_arguments.safety_deposit,
+                                test_state.test_arguments.safety_deposit + additional,
                             ),
                             token_change(taker_ata, test_state.test_arguments.escrow_amount),
                         ],
                     )
                     .await;
+            }
 
-                // Assert accounts were closed
-                assert!(test_state
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_top_up_safety_deposit_fails_after_withdrawal_start(
+                test_state: &mut TestState,
+            ) {
+                let (escrow, _) = create_escrow(test_state).await;
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .dst_timelocks
+                        .get(Stage::DstWithdrawal)
+                        .unwrap(),
+                );
+
+                let transaction = get_top_up_escrow_safety_deposit_tx(test_state, &escrow, 1);
+                test_state
                     .client
-                    .get_account(escrow)
+                    .process_transaction(transaction)
                     .await
-                    .unwrap()
-                    .is_none());
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidTime.into()));
+            }
 
-                // Assert escrow_ata was closed
-                assert!(test_state
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_top_up_safety_deposit_fails_above_rent_exempt_reserve(
+                test_state: &mut TestState,
+            ) {
+                let (escrow, _) = create_escrow(test_state).await;
+
+                let rent_lamports =
+                    get_min_rent_for_size(&mut test_state.client, DEFAULT_DST_ESCROW_SIZE).await;
+                let additional = rent_lamports - test_state.test_arguments.safety_deposit + 1;
+                let transaction =
+                    get_top_up_escrow_safety_deposit_tx(test_state, &escrow, additional);
+                test_state
                     .client
-                    .get_account(escrow_ata)
+                    .process_transaction(transaction)
                     .await
-                    .unwrap()
-                    .is_none());
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::SafetyDepositTooLarge.into(),
+                    ));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_withdraw_with_safety_deposit_split(test_state: &mut TestState) {
+                let withdrawer = Keypair::new();
+                prepare_resolvers(test_state, &[withdrawer.pubkey()]).await;
+                transfer_lamports(
+                    &mut test_state.context,
+                    WALLET_DEFAULT_LAMPORTS,
+                    &test_state.payer_kp,
+                    &withdrawer.pubkey(),
+                )
+                .await;
+                // 33.33% of the default safety deposit is not a whole number of lamports
+                common_escrow_tests::test_public_withdraw_with_safety_deposit_split(
+                    test_state,
+                    &withdrawer,
+                    3_333,
+                )
+                .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_withdraw_by_rent_recipient_with_split(test_state: &mut TestState) {
+                prepare_resolvers(test_state, &[test_state.maker_wallet.keypair.pubkey()]).await;
+                common_escrow_tests::test_public_withdraw_by_rent_recipient_with_split(
+                    test_state, 3_333,
+                )
+                .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_withdraw_with_reward_recipient(test_state: &mut TestState) {
+                let withdrawer = Keypair::new();
+                prepare_resolvers(test_state, &[withdrawer.pubkey()]).await;
+                transfer_lamports(
+                    &mut test_state.context,
+                    WALLET_DEFAULT_LAMPORTS,
+                    &test_state.payer_kp,
+                    &withdrawer.pubkey(),
+                )
+                .await;
+                common_escrow_tests::test_public_withdraw_with_reward_recipient(
+                    test_state,
+                    &withdrawer,
+                )
+                .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_withdraw_fails_with_invalid_safety_deposit_split(
+                test_state: &mut TestState,
+            ) {
+                let withdrawer = Keypair::new();
+                prepare_resolvers(test_state, &[withdrawer.pubkey()]).await;
+                transfer_lamports(
+                    &mut test_state.context,
+                    WALLET_DEFAULT_LAMPORTS,
+                    &test_state.payer_kp,
+                    &withdrawer.pubkey(),
+                )
+                .await;
+                common_escrow_tests::test_public_withdraw_fails_with_invalid_safety_deposit_split(
+                    test_state,
+                    &withdrawer,
+                )
+                .await;
             }
 
             #[test_context(TestState)]
@@ -639,30 +1472,6 @@ run_for_tokens!(
                 );
             }
 
-            #[test_context(TestState)]
-            #[tokio::test]
-            async fn test_public_withdraw_fails_if_public_withdrawal_duration_overflows(
-                test_state: &mut TestState,
-            ) {
-                prepare_resolvers(test_state, &[test_state.maker_wallet.keypair.pubkey()]).await;
-                test_state.test_arguments.dst_timelocks =
-                    init_timelocks(0, 0, 0, 0, 0, u32::MAX, 0, 0);
-                let (escrow, escrow_ata) = create_escrow(test_state).await;
-
-                let transaction = DstProgram::get_public_withdraw_tx(
-                    test_state,
-                    &escrow,
-                    &escrow_ata,
-                    &test_state.maker_wallet.keypair,
-                );
-
-                test_state
-                    .client
-                    .process_transaction(transaction)
-                    .await
-                    .expect_error(ProgramError::ArithmeticOverflow);
-            }
-
             #[test_context(TestState)]
             #[tokio::test]
             async fn test_public_withdraw_fails_with_wrong_secret(test_state: &mut TestState) {
@@ -748,22 +1557,129 @@ run_for_tokens!(
                 let transaction =
                     DstProgram::get_public_withdraw_tx(test_state, &escrow, &escrow_ata, &taker_kp);
 
-                test_state
-                    .client
-                    .process_transaction(transaction)
-                    .await
-                    .expect_error(ProgramError::Custom(ErrorCode::ConstraintTokenMint.into()));
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(ErrorCode::ConstraintTokenMint.into()));
+            }
+        }
+
+        mod test_escrow_cancel {
+            use super::*;
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel(test_state: &mut TestState) {
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+                common_escrow_tests::test_cancel(test_state, &escrow, &escrow_ata).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_emits_escrow_cancelled(test_state: &mut TestState) {
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+                let transaction = DstProgram::get_cancel_tx(test_state, &escrow, &escrow_ata);
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .dst_timelocks
+                        .get(Stage::DstCancellation)
+                        .unwrap(),
+                );
+
+                let events = common_escrow_tests::simulate_tx_events::<_, _, EscrowCancelled>(
+                    test_state,
+                    transaction,
+                )
+                .await;
+                assert_eq!(events.len(), 1);
+                assert_eq!(
+                    events[0].order_hash,
+                    DstProgram::get_escrow_order_hash(test_state)
+                );
+                assert_eq!(events[0].hashlock, test_state.hashlock.to_bytes());
+                assert_eq!(events[0].amount, test_state.test_arguments.escrow_amount);
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_without_creator_ata(test_state: &mut TestState) {
+                type S = <TestState as HasTokenVariant>::Token;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+
+                // The creator closes their ATA after the escrow is created
+                let (creator_ata, _) = find_user_ata(test_state);
+                let balance = get_token_balance(&mut test_state.context, &creator_ata).await;
+                S::burn_tokens(
+                    &mut test_state.context,
+                    &creator_ata,
+                    &test_state.token,
+                    &test_state.maker_wallet.keypair,
+                    &test_state.payer_kp,
+                    balance,
+                )
+                .await;
+                S::close_ata(
+                    &mut test_state.context,
+                    &creator_ata,
+                    &test_state.maker_wallet.keypair.pubkey(),
+                    &test_state.maker_wallet.keypair.pubkey(),
+                    &test_state.maker_wallet.keypair,
+                )
+                .await;
+
+                let transaction = DstProgram::get_cancel_tx(test_state, &escrow, &escrow_ata);
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .dst_timelocks
+                        .get(Stage::DstCancellation)
+                        .unwrap(),
+                );
+
+                // The creator pays for the recreated ATA out of the escrow ATA rent,
+                // as both token accounts have the same size
+                let escrow_rent =
+                    get_min_rent_for_size(&mut test_state.client, DEFAULT_DST_ESCROW_SIZE).await;
+
+                test_state
+                    .expect_state_change(
+                        transaction,
+                        &[
+                            native_change(test_state.maker_wallet.keypair.pubkey(), escrow_rent),
+                            account_closure(escrow, true),
+                            account_closure(escrow_ata, true),
+                        ],
+                    )
+                    .await;
+
+                assert_eq!(
+                    get_token_balance(&mut test_state.context, &creator_ata).await,
+                    test_state.test_arguments.escrow_amount
+                );
             }
-        }
 
-        mod test_escrow_cancel {
-            use super::*;
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_status_follows_timelocks(test_state: &mut TestState) {
+                common_escrow_tests::test_escrow_status_follows_timelocks(test_state).await
+            }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_cancel(test_state: &mut TestState) {
-                let (escrow, escrow_ata) = create_escrow(test_state).await;
-                common_escrow_tests::test_cancel(test_state, &escrow, &escrow_ata).await
+            async fn test_derive_escrow_pda_matches_created_escrow(test_state: &mut TestState) {
+                common_escrow_tests::test_derive_escrow_pda_matches_created_escrow(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_rent_matches_escrow_creation(test_state: &mut TestState) {
+                common_escrow_tests::test_escrow_rent_matches_escrow_creation(test_state).await
             }
 
             #[test_context(TestState)]
@@ -826,6 +1742,15 @@ run_for_tokens!(
                 common_escrow_tests::test_cannot_cancel_before_cancellation_start(test_state).await
             }
 
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_fails_with_insufficient_escrow_balance(
+                test_state: &mut TestState,
+            ) {
+                common_escrow_tests::test_cancel_fails_with_insufficient_escrow_balance(test_state)
+                    .await
+            }
+
             #[test_context(TestState)]
             #[tokio::test]
             async fn test_cancel_fails_with_incorrect_token(test_state: &mut TestState) {
@@ -846,6 +1771,158 @@ run_for_tokens!(
                     .expect_error(ProgramError::Custom(EscrowError::InvalidMint.into()));
             }
         }
+        mod test_escrow_public_cancel {
+            use super::*;
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_cancel_by_any_resolver(test_state: &mut TestState) {
+                let canceller = Keypair::new();
+                transfer_lamports(
+                    &mut test_state.context,
+                    WALLET_DEFAULT_LAMPORTS,
+                    &test_state.payer_kp,
+                    &canceller.pubkey(),
+                )
+                .await;
+                prepare_resolvers(test_state, &[canceller.pubkey()]).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+
+                let transaction =
+                    get_public_cancel_tx(test_state, &escrow, &escrow_ata, &canceller);
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .dst_timelocks
+                        .get(Stage::DstPublicCancellation)
+                        .unwrap(),
+                );
+
+                let escrow_rent =
+                    get_min_rent_for_size(&mut test_state.client, DEFAULT_DST_ESCROW_SIZE).await;
+                let token_account_rent = get_min_rent_for_size(
+                    &mut test_state.client,
+                    <TestState as HasTokenVariant>::Token::get_token_account_size(),
+                )
+                .await;
+
+                let (maker_ata, _) = find_user_ata(test_state);
+
+                // The creator gets the tokens and the rent back, except for the safety deposit
+                // awarded to the canceller
+                test_state
+                    .expect_state_change(
+                        transaction,
+                        &[
+                            token_change(maker_ata, test_state.test_arguments.escrow_amount),
+                            native_change(
+                                test_state.maker_wallet.keypair.pubkey(),
+                                escrow_rent + token_account_rent
+                                    - test_state.test_arguments.safety_deposit,
+                            ),
+                            native_change(
+                                canceller.pubkey(),
+                                test_state.test_arguments.safety_deposit,
+                            ),
+                            account_closure(escrow_ata, true),
+                            account_closure(escrow, true),
+                        ],
+                    )
+                    .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_cancel_emits_escrow_public_cancelled(test_state: &mut TestState) {
+                let canceller = test_state.payer_kp.insecure_clone();
+                prepare_resolvers(test_state, &[canceller.pubkey()]).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+                let transaction =
+                    get_public_cancel_tx(test_state, &escrow, &escrow_ata, &canceller);
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .dst_timelocks
+                        .get(Stage::DstPublicCancellation)
+                        .unwrap(),
+                );
+
+                let events =
+                    common_escrow_tests::simulate_tx_events::<_, _, EscrowPublicCancelled>(
+                        test_state,
+                        transaction,
+                    )
+                    .await;
+                assert_eq!(events.len(), 1);
+                assert_eq!(
+                    events[0].order_hash,
+                    DstProgram::get_escrow_order_hash(test_state)
+                );
+                assert_eq!(events[0].hashlock, test_state.hashlock.to_bytes());
+                assert_eq!(events[0].amount, test_state.test_arguments.escrow_amount);
+                assert_eq!(events[0].canceller, canceller.pubkey());
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cannot_public_cancel_before_public_cancellation_start(
+                test_state: &mut TestState,
+            ) {
+                prepare_resolvers(test_state, &[test_state.payer_kp.pubkey()]).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+
+                let transaction =
+                    get_public_cancel_tx(test_state, &escrow, &escrow_ata, &test_state.payer_kp);
+
+                // Only the creator can cancel between the cancellation and the public cancellation
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .dst_timelocks
+                        .get(Stage::DstCancellation)
+                        .unwrap(),
+                );
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidTime.into()));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_cancel_fails_without_resolver_access(test_state: &mut TestState) {
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+
+                // The payer does not have resolver access
+                let transaction =
+                    get_public_cancel_tx(test_state, &escrow, &escrow_ata, &test_state.payer_kp);
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .dst_timelocks
+                        .get(Stage::DstPublicCancellation)
+                        .unwrap(),
+                );
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        ErrorCode::AccountNotInitialized.into(),
+                    ));
+            }
+        }
+
         mod test_escrow_rescue_funds {
             use super::*;
 
@@ -856,6 +1933,28 @@ run_for_tokens!(
                 common_escrow_tests::test_rescue_all_tokens_and_close_ata(test_state).await
             }
 
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_rescue_fails_with_frozen_escrow_ata(test_state: &mut TestState) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                common_escrow_tests::test_rescue_fails_with_frozen_escrow_ata(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_rescue_all_tokens_with_rent_recipient(test_state: &mut TestState) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                common_escrow_tests::test_rescue_all_tokens_with_rent_recipient(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_rescue_fails_with_non_system_rent_recipient(test_state: &mut TestState) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                common_escrow_tests::test_rescue_fails_with_non_system_rent_recipient(test_state)
+                    .await
+            }
+
             #[test_context(TestState)]
             #[tokio::test]
             async fn test_rescue_part_of_tokens_and_not_close_ata(test_state: &mut TestState) {
@@ -901,9 +2000,344 @@ run_for_tokens!(
                     .await
             }
         }
+
+        mod test_tvl_stats {
+            use super::*;
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_tvl_follows_escrows_until_resolved(test_state: &mut TestState) {
+                enable_tvl_tracking(test_state).await;
+                let amount = test_state.test_arguments.escrow_amount;
+
+                let (escrow_to_withdraw, escrow_ata_to_withdraw) = create_escrow(test_state).await;
+                assert_eq!(get_tvl_total_locked(test_state).await, amount);
+
+                // An escrow of another order, which is cancelled instead of withdrawn
+                test_state.order_hash = Pubkey::new_unique();
+                let (escrow_to_cancel, escrow_ata_to_cancel) = create_escrow(test_state).await;
+                assert_eq!(get_tvl_total_locked(test_state).await, 2 * amount);
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .dst_timelocks
+                        .get(Stage::DstWithdrawal)
+                        .unwrap(),
+                );
+                let transaction = DstProgram::get_withdraw_tx(
+                    test_state,
+                    &escrow_to_withdraw,
+                    &escrow_ata_to_withdraw,
+                );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success();
+                assert_eq!(get_tvl_total_locked(test_state).await, amount);
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .dst_timelocks
+                        .get(Stage::DstCancellation)
+                        .unwrap(),
+                );
+                let transaction =
+                    DstProgram::get_cancel_tx(test_state, &escrow_to_cancel, &escrow_ata_to_cancel);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success();
+                assert_eq!(get_tvl_total_locked(test_state).await, 0);
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_fails_without_tvl_stats_when_tracked(
+                test_state: &mut TestState,
+            ) {
+                enable_tvl_tracking(test_state).await;
+                test_state.test_arguments.pass_tvl_stats = false;
+
+                let (_, _, transaction) = create_escrow_data(test_state);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::MissingTvlStats.into()));
+            }
+        }
     }
 );
 
+// Transfer fees and permanent delegates are Token-2022 extensions
+mod test_token_2022_extensions {
+    use super::*;
+
+    type TestState = TestStateBase<DstProgram, Token2022>;
+
+    mod test_transfer_fee {
+        use super::*;
+
+        const FEE_BPS: u16 = DEFAULT_TRANSFER_FEE_BASIS_POINTS;
+
+        // Creates an escrow funded with a transfer fee token, checking that the escrow ATA receives
+        // the escrow amount net of the fee and is rent exempt for its larger size
+        async fn create_escrow_with_transfer_fee(test_state: &mut TestState) -> (Pubkey, Pubkey) {
+            use_transfer_fee_token(test_state, FEE_BPS).await;
+            let (escrow, escrow_ata) = create_escrow(test_state).await;
+
+            let escrow_amount = test_state.test_arguments.escrow_amount;
+            assert_eq!(
+                escrow_amount - get_transfer_fee(escrow_amount, FEE_BPS),
+                get_token_balance(&mut test_state.context, &escrow_ata).await
+            );
+            assert_eq!(
+                WALLET_DEFAULT_TOKENS - escrow_amount,
+                get_token_balance(
+                    &mut test_state.context,
+                    &test_state.maker_wallet.token_account
+                )
+                .await
+            );
+
+            let escrow_rent =
+                get_min_rent_for_size(&mut test_state.client, DEFAULT_DST_ESCROW_SIZE).await;
+            let token_account_rent = get_min_rent_for_size(
+                &mut test_state.client,
+                get_transfer_fee_token_account_size(),
+            )
+            .await;
+            assert_eq!(
+                escrow_rent,
+                test_state.client.get_balance(escrow).await.unwrap()
+            );
+            assert_eq!(
+                token_account_rent,
+                test_state.client.get_balance(escrow_ata).await.unwrap()
+            );
+
+            (escrow, escrow_ata)
+        }
+
+        #[test_context(TestState)]
+        #[tokio::test]
+        async fn test_withdraw_with_transfer_fee(test_state: &mut TestState) {
+            let (escrow, escrow_ata) = create_escrow_with_transfer_fee(test_state).await;
+
+            set_time(
+                &mut test_state.context,
+                test_state
+                    .test_arguments
+                    .dst_timelocks
+                    .get(Stage::DstWithdrawal)
+                    .unwrap(),
+            );
+            let transaction = DstProgram::get_withdraw_tx(test_state, &escrow, &escrow_ata);
+
+            let escrow_rent =
+                get_min_rent_for_size(&mut test_state.client, DEFAULT_DST_ESCROW_SIZE).await;
+            let token_account_rent = get_min_rent_for_size(
+                &mut test_state.client,
+                get_transfer_fee_token_account_size(),
+            )
+            .await;
+            let escrow_ata_amount = get_token_balance(&mut test_state.context, &escrow_ata).await;
+            let withheld_fees =
+                get_mint_withheld_fees(&mut test_state.context, &test_state.token).await;
+
+            let (_, taker_ata) = find_user_ata(test_state);
+
+            // The recipient receives the entire escrow ATA balance net of the fee of the last transfer
+            harvest_withheld_fees(&mut test_state.context, &test_state.token, &[&escrow_ata]).await;
+            test_state
+                .expect_state_change(
+                    transaction,
+                    &[
+                        native_change(
+                            test_state.maker_wallet.keypair.pubkey(),
+                            escrow_rent + token_account_rent,
+                        ),
+                        token_change(
+                            taker_ata,
+                            escrow_ata_amount - get_transfer_fee(escrow_ata_amount, FEE_BPS),
+                        ),
+                        account_closure(escrow, true),
+                        account_closure(escrow_ata, true),
+                    ],
+                )
+                .await;
+
+            // The fee withheld when funding the escrow is harvested before closing the escrow ATA
+            assert_eq!(
+                withheld_fees + get_transfer_fee(test_state.test_arguments.escrow_amount, FEE_BPS),
+                get_mint_withheld_fees(&mut test_state.context, &test_state.token).await
+            );
+        }
+
+        #[test_context(TestState)]
+        #[tokio::test]
+        async fn test_public_withdraw_with_transfer_fee_and_max_safety_deposit(
+            test_state: &mut TestState,
+        ) {
+            // The safety deposit is bounded by the escrow rent only, the larger token account of
+            // a transfer fee mint must not affect it
+            let escrow_rent =
+                get_min_rent_for_size(&mut test_state.client, DEFAULT_DST_ESCROW_SIZE).await;
+            test_state.test_arguments.safety_deposit = escrow_rent;
+            let (escrow, escrow_ata) = create_escrow_with_transfer_fee(test_state).await;
+
+            let withdrawer = Keypair::new();
+            prepare_resolvers(test_state, &[withdrawer.pubkey()]).await;
+            transfer_lamports(
+                &mut test_state.context,
+                WALLET_DEFAULT_LAMPORTS,
+                &test_state.payer_kp,
+                &withdrawer.pubkey(),
+            )
+            .await;
+
+            set_time(
+                &mut test_state.context,
+                test_state
+                    .test_arguments
+                    .dst_timelocks
+                    .get(Stage::DstPublicWithdrawal)
+                    .unwrap(),
+            );
+            let transaction =
+                DstProgram::get_public_withdraw_tx(test_state, &escrow, &escrow_ata, &withdrawer);
+
+            let token_account_rent = get_min_rent_for_size(
+                &mut test_state.client,
+                get_transfer_fee_token_account_size(),
+            )
+            .await;
+            let escrow_ata_amount = get_token_balance(&mut test_state.context, &escrow_ata).await;
+
+            let (_, taker_ata) = find_user_ata(test_state);
+
+            harvest_withheld_fees(&mut test_state.context, &test_state.token, &[&escrow_ata]).await;
+            test_state
+                .expect_state_change(
+                    transaction,
+                    &[
+                        native_change(withdrawer.pubkey(), escrow_rent),
+                        native_change(test_state.maker_wallet.keypair.pubkey(), token_account_rent),
+                        token_change(
+                            taker_ata,
+                            escrow_ata_amount - get_transfer_fee(escrow_ata_amount, FEE_BPS),
+                        ),
+                        account_closure(escrow, true),
+                        account_closure(escrow_ata, true),
+                    ],
+                )
+                .await;
+        }
+
+        #[test_context(TestState)]
+        #[tokio::test]
+        async fn test_cancel_with_transfer_fee(test_state: &mut TestState) {
+            let (escrow, escrow_ata) = create_escrow_with_transfer_fee(test_state).await;
+
+            set_time(
+                &mut test_state.context,
+                test_state
+                    .test_arguments
+                    .dst_timelocks
+                    .get(Stage::DstCancellation)
+                    .unwrap(),
+            );
+            let transaction = DstProgram::get_cancel_tx(test_state, &escrow, &escrow_ata);
+
+            let escrow_rent =
+                get_min_rent_for_size(&mut test_state.client, DEFAULT_DST_ESCROW_SIZE).await;
+            let token_account_rent = get_min_rent_for_size(
+                &mut test_state.client,
+                get_transfer_fee_token_account_size(),
+            )
+            .await;
+            let escrow_ata_amount = get_token_balance(&mut test_state.context, &escrow_ata).await;
+
+            let (maker_ata, _) = find_user_ata(test_state);
+
+            // The creator gets back the escrow ATA balance, which is charged the fee once more
+            harvest_withheld_fees(&mut test_state.context, &test_state.token, &[&escrow_ata]).await;
+            test_state
+                .expect_state_change(
+                    transaction,
+                    &[
+                        native_change(
+                            test_state.maker_wallet.keypair.pubkey(),
+                            escrow_rent + token_account_rent,
+                        ),
+                        token_change(
+                            maker_ata,
+                            escrow_ata_amount - get_transfer_fee(escrow_ata_amount, FEE_BPS),
+                        ),
+                        account_closure(escrow, true),
+                        account_closure(escrow_ata, true),
+                    ],
+                )
+                .await;
+        }
+
+        #[test_context(TestState)]
+        #[tokio::test]
+        async fn test_withdraw_fails_with_underfunded_escrow_with_transfer_fee(
+            test_state: &mut TestState,
+        ) {
+            let (escrow, escrow_ata) = create_escrow_with_transfer_fee(test_state).await;
+
+            // The escrow ATA holds less than the escrow amount net of the fee it was funded with
+            let escrow_ata_amount = get_token_balance(&mut test_state.context, &escrow_ata).await;
+            set_token_balance(&mut test_state.context, &escrow_ata, escrow_ata_amount - 1).await;
+
+            set_time(
+                &mut test_state.context,
+                test_state
+                    .test_arguments
+                    .dst_timelocks
+                    .get(Stage::DstWithdrawal)
+                    .unwrap(),
+            );
+            let transaction = DstProgram::get_withdraw_tx(test_state, &escrow, &escrow_ata);
+            test_state
+                .client
+                .process_transaction(transaction)
+                .await
+                .expect_error(ProgramError::Custom(
+                    EscrowError::InsufficientEscrowBalance.into(),
+                ));
+        }
+    }
+
+    mod test_permanent_delegate {
+        use super::*;
+
+        #[test_context(TestState)]
+        #[tokio::test]
+        async fn test_escrow_creation_fails_with_permanent_delegate(test_state: &mut TestState) {
+            let delegate = Keypair::new();
+            use_permanent_delegate_token(test_state, &delegate.pubkey()).await;
+
+            let (_, _, transaction) = create_escrow_data(test_state);
+            test_state
+                .client
+                .process_transaction(transaction)
+                .await
+                .expect_error(ProgramError::Custom(
+                    EscrowError::UnsupportedMintExtension.into(),
+                ));
+        }
+    }
+}
+
 // pub async fn test_cannot_rescue_funds_by_non_whitelisted_resolver<S: TokenVariant>(
 //     test_state: &mut TestStateBase<DstProgram, S>,
 // ) {
@@ -1,4 +1,16 @@
 use anchor_lang::prelude::*;
+use common::error::EscrowError;
+
+/// Directions in which the dst amount can be rounded after applying the rate bump
+pub const ROUNDING_UP: u8 = 0;
+pub const ROUNDING_DOWN: u8 = 1;
+pub const ROUNDING_NEAREST: u8 = 2;
+
+/// Maximum time in seconds by which an auction may start after a fill. A later start would keep
+/// the rate bump at its initial value for as long as the order is open
+pub const MAX_AUCTION_LEAD: u32 = 24 * 60 * 60;
+/// Maximum duration of an auction in seconds
+pub const MAX_AUCTION_DURATION: u32 = 7 * 24 * 60 * 60;
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
 pub struct PointAndTimeDelta {
@@ -12,6 +24,52 @@ pub struct AuctionData {
     pub duration: u32,
     pub initial_rate_bump: U24,
     pub points_and_time_deltas: Vec<PointAndTimeDelta>,
+    /// Rounding of the bumped dst amount, one of the `ROUNDING_*` values. Rounding up favors
+    /// the maker, the other modes allow matching the math of other implementations
+    pub rounding: u8,
+}
+
+impl AuctionData {
+    /// Checks that the points are sorted by time within the auction duration and that the
+    /// rate bump does not increase over the course of the auction.
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.rounding <= ROUNDING_NEAREST,
+            EscrowError::InvalidRounding
+        );
+
+        let mut current_rate_bump = self.initial_rate_bump.to_u64();
+        let mut current_point_time = 0u64;
+
+        for point_and_time_delta in self.points_and_time_deltas.iter() {
+            let next_rate_bump = point_and_time_delta.rate_bump.to_u64();
+            let point_time_delta = point_and_time_delta.time_delta as u64;
+            // Overflow is not possible as the number of points is bounded by the transaction size
+            current_point_time += point_time_delta;
+
+            require!(
+                point_time_delta != 0
+                    && current_point_time <= self.duration as u64
+                    && next_rate_bump <= current_rate_bump,
+                EscrowError::InvalidAuctionCurve
+            );
+
+            current_rate_bump = next_rate_bump;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that an auction used for a fill at `now` starts within `MAX_AUCTION_LEAD`
+    /// and lasts at most `MAX_AUCTION_DURATION`.
+    pub fn validate_timing(&self, now: u32) -> Result<()> {
+        require!(
+            self.start_time as u64 <= now as u64 + MAX_AUCTION_LEAD as u64
+                && self.duration <= MAX_AUCTION_DURATION,
+            EscrowError::InvalidAuctionTiming
+        );
+        Ok(())
+    }
 }
 
 pub fn calculate_rate_bump(timestamp: u64, data: &AuctionData) -> u64 {
@@ -1,18 +1,19 @@
-use crate::merkle_tree::MerkleProof;
+use crate::merkle_tree::{decode_parts_amount, MerkleProof};
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::keccak;
-use anchor_spl::associated_token::{AssociatedToken, ID as ASSOCIATED_TOKEN_PROGRAM_ID};
-use anchor_spl::token::spl_token::native_mint::ID as NATIVE_MINT;
-use anchor_spl::token_interface::{
-    close_account, CloseAccount, Mint, TokenAccount, TokenInterface,
+use anchor_spl::associated_token::{
+    get_associated_token_address_with_program_id, AssociatedToken,
+    ID as ASSOCIATED_TOKEN_PROGRAM_ID,
 };
+use anchor_spl::memo::Memo;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 pub use auction::{calculate_premium, calculate_rate_bump, AuctionData};
 pub use common::constants;
 use common::{
     error::EscrowError,
-    escrow::{uni_transfer, UniTransferParams},
-    timelocks::{Stage, Timelocks},
-    utils::get_current_timestamp,
+    escrow::{attach_memo, close_token_ata, lock_tvl, uni_transfer, unlock_tvl, UniTransferParams},
+    timelocks::{Stage, Timelocks, DST_STAGES, SRC_STAGES},
+    utils::{get_current_timestamp, get_order_hash_with_dst_terms},
 };
 
 use primitive_types::U256;
@@ -28,270 +29,282 @@ pub mod cross_chain_escrow_src {
 
     use super::*;
 
-    #[allow(clippy::too_many_arguments)]
-    pub fn create(
-        ctx: Context<Create>,
-        hashlock: [u8; 32], // Root of merkle tree if partially filled
-        amount: u64,
-        safety_deposit: u64,
-        timelocks: [u64; 4],
-        expiration_time: u32,
-        asset_is_native: bool,
-        dst_amount: [u64; 4],
-        dutch_auction_data_hash: [u8; 32],
-        max_cancellation_premium: u64,
-        cancellation_auction_duration: u32,
-        allow_multiple_fills: bool,
-        salt: u64,
-        _dst_chain_params: DstChainParams,
-    ) -> Result<()> {
-        require!(
-            ctx.accounts.order_ata.to_account_info().lamports() >= max_cancellation_premium,
-            EscrowError::InvalidCancellationFee
-        );
-
-        if allow_multiple_fills {
-            let parts_amount = u16::from_be_bytes([hashlock[0], hashlock[1]]);
+    pub fn create(ctx: Context<Create>, args: OrderArgs) -> Result<()> {
+        utils::create_order(
+            &ctx.accounts.creator,
+            &ctx.accounts.mint,
+            ctx.accounts.creator_ata.as_deref(),
+            ctx.accounts.delegate.as_ref(),
+            &mut ctx.accounts.order,
+            ctx.bumps.order,
+            &ctx.accounts.order_ata,
+            &ctx.accounts.token_program,
+            &ctx.accounts.system_program,
+            args,
+        )
+    }
 
-            require!(parts_amount > 1, EscrowError::InvalidPartsAmount);
+    /// Same as `create`, but succeeds without changes if the order already exists with the
+    /// same parameters, so that a creation can be retried safely. Once the order is filled or
+    /// cancelled and closed, a retry creates it anew.
+    pub fn create_idempotent(ctx: Context<CreateIdempotent>, args: OrderArgs) -> Result<()> {
+        // A newly initialized order is zeroed, while a created one records its creator
+        if ctx.accounts.order.creator != Pubkey::default() {
+            // The order may have been handed over to another creator since it was created
+            require!(
+                ctx.accounts.order.creator == ctx.accounts.creator.key(),
+                EscrowError::InvalidAccount
+            );
+            require!(
+                utils::order_matches_args(&ctx.accounts.order, &args),
+                EscrowError::OrderParamsMismatch
+            );
+            return Ok(());
         }
 
-        let now = get_current_timestamp()?;
-
-        require!(now < expiration_time, EscrowError::OrderHasExpired);
-
-        let order_hash = get_order_hash(
-            hashlock,
-            ctx.accounts.creator.key(),
-            ctx.accounts.mint.key(),
-            amount,
-            safety_deposit,
-            timelocks,
-            expiration_time,
-            asset_is_native,
-            dst_amount,
-            dutch_auction_data_hash,
-            max_cancellation_premium,
-            cancellation_auction_duration,
-            allow_multiple_fills,
-            salt,
-        );
+        utils::create_order(
+            &ctx.accounts.creator,
+            &ctx.accounts.mint,
+            ctx.accounts.creator_ata.as_deref(),
+            ctx.accounts.delegate.as_ref(),
+            &mut ctx.accounts.order,
+            ctx.bumps.order,
+            &ctx.accounts.order_ata,
+            &ctx.accounts.token_program,
+            &ctx.accounts.system_program,
+            args,
+        )
+    }
 
-        // TODO: Verify that safety_deposit is enough to cover public_withdraw and public_cancel methods
+    /// If the maker has assigned parts of the order to resolvers, the remaining accounts hold the
+    /// part authorization PDAs of every part the fill covers, in the order of their indices
+    pub fn create_escrow<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CreateEscrow<'info>>,
+        amount: u64,
+        merkle_proof: Option<MerkleProof>,
+        dutch_auction_data: AuctionData,
+        affiliate: Option<Pubkey>, // Attribution tag only, does not affect transfers or PDAs
+        deadline: u32,             // Latest time the fill may land at, zero if none
+        // Durations of the src stages after deployment, at most those of the order. Lets the
+        // resolver opt into faster windows for this escrow, the order timelocks if none
+        src_timelock_durations: Option<[u32; 4]>,
+        // Highest dst amount the resolver accepts to fill for, no limit if none
+        max_dst_amount: Option<[u64; 4]>,
+    ) -> Result<()> {
+        // Protects the resolver's quote from the auction moving on while the transaction is pending
         require!(
-            amount != 0 && safety_deposit != 0,
-            EscrowError::ZeroAmountOrDeposit
+            deadline == 0 || get_current_timestamp()? <= deadline,
+            EscrowError::FillDeadlinePassed
         );
+        utils::verify_top_level(
+            &ctx.accounts.config,
+            ctx.accounts.instructions_sysvar.as_ref(),
+        )?;
+        let tvl_tracked = lock_tvl(
+            ctx.accounts.config.track_tvl,
+            ctx.accounts
+                .tvl_stats
+                .as_deref_mut()
+                .map(|tvl_stats| &mut tvl_stats.total_locked),
+            amount,
+        )?;
 
-        // Verify that safety_deposit is less than escrow rent_exempt_reserve
-        let rent_exempt_reserve =
-            Rent::get()?.minimum_balance(EscrowSrc::INIT_SPACE + constants::DISCRIMINATOR_BYTES);
+        utils::create_escrow(
+            utils::FillAccounts {
+                taker: &ctx.accounts.taker,
+                resolver_access: &ctx.accounts.resolver_access,
+                fill_authority: &ctx.accounts.fill_authority,
+                fill_authority_bump: ctx.bumps.fill_authority,
+                maker: &ctx.accounts.maker,
+                mint: &ctx.accounts.mint,
+                order: &mut ctx.accounts.order,
+                order_ata: &ctx.accounts.order_ata,
+                escrow: &mut ctx.accounts.escrow,
+                escrow_bump: ctx.bumps.escrow,
+                escrow_ata: &ctx.accounts.escrow_ata,
+                token_program: &ctx.accounts.token_program,
+                whitelist_program: &ctx.accounts.whitelist_program,
+            },
+            ctx.remaining_accounts,
+            merkle_proof,
+            utils::FillArgs {
+                amount,
+                dutch_auction_data,
+                affiliate,
+                src_timelock_durations,
+                max_dst_amount,
+                tvl_tracked,
+            },
+        )
+    }
+
+    /// Fills an order that does not allow multiple fills with its whole amount. Produces the
+    /// same escrow as `create_escrow`, but skips the merkle proof and partial fill handling,
+    /// which saves compute units on the common single fill path.
+    pub fn create_escrow_simple(
+        ctx: Context<CreateEscrowSimple>,
+        dutch_auction_data: AuctionData,
+        affiliate: Option<Pubkey>, // Attribution tag only, does not affect transfers or PDAs
+        deadline: u32,             // Latest time the fill may land at, zero if none
+    ) -> Result<()> {
         require!(
-            safety_deposit <= rent_exempt_reserve,
-            EscrowError::SafetyDepositTooLarge
+            !ctx.accounts.order.allow_multiple_fills,
+            EscrowError::InconsistentMerkleProofTrait
         );
-
         require!(
-            ctx.accounts.mint.key() == NATIVE_MINT || !asset_is_native,
-            EscrowError::InconsistentNativeTrait
+            deadline == 0 || get_current_timestamp()? <= deadline,
+            EscrowError::FillDeadlinePassed
         );
+        utils::verify_top_level(
+            &ctx.accounts.config,
+            ctx.accounts.instructions_sysvar.as_ref(),
+        )?;
 
-        // Check if token is native (WSOL) and is expected to be wrapped
-        if asset_is_native {
-            // Transfer native tokens from creator to escrow_ata and wrap
-            uni_transfer(
-                &UniTransferParams::NativeTransfer {
-                    from: ctx.accounts.creator.to_account_info(),
-                    to: ctx.accounts.order_ata.to_account_info(),
-                    amount,
-                    program: ctx.accounts.system_program.clone(),
-                },
-                None,
-            )?;
-
-            anchor_spl::token::sync_native(CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                anchor_spl::token::SyncNative {
-                    account: ctx.accounts.order_ata.to_account_info(),
-                },
-            ))?;
-        } else {
-            // Do SPL token transfer
-            uni_transfer(
-                &UniTransferParams::TokenTransfer {
-                    from: ctx
-                        .accounts
-                        .creator_ata
-                        .as_ref()
-                        .ok_or(EscrowError::MissingCreatorAta)?
-                        .to_account_info(),
-                    authority: ctx.accounts.creator.to_account_info(),
-                    to: ctx.accounts.order_ata.to_account_info(),
-                    mint: *ctx.accounts.mint.clone(),
-                    amount,
-                    program: ctx.accounts.token_program.clone(),
-                },
-                None,
-            )?;
-        }
-
-        let updated_timelocks = Timelocks(U256(timelocks)).set_deployed_at(now);
-
-        ctx.accounts.order.set_inner(Order {
-            order_hash,
-            hashlock,
-            creator: ctx.accounts.creator.key(),
-            token: ctx.accounts.mint.key(),
+        let amount = ctx.accounts.order.amount;
+        let tvl_tracked = lock_tvl(
+            ctx.accounts.config.track_tvl,
+            ctx.accounts
+                .tvl_stats
+                .as_deref_mut()
+                .map(|tvl_stats| &mut tvl_stats.total_locked),
             amount,
-            remaining_amount: amount,
-            safety_deposit,
-            timelocks: updated_timelocks.get_timelocks(),
-            expiration_time,
-            asset_is_native,
-            dst_amount,
-            dutch_auction_data_hash,
-            max_cancellation_premium,
-            cancellation_auction_duration,
-            allow_multiple_fills,
-            bump: ctx.bumps.order,
-        });
-
-        Ok(())
+        )?;
+        let now = utils::validate_fill(
+            &ctx.accounts.order,
+            &ctx.accounts.taker.key(),
+            &ctx.accounts.maker,
+            amount,
+            &dutch_auction_data,
+        )?;
+        let hashlock = ctx.accounts.order.hashlock;
+
+        utils::fill_order(
+            utils::FillAccounts {
+                taker: &ctx.accounts.taker,
+                resolver_access: &ctx.accounts.resolver_access,
+                fill_authority: &ctx.accounts.fill_authority,
+                fill_authority_bump: ctx.bumps.fill_authority,
+                maker: &ctx.accounts.maker,
+                mint: &ctx.accounts.mint,
+                order: &mut ctx.accounts.order,
+                order_ata: &ctx.accounts.order_ata,
+                escrow: &mut ctx.accounts.escrow,
+                escrow_bump: ctx.bumps.escrow,
+                escrow_ata: &ctx.accounts.escrow_ata,
+                token_program: &ctx.accounts.token_program,
+                whitelist_program: &ctx.accounts.whitelist_program,
+            },
+            utils::FillArgs {
+                amount,
+                dutch_auction_data,
+                affiliate,
+                src_timelock_durations: None,
+                max_dst_amount: None,
+                tvl_tracked,
+            },
+            hashlock,
+            now,
+        )
     }
 
-    pub fn create_escrow(
-        ctx: Context<CreateEscrow>,
-        amount: u64,
+    /// Creates an order and fills it with its first escrow within a single instruction,
+    /// so that the order is never observable unfilled
+    pub fn create_and_fill(
+        ctx: Context<CreateAndFill>,
+        order_hash: [u8; 32], // Derives the order and escrow PDAs, checked against the args
+        args: OrderArgs,
+        fill_amount: u64,
         merkle_proof: Option<MerkleProof>,
         dutch_auction_data: AuctionData,
+        affiliate: Option<Pubkey>,
     ) -> Result<()> {
-        let order = &mut ctx.accounts.order;
-
-        require!(
-            (order.allow_multiple_fills && amount <= order.remaining_amount)
-                || (!order.allow_multiple_fills && amount == order.amount),
-            EscrowError::InvalidAmount
-        );
-
-        let now = get_current_timestamp()?;
-
-        require!(now < order.expiration_time, EscrowError::OrderHasExpired);
-
-        let calculated_hash = keccak::hashv(&[&dutch_auction_data.try_to_vec()?]).to_bytes();
+        utils::verify_top_level(
+            &ctx.accounts.config,
+            ctx.accounts.instructions_sysvar.as_ref(),
+        )?;
 
-        require!(
-            calculated_hash == order.dutch_auction_data_hash,
-            EscrowError::DutchAuctionDataHashMismatch
-        );
+        utils::create_order(
+            &ctx.accounts.creator,
+            &ctx.accounts.mint,
+            ctx.accounts.creator_ata.as_deref(),
+            None,
+            &mut ctx.accounts.order,
+            ctx.bumps.order,
+            &ctx.accounts.order_ata,
+            &ctx.accounts.token_program,
+            &ctx.accounts.system_program,
+            args,
+        )?;
 
+        // The order terms are hashed once, by the order creation
         require!(
-            order.allow_multiple_fills == merkle_proof.is_some(),
-            EscrowError::InconsistentMerkleProofTrait
+            ctx.accounts.order.order_hash == order_hash,
+            ErrorCode::ConstraintSeeds
         );
 
-        let hashlock = if let Some(proof) = merkle_proof {
-            require!(
-                proof.process_proof()[2..] == order.hashlock[2..],
-                EscrowError::InvalidMerkleProof
-            );
-            let parts_amount = u16::from_be_bytes([order.hashlock[0], order.hashlock[1]]);
-            require!(
-                is_valid_partial_fill(
-                    amount,
-                    order.remaining_amount,
-                    order.amount,
-                    parts_amount as u64,
-                    proof.index,
-                ),
-                EscrowError::InvalidPartialFill
-            );
-            proof.hashed_secret
-        } else {
-            order.hashlock
-        };
-
-        let order_seeds = ["order".as_bytes(), &order.order_hash, &[order.bump]];
-
-        let mut amount_to_transfer = amount;
-        if order.remaining_amount == amount {
-            // Transfer amount may be increased due to external transfers
-            amount_to_transfer = ctx.accounts.order_ata.amount;
-        }
-
-        uni_transfer(
-            &UniTransferParams::TokenTransfer {
-                from: ctx.accounts.order_ata.to_account_info(),
-                authority: order.to_account_info(),
-                to: ctx.accounts.escrow_ata.to_account_info(),
-                mint: *ctx.accounts.mint.clone(),
-                amount: amount_to_transfer,
-                program: ctx.accounts.token_program.clone(),
-            },
-            Some(&[&order_seeds]),
+        // The order ATA was loaded before the order was funded
+        ctx.accounts.order_ata.reload()?;
+        let tvl_tracked = lock_tvl(
+            ctx.accounts.config.track_tvl,
+            ctx.accounts
+                .tvl_stats
+                .as_deref_mut()
+                .map(|tvl_stats| &mut tvl_stats.total_locked),
+            fill_amount,
         )?;
 
-        let updated_timelocks = Timelocks(U256(order.timelocks)).set_deployed_at(now);
-
-        ctx.accounts.escrow.set_inner(EscrowSrc {
-            order_hash: order.order_hash,
-            hashlock,
-            maker: order.creator,
-            taker: ctx.accounts.taker.key(),
-            token: order.token,
-            amount,
-            safety_deposit: order.safety_deposit,
-            timelocks: updated_timelocks.get_timelocks(),
-            asset_is_native: order.asset_is_native,
-            dst_amount: get_dst_amount(
-                U256(order.dst_amount)
-                    .checked_mul(U256::from(amount))
-                    .expect("Overflow during multiplication in dst_amount calculation")
-                    .checked_add(U256::from(order.amount - 1)) // Add (divisor - 1) for ceiling division
-                    .expect("Overflow when adding divisor - 1 for ceiling division")
-                    .checked_div(U256::from(order.amount))
-                    .expect(
-                        "Division by zero or overflow during division in dst_amount calculation",
-                    )
-                    .0,
-                &dutch_auction_data,
-            )?,
-            bump: ctx.bumps.escrow,
-        });
-
-        if !order.allow_multiple_fills || order.remaining_amount == amount {
-            // Close the order ATA
-            close_account(CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                CloseAccount {
-                    account: ctx.accounts.order_ata.to_account_info(),
-                    destination: ctx.accounts.maker.to_account_info(),
-                    authority: order.to_account_info(),
-                },
-                &[&order_seeds],
-            ))?;
-
-            // Close the order account
-            order.close(ctx.accounts.maker.to_account_info())?;
-        } else {
-            order.remaining_amount -= amount;
-        }
-
-        Ok(())
+        utils::create_escrow(
+            utils::FillAccounts {
+                taker: &ctx.accounts.taker,
+                resolver_access: &ctx.accounts.resolver_access,
+                fill_authority: &ctx.accounts.fill_authority,
+                fill_authority_bump: ctx.bumps.fill_authority,
+                maker: &ctx.accounts.creator,
+                mint: &ctx.accounts.mint,
+                order: &mut ctx.accounts.order,
+                order_ata: &ctx.accounts.order_ata,
+                escrow: &mut ctx.accounts.escrow,
+                escrow_bump: ctx.bumps.escrow,
+                escrow_ata: &ctx.accounts.escrow_ata,
+                token_program: &ctx.accounts.token_program,
+                whitelist_program: &ctx.accounts.whitelist_program,
+            },
+            // No part of the order can be assigned to a resolver before the order exists
+            &[],
+            merkle_proof,
+            utils::FillArgs {
+                amount: fill_amount,
+                dutch_auction_data,
+                affiliate,
+                src_timelock_durations: None,
+                max_dst_amount: None,
+                tvl_tracked,
+            },
+        )
     }
 
-    pub fn withdraw(ctx: Context<Withdraw>, secret: [u8; 32]) -> Result<()> {
+    /// Returns the validated secret, so that a program invoking the withdrawal can read it from
+    /// the return data and act on it within the same transaction
+    pub fn withdraw(
+        ctx: Context<Withdraw>,
+        secret: [u8; 32],
+        memo: Option<Vec<u8>>,
+    ) -> Result<[u8; 32]> {
         let now = get_current_timestamp()?;
 
         let timelocks = Timelocks(U256(ctx.accounts.escrow.timelocks));
         require!(
             now >= timelocks.get(Stage::SrcWithdrawal)?
-                && now < timelocks.get(Stage::SrcCancellation)?,
+                && now < utils::get_withdrawal_end(&ctx.accounts.escrow)?,
             EscrowError::InvalidTime
         );
 
         // In a standard withdrawal, the taker receives the entire rent amount, including the safety deposit,
         // because they initially covered the entire rent during escrow creation.
 
+        // The memo is logged ahead of the token transfer it accompanies
+        attach_memo(memo.as_deref(), ctx.accounts.memo_program.as_ref())?;
+
         utils::withdraw(
             &ctx.accounts.escrow,
             ctx.accounts.escrow.bump,
@@ -301,11 +314,135 @@ pub mod cross_chain_escrow_src {
             &ctx.accounts.token_program,
             &ctx.accounts.taker, // rent recipient
             &ctx.accounts.taker, // safety deposit recipient
+            ctx.accounts.escrow.safety_deposit,
+            None,
             secret,
-        )
+        )?;
+        unlock_tvl(
+            ctx.accounts.escrow.tvl_tracked,
+            ctx.accounts
+                .tvl_stats
+                .as_deref_mut()
+                .map(|tvl_stats| &mut tvl_stats.total_locked),
+            ctx.accounts.escrow.amount,
+        )?;
+
+        Ok(secret)
+    }
+
+    /// Publishes the secret of the escrow in the taker withdrawal window without moving any
+    /// funds, so that the dst side can proceed before the src withdrawal. The escrow stays open
+    /// and is withdrawn later with the same secret.
+    pub fn reveal_secret(ctx: Context<RevealSecret>, secret: [u8; 32]) -> Result<()> {
+        let now = get_current_timestamp()?;
+
+        let timelocks = Timelocks(U256(ctx.accounts.escrow.timelocks));
+        require!(
+            now >= timelocks.get(Stage::SrcWithdrawal)?
+                && now < utils::get_withdrawal_end(&ctx.accounts.escrow)?,
+            EscrowError::InvalidTime
+        );
+
+        utils::reveal_secret(&ctx.accounts.escrow, secret)
+    }
+
+    /// Withdraws several escrows of the taker in the same mint at once. The remaining accounts
+    /// hold an (escrow, escrow_ata, taker_ata) triple per secret, in the order of the secrets.
+    pub fn withdraw_many<'info>(
+        ctx: Context<'_, '_, 'info, 'info, WithdrawMany<'info>>,
+        secrets: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        require!(
+            !secrets.is_empty()
+                && secrets.len() <= constants::MAX_BATCH_WITHDRAWALS
+                && ctx.remaining_accounts.len() == secrets.len() * 3,
+            EscrowError::InvalidWithdrawalBatch
+        );
+
+        let now = get_current_timestamp()?;
+        let taker = ctx.accounts.taker.to_account_info();
+        let mint = &ctx.accounts.mint;
+        let token_program = &ctx.accounts.token_program;
+
+        for (accounts, secret) in ctx.remaining_accounts.chunks(3).zip(secrets) {
+            let escrow = Account::<EscrowSrc>::try_from(&accounts[0])?;
+            let escrow_ata = InterfaceAccount::<TokenAccount>::try_from(&accounts[1])?;
+            let taker_ata = InterfaceAccount::<TokenAccount>::try_from(&accounts[2])?;
+
+            // Stands in for the seeds constraint of `Withdraw`
+            let expected_key = Pubkey::create_program_address(
+                &[
+                    "escrow".as_bytes(),
+                    escrow.order_hash.as_ref(),
+                    escrow.hashlock.as_ref(),
+                    escrow.taker.as_ref(),
+                    escrow.amount.to_be_bytes().as_ref(),
+                    &[escrow.bump],
+                ],
+                &crate::ID,
+            )
+            .map_err(|_| EscrowError::InvalidAccount)?;
+            require!(escrow.key() == expected_key, EscrowError::InvalidAccount);
+            require!(escrow.taker == taker.key(), EscrowError::InvalidAccount);
+            require!(escrow.token == mint.key(), EscrowError::InvalidMint);
+            require!(
+                escrow_ata.key()
+                    == get_associated_token_address_with_program_id(
+                        &escrow.key(),
+                        &mint.key(),
+                        &token_program.key(),
+                    )
+                    && taker_ata.key()
+                        == get_associated_token_address_with_program_id(
+                            &taker.key(),
+                            &mint.key(),
+                            &token_program.key(),
+                        ),
+                EscrowError::InvalidAccount
+            );
+
+            let timelocks = Timelocks(U256(escrow.timelocks));
+            require!(
+                now >= timelocks.get(Stage::SrcWithdrawal)?
+                    && now < utils::get_withdrawal_end(&escrow)?,
+                EscrowError::InvalidTime
+            );
+
+            utils::withdraw(
+                &escrow,
+                escrow.bump,
+                &escrow_ata,
+                &taker_ata,
+                mint,
+                token_program,
+                &taker, // rent recipient
+                &taker, // safety deposit recipient
+                escrow.safety_deposit,
+                None,
+                secret,
+            )?;
+            unlock_tvl(
+                escrow.tvl_tracked,
+                ctx.accounts
+                    .tvl_stats
+                    .as_deref_mut()
+                    .map(|tvl_stats| &mut tvl_stats.total_locked),
+                escrow.amount,
+            )?;
+
+            // Closing the escrow also prevents it from being withdrawn twice in the same batch
+            escrow.close(taker.clone())?;
+        }
+
+        Ok(())
     }
 
-    pub fn public_withdraw(ctx: Context<PublicWithdraw>, secret: [u8; 32]) -> Result<()> {
+    pub fn public_withdraw(
+        ctx: Context<PublicWithdraw>,
+        secret: [u8; 32],
+        safety_deposit_split_bps: u16,
+        memo: Option<Vec<u8>>,
+    ) -> Result<[u8; 32]> {
         let now = get_current_timestamp()?;
 
         let timelocks = Timelocks(U256(ctx.accounts.escrow.timelocks));
@@ -315,8 +452,25 @@ pub mod cross_chain_escrow_src {
             EscrowError::InvalidTime
         );
 
-        // In a public withdrawal, the taker receives the rent minus the safety deposit
-        // while the safety deposit is awarded to the payer who executed the public withdrawal
+        // In a public withdrawal, the taker receives the rent minus the safety deposit award
+        // while the award is given to the payer who executed the public withdrawal (or to the
+        // reward recipient they specify), optionally shared with a secondary recipient. The award
+        // ramps up over time like the cancellation premium, so that calling the public withdrawal
+        // early pays less
+        let premium_duration = ctx.accounts.escrow.public_withdrawal_premium_duration;
+        let safety_deposit_award = if premium_duration == 0 {
+            ctx.accounts.escrow.safety_deposit
+        } else {
+            calculate_premium(
+                now,
+                timelocks.get(Stage::SrcPublicWithdrawal)?,
+                premium_duration,
+                ctx.accounts.escrow.safety_deposit,
+            )
+        };
+
+        // The memo is logged ahead of the token transfer it accompanies
+        attach_memo(memo.as_deref(), ctx.accounts.memo_program.as_ref())?;
 
         utils::withdraw(
             &ctx.accounts.escrow,
@@ -326,16 +480,34 @@ pub mod cross_chain_escrow_src {
             &ctx.accounts.mint,
             &ctx.accounts.token_program,
             &ctx.accounts.taker, // rent recipient
-            &ctx.accounts.payer, // safety deposit recipient
+            ctx.accounts
+                .reward_recipient
+                .as_deref()
+                .unwrap_or(&ctx.accounts.payer), // safety deposit recipient
+            safety_deposit_award,
+            ctx.accounts
+                .safety_deposit_secondary_recipient
+                .as_deref()
+                .map(|recipient| (recipient, safety_deposit_split_bps)),
             secret,
-        )
+        )?;
+        unlock_tvl(
+            ctx.accounts.escrow.tvl_tracked,
+            ctx.accounts
+                .tvl_stats
+                .as_deref_mut()
+                .map(|tvl_stats| &mut tvl_stats.total_locked),
+            ctx.accounts.escrow.amount,
+        )?;
+
+        Ok(secret)
     }
 
     pub fn cancel_escrow(ctx: Context<CancelEscrow>) -> Result<()> {
         let now = get_current_timestamp()?;
 
         require!(
-            now >= Timelocks(U256(ctx.accounts.escrow.timelocks)).get(Stage::SrcCancellation)?,
+            now >= utils::get_withdrawal_end(&ctx.accounts.escrow)?,
             EscrowError::InvalidTime
         );
 
@@ -353,7 +525,22 @@ pub mod cross_chain_escrow_src {
             &ctx.accounts.taker, // rent recipient
             &ctx.accounts.maker, // order creator
             &ctx.accounts.taker, // safety deposit recipient
-        )
+        )?;
+        unlock_tvl(
+            ctx.accounts.escrow.tvl_tracked,
+            ctx.accounts
+                .tvl_stats
+                .as_deref_mut()
+                .map(|tvl_stats| &mut tvl_stats.total_locked),
+            ctx.accounts.escrow.amount,
+        )?;
+
+        emit!(EscrowCancelled {
+            order_hash: ctx.accounts.escrow.order_hash,
+            hashlock: ctx.accounts.escrow.hashlock,
+            amount: ctx.accounts.escrow.amount,
+        });
+        Ok(())
     }
 
     pub fn public_cancel_escrow(ctx: Context<PublicCancelEscrow>) -> Result<()> {
@@ -368,6 +555,15 @@ pub mod cross_chain_escrow_src {
         // which is awarded to the payer who executed the public cancellation, while the maker
         // receives their tokens back to their initial ATA or wallet if the token is native.
 
+        // The payouts are read off the escrow lamports, as the taker and payer may be the same
+        // account. A native escrow ATA is closed into the escrow, which then pays the amount
+        // out to the maker, otherwise its rent goes straight to the taker.
+        let escrow_ata_lamports = ctx.accounts.escrow_ata.to_account_info().lamports();
+        let mut escrow_lamports = ctx.accounts.escrow.to_account_info().lamports();
+        if ctx.accounts.escrow.asset_is_native {
+            escrow_lamports += escrow_ata_lamports - ctx.accounts.escrow.amount;
+        }
+
         utils::cancel(
             &ctx.accounts.escrow,
             ctx.accounts.escrow.bump,
@@ -378,10 +574,50 @@ pub mod cross_chain_escrow_src {
             &ctx.accounts.taker, // rent recipient
             &ctx.accounts.maker, // order creator
             &ctx.accounts.payer, // safety deposit recipient
-        )
+        )?;
+        unlock_tvl(
+            ctx.accounts.escrow.tvl_tracked,
+            ctx.accounts
+                .tvl_stats
+                .as_deref_mut()
+                .map(|tvl_stats| &mut tvl_stats.total_locked),
+            ctx.accounts.escrow.amount,
+        )?;
+
+        emit!(EscrowPublicCancelled {
+            order_hash: ctx.accounts.escrow.order_hash,
+            hashlock: ctx.accounts.escrow.hashlock,
+            amount: ctx.accounts.escrow.amount,
+            canceller: ctx.accounts.payer.key(),
+        });
+
+        // The escrow is closed to the taker once the instruction completes
+        let remaining_lamports = ctx.accounts.escrow.to_account_info().lamports();
+        let rent_to_taker = if ctx.accounts.escrow.asset_is_native {
+            remaining_lamports
+        } else {
+            remaining_lamports + escrow_ata_lamports
+        };
+        emit!(PublicCancelSettled {
+            order_hash: ctx.accounts.escrow.order_hash,
+            taker: ctx.accounts.taker.key(),
+            maker: ctx.accounts.maker.key(),
+            payer: ctx.accounts.payer.key(),
+            rent_to_taker,
+            deposit_to_payer: escrow_lamports - remaining_lamports,
+        });
+        Ok(())
     }
 
-    pub fn cancel_order(ctx: Context<CancelOrder>) -> Result<()> {
+    /// The remaining accounts hold the part authorizations of the order, which are closed with it
+    pub fn cancel_order<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CancelOrder<'info>>,
+    ) -> Result<()> {
+        utils::close_part_authorizations(
+            &mut ctx.accounts.order,
+            ctx.remaining_accounts,
+            &ctx.accounts.creator.to_account_info(),
+        )?;
         let order = &ctx.accounts.order;
 
         require!(
@@ -414,51 +650,266 @@ pub mod cross_chain_escrow_src {
             )?;
         };
 
-        close_account(CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            CloseAccount {
-                account: ctx.accounts.order_ata.to_account_info(),
-                destination: ctx.accounts.creator.to_account_info(),
-                authority: order.to_account_info(),
-            },
-            &[&seeds],
-        ))
+        close_token_ata(
+            &ctx.accounts.order_ata.to_account_info(),
+            &ctx.accounts.creator.to_account_info(),
+            &order.to_account_info(),
+            &ctx.accounts.token_program,
+            &seeds,
+        )?;
+
+        emit!(OrderCancelled {
+            order_hash: order.order_hash,
+            amount: ctx.accounts.order_ata.amount,
+        });
+        Ok(())
     }
 
-    pub fn cancel_order_by_resolver(
-        ctx: Context<CancelOrderbyResolver>,
-        reward_limit: u64,
-    ) -> Result<()> {
-        let order = &ctx.accounts.order;
+    pub fn extend_order(ctx: Context<ExtendOrder>, new_expiration_time: u32) -> Result<()> {
+        let order = &mut ctx.accounts.order;
         let now = get_current_timestamp()?;
 
-        require!(now >= order.expiration_time, EscrowError::OrderNotExpired);
+        // An expired order may already be cancelled by a resolver for a premium,
+        // so it can only be extended while it is still fillable
+        require!(now < order.expiration_time, EscrowError::OrderHasExpired);
 
         require!(
-            order.max_cancellation_premium > 0,
-            EscrowError::CancelOrderByResolverIsForbidden
+            new_expiration_time > order.expiration_time,
+            EscrowError::InvalidExpirationTime
         );
 
-        require!(
-            order.asset_is_native == ctx.accounts.creator_ata.is_none(),
-            EscrowError::InconsistentNativeTrait
-        );
+        // The expiration time is not part of the order hash, so the order PDA stays the same
+        order.expiration_time = new_expiration_time;
 
-        let seeds = ["order".as_bytes(), &order.order_hash, &[order.bump]];
+        Ok(())
+    }
 
-        // Order creator receives the amount of tokens back to their initial ATA
-        if !order.asset_is_native {
-            uni_transfer(
-                &UniTransferParams::TokenTransfer {
-                    from: ctx.accounts.order_ata.to_account_info(),
-                    authority: order.to_account_info(),
-                    to: ctx
-                        .accounts
-                        .creator_ata
-                        .as_ref()
-                        .ok_or(EscrowError::MissingCreatorAta)?
-                        .to_account_info(),
-                    mint: *ctx.accounts.mint.clone(),
+    /// Updates the parameters of the cancellation by resolver auction while the order is fillable.
+    /// The order hash keeps committing to the original parameters, so the order PDA stays the
+    /// same, but `rescue_funds_for_order` must be called with the original parameters.
+    pub fn update_cancellation_auction(
+        ctx: Context<UpdateCancellationAuction>,
+        new_max_premium: u64,
+        new_duration: u32,
+    ) -> Result<()> {
+        let order = &mut ctx.accounts.order;
+        let now = get_current_timestamp()?;
+
+        // Once expired, the auction may already be running for the resolvers
+        require!(now < order.expiration_time, EscrowError::OrderHasExpired);
+
+        // A zero premium disables cancellation by resolver, so there is no premium to cover
+        if new_max_premium > 0 {
+            require!(
+                ctx.accounts.order_ata.to_account_info().lamports() >= new_max_premium,
+                EscrowError::InvalidCancellationFee
+            );
+        }
+
+        order.max_cancellation_premium = new_max_premium;
+        order.cancellation_auction_duration = new_duration;
+
+        Ok(())
+    }
+
+    /// Hands the order over to a new creator, who receives the refunds and rent of the order and
+    /// of the escrows created afterwards. The order hash keeps committing to the original maker,
+    /// so the order PDA stays the same. Escrows already created keep their maker, and part
+    /// authorizations already paid for can only be revoked by their payer.
+    pub fn transfer_order_ownership(
+        ctx: Context<TransferOrderOwnership>,
+        new_creator: Pubkey,
+    ) -> Result<()> {
+        require!(
+            new_creator != Pubkey::default(),
+            EscrowError::InvalidAccount
+        );
+
+        let order = &mut ctx.accounts.order;
+        let previous_creator = order.creator;
+        order.creator = new_creator;
+
+        emit!(OrderOwnershipTransferred {
+            order_hash: order.order_hash,
+            previous_creator,
+            new_creator,
+        });
+
+        Ok(())
+    }
+
+    /// Transfers `additional_amount` of the creator's tokens to the order ATA and makes them
+    /// fillable. The destination amount is left unchanged.
+    pub fn increase_order(ctx: Context<IncreaseOrder>, additional_amount: u64) -> Result<()> {
+        let order = &mut ctx.accounts.order;
+        let now = get_current_timestamp()?;
+
+        require!(now < order.expiration_time, EscrowError::OrderHasExpired);
+        require!(additional_amount != 0, EscrowError::ZeroAmountOrDeposit);
+        require!(order.allow_multiple_fills, EscrowError::InvalidPartialFill);
+
+        // Partial fill indices are derived from the order amount, so it can only be changed
+        // before the first fill
+        require!(
+            order.remaining_amount == order.amount,
+            EscrowError::OrderPartiallyFilled
+        );
+
+        require!(
+            order.asset_is_native == ctx.accounts.creator_ata.is_none(),
+            EscrowError::InconsistentNativeTrait
+        );
+
+        if order.asset_is_native {
+            uni_transfer(
+                &UniTransferParams::NativeTransfer {
+                    from: ctx.accounts.creator.to_account_info(),
+                    to: ctx.accounts.order_ata.to_account_info(),
+                    amount: additional_amount,
+                    program: ctx.accounts.system_program.clone(),
+                },
+                None,
+            )?;
+
+            anchor_spl::token::sync_native(CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token::SyncNative {
+                    account: ctx.accounts.order_ata.to_account_info(),
+                },
+            ))?;
+        } else {
+            uni_transfer(
+                &UniTransferParams::TokenTransfer {
+                    from: ctx
+                        .accounts
+                        .creator_ata
+                        .as_ref()
+                        .ok_or(EscrowError::MissingCreatorAta)?
+                        .to_account_info(),
+                    authority: ctx.accounts.creator.to_account_info(),
+                    to: ctx.accounts.order_ata.to_account_info(),
+                    mint: *ctx.accounts.mint.clone(),
+                    amount: additional_amount,
+                    program: ctx.accounts.token_program.clone(),
+                },
+                None,
+            )?;
+        }
+
+        order.amount = order
+            .amount
+            .checked_add(additional_amount)
+            .ok_or(EscrowError::InvalidAmount)?;
+        order.remaining_amount = order.amount;
+
+        Ok(())
+    }
+
+    pub fn authorize_part_fill(
+        ctx: Context<AuthorizePartFill>,
+        part_index: u64,
+        resolver: Pubkey,
+    ) -> Result<()> {
+        let order = &mut ctx.accounts.order;
+
+        require!(order.allow_multiple_fills, EscrowError::InvalidPartialFill);
+
+        // Merkle leaves are indexed from 0 to parts_amount, the last one is used to fill the rest.
+        // The authorization of a part is consumed by the fill covering it, so parts that are
+        // already filled cannot be assigned
+        let parts_amount = decode_parts_amount(&order.hashlock, order.allow_multiple_fills)?;
+        require!(
+            part_index <= parts_amount as u64
+                && part_index
+                    >= get_next_part_index(
+                        order.remaining_amount,
+                        order.amount,
+                        parts_amount as u64
+                    )?,
+            EscrowError::InvalidPartialFill
+        );
+
+        order.part_authorizations = order
+            .part_authorizations
+            .checked_add(1)
+            .ok_or(EscrowError::InvalidPartialFill)?;
+
+        ctx.accounts
+            .part_authorization
+            .set_inner(PartAuthorization {
+                part_index,
+                resolver,
+                bump: ctx.bumps.part_authorization,
+            });
+
+        Ok(())
+    }
+
+    pub fn revoke_part_fill(ctx: Context<RevokePartFill>, _part_index: u64) -> Result<()> {
+        // The part authorization account is closed by the `close` constraint,
+        // the part becomes open to any whitelisted resolver again
+        let order = &mut ctx.accounts.order;
+        order.part_authorizations = order
+            .part_authorizations
+            .checked_sub(1)
+            .ok_or(EscrowError::InvalidPartialFill)?;
+        Ok(())
+    }
+
+    /// The remaining accounts hold the part authorizations of the order, which are closed with it
+    pub fn cancel_order_by_resolver<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CancelOrderbyResolver<'info>>,
+        reward_limit: u64,
+    ) -> Result<()> {
+        utils::close_part_authorizations(
+            &mut ctx.accounts.order,
+            ctx.remaining_accounts,
+            &ctx.accounts.creator.to_account_info(),
+        )?;
+        let order = &ctx.accounts.order;
+        let now = get_current_timestamp()?;
+
+        require!(now >= order.expiration_time, EscrowError::OrderNotExpired);
+
+        require!(
+            order.max_cancellation_premium > 0,
+            EscrowError::CancelOrderByResolverIsForbidden
+        );
+
+        require!(
+            order.asset_is_native == ctx.accounts.creator_ata.is_none(),
+            EscrowError::InconsistentNativeTrait
+        );
+
+        // The recipient is chosen by whoever builds the transaction, so the maker has to approve it
+        require!(
+            ctx.accounts.maker_lamport_recipient.is_none() || ctx.accounts.creator.is_signer,
+            EscrowError::UnauthorizedLamportRecipient
+        );
+
+        // The premium is paid out of the order ATA lamports, so a larger limit can never be
+        // reached and would only hide a premium exceeding the balance
+        let order_ata_lamports = ctx.accounts.order_ata.to_account_info().lamports();
+        require!(
+            reward_limit <= order_ata_lamports,
+            EscrowError::PremiumExceedsBalance
+        );
+
+        let seeds = ["order".as_bytes(), &order.order_hash, &[order.bump]];
+
+        // Order creator receives the amount of tokens back to their initial ATA
+        if !order.asset_is_native {
+            uni_transfer(
+                &UniTransferParams::TokenTransfer {
+                    from: ctx.accounts.order_ata.to_account_info(),
+                    authority: order.to_account_info(),
+                    to: ctx
+                        .accounts
+                        .creator_ata
+                        .as_ref()
+                        .ok_or(EscrowError::MissingCreatorAta)?
+                        .to_account_info(),
+                    mint: *ctx.accounts.mint.clone(),
                     amount: ctx.accounts.order_ata.amount,
                     program: ctx.accounts.token_program.clone(),
                 },
@@ -473,33 +924,222 @@ pub mod cross_chain_escrow_src {
             order.max_cancellation_premium,
         );
 
-        // The amount that the order maker will receive, which is the entire native
-        // balance of the order ATA (or rent + wSOL) minus the cancellation premium
-        let maker_amount = ctx.accounts.order_ata.to_account_info().lamports()
-            - std::cmp::min(cancellation_premium, reward_limit);
-
-        // Transfer all the remaining lamports to the resolver first
-        close_account(CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            CloseAccount {
-                account: ctx.accounts.order_ata.to_account_info(),
-                destination: ctx.accounts.resolver.to_account_info(),
-                authority: order.to_account_info(),
-            },
-            &[&seeds],
-        ))?;
+        let resolver_premium = std::cmp::min(cancellation_premium, reward_limit);
 
-        // Transfer all lamports from the order ATA that resolver received,
-        // minus the cancellation premium, to the maker
-        uni_transfer(
-            &UniTransferParams::NativeTransfer {
-                from: ctx.accounts.resolver.to_account_info(),
-                to: ctx.accounts.creator.to_account_info(),
-                amount: maker_amount,
-                program: ctx.accounts.system_program.clone(),
-            },
-            None,
-        )
+        // Using order pda as an intermediate account, so that the entire native balance
+        // of the order ATA (rent or rent + wSOL) never passes through the resolver
+        close_token_ata(
+            &ctx.accounts.order_ata.to_account_info(),
+            &order.to_account_info(),
+            &order.to_account_info(),
+            &ctx.accounts.token_program,
+            &seeds,
+        )?;
+
+        // Pay the cancellation premium to the resolver, the remaining lamports
+        // will be transferred to the maker after closing the order account
+        order.sub_lamports(resolver_premium)?;
+        ctx.accounts.resolver.add_lamports(resolver_premium)?;
+
+        // The order account is closed to the creator, so the maker amount is moved out beforehand
+        // if it goes to a separate recipient
+        if let Some(maker_lamport_recipient) = &ctx.accounts.maker_lamport_recipient {
+            let maker_amount = order.get_lamports();
+            order.sub_lamports(maker_amount)?;
+            maker_lamport_recipient.add_lamports(maker_amount)?;
+        }
+
+        emit!(OrderCancelledByResolver {
+            order_hash: order.order_hash,
+            amount: ctx.accounts.order_ata.amount,
+            resolver: ctx.accounts.resolver.key(),
+            premium: resolver_premium,
+        });
+        Ok(())
+    }
+
+    /// Returns the cancellation premium a resolver would receive for cancelling the order at `now`
+    /// (or at the current time if not provided).
+    pub fn preview_cancellation_premium(
+        ctx: Context<PreviewOrder>,
+        now: Option<u32>,
+    ) -> Result<u64> {
+        let order = &ctx.accounts.order;
+        let now = match now {
+            Some(now) => now,
+            None => get_current_timestamp()?,
+        };
+
+        Ok(calculate_premium(
+            now,
+            order.expiration_time,
+            order.cancellation_auction_duration,
+            order.max_cancellation_premium,
+        ))
+    }
+
+    /// Returns the destination amount that an escrow created at `now` (or at the current time
+    /// if not provided) for `fill_amount` of the order would store.
+    pub fn preview_dst_amount(
+        ctx: Context<PreviewOrder>,
+        fill_amount: u64,
+        dutch_auction_data: AuctionData,
+        now: Option<u32>,
+    ) -> Result<[u64; 4]> {
+        let order = &ctx.accounts.order;
+        let now = match now {
+            Some(now) => now,
+            None => get_current_timestamp()?,
+        };
+
+        let calculated_hash = keccak::hashv(&[&dutch_auction_data.try_to_vec()?]).to_bytes();
+
+        require!(
+            calculated_hash == order.dutch_auction_data_hash,
+            EscrowError::DutchAuctionDataHashMismatch
+        );
+        dutch_auction_data.validate()?;
+
+        get_fill_dst_amount(order, fill_amount, &dutch_auction_data, now)
+    }
+
+    /// Returns the amounts of the order along with the smallest merkle index the next fill can use,
+    /// so that resolvers coordinating partial fills do not have to reimplement its computation.
+    pub fn get_order_fill_state(ctx: Context<PreviewOrder>) -> Result<OrderFillState> {
+        let order = &ctx.accounts.order;
+
+        let (parts_amount, next_part_index) = if order.allow_multiple_fills {
+            let parts_amount = decode_parts_amount(&order.hashlock, order.allow_multiple_fills)?;
+            let next_part_index =
+                get_next_part_index(order.remaining_amount, order.amount, parts_amount as u64)?;
+            (parts_amount, next_part_index)
+        } else {
+            (0, 0)
+        };
+
+        Ok(OrderFillState {
+            amount: order.amount,
+            remaining_amount: order.remaining_amount,
+            parts_amount,
+            next_part_index,
+        })
+    }
+
+    /// Checks that a sample proof reproduces the merkle root stored in the hashlock of a
+    /// multiple fill order, so that clients can detect a mistyped root after the order creation.
+    pub fn verify_order_root(
+        ctx: Context<PreviewOrder>,
+        sample_proof: MerkleProof,
+    ) -> Result<bool> {
+        let order = &ctx.accounts.order;
+        require!(
+            order.allow_multiple_fills,
+            EscrowError::InconsistentMerkleProofTrait
+        );
+
+        // The first two bytes of the hashlock hold the parts amount instead of the root
+        Ok(sample_proof.process_proof(order.hash_algo)?[2..] == order.hashlock[2..])
+    }
+
+    /// Returns the stored escrow amount, the actual escrow ATA balance and their difference,
+    /// which may diverge due to excess transfers or Token-2022 transfer fees.
+    pub fn reconcile_escrow(ctx: Context<ReconcileEscrow>) -> Result<EscrowReconciliation> {
+        let stored_amount = ctx.accounts.escrow.amount;
+        let actual_amount = ctx.accounts.escrow_ata.amount;
+
+        Ok(EscrowReconciliation {
+            stored_amount,
+            actual_amount,
+            difference: actual_amount as i128 - stored_amount as i128,
+        })
+    }
+
+    /// Returns which actions the timelocks of the escrow allow at the current time, so that
+    /// keepers do not have to replicate the stage arithmetic. Only the timing is checked,
+    /// other requirements of the instructions such as the resolver access are not.
+    pub fn escrow_status(ctx: Context<GetEscrowStatus>) -> Result<EscrowStatus> {
+        let now = get_current_timestamp()?;
+        let timelocks = Timelocks(U256(ctx.accounts.escrow.timelocks));
+        let [_, public_withdrawable, _, public_cancellable] =
+            timelocks.get_open_stages(&SRC_STAGES, now)?;
+
+        // The withdrawal grace moves the end of the taker withdrawal and the start of the
+        // cancellation past the src cancellation stage
+        let withdrawal_end = utils::get_withdrawal_end(&ctx.accounts.escrow)?;
+        let withdrawable = now >= timelocks.get(Stage::SrcWithdrawal)? && now < withdrawal_end;
+        let cancellable = now >= withdrawal_end;
+
+        Ok(EscrowStatus {
+            withdrawable,
+            public_withdrawable,
+            cancellable,
+            public_cancellable,
+        })
+    }
+
+    /// Returns the start of every stage, in the order of `Stage`, for the given timelocks
+    /// deployed at `deployed_at`, so that clients can simulate the windows of an escrow before
+    /// it exists. The dst public cancellation shares its slot with the src one, see `Stage`.
+    pub fn decode_timelocks(
+        _ctx: Context<DecodeTimelocks>,
+        timelocks: [u64; 4],
+        deployed_at: u32,
+    ) -> Result<[u32; 8]> {
+        let timelocks = Timelocks(U256(timelocks)).set_deployed_at(deployed_at);
+        let mut stage_starts = [0; 8];
+        for (start, stage) in stage_starts
+            .iter_mut()
+            .zip(SRC_STAGES.iter().chain(DST_STAGES.iter()))
+        {
+            *start = timelocks.get(*stage)?;
+        }
+        Ok(stage_starts)
+    }
+
+    /// Returns the address and bump of the escrow for the given parameters, derived with the
+    /// same seeds the escrow accounts are constrained to, so that clients do not have to
+    /// replicate the seed layout such as the big-endian amount.
+    pub fn derive_escrow_pda(
+        _ctx: Context<DeriveEscrowPda>,
+        order_hash: [u8; 32],
+        hashlock: [u8; 32],
+        taker: Pubkey,
+        amount: u64,
+    ) -> Result<EscrowPda> {
+        let (address, bump) = Pubkey::find_program_address(
+            &[
+                "escrow".as_bytes(),
+                order_hash.as_ref(),
+                hashlock.as_ref(),
+                taker.as_ref(),
+                amount.to_be_bytes().as_ref(),
+            ],
+            &crate::ID,
+        );
+
+        Ok(EscrowPda { address, bump })
+    }
+
+    /// Returns the rent-exempt minimum of an escrow account, which the taker pays on escrow
+    /// creation, so that clients do not depend on the size of the escrow layout.
+    pub fn get_escrow_rent(_ctx: Context<GetRent>) -> Result<u64> {
+        Ok(Rent::get()?.minimum_balance(constants::DISCRIMINATOR_BYTES + EscrowSrc::INIT_SPACE))
+    }
+
+    /// Returns the rent-exempt minimum of an order account, which the maker pays on `create`.
+    pub fn get_order_rent(_ctx: Context<GetRent>) -> Result<u64> {
+        Ok(Rent::get()?.minimum_balance(constants::DISCRIMINATOR_BYTES + Order::INIT_SPACE))
+    }
+
+    /// Creates the account counting the value locked in the escrows of the mint, which escrow
+    /// creation requires while the whitelist config tracks it. Anyone can pay for it.
+    pub fn init_tvl_stats(ctx: Context<InitTvlStats>) -> Result<()> {
+        ctx.accounts.tvl_stats.set_inner(TvlStats {
+            mint: ctx.accounts.mint.key(),
+            total_locked: 0,
+            bump: ctx.bumps.tvl_stats,
+        });
+        Ok(())
     }
 
     pub fn rescue_funds_for_escrow(
@@ -532,6 +1172,10 @@ pub mod cross_chain_escrow_src {
             rescue_start,
             &ctx.accounts.escrow_ata,
             &ctx.accounts.taker,
+            ctx.accounts
+                .rent_recipient
+                .as_ref()
+                .map(|rent_recipient| rent_recipient.as_ref()),
             &ctx.accounts.taker_ata,
             &ctx.accounts.mint,
             &ctx.accounts.token_program,
@@ -540,25 +1184,22 @@ pub mod cross_chain_escrow_src {
         )
     }
 
-    #[allow(clippy::too_many_arguments)]
+    /// Rescues tokens sent by mistake to the ATA of an order. The order is derived from the
+    /// arguments it was created with, which still hold after an increase of the order.
     pub fn rescue_funds_for_order(
         ctx: Context<RescueFundsForOrder>,
-        hashlock: [u8; 32],
         maker: Pubkey,
         token: Pubkey,
-        order_amount: u64,
-        safety_deposit: u64,
-        timelocks: [u64; 4],
-        expiration_time: u32,
-        asset_is_native: bool,
-        dst_amount: [u64; 4],
-        dutch_auction_data_hash: [u8; 32],
-        max_cancellation_premium: u64,
-        cancellation_auction_duration: u32,
-        allow_multiple_fills: bool,
-        salt: u64,
+        args: OrderArgs,
         rescue_amount: u64,
     ) -> Result<()> {
+        // Derived here rather than by a seeds constraint, so that the order terms are hashed
+        // once for both the derivation and the signer seeds
+        let order_hash = get_order_hash(maker, token, &args);
+        let (order, order_bump) =
+            Pubkey::find_program_address(&["order".as_bytes(), order_hash.as_ref()], &crate::ID);
+        require_keys_eq!(ctx.accounts.order.key(), order, ErrorCode::ConstraintSeeds);
+
         let rescue_start = if !ctx.accounts.order.data_is_empty() {
             let order_data = Order::try_deserialize(&mut &ctx.accounts.order.data.borrow()[..])?;
             Some(Timelocks(U256(order_data.timelocks)).rescue_start(constants::RESCUE_DELAY)?)
@@ -566,30 +1207,17 @@ pub mod cross_chain_escrow_src {
             None
         };
 
-        let order_hash = get_order_hash(
-            hashlock,
-            maker,
-            token,
-            order_amount,
-            safety_deposit,
-            timelocks,
-            expiration_time,
-            asset_is_native,
-            dst_amount,
-            dutch_auction_data_hash,
-            max_cancellation_premium,
-            cancellation_auction_duration,
-            allow_multiple_fills,
-            salt,
-        );
-
-        let seeds = ["order".as_bytes(), order_hash.as_ref(), &[ctx.bumps.order]];
+        let seeds = ["order".as_bytes(), order_hash.as_ref(), &[order_bump]];
 
         common::escrow::rescue_funds(
             &ctx.accounts.order,
             rescue_start,
             &ctx.accounts.order_ata,
             &ctx.accounts.resolver,
+            ctx.accounts
+                .rent_recipient
+                .as_ref()
+                .map(|rent_recipient| rent_recipient.as_ref()),
             &ctx.accounts.resolver_ata,
             &ctx.accounts.mint,
             &ctx.accounts.token_program,
@@ -597,29 +1225,80 @@ pub mod cross_chain_escrow_src {
             &seeds,
         )
     }
+
+    /// Moves lamports left on a closed escrow PDA to the treasury picked by the whitelist
+    /// authority. Only balances below the rent-exempt minimum of an escrow count as dust
+    pub fn sweep_dust(
+        ctx: Context<SweepDust>,
+        order_hash: [u8; 32],
+        hashlock: [u8; 32],
+        taker: Pubkey,
+        amount: u64,
+    ) -> Result<()> {
+        let escrow = &ctx.accounts.escrow;
+        require!(
+            escrow.data_is_empty() && escrow.owner == &System::id(),
+            EscrowError::EscrowNotClosed
+        );
+
+        let dust = escrow.lamports();
+        let rent_exempt_reserve =
+            Rent::get()?.minimum_balance(constants::DISCRIMINATOR_BYTES + EscrowSrc::INIT_SPACE);
+        require!(
+            dust != 0 && dust < rent_exempt_reserve,
+            EscrowError::InvalidDustAmount
+        );
+
+        let seeds = [
+            "escrow".as_bytes(),
+            order_hash.as_ref(),
+            hashlock.as_ref(),
+            taker.as_ref(),
+            &amount.to_be_bytes(),
+            &[ctx.bumps.escrow],
+        ];
+
+        uni_transfer(
+            &UniTransferParams::NativeTransfer {
+                from: escrow.to_account_info(),
+                to: ctx.accounts.treasury.to_account_info(),
+                amount: dust,
+                program: ctx.accounts.system_program.clone(),
+            },
+            Some(&[&seeds]),
+        )
+    }
 }
 
 #[derive(Accounts)]
-#[instruction(hashlock: [u8; 32],
-              amount: u64,
-              safety_deposit: u64,
-              timelocks: [u64; 4],
-              expiration_time: u32,
-              asset_is_native: bool,
-              dst_amount: [u64; 4],
-              dutch_auction_data_hash: [u8; 32],
-              max_cancellation_premium: u64,
-              cancellation_auction_duration: u32,
-              allow_multiple_fills: bool,
-              salt: u64,
-            )]
+#[instruction(args: OrderArgs)]
 pub struct Create<'info> {
+    /// CHECK: Signs the order creation unless a delegate funds the order in their place
     #[account(
-        mut, // Needed because this account transfers lamports if the token is native and to pay for the order creation
+        mut, // Needed because this account transfers lamports if the token is native
     )]
-    creator: Signer<'info>,
+    creator: UncheckedAccount<'info>,
+    /// Pays for the order creation, the creator unless a delegate funds the order
+    #[account(mut)]
+    payer: Signer<'info>,
     /// CHECK: check is not necessary as token is only used as a constraint to creator_ata and order
     mint: Box<InterfaceAccount<'info, Mint>>,
+    /// Config with the token programs supported by the protocol
+    #[account(
+        seeds = [whitelist::CONFIG_SEED],
+        bump = config.bump,
+        seeds::program = whitelist::ID,
+        constraint = config.supported_token_programs.contains(&token_program.key()) @ EscrowError::UnsupportedTokenProgram,
+    )]
+    config: Box<Account<'info, whitelist::Config>>,
+    /// CHECK: Created by the whitelist authority to block the mint, must not exist
+    #[account(
+        seeds = [whitelist::BLOCKED_MINT_SEED, mint.key().as_ref()],
+        bump,
+        seeds::program = whitelist::ID,
+        constraint = blocked_mint.data_is_empty() @ EscrowError::MintBlocked,
+    )]
+    blocked_mint: UncheckedAccount<'info>,
     #[account(
         mut,
         associated_token::mint = mint,
@@ -631,26 +1310,11 @@ pub struct Create<'info> {
     /// Account to store order details
     #[account(
         init,
-        payer = creator,
+        payer = payer,
         space = constants::DISCRIMINATOR_BYTES + Order::INIT_SPACE,
         seeds = [
             "order".as_bytes(),
-            &get_order_hash(
-                hashlock,
-                creator.key(),
-                mint.key(),
-                amount,
-                safety_deposit,
-                timelocks,
-                expiration_time,
-                asset_is_native,
-                dst_amount,
-                dutch_auction_data_hash,
-                max_cancellation_premium,
-                cancellation_auction_duration,
-                allow_multiple_fills,
-                salt,
-            )
+            &get_order_hash(creator.key(), mint.key(), &args)
             ],
         bump,
     )]
@@ -658,7 +1322,76 @@ pub struct Create<'info> {
     /// Account to store escrowed tokens
     #[account(
         init_if_needed,
-        payer = creator,
+        payer = payer,
+        associated_token::mint = mint,
+        associated_token::authority = order,
+        associated_token::token_program = token_program
+    )]
+    order_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(address = ASSOCIATED_TOKEN_PROGRAM_ID)]
+    associated_token_program: Program<'info, AssociatedToken>,
+    token_program: Interface<'info, TokenInterface>,
+    rent: Sysvar<'info, Rent>,
+    system_program: Program<'info, System>,
+    /// Transfer authority approved on creator_ata, funds the order in place of the creator,
+    /// who then does not sign (Optional)
+    delegate: Option<Signer<'info>>,
+}
+
+#[derive(Accounts)]
+#[instruction(args: OrderArgs)]
+pub struct CreateIdempotent<'info> {
+    /// CHECK: Signs the order creation unless a delegate funds the order in their place
+    #[account(
+        mut, // Needed because this account transfers lamports if the token is native
+    )]
+    creator: UncheckedAccount<'info>,
+    /// Pays for the order creation, the creator unless a delegate funds the order
+    #[account(mut)]
+    payer: Signer<'info>,
+    /// CHECK: check is not necessary as token is only used as a constraint to creator_ata and order
+    mint: Box<InterfaceAccount<'info, Mint>>,
+    /// Config with the token programs supported by the protocol
+    #[account(
+        seeds = [whitelist::CONFIG_SEED],
+        bump = config.bump,
+        seeds::program = whitelist::ID,
+        constraint = config.supported_token_programs.contains(&token_program.key()) @ EscrowError::UnsupportedTokenProgram,
+    )]
+    config: Box<Account<'info, whitelist::Config>>,
+    /// CHECK: Created by the whitelist authority to block the mint, must not exist
+    #[account(
+        seeds = [whitelist::BLOCKED_MINT_SEED, mint.key().as_ref()],
+        bump,
+        seeds::program = whitelist::ID,
+        constraint = blocked_mint.data_is_empty() @ EscrowError::MintBlocked,
+    )]
+    blocked_mint: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = creator,
+        associated_token::token_program = token_program
+    )]
+    /// Account to store creator's tokens (Optional if the token is native)
+    creator_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    /// Account to store order details, left as is if it already exists
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = constants::DISCRIMINATOR_BYTES + Order::INIT_SPACE,
+        seeds = [
+            "order".as_bytes(),
+            &get_order_hash(creator.key(), mint.key(), &args)
+            ],
+        bump,
+    )]
+    order: Box<Account<'info, Order>>,
+    /// Account to store escrowed tokens
+    #[account(
+        init_if_needed,
+        payer = payer,
         associated_token::mint = mint,
         associated_token::authority = order,
         associated_token::token_program = token_program
@@ -670,6 +1403,9 @@ pub struct Create<'info> {
     token_program: Interface<'info, TokenInterface>,
     rent: Sysvar<'info, Rent>,
     system_program: Program<'info, System>,
+    /// Transfer authority approved on creator_ata, funds the order in place of the creator,
+    /// who then does not sign (Optional)
+    delegate: Option<Signer<'info>>,
 }
 
 #[derive(Accounts)]
@@ -678,42 +1414,291 @@ pub struct CreateEscrow<'info> {
     #[account(mut)]
     taker: Signer<'info>,
     #[account(
-        seeds = [whitelist::RESOLVER_ACCESS_SEED, taker.key().as_ref()],
-        bump = resolver_access.bump,
+        mut, // Needed to record the fill volume of the resolver
+        seeds = [whitelist::RESOLVER_ACCESS_SEED, taker.key().as_ref()],
+        bump = resolver_access.bump,
+        seeds::program = whitelist::ID,
+    )]
+    resolver_access: Account<'info, whitelist::ResolverAccess>,
+    /// CHECK: PDA authorizing this program to record fills on the whitelist program
+    #[account(
+        seeds = [whitelist::FILL_AUTHORITY_SEED],
+        bump,
+    )]
+    fill_authority: UncheckedAccount<'info>,
+    #[account(
+        mut, // Necessary because lamports will be transferred to this account when the order accounts are closed.
+        constraint = maker.key() == order.creator @ EscrowError::InvalidAccount
+    )]
+    /// CHECK: this account is used only to receive rent for order and order_ata accounts
+    maker: AccountInfo<'info>,
+    #[account(
+        constraint = mint.key() == order.token @ EscrowError::InvalidMint
+    )]
+    /// CHECK: check is not necessary as token is only used as a constraint to creator_ata and order
+    mint: Box<InterfaceAccount<'info, Mint>>,
+    /// Config with the token programs supported by the protocol
+    #[account(
+        seeds = [whitelist::CONFIG_SEED],
+        bump = config.bump,
+        seeds::program = whitelist::ID,
+        constraint = config.supported_token_programs.contains(&token_program.key()) @ EscrowError::UnsupportedTokenProgram,
+    )]
+    config: Box<Account<'info, whitelist::Config>>,
+    /// CHECK: Created by the whitelist authority to block the mint, must not exist
+    #[account(
+        seeds = [whitelist::BLOCKED_MINT_SEED, mint.key().as_ref()],
+        bump,
+        seeds::program = whitelist::ID,
+        constraint = blocked_mint.data_is_empty() @ EscrowError::MintBlocked,
+    )]
+    blocked_mint: UncheckedAccount<'info>,
+
+    /// Account to store order details
+    #[account(
+        mut,
+        seeds = [
+            "order".as_bytes(),
+            order.order_hash.as_ref(),
+        ],
+        bump = order.bump,
+    )]
+    order: Box<Account<'info, Order>>,
+    /// Account to store orders tokens
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = order,
+        associated_token::token_program = token_program
+    )]
+    order_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// Account to store escrow details
+    #[account(
+        init,
+        payer = taker,
+        space = constants::DISCRIMINATOR_BYTES + EscrowSrc::INIT_SPACE,
+        seeds = [
+            "escrow".as_bytes(),
+            order.order_hash.as_ref(),
+            &get_escrow_hashlock(
+                order.hashlock,
+                merkle_proof.clone()
+            ),
+            taker.key().as_ref(),
+            amount.to_be_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    escrow: Box<Account<'info, EscrowSrc>>,
+    /// Account to store escrowed tokens
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program
+    )]
+    escrow_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(address = ASSOCIATED_TOKEN_PROGRAM_ID)]
+    associated_token_program: Program<'info, AssociatedToken>,
+    token_program: Interface<'info, TokenInterface>,
+    /// System program required for account initialization
+    system_program: Program<'info, System>,
+    whitelist_program: Program<'info, whitelist::program::Whitelist>,
+    /// CHECK: Instructions sysvar, required only if the config rejects fills through CPI
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    instructions_sysvar: Option<UncheckedAccount<'info>>,
+    /// Required only if the config tracks the value locked in escrows
+    #[account(
+        mut,
+        seeds = [constants::TVL_STATS_SEED, mint.key().as_ref()],
+        bump = tvl_stats.bump,
+    )]
+    tvl_stats: Option<Box<Account<'info, TvlStats>>>,
+}
+
+/// Same as `CreateEscrow` for orders filled at once, which need no part authorization
+#[derive(Accounts)]
+pub struct CreateEscrowSimple<'info> {
+    #[account(mut)]
+    taker: Signer<'info>,
+    #[account(
+        mut, // Needed to record the fill volume of the resolver
+        seeds = [whitelist::RESOLVER_ACCESS_SEED, taker.key().as_ref()],
+        bump = resolver_access.bump,
+        seeds::program = whitelist::ID,
+    )]
+    resolver_access: Account<'info, whitelist::ResolverAccess>,
+    /// CHECK: PDA authorizing this program to record fills on the whitelist program
+    #[account(
+        seeds = [whitelist::FILL_AUTHORITY_SEED],
+        bump,
+    )]
+    fill_authority: UncheckedAccount<'info>,
+    #[account(
+        mut, // Necessary because lamports will be transferred to this account when the order accounts are closed.
+        constraint = maker.key() == order.creator @ EscrowError::InvalidAccount
+    )]
+    /// CHECK: this account is used only to receive rent for order and order_ata accounts
+    maker: AccountInfo<'info>,
+    #[account(
+        constraint = mint.key() == order.token @ EscrowError::InvalidMint
+    )]
+    mint: Box<InterfaceAccount<'info, Mint>>,
+    /// Config with the token programs supported by the protocol
+    #[account(
+        seeds = [whitelist::CONFIG_SEED],
+        bump = config.bump,
+        seeds::program = whitelist::ID,
+        constraint = config.supported_token_programs.contains(&token_program.key()) @ EscrowError::UnsupportedTokenProgram,
+    )]
+    config: Box<Account<'info, whitelist::Config>>,
+    /// CHECK: Created by the whitelist authority to block the mint, must not exist
+    #[account(
+        seeds = [whitelist::BLOCKED_MINT_SEED, mint.key().as_ref()],
+        bump,
+        seeds::program = whitelist::ID,
+        constraint = blocked_mint.data_is_empty() @ EscrowError::MintBlocked,
+    )]
+    blocked_mint: UncheckedAccount<'info>,
+
+    /// Account to store order details
+    #[account(
+        mut,
+        seeds = [
+            "order".as_bytes(),
+            order.order_hash.as_ref(),
+        ],
+        bump = order.bump,
+    )]
+    order: Box<Account<'info, Order>>,
+    /// Account to store orders tokens
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = order,
+        associated_token::token_program = token_program
+    )]
+    order_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Account to store escrow details
+    #[account(
+        init,
+        payer = taker,
+        space = constants::DISCRIMINATOR_BYTES + EscrowSrc::INIT_SPACE,
+        seeds = [
+            "escrow".as_bytes(),
+            order.order_hash.as_ref(),
+            order.hashlock.as_ref(),
+            taker.key().as_ref(),
+            order.amount.to_be_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    escrow: Box<Account<'info, EscrowSrc>>,
+    /// Account to store escrowed tokens
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program
+    )]
+    escrow_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(address = ASSOCIATED_TOKEN_PROGRAM_ID)]
+    associated_token_program: Program<'info, AssociatedToken>,
+    token_program: Interface<'info, TokenInterface>,
+    /// System program required for account initialization
+    system_program: Program<'info, System>,
+    whitelist_program: Program<'info, whitelist::program::Whitelist>,
+    /// CHECK: Instructions sysvar, required only if the config rejects fills through CPI
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    instructions_sysvar: Option<UncheckedAccount<'info>>,
+    /// Required only if the config tracks the value locked in escrows
+    #[account(
+        mut,
+        seeds = [constants::TVL_STATS_SEED, mint.key().as_ref()],
+        bump = tvl_stats.bump,
+    )]
+    tvl_stats: Option<Box<Account<'info, TvlStats>>>,
+}
+
+#[derive(Accounts)]
+#[instruction(
+    order_hash: [u8; 32],
+    args: OrderArgs,
+    fill_amount: u64,
+    merkle_proof: Option<MerkleProof>,
+)]
+pub struct CreateAndFill<'info> {
+    #[account(
+        mut, // Needed because this account transfers lamports if the token is native and to pay for the order creation
+    )]
+    creator: Signer<'info>,
+    #[account(mut)]
+    taker: Signer<'info>,
+    #[account(
+        mut, // Needed to record the fill volume of the resolver
+        seeds = [whitelist::RESOLVER_ACCESS_SEED, taker.key().as_ref()],
+        bump = resolver_access.bump,
+        seeds::program = whitelist::ID,
+    )]
+    resolver_access: Box<Account<'info, whitelist::ResolverAccess>>,
+    /// CHECK: PDA authorizing this program to record fills on the whitelist program
+    #[account(
+        seeds = [whitelist::FILL_AUTHORITY_SEED],
+        bump,
+    )]
+    fill_authority: UncheckedAccount<'info>,
+    /// CHECK: check is not necessary as token is only used as a constraint to creator_ata and order
+    mint: Box<InterfaceAccount<'info, Mint>>,
+    /// Config with the token programs supported by the protocol
+    #[account(
+        seeds = [whitelist::CONFIG_SEED],
+        bump = config.bump,
         seeds::program = whitelist::ID,
+        constraint = config.supported_token_programs.contains(&token_program.key()) @ EscrowError::UnsupportedTokenProgram,
     )]
-    resolver_access: Account<'info, whitelist::ResolverAccess>,
+    config: Box<Account<'info, whitelist::Config>>,
+    /// CHECK: Created by the whitelist authority to block the mint, must not exist
     #[account(
-        mut, // Necessary because lamports will be transferred to this account when the order accounts are closed.
-        constraint = maker.key() == order.creator @ EscrowError::InvalidAccount
+        seeds = [whitelist::BLOCKED_MINT_SEED, mint.key().as_ref()],
+        bump,
+        seeds::program = whitelist::ID,
+        constraint = blocked_mint.data_is_empty() @ EscrowError::MintBlocked,
     )]
-    /// CHECK: this account is used only to receive rent for order and order_ata accounts
-    maker: AccountInfo<'info>,
+    blocked_mint: UncheckedAccount<'info>,
     #[account(
-        constraint = mint.key() == order.token @ EscrowError::InvalidMint
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = creator,
+        associated_token::token_program = token_program
     )]
-    /// CHECK: check is not necessary as token is only used as a constraint to creator_ata and order
-    mint: Box<InterfaceAccount<'info, Mint>>,
-
+    /// Account to store creator's tokens (Optional if the token is native)
+    creator_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
     /// Account to store order details
     #[account(
-        mut,
+        init,
+        payer = creator,
+        space = constants::DISCRIMINATOR_BYTES + Order::INIT_SPACE,
         seeds = [
             "order".as_bytes(),
-            order.order_hash.as_ref(),
+            order_hash.as_ref(),
         ],
-        bump = order.bump,
+        bump,
     )]
     order: Box<Account<'info, Order>>,
     /// Account to store orders tokens
     #[account(
-        mut,
+        init_if_needed,
+        payer = creator,
         associated_token::mint = mint,
         associated_token::authority = order,
         associated_token::token_program = token_program
     )]
     order_ata: Box<InterfaceAccount<'info, TokenAccount>>,
-
     /// Account to store escrow details
     #[account(
         init,
@@ -721,13 +1706,13 @@ pub struct CreateEscrow<'info> {
         space = constants::DISCRIMINATOR_BYTES + EscrowSrc::INIT_SPACE,
         seeds = [
             "escrow".as_bytes(),
-            order.order_hash.as_ref(),
+            order_hash.as_ref(),
             &get_escrow_hashlock(
-                order.hashlock,
+                args.hashlock,
                 merkle_proof.clone()
             ),
             taker.key().as_ref(),
-            amount.to_be_bytes().as_ref(),
+            fill_amount.to_be_bytes().as_ref(),
         ],
         bump,
     )]
@@ -747,6 +1732,17 @@ pub struct CreateEscrow<'info> {
     token_program: Interface<'info, TokenInterface>,
     /// System program required for account initialization
     system_program: Program<'info, System>,
+    whitelist_program: Program<'info, whitelist::program::Whitelist>,
+    /// CHECK: Instructions sysvar, required only if the config rejects fills through CPI
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    instructions_sysvar: Option<UncheckedAccount<'info>>,
+    /// Required only if the config tracks the value locked in escrows
+    #[account(
+        mut,
+        seeds = [constants::TVL_STATS_SEED, mint.key().as_ref()],
+        bump = tvl_stats.bump,
+    )]
+    tvl_stats: Option<Box<Account<'info, TvlStats>>>,
 }
 
 #[derive(Accounts)]
@@ -789,6 +1785,52 @@ pub struct Withdraw<'info> {
     taker_ata: Box<InterfaceAccount<'info, TokenAccount>>,
     token_program: Interface<'info, TokenInterface>,
     system_program: Program<'info, System>,
+    /// Required only when a memo is attached to the withdrawal
+    memo_program: Option<Program<'info, Memo>>,
+    /// Required only if the escrow is counted in the value locked
+    #[account(
+        mut,
+        seeds = [constants::TVL_STATS_SEED, mint.key().as_ref()],
+        bump = tvl_stats.bump,
+    )]
+    tvl_stats: Option<Box<Account<'info, TvlStats>>>,
+}
+
+#[derive(Accounts)]
+pub struct RevealSecret<'info> {
+    #[account(
+        constraint = taker.key() == escrow.taker @ EscrowError::InvalidAccount,
+    )]
+    taker: Signer<'info>,
+    #[account(
+        seeds = [
+            "escrow".as_bytes(),
+            escrow.order_hash.as_ref(),
+            escrow.hashlock.as_ref(),
+            escrow.taker.as_ref(),
+            escrow.amount.to_be_bytes().as_ref(),
+        ],
+        bump = escrow.bump,
+    )]
+    escrow: Box<Account<'info, EscrowSrc>>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawMany<'info> {
+    #[account(
+        mut, // Necessary because lamports will be transferred to this account when the escrows are closed.
+    )]
+    taker: Signer<'info>,
+    mint: Box<InterfaceAccount<'info, Mint>>,
+    token_program: Interface<'info, TokenInterface>,
+    system_program: Program<'info, System>,
+    /// Required only if the escrow is counted in the value locked
+    #[account(
+        mut,
+        seeds = [constants::TVL_STATS_SEED, mint.key().as_ref()],
+        bump = tvl_stats.bump,
+    )]
+    tvl_stats: Option<Box<Account<'info, TvlStats>>>,
 }
 
 #[derive(Accounts)]
@@ -807,6 +1849,12 @@ pub struct PublicWithdraw<'info> {
         seeds::program = whitelist::ID,
     )]
     resolver_access: Account<'info, whitelist::ResolverAccess>,
+    /// CHECK: Optional account that receives the part of the safety deposit not awarded to the payer
+    #[account(mut)]
+    safety_deposit_secondary_recipient: Option<UncheckedAccount<'info>>,
+    /// CHECK: Optional account that receives the safety deposit award instead of the payer
+    #[account(mut)]
+    reward_recipient: Option<UncheckedAccount<'info>>,
     #[account(
         constraint = mint.key() == escrow.token @ EscrowError::InvalidMint
     )]
@@ -840,6 +1888,15 @@ pub struct PublicWithdraw<'info> {
     taker_ata: Box<InterfaceAccount<'info, TokenAccount>>,
     token_program: Interface<'info, TokenInterface>,
     system_program: Program<'info, System>,
+    /// Required only when a memo is attached to the withdrawal
+    memo_program: Option<Program<'info, Memo>>,
+    /// Required only if the escrow is counted in the value locked
+    #[account(
+        mut,
+        seeds = [constants::TVL_STATS_SEED, mint.key().as_ref()],
+        bump = tvl_stats.bump,
+    )]
+    tvl_stats: Option<Box<Account<'info, TvlStats>>>,
 }
 
 #[derive(Accounts)]
@@ -879,16 +1936,28 @@ pub struct CancelEscrow<'info> {
         associated_token::token_program = token_program
     )]
     escrow_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    // The maker ATA is recreated at the taker's expense if the maker has closed it,
+    // so that the escrow can always be cancelled
     #[account(
-        mut,
+        init_if_needed,
+        payer = taker,
         associated_token::mint = mint,
         associated_token::authority = maker,
         associated_token::token_program = token_program
     )]
     // Optional if the token is native
     maker_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(address = ASSOCIATED_TOKEN_PROGRAM_ID)]
+    associated_token_program: Program<'info, AssociatedToken>,
     token_program: Interface<'info, TokenInterface>,
     system_program: Program<'info, System>,
+    /// Required only if the escrow is counted in the value locked
+    #[account(
+        mut,
+        seeds = [constants::TVL_STATS_SEED, mint.key().as_ref()],
+        bump = tvl_stats.bump,
+    )]
+    tvl_stats: Option<Box<Account<'info, TvlStats>>>,
 }
 
 #[derive(Accounts)]
@@ -943,27 +2012,212 @@ pub struct PublicCancelEscrow<'info> {
         associated_token::authority = escrow.maker,
         associated_token::token_program = token_program
     )]
-    // Optional if the token is native
-    maker_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
-    token_program: Interface<'info, TokenInterface>,
+    // Optional if the token is native
+    maker_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    token_program: Interface<'info, TokenInterface>,
+    system_program: Program<'info, System>,
+    /// Required only if the escrow is counted in the value locked
+    #[account(
+        mut,
+        seeds = [constants::TVL_STATS_SEED, mint.key().as_ref()],
+        bump = tvl_stats.bump,
+    )]
+    tvl_stats: Option<Box<Account<'info, TvlStats>>>,
+}
+
+#[derive(Accounts)]
+pub struct CancelOrder<'info> {
+    /// Account that created the order
+    #[account(
+        mut,
+        constraint = creator.key() == order.creator @ EscrowError::InvalidAccount
+    )]
+    creator: Signer<'info>,
+    #[account(
+        constraint = mint.key() == order.token @ EscrowError::InvalidMint
+    )]
+    mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        close = creator,
+        seeds = [
+            "order".as_bytes(),
+            order.order_hash.as_ref(),
+        ],
+        bump = order.bump,
+    )]
+    order: Box<Account<'info, Order>>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = order,
+        associated_token::token_program = token_program
+    )]
+    order_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = creator,
+        associated_token::token_program = token_program
+    )]
+    // Optional if the token is native
+    creator_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    token_program: Interface<'info, TokenInterface>,
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExtendOrder<'info> {
+    /// Account that created the order
+    #[account(
+        constraint = creator.key() == order.creator @ EscrowError::InvalidAccount
+    )]
+    creator: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            "order".as_bytes(),
+            order.order_hash.as_ref(),
+        ],
+        bump = order.bump,
+    )]
+    order: Box<Account<'info, Order>>,
+}
+
+#[derive(Accounts)]
+pub struct TransferOrderOwnership<'info> {
+    /// Account that currently owns the order
+    #[account(
+        constraint = creator.key() == order.creator @ EscrowError::InvalidAccount
+    )]
+    creator: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            "order".as_bytes(),
+            order.order_hash.as_ref(),
+        ],
+        bump = order.bump,
+    )]
+    order: Box<Account<'info, Order>>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateCancellationAuction<'info> {
+    /// Account that created the order
+    #[account(
+        constraint = creator.key() == order.creator @ EscrowError::InvalidAccount
+    )]
+    creator: Signer<'info>,
+    #[account(
+        constraint = mint.key() == order.token @ EscrowError::InvalidMint
+    )]
+    mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        seeds = [
+            "order".as_bytes(),
+            order.order_hash.as_ref(),
+        ],
+        bump = order.bump,
+    )]
+    order: Box<Account<'info, Order>>,
+    /// Account holding the order tokens, whose lamports cover the cancellation premium
+    #[account(
+        associated_token::mint = mint,
+        associated_token::authority = order,
+        associated_token::token_program = token_program
+    )]
+    order_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct IncreaseOrder<'info> {
+    /// Account that created the order
+    #[account(
+        mut, // Needed because this account transfers lamports if the token is native
+        constraint = creator.key() == order.creator @ EscrowError::InvalidAccount
+    )]
+    creator: Signer<'info>,
+    #[account(
+        constraint = mint.key() == order.token @ EscrowError::InvalidMint
+    )]
+    mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        seeds = [
+            "order".as_bytes(),
+            order.order_hash.as_ref(),
+        ],
+        bump = order.bump,
+    )]
+    order: Box<Account<'info, Order>>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = order,
+        associated_token::token_program = token_program
+    )]
+    order_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = creator,
+        associated_token::token_program = token_program
+    )]
+    // Optional if the token is native
+    creator_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    token_program: Interface<'info, TokenInterface>,
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(part_index: u64)]
+pub struct AuthorizePartFill<'info> {
+    /// Account that created the order and pays for the part authorization
+    #[account(
+        mut,
+        constraint = creator.key() == order.creator @ EscrowError::InvalidAccount
+    )]
+    creator: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            "order".as_bytes(),
+            order.order_hash.as_ref(),
+        ],
+        bump = order.bump,
+    )]
+    order: Box<Account<'info, Order>>,
+    /// Account to store the resolver allowed to fill the part
+    #[account(
+        init,
+        payer = creator,
+        space = constants::DISCRIMINATOR_BYTES + PartAuthorization::INIT_SPACE,
+        seeds = [
+            "part_authorization".as_bytes(),
+            order.order_hash.as_ref(),
+            part_index.to_be_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    part_authorization: Box<Account<'info, PartAuthorization>>,
+    /// System program required for account initialization
     system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct CancelOrder<'info> {
-    /// Account that created the order
+#[instruction(part_index: u64)]
+pub struct RevokePartFill<'info> {
+    /// Account that created the order, receives the rent of the part authorization back
     #[account(
         mut,
         constraint = creator.key() == order.creator @ EscrowError::InvalidAccount
     )]
     creator: Signer<'info>,
-    #[account(
-        constraint = mint.key() == order.token @ EscrowError::InvalidMint
-    )]
-    mint: Box<InterfaceAccount<'info, Mint>>,
     #[account(
         mut,
-        close = creator,
         seeds = [
             "order".as_bytes(),
             order.order_hash.as_ref(),
@@ -973,21 +2227,15 @@ pub struct CancelOrder<'info> {
     order: Box<Account<'info, Order>>,
     #[account(
         mut,
-        associated_token::mint = mint,
-        associated_token::authority = order,
-        associated_token::token_program = token_program
-    )]
-    order_ata: Box<InterfaceAccount<'info, TokenAccount>>,
-    #[account(
-        mut,
-        associated_token::mint = mint,
-        associated_token::authority = creator,
-        associated_token::token_program = token_program
+        close = creator,
+        seeds = [
+            "part_authorization".as_bytes(),
+            order.order_hash.as_ref(),
+            part_index.to_be_bytes().as_ref(),
+        ],
+        bump = part_authorization.bump,
     )]
-    // Optional if the token is native
-    creator_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
-    token_program: Interface<'info, TokenInterface>,
-    system_program: Program<'info, System>,
+    part_authorization: Box<Account<'info, PartAuthorization>>,
 }
 
 #[derive(Accounts)]
@@ -1036,10 +2284,91 @@ pub struct CancelOrderbyResolver<'info> {
     )]
     // Optional if the token is native
     creator_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    /// Receives the remaining lamports instead of the creator, requires the creator's signature
+    #[account(mut)]
+    maker_lamport_recipient: Option<SystemAccount<'info>>,
+    token_program: Interface<'info, TokenInterface>,
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PreviewOrder<'info> {
+    #[account(
+        seeds = [
+            "order".as_bytes(),
+            order.order_hash.as_ref(),
+        ],
+        bump = order.bump,
+    )]
+    order: Box<Account<'info, Order>>,
+}
+
+#[derive(Accounts)]
+pub struct ReconcileEscrow<'info> {
+    #[account(
+        constraint = mint.key() == escrow.token @ EscrowError::InvalidMint
+    )]
+    mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        seeds = [
+            "escrow".as_bytes(),
+            escrow.order_hash.as_ref(),
+            escrow.hashlock.as_ref(),
+            escrow.taker.as_ref(),
+            escrow.amount.to_be_bytes().as_ref(),
+        ],
+        bump = escrow.bump,
+    )]
+    escrow: Box<Account<'info, EscrowSrc>>,
+    #[account(
+        associated_token::mint = mint,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program
+    )]
+    escrow_ata: Box<InterfaceAccount<'info, TokenAccount>>,
     token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct GetEscrowStatus<'info> {
+    #[account(
+        seeds = [
+            "escrow".as_bytes(),
+            escrow.order_hash.as_ref(),
+            escrow.hashlock.as_ref(),
+            escrow.taker.as_ref(),
+            escrow.amount.to_be_bytes().as_ref(),
+        ],
+        bump = escrow.bump,
+    )]
+    escrow: Box<Account<'info, EscrowSrc>>,
+}
+
+#[derive(Accounts)]
+pub struct DeriveEscrowPda {}
+
+#[derive(Accounts)]
+pub struct GetRent {}
+
+#[derive(Accounts)]
+pub struct InitTvlStats<'info> {
+    #[account(mut)]
+    payer: Signer<'info>,
+    mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        init,
+        payer = payer,
+        space = constants::DISCRIMINATOR_BYTES + TvlStats::INIT_SPACE,
+        seeds = [constants::TVL_STATS_SEED, mint.key().as_ref()],
+        bump,
+    )]
+    tvl_stats: Box<Account<'info, TvlStats>>,
     system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct DecodeTimelocks {}
+
 #[derive(Accounts)]
 #[instruction(order_hash: [u8; 32], hashlock: [u8; 32], amount: u64)]
 pub struct RescueFundsForEscrow<'info> {
@@ -1074,27 +2403,14 @@ pub struct RescueFundsForEscrow<'info> {
         associated_token::token_program = token_program
     )]
     taker_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// Receives the rent of the closed token account instead of the signer if set
+    #[account(mut)]
+    rent_recipient: Option<SystemAccount<'info>>,
     token_program: Interface<'info, TokenInterface>,
     system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction(
-        hashlock: [u8; 32],
-        maker: Pubkey,
-        token: Pubkey,
-        order_amount: u64,
-        safety_deposit: u64,
-        timelocks: [u64; 4],
-        expiration_time: u32,
-        asset_is_native: bool,
-        dst_amount: [u64; 4],
-        dutch_auction_data_hash: [u8; 32],
-        max_cancellation_premium: u64,
-        cancellation_auction_duration: u32,
-        allow_multiple_fills: bool,
-        salt: u64,
-)]
 pub struct RescueFundsForOrder<'info> {
     #[account(
         mut, // Needed because this account receives lamports from closed token account.
@@ -1108,28 +2424,7 @@ pub struct RescueFundsForOrder<'info> {
     resolver_access: Account<'info, whitelist::ResolverAccess>,
     mint: Box<InterfaceAccount<'info, Mint>>,
     /// CHECK: We don't accept order as 'Account<'info, Order>' because it may be already closed at the time of rescue funds.
-    #[account(
-        seeds = [
-            "order".as_bytes(),
-            &get_order_hash(
-                hashlock,
-                maker,
-                token.key(),
-                order_amount,
-                safety_deposit,
-                timelocks,
-                expiration_time,
-                asset_is_native,
-                dst_amount,
-                dutch_auction_data_hash,
-                max_cancellation_premium,
-                cancellation_auction_duration,
-                allow_multiple_fills,
-                salt,
-            )
-        ],
-        bump,
-    )]
+    /// The handler checks that it is the PDA of the order arguments.
     order: AccountInfo<'info>,
     #[account(
         mut,
@@ -1145,10 +2440,43 @@ pub struct RescueFundsForOrder<'info> {
         associated_token::token_program = token_program
     )]
     resolver_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// Receives the rent of the closed token account instead of the signer if set
+    #[account(mut)]
+    rent_recipient: Option<SystemAccount<'info>>,
     token_program: Interface<'info, TokenInterface>,
     system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+#[instruction(order_hash: [u8; 32], hashlock: [u8; 32], taker: Pubkey, amount: u64)]
+pub struct SweepDust<'info> {
+    authority: Signer<'info>,
+    #[account(
+        seeds = [whitelist::WHITELIST_STATE_SEED],
+        bump,
+        seeds::program = whitelist::ID,
+        constraint = whitelist_state.authority == authority.key() @ EscrowError::Unauthorized,
+    )]
+    whitelist_state: Account<'info, whitelist::WhitelistState>,
+    /// CHECK: Only the lamports of the escrow are moved, and only once it is closed
+    #[account(
+        mut,
+        seeds = [
+            "escrow".as_bytes(),
+            order_hash.as_ref(),
+            hashlock.as_ref(),
+            taker.as_ref(),
+            amount.to_be_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    escrow: UncheckedAccount<'info>,
+    /// Receives the swept lamports
+    #[account(mut)]
+    treasury: SystemAccount<'info>,
+    system_program: Program<'info, System>,
+}
+
 #[account]
 #[derive(InitSpace)]
 pub struct Order {
@@ -1163,13 +2491,34 @@ pub struct Order {
     expiration_time: u32,
     asset_is_native: bool,
     dst_amount: [u64; 4],
+    min_dst_amount: [u64; 4],
+    dst_safety_deposit: u128,
     dutch_auction_data_hash: [u8; 32],
     max_cancellation_premium: u64,
     cancellation_auction_duration: u32,
+    public_withdrawal_premium_duration: u32,
     allow_multiple_fills: bool,
+    min_fill_amount: u64,
+    hash_algo: u8,
+    dst_decimals_shift: i8,
+    allowed_taker: Pubkey,
+    simple_parts_amount: u16,
+    withdrawal_grace: u32,
+    require_maker_cosign: bool,
+    min_withdrawal_slots: u32,
+    filled_amount: u64,
+    part_authorizations: u16,
     bump: u8,
 }
 
+#[account]
+#[derive(InitSpace)]
+pub struct PartAuthorization {
+    pub part_index: u64,
+    pub resolver: Pubkey,
+    pub bump: u8,
+}
+
 #[account]
 #[derive(InitSpace)]
 pub struct EscrowSrc {
@@ -1183,49 +2532,307 @@ pub struct EscrowSrc {
     pub timelocks: [u64; 4],
     pub asset_is_native: bool,
     pub dst_amount: [u64; 4],
+    /// Safety deposit for the destination escrow, denominated in the destination chain's
+    /// native asset, as committed by the maker in `DstChainParams` at order creation.
+    pub dst_safety_deposit: u128,
+    /// Duration over which the safety deposit awarded on public withdrawal ramps up to its
+    /// full amount, zero if the full amount is awarded from the start.
+    pub public_withdrawal_premium_duration: u32,
+    /// Affiliate that sourced the order, all zeros if none. Used for attribution only.
+    pub affiliate: Pubkey,
+    /// Hashlock algorithm the secret is checked against, committed by the maker in the order.
+    pub hash_algo: u8,
+    /// Time past the src cancellation start during which the taker can still withdraw and the
+    /// escrow can't be cancelled yet, committed by the maker in the order.
+    pub withdrawal_grace: u32,
+    /// Slot the escrow was created at. Withdrawals also wait for `min_withdrawal_slots` to pass
+    /// since then, so that a drifting unix time alone can't open the withdrawal early.
+    pub deployed_slot: u64,
+    pub min_withdrawal_slots: u32,
+    /// Whether the amount is counted in the `TvlStats` of the mint, which its withdrawal or
+    /// cancellation then updates as well
+    pub tvl_tracked: bool,
+    /// Balance the escrow ATA was funded with, net of the transfer fee of the mint, which it must
+    /// still hold when the escrow is withdrawn or cancelled
+    pub funded_amount: u64,
+    pub bump: u8,
+}
+
+/// Value locked in the escrows of the mint, counted while the whitelist config tracks it.
+/// Native escrows are counted under the native mint.
+#[account]
+#[derive(InitSpace)]
+pub struct TvlStats {
+    pub mint: Pubkey,
+    /// Sum of the amounts of the counted escrows not yet withdrawn or cancelled
+    pub total_locked: u64,
+    pub bump: u8,
+}
+
+/// Emitted on escrow creation
+#[event]
+pub struct EscrowCreated {
+    pub order_hash: [u8; 32],
+    pub hashlock: [u8; 32],
+    pub taker: Pubkey,
+    pub amount: u64,
+    /// Affiliate that sourced the order, all zeros if none
+    pub affiliate: Pubkey,
+}
+
+/// Emitted on each fill of an order, the filled and remaining amounts add up to the order amount
+#[event]
+pub struct OrderFilled {
+    pub order_hash: [u8; 32],
+    pub fill_amount: u64,
+    pub filled_amount: u64,
+    pub remaining_amount: u64,
+}
+
+/// Emitted on withdrawal once the secret has been validated against the escrow hashlock
+#[event]
+pub struct SecretRevealed {
+    pub order_hash: [u8; 32],
+    pub hashlock: [u8; 32],
+    pub secret: [u8; 32],
+}
+
+/// Emitted when the taker cancels an escrow, returning its amount to the maker
+#[event]
+pub struct EscrowCancelled {
+    pub order_hash: [u8; 32],
+    pub hashlock: [u8; 32],
+    pub amount: u64,
+}
+
+/// Emitted when an escrow is cancelled in the public cancellation period
+#[event]
+pub struct EscrowPublicCancelled {
+    pub order_hash: [u8; 32],
+    pub hashlock: [u8; 32],
+    pub amount: u64,
+    /// Account that executed the cancellation and received the safety deposit
+    pub canceller: Pubkey,
+}
+
+/// Emitted along with `EscrowPublicCancelled` with the lamports paid out by the cancellation
+#[event]
+pub struct PublicCancelSettled {
+    pub order_hash: [u8; 32],
+    pub taker: Pubkey,
+    pub maker: Pubkey,
+    pub payer: Pubkey,
+    /// Escrow and escrow ATA rent left to the taker after the safety deposit
+    pub rent_to_taker: u64,
+    /// Safety deposit awarded to the payer, zero if the payer is the taker
+    pub deposit_to_payer: u64,
+}
+
+/// Emitted when the maker cancels an order, with the unfilled amount returned to them
+#[event]
+pub struct OrderCancelled {
+    pub order_hash: [u8; 32],
+    pub amount: u64,
+}
+
+/// Emitted when the creator of an order hands it over to another account
+#[event]
+pub struct OrderOwnershipTransferred {
+    pub order_hash: [u8; 32],
+    pub previous_creator: Pubkey,
+    pub new_creator: Pubkey,
+}
+
+/// Emitted when a resolver cancels an expired order in the cancellation auction
+#[event]
+pub struct OrderCancelledByResolver {
+    pub order_hash: [u8; 32],
+    /// Unfilled amount returned to the maker
+    pub amount: u64,
+    pub resolver: Pubkey,
+    /// Lamports paid to the resolver out of the order rent
+    pub premium: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EscrowReconciliation {
+    pub stored_amount: u64,
+    pub actual_amount: u64,
+    /// `actual_amount - stored_amount`, negative if the escrow ATA holds less than stored
+    pub difference: i128,
+}
+
+/// Actions the timelocks of an escrow allow at a given time, see `escrow_status`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EscrowStatus {
+    pub withdrawable: bool,
+    pub public_withdrawable: bool,
+    pub cancellable: bool,
+    pub public_cancellable: bool,
+}
+
+/// Address and bump of an escrow PDA, see `derive_escrow_pda`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EscrowPda {
+    pub address: Pubkey,
     pub bump: u8,
 }
 
-fn get_dst_amount(dst_amount: [u64; 4], data: &AuctionData) -> Result<[u64; 4]> {
-    let rate_bump = calculate_rate_bump(Clock::get()?.unix_timestamp as u64, data);
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct OrderFillState {
+    pub amount: u64,
+    pub remaining_amount: u64,
+    /// Zero if the order does not allow multiple fills
+    pub parts_amount: u16,
+    /// Zero if the order does not allow multiple fills. A fill of the whole remaining amount
+    /// uses the index following the last part instead, see `is_valid_partial_fill`
+    pub next_part_index: u64,
+}
+
+/// Calculates the destination amount for filling `amount` of the order at `timestamp`,
+/// i.e. the pro-rata share of the order's `dst_amount` increased by the auction rate bump.
+fn get_fill_dst_amount(
+    order: &Order,
+    amount: u64,
+    data: &AuctionData,
+    timestamp: u32,
+) -> Result<[u64; 4]> {
+    scale_dst_amount(
+        get_dst_amount(
+            get_pro_rata_dst_amount(order.dst_amount, amount, order.amount)?,
+            data,
+            timestamp,
+        )?,
+        order.dst_decimals_shift,
+    )
+}
+
+/// Scales `dst_amount` by `10^decimals_shift` to convert it to the decimals of the dst token.
+/// Negative shifts divide and round up, so that the maker never receives less than committed.
+pub fn scale_dst_amount(dst_amount: [u64; 4], decimals_shift: i8) -> Result<[u64; 4]> {
+    let scale = U256::from(10)
+        .checked_pow(U256::from(decimals_shift.unsigned_abs()))
+        .ok_or(EscrowError::DstAmountOverflow)?;
+    let result = if decimals_shift >= 0 {
+        U256(dst_amount)
+            .checked_mul(scale)
+            .ok_or(EscrowError::DstAmountOverflow)?
+    } else {
+        let (quotient, remainder) = U256(dst_amount).div_mod(scale);
+        quotient + U256::from(!remainder.is_zero() as u8)
+    };
+    Ok(result.0)
+}
+
+/// Calculates the share of `dst_amount` corresponding to `amount` out of `order_amount`,
+/// rounded up.
+pub fn get_pro_rata_dst_amount(
+    dst_amount: [u64; 4],
+    amount: u64,
+    order_amount: u64,
+) -> Result<[u64; 4]> {
+    let result = U256(dst_amount)
+        .checked_mul(U256::from(amount))
+        .and_then(|product| product.checked_add(U256::from(order_amount - 1))) // Ceiling division
+        .and_then(|product| product.checked_div(U256::from(order_amount)))
+        .ok_or(EscrowError::DstAmountOverflow)?;
+    Ok(result.0)
+}
+
+/// Increases `dst_amount` by the auction rate bump at `timestamp`, rounded as set in the auction.
+/// Round to nearest rounds halves up.
+pub fn get_dst_amount(
+    dst_amount: [u64; 4],
+    data: &AuctionData,
+    timestamp: u32,
+) -> Result<[u64; 4]> {
+    let rate_bump = calculate_rate_bump(timestamp as u64, data);
     let multiplier = constants::BASE_1E7 + rate_bump;
+    let rounding_addend = match data.rounding {
+        auction::ROUNDING_UP => constants::BASE_1E7 - 1,
+        auction::ROUNDING_DOWN => 0,
+        auction::ROUNDING_NEAREST => constants::BASE_1E7 / 2,
+        _ => return err!(EscrowError::InvalidRounding),
+    };
 
     let result = U256(dst_amount)
         .checked_mul(U256::from(multiplier))
-        .expect("Overflow when multiplying destination amount with rate bump")
-        .checked_add(U256::from(constants::BASE_1E7 - 1)) // To ensure rounding up
-        .expect("Overflow when adding BASE_1E7 - 1")
-        .checked_div(U256::from(constants::BASE_1E7))
-        .expect("Overflow when dividing by BASE_1E7");
+        .and_then(|product| product.checked_add(U256::from(rounding_addend)))
+        .and_then(|product| product.checked_div(U256::from(constants::BASE_1E7)))
+        .ok_or(EscrowError::DstAmountOverflow)?;
     Ok(result.0)
 }
 
-fn is_valid_partial_fill(
+/// Checks that `validated_index` is the index of the secret that must be used for a fill of
+/// `making_amount`. The part indices are computed in U256, as the product of an amount and
+/// the parts count can exceed u64.
+pub fn is_valid_partial_fill(
     making_amount: u64,
     remaining_making_amount: u64,
     order_making_amount: u64,
     parts_amount: u64,
     validated_index: u64,
-) -> bool {
-    let calculated_index = ((order_making_amount - remaining_making_amount + making_amount - 1)
-        * parts_amount)
-        / order_making_amount;
+) -> Result<bool> {
+    let filled_amount = order_making_amount
+        .checked_sub(remaining_making_amount)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    let last_filled_amount = filled_amount
+        .checked_add(making_amount)
+        .and_then(|amount| amount.checked_sub(1))
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    let calculated_index = get_part_index(last_filled_amount, parts_amount, order_making_amount);
 
     if remaining_making_amount == making_amount {
         // If the order is filled to completion, a secret with index i + 1 must be used
         // where i is the index of the secret for the last part.
-        return calculated_index + 1 == validated_index;
-    } else if order_making_amount != remaining_making_amount {
+        return Ok(calculated_index + 1 == U256::from(validated_index));
+    } else if filled_amount != 0 {
         // Calculate the previous fill index only if this is not the first fill.
-        let prev_calculated_index = ((order_making_amount - remaining_making_amount - 1)
-            * parts_amount)
-            / order_making_amount;
+        let prev_calculated_index =
+            get_part_index(filled_amount - 1, parts_amount, order_making_amount);
         if calculated_index == prev_calculated_index {
-            return false;
+            return Ok(false);
         }
     }
 
-    calculated_index == validated_index
+    Ok(calculated_index == U256::from(validated_index))
+}
+
+/// Returns the smallest index of the secret that the next fill can use, as every fill but
+/// the first one must move on to a part that has not been filled yet.
+pub fn get_next_part_index(
+    remaining_making_amount: u64,
+    order_making_amount: u64,
+    parts_amount: u64,
+) -> Result<u64> {
+    let filled_amount = order_making_amount
+        .checked_sub(remaining_making_amount)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    if filled_amount == 0 {
+        return Ok(0);
+    }
+
+    // The part index is below parts_amount, so it fits into u64
+    Ok((get_part_index(filled_amount - 1, parts_amount, order_making_amount) + 1).as_u64())
+}
+
+/// Returns the amount the next fill of an order with simple fills must take. Every fill takes
+/// an equal tranche except the last one, which also takes the remainder of the division.
+pub fn get_simple_fill_amount(
+    remaining_making_amount: u64,
+    order_making_amount: u64,
+    parts_amount: u16,
+) -> u64 {
+    let tranche = order_making_amount / parts_amount as u64;
+    if remaining_making_amount.saturating_sub(tranche) < tranche {
+        remaining_making_amount
+    } else {
+        tranche
+    }
+}
+
+fn get_part_index(filled_amount: u64, parts_amount: u64, order_making_amount: u64) -> U256 {
+    U256::from(filled_amount) * U256::from(parts_amount) / U256::from(order_making_amount)
 }
 
 pub fn get_escrow_hashlock(
@@ -1239,6 +2846,47 @@ pub fn get_escrow_hashlock(
     }
 }
 
+/// Order terms passed to `create`, `create_idempotent` and `create_and_fill`, and to
+/// `rescue_funds_for_order` to derive the order
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct OrderArgs {
+    /// Root of merkle tree if partially filled
+    pub hashlock: [u8; 32],
+    pub amount: u64,
+    pub safety_deposit: u64,
+    pub timelocks: [u64; 4],
+    pub expiration_time: u32,
+    pub asset_is_native: bool,
+    pub dst_amount: [u64; 4],
+    /// Floor for the destination amount of a fill, zero if none
+    pub min_dst_amount: [u64; 4],
+    pub dutch_auction_data_hash: [u8; 32],
+    pub max_cancellation_premium: u64,
+    pub cancellation_auction_duration: u32,
+    /// Public withdrawal award ramp, zero if none
+    pub public_withdrawal_premium_duration: u32,
+    pub allow_multiple_fills: bool,
+    /// Floor for the amount of a fill except the last one, zero if none
+    pub min_fill_amount: u64,
+    /// Disambiguates otherwise identical orders, see `derive_salted_order_hash`
+    pub salt: u64,
+    /// Hashlock algorithm, either HASH_ALGO_KECCAK or HASH_ALGO_SHA256
+    pub hash_algo: u8,
+    /// Power of ten the dst amounts are scaled by, zero if none
+    pub dst_decimals_shift: i8,
+    /// Only taker allowed to fill the order, any resolver if default
+    pub allowed_taker: Pubkey,
+    /// Equal tranches under the order hashlock, zero if disabled
+    pub simple_parts_amount: u16,
+    /// Taker withdrawal window past the src cancellation, zero if none
+    pub withdrawal_grace: u32,
+    /// Maker must co-sign every fill, not required if false
+    pub require_maker_cosign: bool,
+    /// Slots an escrow must age before withdrawals, zero if none
+    pub min_withdrawal_slots: u32,
+    pub dst_chain_params: DstChainParams,
+}
+
 #[account]
 pub struct DstChainParams {
     pub chain_id: u32,
@@ -1247,38 +2895,70 @@ pub struct DstChainParams {
     pub safety_deposit: u128,
 }
 
-#[allow(clippy::too_many_arguments)]
-fn get_order_hash(
-    hashlock: [u8; 32],
+/// The order expiration time is intentionally excluded from the hash so that the order PDA
+/// does not change when the maker extends the order with `extend_order`. The dst terms are
+/// hashed along with the hash of the other parameters, see `get_order_hash_with_dst_terms`.
+pub fn get_order_hash(maker: Pubkey, token: Pubkey, args: &OrderArgs) -> [u8; 32] {
+    let order_params_hash = keccak::hashv(&[
+        &args.hashlock,
+        maker.as_ref(),
+        token.as_ref(),
+        &args.amount.to_be_bytes(),
+        &args.safety_deposit.to_be_bytes(),
+        &u64_array_to_le_bytes(args.timelocks),
+        &[args.asset_is_native as u8],
+        &u64_array_to_le_bytes(args.dst_amount),
+        &u64_array_to_le_bytes(args.min_dst_amount),
+        args.dutch_auction_data_hash.as_ref(),
+        &args.max_cancellation_premium.to_be_bytes(),
+        &args.cancellation_auction_duration.to_be_bytes(),
+        &args.public_withdrawal_premium_duration.to_be_bytes(),
+        &[args.allow_multiple_fills as u8],
+        &args.min_fill_amount.to_be_bytes(),
+        &args.salt.to_be_bytes(),
+        &[args.hash_algo],
+        &args.dst_decimals_shift.to_be_bytes(),
+        args.allowed_taker.as_ref(),
+        &args.simple_parts_amount.to_be_bytes(),
+        &args.withdrawal_grace.to_be_bytes(),
+        &[args.require_maker_cosign as u8],
+        &args.min_withdrawal_slots.to_be_bytes(),
+    ])
+    .to_bytes();
+    get_order_hash_with_dst_terms(
+        &order_params_hash,
+        &args.dst_chain_params.token,
+        args.dst_chain_params.safety_deposit,
+    )
+}
+
+/// Computes the order hash for the given arguments with their salt replaced by the given one.
+/// Orders that share all other arguments get distinct hashes, and therefore distinct PDAs, as
+/// long as their salts differ. Reusing a salt for identical arguments derives the PDA of the
+/// existing order, so `create` fails with `SystemError::AccountAlreadyInUse` while that order
+/// is open.
+pub fn derive_salted_order_hash(
     maker: Pubkey,
     token: Pubkey,
-    order_amount: u64,
-    safety_deposit: u64,
-    timelocks: [u64; 4],
-    expiration_time: u32,
-    asset_is_native: bool,
-    dst_amount: [u64; 4],
-    dutch_auction_data_hash: [u8; 32],
-    max_cancellation_premium: u64,
-    cancellation_auction_duration: u32,
-    allow_multiple_fills: bool,
+    base_args: &OrderArgs,
     salt: u64,
 ) -> [u8; 32] {
-    keccak::hashv(&[
-        &hashlock,
-        maker.as_ref(),
-        token.as_ref(),
-        &order_amount.to_be_bytes(),
-        &safety_deposit.to_be_bytes(),
-        &timelocks.try_to_vec().unwrap(),
-        &expiration_time.to_be_bytes(),
-        &[asset_is_native as u8],
-        &dst_amount.try_to_vec().unwrap(),
-        dutch_auction_data_hash.as_ref(),
-        &max_cancellation_premium.to_be_bytes(),
-        &cancellation_auction_duration.to_be_bytes(),
-        &[allow_multiple_fills as u8],
-        &salt.to_be_bytes(),
-    ])
-    .to_bytes()
+    get_order_hash(
+        maker,
+        token,
+        &OrderArgs {
+            salt,
+            ..base_args.clone()
+        },
+    )
+}
+
+/// Encodes the array the same way as its Borsh serialization (little-endian words),
+/// but into a stack buffer to avoid heap allocations.
+fn u64_array_to_le_bytes(values: [u64; 4]) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    for (chunk, value) in bytes.chunks_exact_mut(8).zip(values) {
+        chunk.copy_from_slice(&value.to_le_bytes());
+    }
+    bytes
 }
@@ -1,5 +1,5 @@
 use anchor_lang::prelude::*;
-use solana_program::keccak::hashv;
+use common::{error::EscrowError, utils::hashv_with_algo};
 #[account]
 pub struct MerkleProof {
     pub proof: Vec<[u8; 32]>,
@@ -8,24 +8,49 @@ pub struct MerkleProof {
 }
 
 impl MerkleProof {
-    /// Computes the Merkle root using the provided proof.
-    pub fn process_proof(&self) -> [u8; 32] {
-        let leaf = self.hash_leaf();
+    /// Computes the Merkle root using the provided proof and the order's hashlock algorithm.
+    pub fn process_proof(&self, hash_algo: u8) -> Result<[u8; 32]> {
+        // An empty proof would return the leaf itself as the root, so a degenerate
+        // single-element tree could pass for a valid one
+        require!(!self.proof.is_empty(), EscrowError::EmptyMerkleProof);
+
+        let leaf = self.hash_leaf(hash_algo);
         let mut computed_hash = leaf;
 
         for proof_element in &self.proof {
-            computed_hash = hashv(&[
-                std::cmp::min(proof_element, &computed_hash),
-                std::cmp::max(proof_element, &computed_hash),
-            ])
-            .0;
+            computed_hash = hashv_with_algo(
+                &[
+                    std::cmp::min(proof_element, &computed_hash),
+                    std::cmp::max(proof_element, &computed_hash),
+                ],
+                hash_algo,
+            );
         }
 
-        computed_hash
+        Ok(computed_hash)
     }
 
     /// Computes the hash of the leaf using index and hashed_secret.
-    fn hash_leaf(&self) -> [u8; 32] {
-        hashv(&[&self.index.to_be_bytes(), &self.hashed_secret[..]]).0
+    fn hash_leaf(&self, hash_algo: u8) -> [u8; 32] {
+        hashv_with_algo(
+            &[&self.index.to_be_bytes(), &self.hashed_secret[..]],
+            hash_algo,
+        )
     }
 }
+
+/// Decodes the parts count that an order with multiple fills keeps in the first two bytes of
+/// its hashlock. In a single fill order these bytes are part of the secret hash, so decoding
+/// them there is rejected rather than yielding an arbitrary count.
+pub fn decode_parts_amount(hashlock: &[u8; 32], allow_multiple_fills: bool) -> Result<u16> {
+    require!(allow_multiple_fills, EscrowError::InconsistentFillMode);
+    Ok(u16::from_be_bytes([hashlock[0], hashlock[1]]))
+}
+
+/// Returns the depth of a merkle tree holding a secret per part plus one for the final fill,
+/// which is the length of every proof in it.
+pub fn get_max_proof_len(parts_amount: u16) -> usize {
+    (parts_amount as u32 + 1)
+        .next_power_of_two()
+        .trailing_zeros() as usize
+}
@@ -1,11 +1,30 @@
-use anchor_lang::{prelude::*, solana_program::keccak::hash};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::keccak;
+use anchor_lang::solana_program::program_option::COption;
+use anchor_lang::solana_program::sysvar::instructions::{
+    load_current_index_checked, load_instruction_at_checked,
+};
+use anchor_spl::token::spl_token::native_mint::ID as NATIVE_MINT;
 use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 use common::{
+    constants::{self, MAX_PARTS},
     error::EscrowError,
-    escrow::{process_payout, withdraw_and_close_token_ata},
+    escrow::{
+        close_token_ata, distribute_safety_deposit, get_amount_net_of_fee, has_permanent_delegate,
+        has_transfer_fee, process_payout, uni_transfer, verify_escrow_balance,
+        withdraw_and_close_token_ata, UniTransferParams,
+    },
+    timelocks::{Stage, Timelocks, SRC_STAGES},
+    utils::{get_current_timestamp, is_valid_hash_algo, is_valid_secret},
 };
+use primitive_types::U256;
 
-use crate::EscrowSrc;
+use crate::merkle_tree::{decode_parts_amount, get_max_proof_len, MerkleProof};
+use crate::{
+    get_fill_dst_amount, get_next_part_index, get_order_hash, get_pro_rata_dst_amount,
+    get_simple_fill_amount, is_valid_partial_fill, scale_dst_amount, AuctionData, EscrowCreated,
+    EscrowSrc, Order, OrderArgs, OrderFilled, PartAuthorization, SecretRevealed,
+};
 
 pub fn withdraw<'info>(
     escrow: &Account<'info, EscrowSrc>,
@@ -16,13 +35,24 @@ pub fn withdraw<'info>(
     token_program: &Interface<'info, TokenInterface>,
     rent_recipient: &AccountInfo<'info>,
     safety_deposit_recipient: &AccountInfo<'info>,
+    safety_deposit_award: u64,
+    safety_deposit_split: Option<(&AccountInfo<'info>, u16)>,
     secret: [u8; 32],
 ) -> Result<()> {
-    // Verify that the secret matches the hashlock
-    require!(
-        hash(&secret).to_bytes() == escrow.hashlock,
-        EscrowError::InvalidSecret
-    );
+    verify_escrow_balance(escrow_ata.amount, escrow.funded_amount)?;
+
+    // The slot guard is optional, escrows of orders without it only follow the timelocks
+    if escrow.min_withdrawal_slots > 0 {
+        require!(
+            Clock::get()?.slot
+                >= escrow
+                    .deployed_slot
+                    .saturating_add(escrow.min_withdrawal_slots as u64),
+            EscrowError::WithdrawalSlotsNotElapsed
+        );
+    }
+
+    reveal_secret(escrow, secret)?;
 
     let seeds = [
         "escrow".as_bytes(),
@@ -43,15 +73,48 @@ pub fn withdraw<'info>(
         &seeds,
     )?;
 
-    // Disrtibute the safety deposit if needed
-    if rent_recipient.key() != safety_deposit_recipient.key() {
-        escrow.sub_lamports(escrow.safety_deposit)?;
-        safety_deposit_recipient.add_lamports(escrow.safety_deposit)?;
+    // Disrtibute the safety deposit if needed, the part not awarded is left to the rent recipient.
+    // A secondary recipient takes its share even if the rent recipient gets the rest.
+    if rent_recipient.key() != safety_deposit_recipient.key() || safety_deposit_split.is_some() {
+        distribute_safety_deposit(
+            &escrow.to_account_info(),
+            safety_deposit_award,
+            safety_deposit_recipient,
+            safety_deposit_split,
+        )?;
     }
 
     Ok(())
 }
 
+/// Checks the secret against the escrow hashlock and publishes it, so that it can be used on the
+/// other chain. The secret is only emitted once validated
+pub fn reveal_secret(escrow: &EscrowSrc, secret: [u8; 32]) -> Result<()> {
+    require!(
+        is_valid_secret(&secret, &escrow.hashlock, escrow.hash_algo),
+        EscrowError::InvalidSecret
+    );
+
+    emit!(SecretRevealed {
+        order_hash: escrow.order_hash,
+        hashlock: escrow.hashlock,
+        secret,
+    });
+
+    Ok(())
+}
+
+/// Returns the end of the taker withdrawal, which is also the start of the cancellation. The
+/// withdrawal grace extends it past the src cancellation stage, though never past the start of
+/// the public cancellation.
+pub fn get_withdrawal_end(escrow: &EscrowSrc) -> Result<u32> {
+    let timelocks = Timelocks(U256(escrow.timelocks));
+    let withdrawal_end = timelocks
+        .get(Stage::SrcCancellation)?
+        .saturating_add(escrow.withdrawal_grace);
+    Ok(withdrawal_end.min(timelocks.get(Stage::SrcPublicCancellation)?))
+}
+
 pub fn cancel<'info>(
     escrow: &Account<'info, EscrowSrc>,
     escrow_bump: u8,
@@ -63,6 +126,8 @@ pub fn cancel<'info>(
     creator: &AccountInfo<'info>,
     safety_deposit_recipient: &AccountInfo<'info>,
 ) -> Result<()> {
+    verify_escrow_balance(escrow_ata.amount, escrow.funded_amount)?;
+
     let seeds = [
         "escrow".as_bytes(),
         &escrow.order_hash,
@@ -93,3 +158,679 @@ pub fn cancel<'info>(
 
     Ok(())
 }
+
+pub fn create_order<'info>(
+    creator: &AccountInfo<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    creator_ata: Option<&InterfaceAccount<'info, TokenAccount>>,
+    delegate: Option<&Signer<'info>>,
+    order: &mut Account<'info, Order>,
+    order_bump: u8,
+    order_ata: &InterfaceAccount<'info, TokenAccount>,
+    token_program: &Interface<'info, TokenInterface>,
+    system_program: &Program<'info, System>,
+    args: OrderArgs,
+) -> Result<()> {
+    let order_hash = get_order_hash(creator.key(), mint.key(), &args);
+    let OrderArgs {
+        hashlock,
+        amount,
+        safety_deposit,
+        timelocks,
+        expiration_time,
+        asset_is_native,
+        dst_amount,
+        min_dst_amount,
+        dutch_auction_data_hash,
+        max_cancellation_premium,
+        cancellation_auction_duration,
+        public_withdrawal_premium_duration,
+        allow_multiple_fills,
+        min_fill_amount,
+        salt: _,
+        hash_algo,
+        dst_decimals_shift,
+        allowed_taker,
+        simple_parts_amount,
+        withdrawal_grace,
+        require_maker_cosign,
+        min_withdrawal_slots,
+        dst_chain_params,
+    } = args;
+
+    // The creator signs unless an approved delegate funds the order with their tokens
+    require!(
+        creator.is_signer || (delegate.is_some() && !asset_is_native),
+        ErrorCode::AccountNotSigner
+    );
+
+    // The escrow could not guarantee its custody of tokens a permanent delegate can move
+    require!(
+        !has_permanent_delegate(mint)?,
+        EscrowError::UnsupportedMintExtension
+    );
+
+    // A zero premium disables cancellation by resolver, so there is no premium to cover
+    if max_cancellation_premium > 0 {
+        require!(
+            order_ata.to_account_info().lamports() >= max_cancellation_premium,
+            EscrowError::InvalidCancellationFee
+        );
+    }
+
+    if simple_parts_amount != 0 {
+        // Simple fills all lock the order hashlock, so they can't be mixed with merkle parts
+        require!(!allow_multiple_fills, EscrowError::InconsistentFillMode);
+        require!(
+            simple_parts_amount > 1
+                && simple_parts_amount <= MAX_PARTS
+                && amount >= simple_parts_amount as u64,
+            EscrowError::InvalidPartsAmount
+        );
+    }
+
+    if allow_multiple_fills {
+        let parts_amount = decode_parts_amount(&hashlock, allow_multiple_fills)?;
+        require!(
+            parts_amount > 1 && parts_amount <= MAX_PARTS,
+            EscrowError::InvalidPartsAmount
+        );
+    }
+
+    require!(is_valid_hash_algo(hash_algo), EscrowError::InvalidHashAlgo);
+
+    // Rejects shifts under which the dst amount of the order can't be computed
+    scale_dst_amount(dst_amount, dst_decimals_shift)?;
+
+    let now = get_current_timestamp()?;
+
+    require!(now < expiration_time, EscrowError::OrderHasExpired);
+
+    Timelocks(U256(timelocks)).validate_monotonic(&SRC_STAGES)?;
+    // Escrows can be created until the order expires, so the stages must be reachable from then
+    Timelocks(U256(timelocks)).validate_durations(&SRC_STAGES, expiration_time)?;
+
+    // An escrow created right away must be able to reach its cancellation stage before
+    // the order expires, so that order-level and escrow-level timing stay consistent
+    let src_cancellation = Timelocks(U256(timelocks))
+        .set_deployed_at(now)
+        .get(Stage::SrcCancellation)?;
+    require!(
+        expiration_time >= src_cancellation,
+        EscrowError::ExpirationBeforeCancellation
+    );
+
+    // TODO: Verify that safety_deposit is enough to cover public_withdraw and public_cancel methods
+    require!(
+        amount != 0 && safety_deposit != 0 && dst_chain_params.safety_deposit != 0,
+        EscrowError::ZeroAmountOrDeposit
+    );
+
+    // Verify that safety_deposit is less than escrow rent_exempt_reserve
+    let rent_exempt_reserve =
+        Rent::get()?.minimum_balance(EscrowSrc::INIT_SPACE + constants::DISCRIMINATOR_BYTES);
+    require!(
+        safety_deposit <= rent_exempt_reserve,
+        EscrowError::SafetyDepositTooLarge
+    );
+
+    require!(
+        mint.key() == NATIVE_MINT || !asset_is_native,
+        EscrowError::InconsistentNativeTrait
+    );
+
+    // Check if token is native (WSOL) and is expected to be wrapped
+    if asset_is_native {
+        // Transfer native tokens from creator to escrow_ata and wrap
+        uni_transfer(
+            &UniTransferParams::NativeTransfer {
+                from: creator.to_account_info(),
+                to: order_ata.to_account_info(),
+                amount,
+                program: system_program.clone(),
+            },
+            None,
+        )?;
+
+        anchor_spl::token::sync_native(CpiContext::new(
+            token_program.to_account_info(),
+            anchor_spl::token::SyncNative {
+                account: order_ata.to_account_info(),
+            },
+        ))?;
+    } else {
+        // The account constraints already bind creator_ata to the creator, this guards the
+        // transfer source for any caller of this helper that wires the account differently
+        let creator_ata = creator_ata.ok_or(EscrowError::MissingCreatorAta)?;
+        require!(
+            creator_ata.owner == creator.key(),
+            EscrowError::InvalidCreatorAta
+        );
+
+        // A delegate funds the order only if approved on creator_ata for the whole amount
+        let authority = if let Some(delegate) = delegate {
+            require!(
+                creator_ata.delegate == COption::Some(delegate.key())
+                    && creator_ata.delegated_amount >= amount,
+                EscrowError::InvalidDelegate
+            );
+            delegate.to_account_info()
+        } else {
+            creator.to_account_info()
+        };
+
+        // Do SPL token transfer
+        uni_transfer(
+            &UniTransferParams::TokenTransfer {
+                from: creator_ata.to_account_info(),
+                authority,
+                to: order_ata.to_account_info(),
+                mint: mint.clone(),
+                amount,
+                program: token_program.clone(),
+            },
+            None,
+        )?;
+    }
+
+    let updated_timelocks = Timelocks(U256(timelocks)).set_deployed_at(now);
+
+    order.set_inner(Order {
+        order_hash,
+        hashlock,
+        creator: creator.key(),
+        token: mint.key(),
+        amount,
+        remaining_amount: amount,
+        safety_deposit,
+        timelocks: updated_timelocks.get_timelocks(),
+        expiration_time,
+        asset_is_native,
+        dst_amount,
+        min_dst_amount,
+        dst_safety_deposit: dst_chain_params.safety_deposit,
+        dutch_auction_data_hash,
+        max_cancellation_premium,
+        cancellation_auction_duration,
+        public_withdrawal_premium_duration,
+        allow_multiple_fills,
+        min_fill_amount,
+        hash_algo,
+        dst_decimals_shift,
+        allowed_taker,
+        simple_parts_amount,
+        withdrawal_grace,
+        require_maker_cosign,
+        min_withdrawal_slots,
+        filled_amount: 0,
+        part_authorizations: 0,
+        bump: order_bump,
+    });
+
+    Ok(())
+}
+
+/// Checks that an existing order was created with the given arguments. The order PDA already
+/// commits to the hashed ones, so this mainly catches those left out of the order hash.
+pub fn order_matches_args(order: &Order, args: &OrderArgs) -> bool {
+    // The stored timelocks are deployed at the order creation
+    let timelocks = Timelocks(U256(args.timelocks)).set_deployed_at(0);
+    let order_timelocks = Timelocks(U256(order.timelocks)).set_deployed_at(0);
+
+    // The amount is left out as it is committed to by the order PDA and raised by increase_order
+    order.hashlock == args.hashlock
+        && order.safety_deposit == args.safety_deposit
+        && order_timelocks.get_timelocks() == timelocks.get_timelocks()
+        && order.expiration_time == args.expiration_time
+        && order.asset_is_native == args.asset_is_native
+        && order.dst_amount == args.dst_amount
+        && order.min_dst_amount == args.min_dst_amount
+        && order.dst_safety_deposit == args.dst_chain_params.safety_deposit
+        && order.dutch_auction_data_hash == args.dutch_auction_data_hash
+        && order.max_cancellation_premium == args.max_cancellation_premium
+        && order.cancellation_auction_duration == args.cancellation_auction_duration
+        && order.public_withdrawal_premium_duration == args.public_withdrawal_premium_duration
+        && order.allow_multiple_fills == args.allow_multiple_fills
+        && order.min_fill_amount == args.min_fill_amount
+        && order.hash_algo == args.hash_algo
+        && order.dst_decimals_shift == args.dst_decimals_shift
+        && order.allowed_taker == args.allowed_taker
+        && order.simple_parts_amount == args.simple_parts_amount
+        && order.withdrawal_grace == args.withdrawal_grace
+        && order.require_maker_cosign == args.require_maker_cosign
+        && order.min_withdrawal_slots == args.min_withdrawal_slots
+}
+
+/// Accounts an order is filled into an escrow with
+pub struct FillAccounts<'a, 'info> {
+    pub taker: &'a Signer<'info>,
+    pub resolver_access: &'a Account<'info, whitelist::ResolverAccess>,
+    pub fill_authority: &'a UncheckedAccount<'info>,
+    pub fill_authority_bump: u8,
+    pub maker: &'a AccountInfo<'info>,
+    pub mint: &'a InterfaceAccount<'info, Mint>,
+    pub order: &'a mut Account<'info, Order>,
+    pub order_ata: &'a InterfaceAccount<'info, TokenAccount>,
+    pub escrow: &'a mut Account<'info, EscrowSrc>,
+    pub escrow_bump: u8,
+    pub escrow_ata: &'a InterfaceAccount<'info, TokenAccount>,
+    pub token_program: &'a Interface<'info, TokenInterface>,
+    pub whitelist_program: &'a Program<'info, whitelist::program::Whitelist>,
+}
+
+/// Terms of a fill chosen by the resolver
+pub struct FillArgs {
+    pub amount: u64,
+    pub dutch_auction_data: AuctionData,
+    pub affiliate: Option<Pubkey>,
+    pub src_timelock_durations: Option<[u32; 4]>,
+    pub max_dst_amount: Option<[u64; 4]>,
+    pub tvl_tracked: bool,
+}
+
+pub fn create_escrow<'info>(
+    accounts: FillAccounts<'_, 'info>,
+    part_authorizations: &'info [AccountInfo<'info>],
+    merkle_proof: Option<MerkleProof>,
+    args: FillArgs,
+) -> Result<()> {
+    let taker = accounts.taker;
+    let maker = accounts.maker;
+    let amount = args.amount;
+    let order = &mut *accounts.order;
+    let now = validate_fill(order, &taker.key(), maker, amount, &args.dutch_auction_data)?;
+
+    require!(
+        order.allow_multiple_fills == merkle_proof.is_some(),
+        EscrowError::InconsistentMerkleProofTrait
+    );
+
+    let hashlock = if let Some(proof) = merkle_proof {
+        let parts_amount = decode_parts_amount(&order.hashlock, order.allow_multiple_fills)?;
+        // A tree of parts_amount + 1 secrets is never deeper, so longer proofs only add cost
+        require!(
+            proof.proof.len() <= get_max_proof_len(parts_amount),
+            EscrowError::InvalidMerkleProof
+        );
+        require!(
+            proof.process_proof(order.hash_algo)?[2..] == order.hashlock[2..],
+            EscrowError::InvalidMerkleProof
+        );
+        // The order is closed exactly when the fill takes the remaining amount, so the secret
+        // reserved for completing the order must be used by that fill and by no other
+        require!(
+            (amount == order.remaining_amount) == (proof.index == parts_amount as u64),
+            EscrowError::FinalFillIndexMismatch
+        );
+        require!(
+            is_valid_partial_fill(
+                amount,
+                order.remaining_amount,
+                order.amount,
+                parts_amount as u64,
+                proof.index,
+            )?,
+            EscrowError::InvalidPartialFill
+        );
+
+        // The fill covers every part from the first one not filled yet to the part of the proof
+        let first_part_index =
+            get_next_part_index(order.remaining_amount, order.amount, parts_amount as u64)?;
+        consume_part_authorizations(
+            order,
+            part_authorizations,
+            &taker.key(),
+            maker,
+            first_part_index,
+            proof.index,
+        )?;
+
+        proof.hashed_secret
+    } else {
+        order.hashlock
+    };
+
+    fill_order(accounts, args, hashlock, now)
+}
+
+/// Checks the part authorizations of the parts a fill covers, if the maker has assigned any part
+/// of the order. A part assigned to a resolver can only be filled by that resolver, and its
+/// authorization is closed by the fill, returning the rent to the maker
+fn consume_part_authorizations<'info>(
+    order: &mut Account<'info, Order>,
+    part_authorizations: &'info [AccountInfo<'info>],
+    taker: &Pubkey,
+    maker: &AccountInfo<'info>,
+    first_part_index: u64,
+    last_part_index: u64,
+) -> Result<()> {
+    if order.part_authorizations == 0 {
+        return Ok(());
+    }
+
+    // An omitted part would escape the restriction the maker placed on it
+    require!(
+        part_authorizations.len() as u64 == last_part_index + 1 - first_part_index,
+        EscrowError::MissingPartAuthorization
+    );
+
+    for (part_index, account) in (first_part_index..=last_part_index).zip(part_authorizations) {
+        let (expected_key, _) = Pubkey::find_program_address(
+            &[
+                "part_authorization".as_bytes(),
+                order.order_hash.as_ref(),
+                part_index.to_be_bytes().as_ref(),
+            ],
+            &crate::ID,
+        );
+        require!(
+            account.key() == expected_key,
+            EscrowError::MissingPartAuthorization
+        );
+
+        // The part is open to any resolver if the maker has not assigned it
+        if account.data_is_empty() {
+            continue;
+        }
+        // Only this program can populate the part authorization PDA
+        let part_authorization = Account::<PartAuthorization>::try_from(account)?;
+        require!(
+            part_authorization.resolver == *taker,
+            EscrowError::UnauthorizedPartFill
+        );
+        part_authorization.close(maker.clone())?;
+        order.part_authorizations = order
+            .part_authorizations
+            .checked_sub(1)
+            .ok_or(EscrowError::InvalidPartialFill)?;
+    }
+
+    Ok(())
+}
+
+/// Closes the part authorizations of an order that is closed before all of its parts are filled,
+/// returning their rent to the creator. All of them must be passed, so that none outlives the order
+pub fn close_part_authorizations<'info>(
+    order: &mut Account<'info, Order>,
+    part_authorizations: &'info [AccountInfo<'info>],
+    creator: &AccountInfo<'info>,
+) -> Result<()> {
+    require!(
+        part_authorizations.len() == order.part_authorizations as usize,
+        EscrowError::MissingPartAuthorization
+    );
+
+    for account in part_authorizations {
+        let part_authorization = Account::<PartAuthorization>::try_from(account)?;
+        let expected_key = Pubkey::create_program_address(
+            &[
+                "part_authorization".as_bytes(),
+                order.order_hash.as_ref(),
+                part_authorization.part_index.to_be_bytes().as_ref(),
+                &[part_authorization.bump],
+            ],
+            &crate::ID,
+        )
+        .map_err(|_| EscrowError::InvalidAccount)?;
+        // Closing an account twice leaves it empty, so it fails the deserialization above
+        require!(account.key() == expected_key, EscrowError::InvalidAccount);
+        part_authorization.close(creator.clone())?;
+    }
+    order.part_authorizations = 0;
+
+    Ok(())
+}
+
+/// Rejects the fill when the config requires it to be a top-level instruction and it was invoked
+/// through another program. The instructions sysvar lists only top-level instructions, so the
+/// current one belongs to this program exactly when there is no caller in between
+pub fn verify_top_level(
+    config: &whitelist::Config,
+    instructions_sysvar: Option<&UncheckedAccount>,
+) -> Result<()> {
+    if !config.require_top_level {
+        return Ok(());
+    }
+    let instructions_sysvar = instructions_sysvar.ok_or(EscrowError::CpiNotAllowed)?;
+    let current_index = load_current_index_checked(instructions_sysvar)?;
+    let current_instruction =
+        load_instruction_at_checked(current_index as usize, instructions_sysvar)?;
+    require!(
+        current_instruction.program_id == crate::ID,
+        EscrowError::CpiNotAllowed
+    );
+    Ok(())
+}
+
+/// Checks the parts of a fill that do not depend on the merkle proof and returns the current time
+pub fn validate_fill(
+    order: &Order,
+    taker: &Pubkey,
+    maker: &AccountInfo,
+    amount: u64,
+    dutch_auction_data: &AuctionData,
+) -> Result<u32> {
+    // An order whose remaining amount is exhausted has nothing left to fill
+    require!(order.remaining_amount > 0, EscrowError::OrderFullyFilled);
+
+    // The maker may reserve the order for a single resolver
+    require!(
+        order.allowed_taker == Pubkey::default() || *taker == order.allowed_taker,
+        EscrowError::TakerNotAllowed
+    );
+
+    // The maker may also require their own signature on every fill of the order
+    require!(
+        !order.require_maker_cosign || maker.is_signer,
+        EscrowError::MakerCosignRequired
+    );
+
+    let is_valid_amount = if order.allow_multiple_fills {
+        amount <= order.remaining_amount
+    } else if order.simple_parts_amount != 0 {
+        amount
+            == get_simple_fill_amount(
+                order.remaining_amount,
+                order.amount,
+                order.simple_parts_amount,
+            )
+    } else {
+        amount == order.amount
+    };
+    require!(is_valid_amount, EscrowError::InvalidAmount);
+
+    // The final fill may be smaller than the floor so that the order can always be completed
+    require!(
+        amount >= order.min_fill_amount || amount == order.remaining_amount,
+        EscrowError::FillAmountBelowMinimum
+    );
+
+    let now = get_current_timestamp()?;
+
+    require!(now < order.expiration_time, EscrowError::OrderHasExpired);
+
+    let calculated_hash = keccak::hashv(&[&dutch_auction_data.try_to_vec()?]).to_bytes();
+
+    require!(
+        calculated_hash == order.dutch_auction_data_hash,
+        EscrowError::DutchAuctionDataHashMismatch
+    );
+    dutch_auction_data.validate()?;
+    dutch_auction_data.validate_timing(now)?;
+
+    Ok(now)
+}
+
+/// Moves `amount` of the order into the escrow locked with `hashlock` and updates or closes
+/// the order. The fill must have been validated beforehand
+pub fn fill_order(
+    accounts: FillAccounts,
+    args: FillArgs,
+    hashlock: [u8; 32],
+    now: u32,
+) -> Result<()> {
+    let FillAccounts {
+        taker,
+        resolver_access,
+        fill_authority,
+        fill_authority_bump,
+        maker,
+        mint,
+        order,
+        order_ata,
+        escrow,
+        escrow_bump,
+        escrow_ata,
+        token_program,
+        whitelist_program,
+    } = accounts;
+    let FillArgs {
+        amount,
+        dutch_auction_data,
+        affiliate,
+        src_timelock_durations,
+        max_dst_amount,
+        tvl_tracked,
+    } = args;
+
+    // Count the fill towards the volume the resolver can lock into escrows within a window
+    whitelist::cpi::record_fill(
+        CpiContext::new_with_signer(
+            whitelist_program.to_account_info(),
+            whitelist::cpi::accounts::RecordFill {
+                fill_authority: fill_authority.to_account_info(),
+                resolver_access: resolver_access.to_account_info(),
+            },
+            &[&[whitelist::FILL_AUTHORITY_SEED, &[fill_authority_bump]]],
+        ),
+        taker.key(),
+        amount,
+    )?;
+
+    // Fail with a clear error rather than an opaque token error or an under-funded escrow if the
+    // order ATA was drained. Transfer fee mints hold less than the order amount by design
+    require!(
+        order_ata.amount >= amount || has_transfer_fee(mint)?,
+        EscrowError::InsufficientOrderBalance
+    );
+
+    let order_seeds = ["order".as_bytes(), &order.order_hash, &[order.bump]];
+
+    let mut amount_to_transfer = amount;
+    if order.remaining_amount == amount {
+        // Transfer amount may be increased due to external transfers
+        amount_to_transfer = order_ata.amount;
+    } else if order_ata.amount < order.remaining_amount {
+        // The order ATA holds less than the remaining amount if the token charges transfer
+        // fees, so every fill bears its pro-rata share of the fee withheld on order creation
+        amount_to_transfer =
+            (amount as u128 * order_ata.amount as u128 / order.remaining_amount as u128) as u64;
+    }
+
+    uni_transfer(
+        &UniTransferParams::TokenTransfer {
+            from: order_ata.to_account_info(),
+            authority: order.to_account_info(),
+            to: escrow_ata.to_account_info(),
+            mint: mint.clone(),
+            amount: amount_to_transfer,
+            program: token_program.clone(),
+        },
+        Some(&[&order_seeds]),
+    )?;
+    let funded_amount = get_amount_net_of_fee(mint, amount_to_transfer)?;
+
+    let dst_amount = get_fill_dst_amount(order, amount, &dutch_auction_data, now)?;
+
+    // Partial fills are checked against the pro-rata share of the floor. A zero floor
+    // is never binding, so orders without one are unaffected
+    let min_dst_amount = scale_dst_amount(
+        get_pro_rata_dst_amount(order.min_dst_amount, amount, order.amount)?,
+        order.dst_decimals_shift,
+    )?;
+    require!(
+        U256(dst_amount) >= U256(min_dst_amount),
+        EscrowError::DstAmountBelowMinimum
+    );
+
+    // The ceiling is supplied by the resolver for this fill and not signed by the maker, so it
+    // only guards the resolver against an auction demanding more than it quoted for
+    if let Some(max_dst_amount) = max_dst_amount {
+        require!(
+            U256(dst_amount) <= U256(max_dst_amount),
+            EscrowError::DstAmountAboveMaximum
+        );
+    }
+
+    // The resolver may opt into shorter src stages than the order commits to, never longer ones
+    let mut timelocks = Timelocks(U256(order.timelocks));
+    if let Some(durations) = src_timelock_durations {
+        timelocks = timelocks.tighten(&SRC_STAGES, &durations)?;
+        timelocks.validate_monotonic(&SRC_STAGES)?;
+    }
+    let updated_timelocks = timelocks.set_deployed_at(now);
+    let affiliate = affiliate.unwrap_or_default();
+
+    escrow.set_inner(EscrowSrc {
+        order_hash: order.order_hash,
+        hashlock,
+        maker: order.creator,
+        taker: taker.key(),
+        token: order.token,
+        amount,
+        safety_deposit: order.safety_deposit,
+        timelocks: updated_timelocks.get_timelocks(),
+        asset_is_native: order.asset_is_native,
+        dst_amount,
+        dst_safety_deposit: order.dst_safety_deposit,
+        public_withdrawal_premium_duration: order.public_withdrawal_premium_duration,
+        affiliate,
+        hash_algo: order.hash_algo,
+        withdrawal_grace: order.withdrawal_grace,
+        deployed_slot: Clock::get()?.slot,
+        min_withdrawal_slots: order.min_withdrawal_slots,
+        tvl_tracked,
+        funded_amount,
+        bump: escrow_bump,
+    });
+
+    emit!(EscrowCreated {
+        order_hash: order.order_hash,
+        hashlock,
+        taker: taker.key(),
+        amount,
+        affiliate,
+    });
+
+    // The amount never exceeds the remaining amount, which adds up with the filled amount to
+    // the order amount
+    let filled_amount = order.filled_amount + amount;
+    let remaining_amount = order.remaining_amount - amount;
+
+    emit!(OrderFilled {
+        order_hash: order.order_hash,
+        fill_amount: amount,
+        filled_amount,
+        remaining_amount,
+    });
+
+    let is_single_fill = !order.allow_multiple_fills && order.simple_parts_amount == 0;
+    if is_single_fill || order.remaining_amount == amount {
+        // Close the order ATA
+        close_token_ata(
+            &order_ata.to_account_info(),
+            &maker.to_account_info(),
+            &order.to_account_info(),
+            token_program,
+            &order_seeds,
+        )?;
+
+        // Close the order account
+        order.close(maker.to_account_info())?;
+    } else {
+        order.filled_amount = filled_amount;
+        order.remaining_amount = remaining_amount;
+    }
+
+    Ok(())
+}
@@ -2,35 +2,49 @@ use std::marker::PhantomData;
 
 use anchor_lang::error::ErrorCode;
 use anchor_lang::prelude::ProgramError;
+use anchor_lang::AccountDeserialize;
+use anchor_spl::associated_token::ID as ASSOCIATED_TOKEN_PROGRAM_ID;
 use anchor_spl::token::spl_token::native_mint::ID as NATIVE_MINT;
 use common::{constants::RESCUE_DELAY, timelocks::Stage};
 
 use common_tests::helpers::{
-    account_closure, create_escrow_data, find_user_ata, get_min_rent_for_size, get_token_balance,
-    native_change, set_time, token_change, EscrowVariant, Expectation, HasTokenVariant,
-    StateChange, TestStateBase, TokenVariant, DEFAULT_ESCROW_AMOUNT, DEFAULT_ORDER_SIZE,
-    DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE, DEFAULT_SRC_ESCROW_SIZE, WALLET_DEFAULT_LAMPORTS,
-    WALLET_DEFAULT_TOKENS,
+    account_closure, create_escrow, create_escrow_data, find_user_ata, get_min_rent_for_size,
+    get_token_balance, native_change, set_time, token_change, BalanceChange, EscrowVariant,
+    Expectation, HasTokenVariant, StateChange, TestStateBase, TokenVariant, DEFAULT_ESCROW_AMOUNT,
+    DEFAULT_ORDER_SIZE, DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE, DEFAULT_PERIOD_DURATION,
+    DEFAULT_SRC_ESCROW_SIZE, WALLET_DEFAULT_LAMPORTS, WALLET_DEFAULT_TOKENS,
 };
 use common_tests::src_program::{
     create_order, create_order_data, create_public_escrow_cancel_tx,
-    get_cancel_order_by_resolver_tx, get_cancel_order_tx, get_order_addresses,
+    get_cancel_order_by_resolver_tx, get_cancel_order_tx, get_order_addresses, get_order_hash,
     get_rescue_funds_from_order_tx, SrcProgram,
 };
+use common_tests::tests::measure_tx_compute_units;
 use common_tests::whitelist::prepare_resolvers;
 use cross_chain_escrow_src::calculate_premium;
 use cross_chain_escrow_src::merkle_tree::MerkleProof;
+use cross_chain_escrow_src::EscrowSrc;
 use primitive_types::U256;
+use solana_program::instruction::{AccountMeta, Instruction};
 use solana_program::pubkey::Pubkey;
 use solana_sdk::clock::Clock;
+use solana_sdk::hash::hash as sha256;
 use solana_sdk::keccak::{hashv, Hash};
 use solana_sdk::signature::{Keypair, Signer};
 use solana_sdk::transaction::Transaction;
 
 use crate::merkle_tree_helpers::{get_proof, get_root};
 
+/// Upper bound on the compute units consumed by the `create` instruction for an order whose PDA
+/// and ATA are both found at the first bump
+pub const MAX_ORDER_CREATION_COMPUTE_UNITS: u64 = 30_000;
+
 /// Byte offset in the escrow account data where the `dst_amount` field is located
 const DST_AMOUNT_OFFSET: usize = 217;
+/// Byte offset in the order account data where the `remaining_amount` field is located
+const ORDER_REMAINING_AMOUNT_OFFSET: usize = 144;
+/// Byte offset in the token account data where the `amount` field is located
+const TOKEN_ACCOUNT_AMOUNT_OFFSET: usize = 64;
 const U64_SIZE: usize = size_of::<u64>();
 
 /// Reads the `dst_amount` field (u64) directly from the raw account data.
@@ -40,6 +54,63 @@ pub fn get_dst_amount(data: &[u8]) -> Option<[u64; 4]> {
     Some(U256::from_little_endian(slice).0)
 }
 
+/// Fetches and deserializes the escrow account.
+pub async fn get_escrow_src<S: TokenVariant>(
+    test_state: &mut TestStateBase<SrcProgram, S>,
+    escrow: &Pubkey,
+) -> EscrowSrc {
+    let escrow_account = test_state
+        .client
+        .get_account(*escrow)
+        .await
+        .unwrap()
+        .expect("Escrow account not found");
+    EscrowSrc::try_deserialize(&mut escrow_account.data.as_slice()).unwrap()
+}
+
+/// Locks the order with the sha256 hash of the test secret instead of its keccak hash.
+pub fn set_sha256_hashlock<S: TokenVariant>(test_state: &mut TestStateBase<SrcProgram, S>) {
+    test_state.hashlock = Hash::new_from_array(sha256(&test_state.secret).to_bytes());
+}
+
+/// Rebuilds the escrow creation transaction with the maker account as a signer, as required by
+/// orders with the maker cosign.
+pub fn get_cosigned_create_escrow_tx<S: TokenVariant>(
+    test_state: &TestStateBase<SrcProgram, S>,
+    transaction: &Transaction,
+) -> Transaction {
+    let maker = test_state.maker_wallet.keypair.pubkey();
+    let message = &transaction.message;
+    let instruction = &message.instructions[0];
+    let accounts = instruction
+        .accounts
+        .iter()
+        .map(|&index| {
+            let index = index as usize;
+            let pubkey = message.account_keys[index];
+            AccountMeta {
+                pubkey,
+                is_signer: message.is_signer(index) || pubkey == maker,
+                is_writable: message.is_maybe_writable(index, None),
+            }
+        })
+        .collect();
+    Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id: message.account_keys[instruction.program_id_index as usize],
+            accounts,
+            data: instruction.data.clone(),
+        }],
+        Some(&test_state.payer_kp.pubkey()),
+        &[
+            &test_state.context.payer,
+            &test_state.taker_wallet.keypair,
+            &test_state.maker_wallet.keypair,
+        ],
+        test_state.context.last_blockhash,
+    )
+}
+
 pub async fn test_order_creation<S: TokenVariant>(test_state: &mut TestStateBase<SrcProgram, S>) {
     let (order, order_ata, transaction) = create_order_data(test_state);
 
@@ -100,6 +171,48 @@ pub async fn test_order_creation<S: TokenVariant>(test_state: &mut TestStateBase
     );
 }
 
+pub async fn test_order_creation_tx_cost<S: TokenVariant>(
+    test_state: &mut TestStateBase<SrcProgram, S>,
+) {
+    // NOTE: To actually see the output from this test, use the `--show-output` flag as shown below
+    // `cargo test test_order_creation_compute_units -- --show-output`
+
+    // The bump search for the order PDA and ATA depends on the random keys of the test state, so
+    // the salt is picked for both to be found at the first bump and the cost to be the same on
+    // every run
+    loop {
+        test_state.order_hash = get_order_hash(test_state);
+        let (order, _) = get_order_addresses(test_state);
+        let (_, order_bump) = Pubkey::find_program_address(
+            &[b"order", test_state.order_hash.as_ref()],
+            &cross_chain_escrow_src::id(),
+        );
+        let (_, order_ata_bump) = Pubkey::find_program_address(
+            &[
+                order.as_ref(),
+                S::get_token_program_id().as_ref(),
+                test_state.token.as_ref(),
+            ],
+            &ASSOCIATED_TOKEN_PROGRAM_ID,
+        );
+        if order_bump == u8::MAX && order_ata_bump == u8::MAX {
+            break;
+        }
+        test_state.test_arguments.salt += 1;
+    }
+    let (_, _, transaction) = create_order_data(test_state);
+
+    let units_consumed = measure_tx_compute_units(test_state, transaction).await;
+    println!("CU cost for create order: {}", units_consumed);
+
+    assert!(
+        units_consumed <= MAX_ORDER_CREATION_COMPUTE_UNITS,
+        "Order creation consumed {} CU, expected at most {}",
+        units_consumed,
+        MAX_ORDER_CREATION_COMPUTE_UNITS
+    );
+}
+
 pub async fn test_withdraw_escrow<S: TokenVariant>(
     test_state: &mut TestStateBase<SrcProgram, S>,
     escrow: &Pubkey,
@@ -197,6 +310,65 @@ pub async fn test_public_withdraw_escrow<S: TokenVariant>(
         .await;
 }
 
+// Checks that a public withdrawal by a third party awards the part of the safety deposit ramped up
+// over the public withdrawal premium duration, with the rest of it left to the taker.
+pub async fn test_public_withdraw_with_ramped_safety_deposit<S: TokenVariant>(
+    test_state: &mut TestStateBase<SrcProgram, S>,
+    withdrawer: &Keypair,
+    time_since_public_withdrawal: u32,
+) {
+    test_state.test_arguments.public_withdrawal_premium_duration = DEFAULT_PERIOD_DURATION / 2;
+    create_order(test_state).await;
+    prepare_resolvers(
+        test_state,
+        &[
+            test_state.taker_wallet.keypair.pubkey(),
+            withdrawer.pubkey(),
+        ],
+    )
+    .await;
+    let (escrow, escrow_ata) = create_escrow(test_state).await;
+
+    let public_withdrawal_start = test_state
+        .test_arguments
+        .src_timelocks
+        .get(Stage::SrcPublicWithdrawal)
+        .unwrap();
+    let withdrawal_time = public_withdrawal_start + time_since_public_withdrawal;
+    let safety_deposit_award = calculate_premium(
+        withdrawal_time,
+        public_withdrawal_start,
+        test_state.test_arguments.public_withdrawal_premium_duration,
+        test_state.test_arguments.safety_deposit,
+    );
+
+    let transaction =
+        SrcProgram::get_public_withdraw_tx(test_state, &escrow, &escrow_ata, withdrawer);
+    set_time(&mut test_state.context, withdrawal_time);
+
+    let token_account_rent =
+        get_min_rent_for_size(&mut test_state.client, S::get_token_account_size()).await;
+    let escrow_rent = get_min_rent_for_size(&mut test_state.client, DEFAULT_SRC_ESCROW_SIZE).await;
+
+    let (_, taker_ata) = find_user_ata(test_state);
+
+    test_state
+        .expect_state_change(
+            transaction,
+            &[
+                token_change(taker_ata, test_state.test_arguments.escrow_amount),
+                native_change(withdrawer.pubkey(), safety_deposit_award),
+                native_change(
+                    test_state.taker_wallet.keypair.pubkey(),
+                    token_account_rent + escrow_rent - safety_deposit_award,
+                ),
+                account_closure(escrow, true),
+                account_closure(escrow_ata, true),
+            ],
+        )
+        .await;
+}
+
 pub async fn test_cancel_escrow_partial_native<S: TokenVariant>(
     test_state: &mut TestStateBase<SrcProgram, S>,
     escrow: &Pubkey,
@@ -352,6 +524,71 @@ pub async fn test_rescue_all_tokens_from_order_and_close_ata<S: TokenVariant>(
     test_state: &mut TestStateBase<SrcProgram, S>,
 ) {
     let (order, _) = create_order(test_state).await;
+    rescue_all_tokens_from_order_and_close_ata(test_state, &order).await;
+}
+
+/// Rescues tokens sent to an ATA of `order`, which is derived from the order arguments of the
+/// test state
+pub async fn rescue_all_tokens_from_order_and_close_ata<S: TokenVariant>(
+    test_state: &mut TestStateBase<SrcProgram, S>,
+    order: &Pubkey,
+) {
+    let token_to_rescue = S::deploy_spl_token(&mut test_state.context).await.pubkey();
+    let order_ata =
+        S::initialize_spl_associated_account(&mut test_state.context, &token_to_rescue, order)
+            .await;
+    let taker_ata = S::initialize_spl_associated_account(
+        &mut test_state.context,
+        &token_to_rescue,
+        &test_state.taker_wallet.keypair.pubkey(),
+    )
+    .await;
+
+    S::mint_spl_tokens(
+        &mut test_state.context,
+        &token_to_rescue,
+        &order_ata,
+        &test_state.payer_kp.pubkey(),
+        &test_state.payer_kp,
+        test_state.test_arguments.rescue_amount,
+    )
+    .await;
+
+    let transaction =
+        get_rescue_funds_from_order_tx(test_state, order, &order_ata, &token_to_rescue, &taker_ata);
+
+    let token_account_rent =
+        get_min_rent_for_size(&mut test_state.client, S::get_token_account_size()).await;
+
+    set_time(
+        &mut test_state.context,
+        test_state.init_timestamp + RESCUE_DELAY + 100,
+    );
+    test_state
+        .expect_state_change(
+            transaction,
+            &[
+                native_change(test_state.taker_wallet.keypair.pubkey(), token_account_rent),
+                token_change(taker_ata, test_state.test_arguments.rescue_amount),
+            ],
+        )
+        .await;
+
+    // Assert escrow_ata was closed
+    assert!(test_state
+        .client
+        .get_account(order_ata)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+pub async fn test_rescue_all_tokens_from_order_with_rent_recipient<S: TokenVariant>(
+    test_state: &mut TestStateBase<SrcProgram, S>,
+) {
+    let rent_recipient = Keypair::new().pubkey();
+    test_state.test_arguments.rent_recipient = Some(rent_recipient);
+    let (order, _) = create_order(test_state).await;
 
     let token_to_rescue = S::deploy_spl_token(&mut test_state.context).await.pubkey();
     let order_ata =
@@ -393,7 +630,8 @@ pub async fn test_rescue_all_tokens_from_order_and_close_ata<S: TokenVariant>(
         .expect_state_change(
             transaction,
             &[
-                native_change(test_state.taker_wallet.keypair.pubkey(), token_account_rent),
+                native_change(rent_recipient, token_account_rent),
+                native_change(test_state.taker_wallet.keypair.pubkey(), 0),
                 token_change(taker_ata, test_state.test_arguments.rescue_amount),
             ],
         )
@@ -655,6 +893,53 @@ pub async fn test_cancel_by_resolver_for_free_at_the_auction_start<S: TokenVaria
         .await;
 }
 
+pub async fn test_cancel_by_resolver_to_maker_lamport_recipient<S: TokenVariant>(
+    test_state: &mut TestStateBase<SrcProgram, S>,
+) {
+    let maker_lamport_recipient = Keypair::new().pubkey();
+    test_state.test_arguments.maker_lamport_recipient = Some(maker_lamport_recipient);
+
+    let (order, order_ata) = create_order(test_state).await;
+    let transaction = get_cancel_order_by_resolver_tx(test_state, &order, &order_ata, None);
+
+    set_time(
+        &mut test_state.context,
+        test_state.test_arguments.expiration_time,
+    );
+
+    let token_account_rent =
+        get_min_rent_for_size(&mut test_state.client, S::get_token_account_size()).await;
+
+    let order_rent = get_min_rent_for_size(&mut test_state.client, DEFAULT_ORDER_SIZE).await;
+
+    let (maker_ata, _) = find_user_ata(test_state);
+
+    let mut balance_changes: Vec<StateChange> = vec![
+        native_change(test_state.maker_wallet.keypair.pubkey(), 0),
+        account_closure(order, true),
+        account_closure(order_ata, true),
+    ];
+    if test_state.test_arguments.asset_is_native {
+        balance_changes.push(native_change(
+            maker_lamport_recipient,
+            token_account_rent + order_rent + test_state.test_arguments.order_amount,
+        ));
+    } else {
+        balance_changes.push(token_change(
+            maker_ata,
+            test_state.test_arguments.order_amount,
+        ));
+        balance_changes.push(native_change(
+            maker_lamport_recipient,
+            token_account_rent + order_rent,
+        ));
+    }
+
+    test_state
+        .expect_state_change(transaction, &balance_changes)
+        .await;
+}
+
 pub async fn test_cancel_by_resolver_at_different_points<S: TokenVariant>(
     init_test_state: &mut TestStateBase<SrcProgram, S>,
     asset_is_native: bool,
@@ -860,6 +1145,85 @@ pub async fn test_cancel_by_resolver_reward_less_then_auction_calculated<S: Toke
         .await;
 }
 
+// Checks that the resolver receives exactly min(premium, reward_limit) and the maker receives
+// everything else held by the order and its ATA, with the reward limit set one lamport below
+// or above the premium calculated at the cancellation time.
+pub async fn test_cancel_by_resolver_pays_from_order<S: TokenVariant>(
+    test_state: &mut TestStateBase<SrcProgram, S>,
+    reward_limit_exceeds_premium: bool,
+) {
+    let (order, order_ata) = create_order(test_state).await;
+
+    let cancellation_time = test_state.test_arguments.expiration_time
+        + test_state.test_arguments.cancellation_auction_duration / 2;
+    let cancellation_premium = calculate_premium(
+        cancellation_time,
+        test_state.test_arguments.expiration_time,
+        test_state.test_arguments.cancellation_auction_duration,
+        test_state.test_arguments.max_cancellation_premium,
+    );
+    assert!(cancellation_premium > 1);
+
+    test_state.test_arguments.reward_limit = if reward_limit_exceeds_premium {
+        cancellation_premium + 1
+    } else {
+        cancellation_premium - 1
+    };
+    let resolver_premium =
+        std::cmp::min(cancellation_premium, test_state.test_arguments.reward_limit);
+
+    let transaction = get_cancel_order_by_resolver_tx(test_state, &order, &order_ata, None);
+    set_time(&mut test_state.context, cancellation_time);
+
+    let order_rent = get_min_rent_for_size(&mut test_state.client, DEFAULT_ORDER_SIZE).await;
+    let order_ata_lamports = test_state.client.get_balance(order_ata).await.unwrap();
+
+    let (maker_ata, _) = find_user_ata(test_state);
+
+    let mut balance_changes: Vec<StateChange> = vec![
+        native_change(test_state.taker_wallet.keypair.pubkey(), resolver_premium),
+        native_change(
+            test_state.maker_wallet.keypair.pubkey(),
+            order_ata_lamports + order_rent - resolver_premium,
+        ),
+        StateChange::Balance(BalanceChange::Native(
+            order_ata,
+            -(order_ata_lamports as i128),
+        )),
+        account_closure(order, true),
+        account_closure(order_ata, true),
+    ];
+    if !test_state.test_arguments.asset_is_native {
+        balance_changes.push(token_change(
+            maker_ata,
+            test_state.test_arguments.order_amount,
+        ));
+    }
+
+    test_state
+        .expect_state_change(transaction, &balance_changes)
+        .await;
+}
+
+/// Emulates an order whose amount has been entirely filled while its accounts were left open,
+/// by zeroing the remaining amount of the order and the balance of the order ATA.
+pub async fn set_order_consumed<S: TokenVariant>(test_state: &mut TestStateBase<SrcProgram, S>) {
+    let (order, order_ata) = get_order_addresses(test_state);
+    for (address, offset) in [
+        (order, ORDER_REMAINING_AMOUNT_OFFSET),
+        (order_ata, TOKEN_ACCOUNT_AMOUNT_OFFSET),
+    ] {
+        let mut account = test_state
+            .client
+            .get_account(address)
+            .await
+            .unwrap()
+            .expect("Account not found");
+        account.data[offset..offset + U64_SIZE].copy_from_slice(&0u64.to_le_bytes());
+        test_state.context.set_account(&address, &account.into());
+    }
+}
+
 pub async fn create_order_for_partial_fill<S: TokenVariant>(
     test_state: &mut TestStateBase<SrcProgram, S>,
 ) -> (Pubkey, Pubkey) {
@@ -296,6 +296,15 @@ mod test_native_src {
         helpers_src::test_cancel_by_resolver_for_free_at_the_auction_start(test_state).await;
     }
 
+    #[test_context(TestState)]
+    #[tokio::test]
+    async fn test_cancel_by_resolver_to_maker_lamport_recipient(test_state: &mut TestState) {
+        test_state.token = NATIVE_MINT;
+        test_state.test_arguments.asset_is_native = true;
+        prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+        helpers_src::test_cancel_by_resolver_to_maker_lamport_recipient(test_state).await;
+    }
+
     #[test_context(TestState)]
     #[tokio::test]
     async fn test_cancel_by_resolver_at_different_points(test_state: &mut TestState) {
@@ -327,6 +336,28 @@ mod test_native_src {
         helpers_src::test_cancel_by_resolver_reward_less_then_auction_calculated(test_state).await;
     }
 
+    #[test_context(TestState)]
+    #[tokio::test]
+    async fn test_cancel_by_resolver_pays_from_order_with_reward_below_premium(
+        test_state: &mut TestState,
+    ) {
+        test_state.token = NATIVE_MINT;
+        test_state.test_arguments.asset_is_native = true;
+        prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+        helpers_src::test_cancel_by_resolver_pays_from_order(test_state, false).await;
+    }
+
+    #[test_context(TestState)]
+    #[tokio::test]
+    async fn test_cancel_by_resolver_pays_from_order_with_reward_above_premium(
+        test_state: &mut TestState,
+    ) {
+        test_state.token = NATIVE_MINT;
+        test_state.test_arguments.asset_is_native = true;
+        prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+        helpers_src::test_cancel_by_resolver_pays_from_order(test_state, true).await;
+    }
+
     #[test_context(TestState)]
     #[tokio::test]
     async fn test_cancel_by_resolver_fails_if_native_and_maker_ata_provided(
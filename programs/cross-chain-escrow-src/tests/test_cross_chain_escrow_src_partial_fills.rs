@@ -1,20 +1,26 @@
-use anchor_lang::{error::ErrorCode, prelude::ProgramError};
-use common::{error::EscrowError, timelocks::Stage};
+use anchor_lang::{error::ErrorCode, prelude::ProgramError, AnchorDeserialize};
+use common::{constants::MAX_BATCH_WITHDRAWALS, error::EscrowError, timelocks::Stage};
 use common_tests::helpers::*;
 use common_tests::run_for_tokens;
 use common_tests::src_program::create_public_escrow_cancel_tx;
-use common_tests::src_program::{create_order, SrcProgram};
+use common_tests::src_program::{
+    create_order, get_authorize_part_fill_tx, get_cancel_order_tx, get_increase_order_tx,
+    get_order_fill_state_tx, get_part_authorization_address, get_revoke_part_fill_tx,
+    get_verify_order_root_tx, get_withdraw_many_tx, SrcProgram,
+};
 use common_tests::tests as common_escrow_tests;
-use common_tests::whitelist::prepare_resolvers;
+use common_tests::whitelist::{get_resolver_access, prepare_resolvers, set_max_window_volume};
+use cross_chain_escrow_src::OrderFilled;
 use solana_program_test::tokio;
-use solana_sdk::{keccak::hashv, signature::Signer, signer::keypair::Keypair};
+use solana_sdk::{keccak::hashv, pubkey::Pubkey, signature::Signer, signer::keypair::Keypair};
 use test_context::test_context;
+use whitelist::{error::WhitelistError, FILL_WINDOW_DURATION};
 
 use primitive_types::U256;
 pub mod helpers_src;
 use helpers_src::*;
 
-use helpers_src::merkle_tree_helpers::{get_proof, get_root};
+use helpers_src::merkle_tree_helpers::{get_proof, get_root, hash_nodes};
 
 run_for_tokens!(
     (TokenSPL, token_spl_tests),
@@ -66,6 +72,121 @@ run_for_tokens!(
                 assert!(acc_lookup_result.is_some());
             }
 
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_fill_state_reports_index_of_next_fill(test_state: &mut TestState) {
+                let (order, _) = create_order_for_partial_fill(test_state).await;
+
+                let escrow_amount = DEFAULT_ESCROW_AMOUNT / DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                test_escrow_creation_for_partial_fill(test_state, escrow_amount).await;
+
+                let transaction = get_order_fill_state_tx(test_state, &order);
+                let return_data =
+                    common_escrow_tests::simulate_tx_return_data(test_state, transaction).await;
+                let fill_state =
+                    cross_chain_escrow_src::OrderFillState::try_from_slice(&return_data).unwrap();
+
+                assert_eq!(fill_state.amount, DEFAULT_ESCROW_AMOUNT);
+                assert_eq!(
+                    fill_state.remaining_amount,
+                    DEFAULT_ESCROW_AMOUNT - escrow_amount
+                );
+                assert_eq!(
+                    fill_state.parts_amount as u64,
+                    DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE
+                );
+                // The next fill of a single part must use the reported index
+                assert_eq!(
+                    fill_state.next_part_index,
+                    get_index_for_escrow_amount(test_state, escrow_amount) as u64
+                );
+                test_escrow_creation_for_partial_fill(test_state, escrow_amount).await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_verify_order_root(test_state: &mut TestState) {
+                let (order, _) = create_order_for_partial_fill(test_state).await;
+
+                let merkle_hashes = compute_merkle_leaves();
+                let sample_proof = MerkleProof {
+                    proof: get_proof(merkle_hashes.leaves.clone(), 0),
+                    index: 0,
+                    hashed_secret: merkle_hashes.hashed_secrets[0],
+                };
+
+                let transaction = get_verify_order_root_tx(test_state, &order, sample_proof);
+                let return_data =
+                    common_escrow_tests::simulate_tx_return_data(test_state, transaction).await;
+                assert!(bool::try_from_slice(&return_data).unwrap());
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_verify_order_root_detects_corrupted_root(test_state: &mut TestState) {
+                let merkle_hashes = compute_merkle_leaves();
+                let mut root = get_root(merkle_hashes.leaves.clone());
+                root[31] ^= 1;
+                test_state.hashlock =
+                    prepare_hashlock_for_root(root, DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE);
+                test_state.test_arguments.allow_multiple_fills = true;
+                let (order, _) = create_order(test_state).await;
+
+                let sample_proof = MerkleProof {
+                    proof: get_proof(merkle_hashes.leaves.clone(), 0),
+                    index: 0,
+                    hashed_secret: merkle_hashes.hashed_secrets[0],
+                };
+
+                let transaction = get_verify_order_root_tx(test_state, &order, sample_proof);
+                // Trailing zero bytes of the return data are trimmed, so `false` comes back empty
+                let mut return_data = test_state
+                    .client
+                    .simulate_transaction(transaction)
+                    .await
+                    .expect("Simulation RPC failed")
+                    .simulation_details
+                    .expect("Simulation details not found")
+                    .return_data
+                    .map(|return_data| return_data.data)
+                    .unwrap_or_default();
+                return_data.resize(1, 0);
+                assert!(!bool::try_from_slice(&return_data).unwrap());
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_filled_events_track_filled_amount(test_state: &mut TestState) {
+                create_order_for_partial_fill(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                let part_amount = DEFAULT_ESCROW_AMOUNT / DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE;
+                let mut filled_amount = 0;
+                for fill_amount in [part_amount, 2 * part_amount] {
+                    let (_, _, transaction) =
+                        test_escrow_creation_for_partial_fill_data(test_state, fill_amount).await;
+                    let events = common_escrow_tests::simulate_tx_events::<_, _, OrderFilled>(
+                        test_state,
+                        transaction,
+                    )
+                    .await;
+                    filled_amount += fill_amount;
+
+                    assert_eq!(events.len(), 1);
+                    assert_eq!(events[0].order_hash, test_state.order_hash.to_bytes());
+                    assert_eq!(events[0].fill_amount, fill_amount);
+                    assert_eq!(events[0].filled_amount, filled_amount);
+                    assert_eq!(
+                        events[0].filled_amount + events[0].remaining_amount,
+                        DEFAULT_ESCROW_AMOUNT
+                    );
+
+                    test_escrow_creation_for_partial_fill(test_state, fill_amount).await;
+                }
+            }
+
             #[test_context(TestState)]
             #[tokio::test]
             async fn test_create_escrow_with_merkle_proof_and_leaf_validation_for_full_fill(
@@ -230,6 +351,52 @@ run_for_tokens!(
                     .expect_error(ProgramError::Custom(EscrowError::InvalidAmount.into()));
             }
 
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_create_escrow_fails_below_min_fill_amount(test_state: &mut TestState) {
+                let escrow_amount = DEFAULT_ESCROW_AMOUNT / DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE;
+                test_state.test_arguments.min_fill_amount = escrow_amount + 1;
+                create_order_for_partial_fill(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                let (_, _, transaction) =
+                    test_escrow_creation_for_partial_fill_data(test_state, escrow_amount).await;
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::FillAmountBelowMinimum.into(),
+                    ));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_create_escrow_allows_final_fill_below_min_fill_amount(
+                test_state: &mut TestState,
+            ) {
+                let escrow_amount = DEFAULT_ESCROW_AMOUNT / DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE * 3;
+                test_state.test_arguments.min_fill_amount = escrow_amount;
+                let (order, order_ata) = create_order_for_partial_fill(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                test_escrow_creation_for_partial_fill(test_state, escrow_amount).await;
+                // The remaining dust is below the floor but completes the order
+                test_escrow_creation_for_partial_fill(
+                    test_state,
+                    DEFAULT_ESCROW_AMOUNT - escrow_amount,
+                )
+                .await;
+
+                // Check that the order accounts have been closed.
+                let acc_lookup_result = test_state.client.get_account(order).await.unwrap();
+                assert!(acc_lookup_result.is_none());
+
+                let acc_lookup_result = test_state.client.get_account(order_ata).await.unwrap();
+                assert!(acc_lookup_result.is_none());
+            }
+
             #[test_context(TestState)]
             #[tokio::test]
             async fn test_create_escrow_fails_if_second_escrow_have_same_proof_index(
@@ -253,6 +420,100 @@ run_for_tokens!(
                     .expect_error(ProgramError::Custom(EscrowError::InvalidPartialFill.into()));
             }
 
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_create_escrow_fails_if_full_fill_uses_non_final_index(
+                test_state: &mut TestState,
+            ) {
+                create_order_for_partial_fill(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                // The secret of the last part instead of the one reserved for completing the order
+                let merkle_hashes = compute_merkle_leaves();
+                let index_to_validate = DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE as usize - 1;
+                test_state.test_arguments.escrow_amount = DEFAULT_ESCROW_AMOUNT;
+                test_state.test_arguments.merkle_proof = Some(MerkleProof {
+                    proof: get_proof(merkle_hashes.leaves.clone(), index_to_validate),
+                    index: index_to_validate as u64,
+                    hashed_secret: merkle_hashes.hashed_secrets[index_to_validate],
+                });
+                let (_, _, transaction) = create_escrow_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::FinalFillIndexMismatch.into(),
+                    ));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_create_escrow_fails_if_partial_fill_uses_final_index(
+                test_state: &mut TestState,
+            ) {
+                create_order_for_partial_fill(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                let merkle_hashes = compute_merkle_leaves();
+                let index_to_validate = DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE as usize;
+                test_state.test_arguments.escrow_amount = DEFAULT_ESCROW_AMOUNT - 1;
+                test_state.test_arguments.merkle_proof = Some(MerkleProof {
+                    proof: get_proof(merkle_hashes.leaves.clone(), index_to_validate),
+                    index: index_to_validate as u64,
+                    hashed_secret: merkle_hashes.hashed_secrets[index_to_validate],
+                });
+                let (_, _, transaction) = create_escrow_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::FinalFillIndexMismatch.into(),
+                    ));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_create_escrow_fails_with_oversized_merkle_proof(
+                test_state: &mut TestState,
+            ) {
+                // Commit to a root one level above the tree, so that a proof with an extra
+                // sibling would otherwise verify
+                let merkle_hashes = compute_merkle_leaves();
+                let extra_sibling = hashv(&[b"extra_sibling"]).0;
+                let root = hash_nodes(&get_root(merkle_hashes.leaves.clone()), &extra_sibling);
+                test_state.hashlock =
+                    prepare_hashlock_for_root(root, DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE);
+                test_state.test_arguments.allow_multiple_fills = true;
+                create_order(test_state).await;
+
+                test_state.test_arguments.escrow_amount =
+                    DEFAULT_ESCROW_AMOUNT / DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE;
+                let index_to_validate = get_index_for_escrow_amount(
+                    test_state,
+                    test_state.test_arguments.escrow_amount,
+                );
+                let mut proof_hashes = get_proof(merkle_hashes.leaves.clone(), index_to_validate);
+                proof_hashes.push(extra_sibling);
+                let proof = MerkleProof {
+                    proof: proof_hashes,
+                    index: index_to_validate as u64,
+                    hashed_secret: merkle_hashes.hashed_secrets[index_to_validate],
+                };
+                test_state.test_arguments.merkle_proof = Some(proof);
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (_, _, transaction) = create_escrow_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidMerkleProof.into()));
+            }
+
             #[test_context(TestState)]
             #[tokio::test]
             async fn test_create_escrow_fails_with_incorrect_merkle_root(
@@ -267,111 +528,663 @@ run_for_tokens!(
                     DEFAULT_ESCROW_AMOUNT / DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE;
                 let index_to_validate = get_index_for_escrow_amount(
                     test_state,
-                    test_state.test_arguments.escrow_amount,
+                    test_state.test_arguments.escrow_amount,
+                );
+                let hashed_secret = merkle_hashes.hashed_secrets[index_to_validate];
+                let proof_hashes = get_proof(merkle_hashes.leaves.clone(), index_to_validate);
+                let proof = MerkleProof {
+                    proof: proof_hashes,
+                    index: index_to_validate as u64,
+                    hashed_secret,
+                };
+                test_state.test_arguments.merkle_proof = Some(proof);
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (_, _, transaction) = create_escrow_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidMerkleProof.into()));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_create_escrow_fails_with_incorrect_secret_for_leaf(
+                test_state: &mut TestState,
+            ) {
+                create_order_for_partial_fill(test_state).await;
+
+                let merkle_hashes = compute_merkle_leaves();
+                test_state.test_arguments.escrow_amount =
+                    DEFAULT_ESCROW_AMOUNT / DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE * 2;
+                let index_to_validate = get_index_for_escrow_amount(
+                    test_state,
+                    test_state.test_arguments.escrow_amount,
+                );
+                let proof_hashes = get_proof(merkle_hashes.leaves.clone(), index_to_validate);
+
+                // Incorrect hashed_secret
+                let proof = MerkleProof {
+                    proof: proof_hashes,
+                    index: index_to_validate as u64,
+                    hashed_secret: merkle_hashes.hashed_secrets[index_to_validate + 1],
+                };
+                test_state.test_arguments.merkle_proof = Some(proof);
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (_, _, transaction) = create_escrow_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidMerkleProof.into()));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_create_escrow_fails_without_merkle_proof(test_state: &mut TestState) {
+                create_order_for_partial_fill(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                // test_state.test_arguments.merkle_proof is none
+                let (_, _, transaction) = create_escrow_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::InconsistentMerkleProofTrait.into(),
+                    ));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_create_escrow_fails_if_multiple_fills_are_false_and_merkle_proof_is_provided(
+                test_state: &mut TestState,
+            ) {
+                let merkle_hashes = compute_merkle_leaves();
+                let root = get_root(merkle_hashes.leaves.clone());
+                test_state.hashlock =
+                    prepare_hashlock_for_root(root, DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE);
+                // test_state.test_arguments.allow_multiple_fills is false;
+                create_order(test_state).await;
+
+                let index_to_validate = get_index_for_escrow_amount(
+                    test_state,
+                    test_state.test_arguments.escrow_amount,
+                ); // fill the full order
+                let hashed_secret = merkle_hashes.hashed_secrets[index_to_validate];
+                let proof_hashes = get_proof(merkle_hashes.leaves.clone(), index_to_validate);
+                let proof = MerkleProof {
+                    proof: proof_hashes,
+                    index: index_to_validate as u64,
+                    hashed_secret,
+                };
+                test_state.test_arguments.merkle_proof = Some(proof);
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (_, _, transaction) = create_escrow_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::InconsistentMerkleProofTrait.into(),
+                    ));
+            }
+        }
+
+        mod test_partial_fill_authorization {
+            use super::*;
+
+            const PART_AMOUNT: u64 = DEFAULT_ESCROW_AMOUNT / DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE;
+
+            async fn authorize_part(
+                test_state: &mut TestState,
+                order: &Pubkey,
+                part_index: u64,
+                resolver: &Pubkey,
+            ) {
+                let transaction = get_authorize_part_fill_tx(
+                    test_state,
+                    order,
+                    part_index,
+                    resolver,
+                    &test_state.maker_wallet.keypair,
+                );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success();
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_only_authorized_resolver_can_fill_part(test_state: &mut TestState) {
+                let (order, _) = create_order_for_partial_fill(test_state).await;
+                // Maker wallet acts as the second resolver
+                let authorized_resolver = test_state.maker_wallet.clone();
+                prepare_resolvers(
+                    test_state,
+                    &[
+                        test_state.taker_wallet.keypair.pubkey(),
+                        authorized_resolver.keypair.pubkey(),
+                    ],
+                )
+                .await;
+
+                let part_index = get_index_for_escrow_amount(test_state, PART_AMOUNT) as u64;
+                authorize_part(
+                    test_state,
+                    &order,
+                    part_index,
+                    &authorized_resolver.keypair.pubkey(),
+                )
+                .await;
+                test_state.test_arguments.part_authorization_indices = vec![part_index];
+
+                let (_, _, transaction) =
+                    test_escrow_creation_for_partial_fill_data(test_state, PART_AMOUNT).await;
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::UnauthorizedPartFill.into(),
+                    ));
+
+                test_state.taker_wallet = authorized_resolver;
+                test_escrow_creation_for_partial_fill(test_state, PART_AMOUNT).await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_fill_fails_for_authorized_intermediate_part(test_state: &mut TestState) {
+                let (order, _) = create_order_for_partial_fill(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                // The fill of two parts covers the first part, which is assigned to the maker
+                authorize_part(
+                    test_state,
+                    &order,
+                    0,
+                    &test_state.maker_wallet.keypair.pubkey(),
+                )
+                .await;
+                let part_index = get_index_for_escrow_amount(test_state, 2 * PART_AMOUNT) as u64;
+                test_state.test_arguments.part_authorization_indices = (0..=part_index).collect();
+
+                let (_, _, transaction) =
+                    test_escrow_creation_for_partial_fill_data(test_state, 2 * PART_AMOUNT).await;
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::UnauthorizedPartFill.into(),
+                    ));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_fill_fails_without_covered_part_authorizations(
+                test_state: &mut TestState,
+            ) {
+                let (order, _) = create_order_for_partial_fill(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                authorize_part(
+                    test_state,
+                    &order,
+                    0,
+                    &test_state.taker_wallet.keypair.pubkey(),
+                )
+                .await;
+                // Only the part of the proof is passed, not the first part the fill also covers
+                let part_index = get_index_for_escrow_amount(test_state, 2 * PART_AMOUNT) as u64;
+                test_state.test_arguments.part_authorization_indices = vec![part_index];
+
+                let (_, _, transaction) =
+                    test_escrow_creation_for_partial_fill_data(test_state, 2 * PART_AMOUNT).await;
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::MissingPartAuthorization.into(),
+                    ));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_fill_consumes_part_authorization(test_state: &mut TestState) {
+                let (order, _) = create_order_for_partial_fill(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                let part_index = get_index_for_escrow_amount(test_state, PART_AMOUNT) as u64;
+                authorize_part(
+                    test_state,
+                    &order,
+                    part_index,
+                    &test_state.taker_wallet.keypair.pubkey(),
+                )
+                .await;
+                let part_authorization = get_part_authorization_address(test_state, part_index);
+                let part_authorization_rent = test_state
+                    .client
+                    .get_balance(part_authorization)
+                    .await
+                    .unwrap();
+                test_state.test_arguments.part_authorization_indices = vec![part_index];
+
+                let (_, _, transaction) =
+                    test_escrow_creation_for_partial_fill_data(test_state, PART_AMOUNT).await;
+                test_state
+                    .expect_state_change(
+                        transaction,
+                        &[
+                            native_change(
+                                test_state.maker_wallet.keypair.pubkey(),
+                                part_authorization_rent,
+                            ),
+                            account_closure(part_authorization, true),
+                        ],
+                    )
+                    .await;
+
+                // With no part assigned anymore, the next fill passes no part authorizations
+                test_state.test_arguments.part_authorization_indices = vec![];
+                test_state.test_arguments.order_remaining_amount -= PART_AMOUNT;
+                test_escrow_creation_for_partial_fill(test_state, PART_AMOUNT).await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_authorization_does_not_restrict_other_parts(test_state: &mut TestState) {
+                let (order, _) = create_order_for_partial_fill(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                let part_index = get_index_for_escrow_amount(test_state, PART_AMOUNT) as u64;
+                authorize_part(
+                    test_state,
+                    &order,
+                    part_index + 1,
+                    &test_state.maker_wallet.keypair.pubkey(),
+                )
+                .await;
+                test_state.test_arguments.part_authorization_indices = vec![part_index];
+
+                test_escrow_creation_for_partial_fill(test_state, PART_AMOUNT).await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_revoked_part_can_be_filled_by_any_resolver(test_state: &mut TestState) {
+                let (order, _) = create_order_for_partial_fill(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                let part_index = get_index_for_escrow_amount(test_state, PART_AMOUNT) as u64;
+                authorize_part(
+                    test_state,
+                    &order,
+                    part_index,
+                    &test_state.maker_wallet.keypair.pubkey(),
+                )
+                .await;
+
+                let part_authorization = get_part_authorization_address(test_state, part_index);
+                let part_authorization_rent = test_state
+                    .client
+                    .get_balance(part_authorization)
+                    .await
+                    .unwrap();
+
+                let transaction = get_revoke_part_fill_tx(
+                    test_state,
+                    &order,
+                    part_index,
+                    &test_state.maker_wallet.keypair,
+                );
+                test_state
+                    .expect_state_change(
+                        transaction,
+                        &[
+                            native_change(
+                                test_state.maker_wallet.keypair.pubkey(),
+                                part_authorization_rent,
+                            ),
+                            account_closure(part_authorization, true),
+                        ],
+                    )
+                    .await;
+
+                // No part is assigned anymore, so the fill passes no part authorizations
+                test_escrow_creation_for_partial_fill(test_state, PART_AMOUNT).await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_order_closes_part_authorizations(test_state: &mut TestState) {
+                let (order, order_ata) = create_order_for_partial_fill(test_state).await;
+
+                authorize_part(
+                    test_state,
+                    &order,
+                    1,
+                    &test_state.taker_wallet.keypair.pubkey(),
+                )
+                .await;
+                let part_authorization = get_part_authorization_address(test_state, 1);
+                let part_authorization_rent = test_state
+                    .client
+                    .get_balance(part_authorization)
+                    .await
+                    .unwrap();
+
+                // Leaving out the part authorization would leak its rent
+                let transaction = get_cancel_order_tx(test_state, &order, &order_ata, None);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::MissingPartAuthorization.into(),
+                    ));
+
+                test_state.test_arguments.part_authorization_indices = vec![1];
+                let order_rent = test_state.client.get_balance(order).await.unwrap();
+                let order_ata_rent = test_state.client.get_balance(order_ata).await.unwrap();
+                let transaction = get_cancel_order_tx(test_state, &order, &order_ata, None);
+                test_state
+                    .expect_state_change(
+                        transaction,
+                        &[
+                            native_change(
+                                test_state.maker_wallet.keypair.pubkey(),
+                                order_rent + order_ata_rent + part_authorization_rent,
+                            ),
+                            account_closure(part_authorization, true),
+                            account_closure(order, true),
+                        ],
+                    )
+                    .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_authorize_part_fill_fails_for_non_creator(test_state: &mut TestState) {
+                let (order, _) = create_order_for_partial_fill(test_state).await;
+
+                let transaction = get_authorize_part_fill_tx(
+                    test_state,
+                    &order,
+                    0,
+                    &test_state.taker_wallet.keypair.pubkey(),
+                    &test_state.taker_wallet.keypair,
+                );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidAccount.into()));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_authorize_part_fill_fails_with_out_of_range_index(
+                test_state: &mut TestState,
+            ) {
+                let (order, _) = create_order_for_partial_fill(test_state).await;
+
+                let transaction = get_authorize_part_fill_tx(
+                    test_state,
+                    &order,
+                    DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE + 1,
+                    &test_state.taker_wallet.keypair.pubkey(),
+                    &test_state.maker_wallet.keypair,
+                );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidPartialFill.into()));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_authorize_part_fill_fails_for_filled_part(test_state: &mut TestState) {
+                let (order, _) = create_order_for_partial_fill(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                let part_index = get_index_for_escrow_amount(test_state, PART_AMOUNT) as u64;
+                test_escrow_creation_for_partial_fill(test_state, PART_AMOUNT).await;
+
+                let transaction = get_authorize_part_fill_tx(
+                    test_state,
+                    &order,
+                    part_index,
+                    &test_state.taker_wallet.keypair.pubkey(),
+                    &test_state.maker_wallet.keypair,
+                );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidPartialFill.into()));
+            }
+        }
+
+        mod test_increase_order {
+            use super::*;
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_increase_order_before_first_fill(test_state: &mut TestState) {
+                let (_, order_ata) = create_order_for_partial_fill(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                let additional_amount = DEFAULT_ESCROW_AMOUNT;
+                let (maker_ata, _) = find_user_ata(test_state);
+                let transaction = get_increase_order_tx(
+                    test_state,
+                    additional_amount,
+                    None,
+                    &test_state.maker_wallet.keypair,
+                );
+                test_state
+                    .expect_state_change(
+                        transaction,
+                        &[
+                            StateChange::Balance(BalanceChange::Token(
+                                maker_ata,
+                                -(additional_amount as i128),
+                            )),
+                            token_change(order_ata, additional_amount),
+                        ],
+                    )
+                    .await;
+
+                // Fills are validated against the increased order amount
+                test_state.test_arguments.order_amount += additional_amount;
+                test_state.test_arguments.order_remaining_amount += additional_amount;
+
+                let order_amount = test_state.test_arguments.order_amount;
+                let escrow_amount = order_amount / DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE * 3;
+                test_escrow_creation_for_partial_fill(test_state, escrow_amount).await;
+                test_escrow_creation_for_partial_fill(test_state, order_amount - escrow_amount)
+                    .await;
+
+                let acc_lookup_result = test_state.client.get_account(order_ata).await.unwrap();
+                assert!(acc_lookup_result.is_none());
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_fill_after_increase_order_keeps_dst_amount(test_state: &mut TestState) {
+                create_order_for_partial_fill(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                let additional_amount = test_state.test_arguments.order_amount;
+                let transaction = get_increase_order_tx(
+                    test_state,
+                    additional_amount,
+                    None,
+                    &test_state.maker_wallet.keypair,
+                );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success();
+                test_state.test_arguments.order_amount += additional_amount;
+                test_state.test_arguments.order_remaining_amount += additional_amount;
+
+                let order_amount = test_state.test_arguments.order_amount;
+                let escrow_amount = order_amount / DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE;
+                let (escrow, _) =
+                    test_escrow_creation_for_partial_fill(test_state, escrow_amount).await;
+
+                let escrow_account_data = test_state
+                    .client
+                    .get_account(escrow)
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .data;
+                let dst_amount = helpers_src::get_dst_amount(&escrow_account_data)
+                    .expect("Failed to read dst_amount from escrow account data");
+
+                // The destination amount of the order is spread over the increased amount
+                let expected = U256(test_state.test_arguments.dst_amount)
+                    .checked_mul(U256::from(escrow_amount))
+                    .unwrap()
+                    .checked_add(U256::from(order_amount - 1))
+                    .unwrap()
+                    .checked_div(U256::from(order_amount))
+                    .unwrap();
+                assert_eq!(U256(dst_amount), expected);
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_rescue_funds_for_order_after_increase_order(test_state: &mut TestState) {
+                let (order, _) = create_order_for_partial_fill(test_state).await;
+
+                let transaction = get_increase_order_tx(
+                    test_state,
+                    DEFAULT_ESCROW_AMOUNT,
+                    None,
+                    &test_state.maker_wallet.keypair,
+                );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success();
+
+                // The order is still derived from the arguments it was created with
+                helpers_src::rescue_all_tokens_from_order_and_close_ata(test_state, &order).await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_increase_order_fails_after_partial_fill(test_state: &mut TestState) {
+                create_order_for_partial_fill(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                let escrow_amount = DEFAULT_ESCROW_AMOUNT / DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE;
+                test_escrow_creation_for_partial_fill(test_state, escrow_amount).await;
+
+                let transaction = get_increase_order_tx(
+                    test_state,
+                    DEFAULT_ESCROW_AMOUNT,
+                    None,
+                    &test_state.maker_wallet.keypair,
+                );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::OrderPartiallyFilled.into(),
+                    ));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_increase_order_fails_without_multiple_fills(test_state: &mut TestState) {
+                create_order(test_state).await;
+
+                let transaction = get_increase_order_tx(
+                    test_state,
+                    DEFAULT_ESCROW_AMOUNT,
+                    None,
+                    &test_state.maker_wallet.keypair,
                 );
-                let hashed_secret = merkle_hashes.hashed_secrets[index_to_validate];
-                let proof_hashes = get_proof(merkle_hashes.leaves.clone(), index_to_validate);
-                let proof = MerkleProof {
-                    proof: proof_hashes,
-                    index: index_to_validate as u64,
-                    hashed_secret,
-                };
-                test_state.test_arguments.merkle_proof = Some(proof);
-                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                let (_, _, transaction) = create_escrow_data(test_state);
-
                 test_state
                     .client
                     .process_transaction(transaction)
                     .await
-                    .expect_error(ProgramError::Custom(EscrowError::InvalidMerkleProof.into()));
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidPartialFill.into()));
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_create_escrow_fails_with_incorrect_secret_for_leaf(
-                test_state: &mut TestState,
-            ) {
+            async fn test_increase_order_fails_for_non_creator(test_state: &mut TestState) {
                 create_order_for_partial_fill(test_state).await;
 
-                let merkle_hashes = compute_merkle_leaves();
-                test_state.test_arguments.escrow_amount =
-                    DEFAULT_ESCROW_AMOUNT / DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE * 2;
-                let index_to_validate = get_index_for_escrow_amount(
+                let (_, taker_ata) = find_user_ata(test_state);
+                let transaction = get_increase_order_tx(
                     test_state,
-                    test_state.test_arguments.escrow_amount,
+                    DEFAULT_ESCROW_AMOUNT,
+                    Some(&taker_ata),
+                    &test_state.taker_wallet.keypair,
                 );
-                let proof_hashes = get_proof(merkle_hashes.leaves.clone(), index_to_validate);
-
-                // Incorrect hashed_secret
-                let proof = MerkleProof {
-                    proof: proof_hashes,
-                    index: index_to_validate as u64,
-                    hashed_secret: merkle_hashes.hashed_secrets[index_to_validate + 1],
-                };
-                test_state.test_arguments.merkle_proof = Some(proof);
-                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                let (_, _, transaction) = create_escrow_data(test_state);
-
                 test_state
                     .client
                     .process_transaction(transaction)
                     .await
-                    .expect_error(ProgramError::Custom(EscrowError::InvalidMerkleProof.into()));
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidAccount.into()));
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_create_escrow_fails_without_merkle_proof(test_state: &mut TestState) {
+            async fn test_increase_order_fails_with_zero_amount(test_state: &mut TestState) {
                 create_order_for_partial_fill(test_state).await;
-                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-
-                // test_state.test_arguments.merkle_proof is none
-                let (_, _, transaction) = create_escrow_data(test_state);
 
+                let transaction =
+                    get_increase_order_tx(test_state, 0, None, &test_state.maker_wallet.keypair);
                 test_state
                     .client
                     .process_transaction(transaction)
                     .await
                     .expect_error(ProgramError::Custom(
-                        EscrowError::InconsistentMerkleProofTrait.into(),
+                        EscrowError::ZeroAmountOrDeposit.into(),
                     ));
             }
+        }
+
+        mod test_consumed_order {
+            use super::*;
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_create_escrow_fails_if_multiple_fills_are_false_and_merkle_proof_is_provided(
-                test_state: &mut TestState,
-            ) {
-                let merkle_hashes = compute_merkle_leaves();
-                let root = get_root(merkle_hashes.leaves.clone());
-                test_state.hashlock =
-                    prepare_hashlock_for_root(root, DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE);
-                // test_state.test_arguments.allow_multiple_fills is false;
-                create_order(test_state).await;
-
-                let index_to_validate = get_index_for_escrow_amount(
-                    test_state,
-                    test_state.test_arguments.escrow_amount,
-                ); // fill the full order
-                let hashed_secret = merkle_hashes.hashed_secrets[index_to_validate];
-                let proof_hashes = get_proof(merkle_hashes.leaves.clone(), index_to_validate);
-                let proof = MerkleProof {
-                    proof: proof_hashes,
-                    index: index_to_validate as u64,
-                    hashed_secret,
-                };
-                test_state.test_arguments.merkle_proof = Some(proof);
+            async fn test_escrow_creation_fails_for_consumed_order(test_state: &mut TestState) {
+                create_order_for_partial_fill(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                let (_, _, transaction) = create_escrow_data(test_state);
+                set_order_consumed(test_state).await;
 
+                let escrow_amount = DEFAULT_ESCROW_AMOUNT / DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE;
+                let (_, _, transaction) =
+                    test_escrow_creation_for_partial_fill_data(test_state, escrow_amount).await;
                 test_state
                     .client
                     .process_transaction(transaction)
                     .await
-                    .expect_error(ProgramError::Custom(
-                        EscrowError::InconsistentMerkleProofTrait.into(),
-                    ));
+                    .expect_error(ProgramError::Custom(EscrowError::OrderFullyFilled.into()));
             }
         }
 
@@ -420,6 +1233,155 @@ run_for_tokens!(
                 helpers_src::test_withdraw_escrow(test_state, &escrow_2, &escrow_ata_2).await;
             }
 
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_withdraw_many_from_partial_order(test_state: &mut TestState) {
+                create_order_for_partial_fill(test_state).await;
+
+                let escrow_amount = DEFAULT_ESCROW_AMOUNT / DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                let secret_index = get_index_for_escrow_amount(test_state, escrow_amount);
+                let (escrow, escrow_ata) =
+                    test_escrow_creation_for_partial_fill(test_state, escrow_amount).await;
+
+                let secret_index_2 = get_index_for_escrow_amount(test_state, escrow_amount);
+                let (escrow_2, escrow_ata_2) =
+                    test_escrow_creation_for_partial_fill(test_state, escrow_amount).await;
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .src_timelocks
+                        .get(Stage::SrcWithdrawal)
+                        .unwrap(),
+                );
+
+                let secrets = &test_state.test_arguments.partial_secrets;
+                let transaction = get_withdraw_many_tx(
+                    test_state,
+                    &[
+                        (escrow, escrow_ata, secrets[secret_index]),
+                        (escrow_2, escrow_ata_2, secrets[secret_index_2]),
+                    ],
+                );
+
+                let token_account_rent = get_min_rent_for_size(
+                    &mut test_state.client,
+                    <TestState as HasTokenVariant>::Token::get_token_account_size(),
+                )
+                .await;
+                let escrow_rent =
+                    get_min_rent_for_size(&mut test_state.client, DEFAULT_SRC_ESCROW_SIZE).await;
+                let (_, taker_ata) = find_user_ata(test_state);
+
+                test_state
+                    .expect_state_change(
+                        transaction,
+                        &[
+                            native_change(
+                                test_state.taker_wallet.keypair.pubkey(),
+                                2 * (token_account_rent + escrow_rent),
+                            ),
+                            token_change(taker_ata, 2 * escrow_amount),
+                            account_closure(escrow, true),
+                            account_closure(escrow_ata, true),
+                            account_closure(escrow_2, true),
+                            account_closure(escrow_ata_2, true),
+                        ],
+                    )
+                    .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_withdraw_many_fails_atomically_with_invalid_secret(
+                test_state: &mut TestState,
+            ) {
+                create_order_for_partial_fill(test_state).await;
+
+                let escrow_amount = DEFAULT_ESCROW_AMOUNT / DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                let secret_index = get_index_for_escrow_amount(test_state, escrow_amount);
+                let (escrow, escrow_ata) =
+                    test_escrow_creation_for_partial_fill(test_state, escrow_amount).await;
+                let (escrow_2, escrow_ata_2) =
+                    test_escrow_creation_for_partial_fill(test_state, escrow_amount).await;
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .src_timelocks
+                        .get(Stage::SrcWithdrawal)
+                        .unwrap(),
+                );
+
+                let transaction = get_withdraw_many_tx(
+                    test_state,
+                    &[
+                        (
+                            escrow,
+                            escrow_ata,
+                            test_state.test_arguments.partial_secrets[secret_index],
+                        ),
+                        (escrow_2, escrow_ata_2, [0u8; 32]), // Invalid secret
+                    ],
+                );
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidSecret.into()));
+
+                // The valid withdrawal is reverted along with the invalid one
+                let acc_lookup_result = test_state.client.get_account(escrow).await.unwrap();
+                assert!(acc_lookup_result.is_some());
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_withdraw_many_fails_with_too_many_escrows(test_state: &mut TestState) {
+                create_order_for_partial_fill(test_state).await;
+
+                let escrow_amount = DEFAULT_ESCROW_AMOUNT / DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                let secret_index = get_index_for_escrow_amount(test_state, escrow_amount);
+                let (escrow, escrow_ata) =
+                    test_escrow_creation_for_partial_fill(test_state, escrow_amount).await;
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .src_timelocks
+                        .get(Stage::SrcWithdrawal)
+                        .unwrap(),
+                );
+
+                let escrows = vec![
+                    (
+                        escrow,
+                        escrow_ata,
+                        test_state.test_arguments.partial_secrets[secret_index],
+                    );
+                    MAX_BATCH_WITHDRAWALS + 1
+                ];
+                let transaction = get_withdraw_many_tx(test_state, &escrows);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::InvalidWithdrawalBatch.into(),
+                    ));
+            }
+
             #[test_context(TestState)]
             #[tokio::test]
             async fn test_cannot_withdraw_from_partial_order_with_invalid_secret(
@@ -830,5 +1792,319 @@ run_for_tokens!(
                     .expect_error(ProgramError::Custom(ErrorCode::ConstraintTokenOwner.into()))
             }
         }
+
+        mod test_partial_fill_rate_limit {
+            use super::*;
+
+            const PART_AMOUNT: u64 = DEFAULT_ESCROW_AMOUNT / DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE;
+
+            // Creates a multiple fill order and registers the taker with the given fill limit
+            async fn prepare_rate_limited_order(
+                test_state: &mut TestState,
+                max_window_volume: u64,
+            ) {
+                // Keep the order fillable for longer than a fill window
+                test_state.test_arguments.expiration_time =
+                    test_state.init_timestamp + 2 * FILL_WINDOW_DURATION as u32;
+                create_order_for_partial_fill(test_state).await;
+
+                let taker = test_state.taker_wallet.keypair.pubkey();
+                prepare_resolvers(test_state, &[taker]).await;
+                set_max_window_volume(test_state, taker, max_window_volume).await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_fills_accumulate_within_window(test_state: &mut TestState) {
+                prepare_rate_limited_order(test_state, DEFAULT_ESCROW_AMOUNT).await;
+
+                test_escrow_creation_for_partial_fill(test_state, PART_AMOUNT).await;
+                test_escrow_creation_for_partial_fill(test_state, PART_AMOUNT).await;
+
+                let taker = test_state.taker_wallet.keypair.pubkey();
+                let resolver_access = get_resolver_access(test_state, &taker).await;
+                assert_eq!(resolver_access.window_volume, 2 * PART_AMOUNT);
+                assert_eq!(
+                    resolver_access.window_start,
+                    test_state.init_timestamp as i64
+                );
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_fill_fails_above_max_window_volume(test_state: &mut TestState) {
+                prepare_rate_limited_order(test_state, 2 * PART_AMOUNT).await;
+
+                test_escrow_creation_for_partial_fill(test_state, PART_AMOUNT).await;
+
+                let (_, _, transaction) =
+                    test_escrow_creation_for_partial_fill_data(test_state, 2 * PART_AMOUNT).await;
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        WhitelistError::ResolverRateLimited.into(),
+                    ));
+
+                // Filling up to the limit is allowed
+                test_escrow_creation_for_partial_fill(test_state, PART_AMOUNT).await;
+
+                let taker = test_state.taker_wallet.keypair.pubkey();
+                let resolver_access = get_resolver_access(test_state, &taker).await;
+                assert_eq!(resolver_access.window_volume, 2 * PART_AMOUNT);
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_window_volume_resets_after_window(test_state: &mut TestState) {
+                prepare_rate_limited_order(test_state, PART_AMOUNT).await;
+
+                test_escrow_creation_for_partial_fill(test_state, PART_AMOUNT).await;
+
+                let (_, _, transaction) =
+                    test_escrow_creation_for_partial_fill_data(test_state, PART_AMOUNT).await;
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        WhitelistError::ResolverRateLimited.into(),
+                    ));
+
+                let new_window_start = test_state.init_timestamp + FILL_WINDOW_DURATION as u32;
+                set_time(&mut test_state.context, new_window_start);
+                // Update the last blockhash to execute the next identical transaction
+                test_state.context.last_blockhash =
+                    test_state.client.get_latest_blockhash().await.unwrap();
+                test_escrow_creation_for_partial_fill(test_state, PART_AMOUNT).await;
+
+                let taker = test_state.taker_wallet.keypair.pubkey();
+                let resolver_access = get_resolver_access(test_state, &taker).await;
+                assert_eq!(resolver_access.window_volume, PART_AMOUNT);
+                assert_eq!(resolver_access.window_start, new_window_start as i64);
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_zero_max_window_volume_disables_limit(test_state: &mut TestState) {
+                prepare_rate_limited_order(test_state, 0).await;
+
+                test_escrow_creation_for_partial_fill(test_state, DEFAULT_ESCROW_AMOUNT).await;
+
+                let taker = test_state.taker_wallet.keypair.pubkey();
+                let resolver_access = get_resolver_access(test_state, &taker).await;
+                assert_eq!(resolver_access.window_volume, 0);
+            }
+        }
     }
 );
+
+mod test_partial_fill_validation {
+    use anchor_lang::prelude::ProgramError;
+    use cross_chain_escrow_src::{
+        get_next_part_index, get_simple_fill_amount, is_valid_partial_fill,
+    };
+
+    const PARTS_AMOUNT: u64 = u16::MAX as u64;
+    // u64::MAX is divisible by u16::MAX, so every part is of the same size
+    const PART_AMOUNT: u64 = u64::MAX / PARTS_AMOUNT;
+
+    #[test]
+    fn test_first_fill_of_several_parts_with_max_order_amount() {
+        assert!(
+            is_valid_partial_fill(2 * PART_AMOUNT, u64::MAX, u64::MAX, PARTS_AMOUNT, 1).unwrap()
+        );
+        assert!(
+            !is_valid_partial_fill(2 * PART_AMOUNT, u64::MAX, u64::MAX, PARTS_AMOUNT, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_second_fill_with_max_order_amount() {
+        let remaining_amount = u64::MAX - PART_AMOUNT;
+        assert!(
+            is_valid_partial_fill(PART_AMOUNT, remaining_amount, u64::MAX, PARTS_AMOUNT, 1)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_final_fill_with_max_order_amount() {
+        let remaining_amount = u64::MAX - PART_AMOUNT;
+        assert!(is_valid_partial_fill(
+            remaining_amount,
+            remaining_amount,
+            u64::MAX,
+            PARTS_AMOUNT,
+            PARTS_AMOUNT,
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn test_partial_fill_fails_with_remaining_amount_above_order_amount() {
+        assert_eq!(
+            is_valid_partial_fill(1, u64::MAX, u64::MAX - 1, PARTS_AMOUNT, 0).unwrap_err(),
+            ProgramError::ArithmeticOverflow.into()
+        );
+    }
+
+    #[test]
+    fn test_partial_fill_fails_with_zero_amount_on_first_fill() {
+        assert_eq!(
+            is_valid_partial_fill(0, u64::MAX, u64::MAX, PARTS_AMOUNT, 0).unwrap_err(),
+            ProgramError::ArithmeticOverflow.into()
+        );
+    }
+
+    #[test]
+    fn test_next_part_index_matches_partial_fill_validation() {
+        assert_eq!(
+            get_next_part_index(u64::MAX, u64::MAX, PARTS_AMOUNT).unwrap(),
+            0
+        );
+
+        let remaining_amount = u64::MAX - PART_AMOUNT;
+        let next_index = get_next_part_index(remaining_amount, u64::MAX, PARTS_AMOUNT).unwrap();
+        assert_eq!(next_index, 1);
+        assert!(is_valid_partial_fill(
+            PART_AMOUNT,
+            remaining_amount,
+            u64::MAX,
+            PARTS_AMOUNT,
+            next_index
+        )
+        .unwrap());
+        assert!(!is_valid_partial_fill(
+            PART_AMOUNT,
+            remaining_amount,
+            u64::MAX,
+            PARTS_AMOUNT,
+            next_index - 1
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn test_simple_fill_amount_takes_remainder_on_last_fill() {
+        assert_eq!(get_simple_fill_amount(u64::MAX, u64::MAX, 2), u64::MAX / 2);
+        assert_eq!(
+            get_simple_fill_amount(u64::MAX - u64::MAX / 2, u64::MAX, 2),
+            u64::MAX - u64::MAX / 2
+        );
+        assert_eq!(get_simple_fill_amount(10, 10, 3), 3);
+        assert_eq!(get_simple_fill_amount(7, 10, 3), 3);
+        assert_eq!(get_simple_fill_amount(4, 10, 3), 4);
+    }
+}
+
+mod test_merkle_tree {
+    use common::constants::{HASH_ALGO_KECCAK, HASH_ALGO_SHA256, MAX_PARTS};
+    use common::error::EscrowError;
+    use cross_chain_escrow_src::merkle_tree::{
+        decode_parts_amount, get_max_proof_len, MerkleProof,
+    };
+    use solana_program::{hash, keccak::hashv};
+
+    #[test]
+    fn test_process_proof_fails_with_empty_proof() {
+        let proof = MerkleProof {
+            proof: vec![],
+            index: 0,
+            hashed_secret: [0x11; 32],
+        };
+
+        assert_eq!(
+            proof.process_proof(HASH_ALGO_KECCAK).unwrap_err(),
+            EscrowError::EmptyMerkleProof.into()
+        );
+    }
+
+    #[test]
+    fn test_process_proof_with_single_sibling() {
+        let hashed_secret = [0x11; 32];
+        let sibling = [0x22; 32];
+        let leaf = hashv(&[&0u64.to_be_bytes(), &hashed_secret]).0;
+
+        let proof = MerkleProof {
+            proof: vec![sibling],
+            index: 0,
+            hashed_secret,
+        };
+
+        let expected_root = hashv(&[
+            std::cmp::min(&leaf, &sibling),
+            std::cmp::max(&leaf, &sibling),
+        ])
+        .0;
+        assert_eq!(
+            proof.process_proof(HASH_ALGO_KECCAK).unwrap(),
+            expected_root
+        );
+    }
+
+    #[test]
+    fn test_process_proof_with_sha256() {
+        let hashed_secret = [0x11; 32];
+        let sibling = [0x22; 32];
+        let leaf = hash::hashv(&[&0u64.to_be_bytes(), &hashed_secret]).to_bytes();
+
+        let proof = MerkleProof {
+            proof: vec![sibling],
+            index: 0,
+            hashed_secret,
+        };
+
+        let expected_root = hash::hashv(&[
+            std::cmp::min(&leaf, &sibling),
+            std::cmp::max(&leaf, &sibling),
+        ])
+        .to_bytes();
+        assert_eq!(
+            proof.process_proof(HASH_ALGO_SHA256).unwrap(),
+            expected_root
+        );
+        assert_ne!(
+            proof.process_proof(HASH_ALGO_KECCAK).unwrap(),
+            expected_root
+        );
+    }
+
+    #[test]
+    fn test_max_proof_len_is_tree_depth() {
+        // 4 secrets fill a tree of depth 2, a 5th one needs another level
+        assert_eq!(get_max_proof_len(3), 2);
+        assert_eq!(get_max_proof_len(4), 3);
+        assert_eq!(get_max_proof_len(MAX_PARTS - 1), 10);
+        assert_eq!(get_max_proof_len(MAX_PARTS), 11);
+    }
+
+    #[test]
+    fn test_decode_parts_amount_from_hashlock_prefix() {
+        let mut hashlock = [0xff; 32];
+        hashlock[..2].copy_from_slice(&MAX_PARTS.to_be_bytes());
+        assert_eq!(decode_parts_amount(&hashlock, true).unwrap(), MAX_PARTS);
+    }
+
+    #[test]
+    fn test_decode_parts_amount_fails_for_single_fill_order() {
+        // The prefix decodes to a valid parts count, but belongs to the secret hash here
+        let mut hashlock = [0xff; 32];
+        hashlock[..2].copy_from_slice(&2u16.to_be_bytes());
+        assert_eq!(
+            decode_parts_amount(&hashlock, false).unwrap_err(),
+            EscrowError::InconsistentFillMode.into()
+        );
+    }
+}
+
+mod test_fill_authority {
+    #[test]
+    fn test_fill_authority_program_matches_escrow_src() {
+        assert_eq!(
+            whitelist::ESCROW_SRC_PROGRAM_ID,
+            cross_chain_escrow_src::id()
+        );
+    }
+}
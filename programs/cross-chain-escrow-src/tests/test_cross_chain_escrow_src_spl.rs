@@ -1,17 +1,41 @@
-use anchor_lang::{error::ErrorCode, prelude::ProgramError};
-use common::{error::EscrowError, timelocks::Stage};
+use anchor_lang::{error::ErrorCode, prelude::ProgramError, AnchorDeserialize, AnchorSerialize};
+use common::{
+    constants::{HASH_ALGO_SHA256, MAX_PARTS},
+    error::EscrowError,
+    timelocks::{Stage, Timelocks},
+};
+use common_tests::cpi_wrapper::wrap_in_cpi;
 use common_tests::helpers::*;
 use common_tests::run_for_tokens;
 use common_tests::src_program::{
-    create_order, create_order_data, create_public_escrow_cancel_tx,
-    get_cancel_order_by_resolver_tx, get_cancel_order_tx, get_create_order_tx, get_order_addresses,
-    get_rescue_funds_from_order_tx, SrcProgram,
+    create_order, create_order_data, create_order_idempotent, create_public_escrow_cancel_tx,
+    enable_tvl_tracking, get_cancel_order_by_resolver_ix, get_cancel_order_by_resolver_tx,
+    get_cancel_order_tx, get_create_and_fill_tx, get_create_escrow_simple_tx, get_create_order_ix,
+    get_create_order_tx, get_decode_timelocks_tx, get_extend_order_tx, get_order_addresses,
+    get_order_fill_state_tx, get_order_hash, get_order_rent_tx,
+    get_preview_cancellation_premium_tx, get_preview_dst_amount_tx, get_reconcile_escrow_tx,
+    get_rescue_funds_from_order_tx, get_reveal_secret_tx, get_sweep_dust_tx,
+    get_transfer_order_ownership_tx, get_tvl_total_locked, get_update_cancellation_auction_tx,
+    SrcProgram,
 };
 use common_tests::tests as common_escrow_tests;
-use common_tests::whitelist::prepare_resolvers;
+use common_tests::whitelist::{
+    block_mint, prepare_resolvers, set_require_top_level, unblock_mint, unlist_token_program,
+};
+use cross_chain_escrow_src::auction::MAX_AUCTION_LEAD;
+use cross_chain_escrow_src::{
+    EscrowCancelled, EscrowCreated, EscrowPublicCancelled, EscrowSrc, EscrowStatus, OrderCancelled,
+    OrderCancelledByResolver, PublicCancelSettled, SecretRevealed,
+};
+use solana_program::keccak::hashv;
+use solana_program::pubkey::Pubkey;
+use solana_program::system_program;
 use solana_program_test::tokio;
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::instruction::AccountMeta;
 use solana_sdk::signature::Signer;
 use solana_sdk::signer::keypair::Keypair;
+use solana_sdk::transaction::Transaction;
 use test_context::test_context;
 
 use primitive_types::U256;
@@ -82,6 +106,23 @@ run_for_tokens!(
                     ));
             }
 
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_creation_fails_with_zero_dst_safety_deposit(
+                test_state: &mut TestState,
+            ) {
+                test_state.test_arguments.dst_chain_params.safety_deposit = 0;
+                let (_, _, transaction) = create_order_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::ZeroAmountOrDeposit.into(),
+                    ));
+            }
+
             #[test_context(TestState)]
             #[tokio::test]
             async fn test_order_creation_fails_with_insufficient_funds(test_state: &mut TestState) {
@@ -131,6 +172,17 @@ run_for_tokens!(
                     ));
             }
 
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_creation_with_incremented_salt(test_state: &mut TestState) {
+                let (order, _) = create_order(test_state).await;
+
+                test_state.test_arguments.salt += 1;
+                let (next_order, _) = create_order(test_state).await;
+
+                assert_ne!(order, next_order);
+            }
+
             #[test_context(TestState)]
             #[tokio::test]
             async fn test_order_creation_fails_if_after_expiration(test_state: &mut TestState) {
@@ -151,6 +203,169 @@ run_for_tokens!(
                 assert!(acc_lookup_result.is_none());
             }
 
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_creation_fails_with_invalid_hash_algo(test_state: &mut TestState) {
+                test_state.test_arguments.hash_algo = HASH_ALGO_SHA256 + 1;
+                let (order, _, tx) = create_order_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(tx)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidHashAlgo.into()));
+
+                let acc_lookup_result = test_state.client.get_account(order).await.unwrap();
+                assert!(acc_lookup_result.is_none());
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_creation_fails_if_expiring_before_cancellation(
+                test_state: &mut TestState,
+            ) {
+                test_state.test_arguments.expiration_time = test_state
+                    .test_arguments
+                    .src_timelocks
+                    .get(Stage::SrcCancellation)
+                    .unwrap()
+                    - 1;
+                let (order, _, tx) = create_order_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(tx)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::ExpirationBeforeCancellation.into(),
+                    ));
+
+                let acc_lookup_result = test_state.client.get_account(order).await.unwrap();
+                assert!(acc_lookup_result.is_none());
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_creation_expiring_after_cancellation(test_state: &mut TestState) {
+                test_state.test_arguments.expiration_time = test_state
+                    .test_arguments
+                    .src_timelocks
+                    .get(Stage::SrcCancellation)
+                    .unwrap()
+                    + DEFAULT_PERIOD_DURATION;
+                let (order, _) = create_order(test_state).await;
+
+                let acc_lookup_result = test_state.client.get_account(order).await.unwrap();
+                assert!(acc_lookup_result.is_some());
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_creation_fails_with_non_monotonic_timelocks(
+                test_state: &mut TestState,
+            ) {
+                // Public withdrawal starts after cancellation
+                test_state.test_arguments.src_timelocks = init_timelocks(
+                    DEFAULT_PERIOD_DURATION,
+                    DEFAULT_PERIOD_DURATION * 3,
+                    DEFAULT_PERIOD_DURATION * 2,
+                    DEFAULT_PERIOD_DURATION * 4,
+                    DEFAULT_PERIOD_DURATION,
+                    DEFAULT_PERIOD_DURATION * 2,
+                    DEFAULT_PERIOD_DURATION * 3,
+                    0,
+                );
+                let (order, order_ata, tx) = create_order_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(tx)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::NonMonotonicTimelocks.into(),
+                    ));
+
+                // Check that the order accounts have not been created.
+                let acc_lookup_result = test_state.client.get_account(order).await.unwrap();
+                assert!(acc_lookup_result.is_none());
+
+                let acc_lookup_result = test_state.client.get_account(order_ata).await.unwrap();
+                assert!(acc_lookup_result.is_none());
+            }
+
+            // Overflowing stage durations used to surface only on withdrawal or cancellation,
+            // leaving the funds locked until the rescue delay
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_creation_fails_if_stage_duration_overflows(
+                test_state: &mut TestState,
+            ) {
+                test_state.test_arguments.src_timelocks =
+                    init_timelocks(0, u32::MAX - 2, u32::MAX - 1, u32::MAX, 0, 0, 0, 0);
+                let (order, order_ata, tx) = create_order_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(tx)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::TimelockDurationTooLarge.into(),
+                    ));
+
+                let acc_lookup_result = test_state.client.get_account(order).await.unwrap();
+                assert!(acc_lookup_result.is_none());
+
+                let acc_lookup_result = test_state.client.get_account(order_ata).await.unwrap();
+                assert!(acc_lookup_result.is_none());
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_creation_fails_if_public_cancellation_duration_overflows(
+                test_state: &mut TestState,
+            ) {
+                // Only the last stage overflows, the cancellation is still reachable
+                test_state.test_arguments.src_timelocks =
+                    init_timelocks(0, 1, 2, u32::MAX, 0, 0, 0, 0);
+                let (_, _, tx) = create_order_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(tx)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::TimelockDurationTooLarge.into(),
+                    ));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_creation_fails_with_unsupported_token_program(
+                test_state: &mut TestState,
+            ) {
+                unlist_token_program(
+                    test_state,
+                    &<TestState as HasTokenVariant>::Token::get_token_program_id(),
+                )
+                .await;
+                let (order, order_ata, tx) = create_order_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(tx)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::UnsupportedTokenProgram.into(),
+                    ));
+
+                // Check that the order accounts have not been created.
+                let acc_lookup_result = test_state.client.get_account(order).await.unwrap();
+                assert!(acc_lookup_result.is_none());
+
+                let acc_lookup_result = test_state.client.get_account(order_ata).await.unwrap();
+                assert!(acc_lookup_result.is_none());
+            }
+
             #[test_context(TestState)]
             #[tokio::test]
             async fn test_order_creation_fails_if_fee_is_greater_than_lamport_balance(
@@ -196,6 +411,86 @@ run_for_tokens!(
                     .await
                     .expect_error(ProgramError::Custom(EscrowError::InvalidPartsAmount.into()));
             }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_creation_with_max_parts(test_state: &mut TestState) {
+                test_state.test_arguments.allow_multiple_fills = true;
+                test_state.hashlock =
+                    prepare_hashlock_for_root(test_state.hashlock.to_bytes(), MAX_PARTS as u64);
+                let (_, _, transaction) = create_order_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success();
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_creation_fails_with_parts_above_max(test_state: &mut TestState) {
+                test_state.test_arguments.allow_multiple_fills = true;
+                test_state.hashlock =
+                    prepare_hashlock_for_root(test_state.hashlock.to_bytes(), MAX_PARTS as u64 + 1);
+                let (_, _, transaction) = create_order_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidPartsAmount.into()));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_creation_fails_with_third_party_creator_ata(
+                test_state: &mut TestState,
+            ) {
+                // Pass the taker's token account as the source of the maker's funds
+                test_state.maker_wallet.token_account = test_state.taker_wallet.token_account;
+                test_state.maker_wallet.native_token_account =
+                    test_state.taker_wallet.native_token_account;
+                let (_, _, transaction) = create_order_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(ErrorCode::ConstraintTokenOwner.into()));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_creation_fails_with_non_mint_account(test_state: &mut TestState) {
+                // A token account is owned by the token program but doesn't hold a mint, so it is
+                // rejected on deserialization before the order accounts are created
+                test_state.token = test_state.maker_wallet.token_account;
+                let (_, _, transaction) = create_order_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::InvalidAccountData);
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_rent_matches_order_creation(test_state: &mut TestState) {
+                let transaction = get_order_rent_tx(test_state);
+                let mut return_data =
+                    common_escrow_tests::simulate_tx_return_data(test_state, transaction).await;
+                // Trailing zero bytes of the return data are trimmed
+                return_data.resize(8, 0);
+                let order_rent = u64::try_from_slice(&return_data).unwrap();
+
+                let (order, _) = create_order(test_state).await;
+                assert_eq!(
+                    test_state.client.get_balance(order).await.unwrap(),
+                    order_rent
+                );
+            }
         }
 
         mod test_escrow_creation {
@@ -219,50 +514,314 @@ run_for_tokens!(
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_escrow_creation_with_pre_existing_escrow_ata(test_state: &mut TestState) {
+            async fn test_escrow_creation_simple_matches_general_path(test_state: &mut TestState) {
                 create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                let (escrow_pda, _) = get_escrow_addresses(test_state);
 
-                let _escrow_ata =
-                    <TestState as HasTokenVariant>::Token::initialize_spl_associated_account(
-                        &mut test_state.context,
-                        &test_state.token,
-                        &escrow_pda,
-                    )
-                    .await;
-                common_escrow_tests::test_escrow_creation(test_state).await;
-            }
+                let (order, order_ata) = get_order_addresses(test_state);
+                let mut order_accounts = vec![];
+                for address in [order, order_ata] {
+                    let account = test_state
+                        .client
+                        .get_account(address)
+                        .await
+                        .unwrap()
+                        .unwrap();
+                    order_accounts.push((address, account));
+                }
 
-            #[test_context(TestState)]
-            #[tokio::test]
-            async fn test_escrow_creation_with_dutch_auction_params(test_state: &mut TestState) {
-                test_state.test_arguments.dutch_auction_data =
-                    cross_chain_escrow_src::AuctionData {
-                        start_time: test_state.init_timestamp - AUCTION_START_OFFSET,
-                        duration: AUCTION_DURATION,
-                        initial_rate_bump: INITIAL_RATE_BUMP.into(),
-                        points_and_time_deltas: vec![
-                            cross_chain_escrow_src::auction::PointAndTimeDelta {
-                                rate_bump: INTERMEDIATE_RATE_BUMP.into(),
-                                time_delta: INTERMEDIATE_TIME_DELTA,
-                            },
-                        ],
-                    };
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+                let general_escrow = test_state
+                    .client
+                    .get_account(escrow)
+                    .await
+                    .unwrap()
+                    .unwrap();
 
-                create_order(test_state).await;
-                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                common_escrow_tests::test_escrow_creation(test_state).await
+                // Reopen the filled order and remove the escrow to fill the order again
+                for (address, account) in order_accounts {
+                    test_state.context.set_account(&address, &account.into());
+                }
+                for address in [escrow, escrow_ata] {
+                    test_state
+                        .context
+                        .set_account(&address, &AccountSharedData::default());
+                }
+
+                let transaction = get_create_escrow_simple_tx(test_state, &escrow, &escrow_ata);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success();
+
+                let simple_escrow = test_state
+                    .client
+                    .get_account(escrow)
+                    .await
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(simple_escrow.data, general_escrow.data);
+                assert_eq!(simple_escrow.lamports, general_escrow.lamports);
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_escrow_creation_with_excess_tokens(test_state: &mut TestState) {
-                type S = <TestState as HasTokenVariant>::Token;
-
+            async fn test_escrow_creation_simple_fails_for_multiple_fills(
+                test_state: &mut TestState,
+            ) {
+                create_order_for_partial_fill(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                let (_, order_ata) = create_order(test_state).await;
-                let excess_amount = 1000;
+
+                let (escrow, escrow_ata) = get_escrow_addresses(test_state);
+                let transaction = get_create_escrow_simple_tx(test_state, &escrow, &escrow_ata);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::InconsistentMerkleProofTrait.into(),
+                    ));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_with_affiliate(test_state: &mut TestState) {
+                let affiliate = Keypair::new().pubkey();
+                test_state.test_arguments.affiliate = Some(affiliate);
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                let (escrow, _, transaction) = create_escrow_data(test_state);
+                let events = common_escrow_tests::simulate_tx_events::<_, _, EscrowCreated>(
+                    test_state,
+                    transaction.clone(),
+                )
+                .await;
+                assert_eq!(events.len(), 1);
+                assert_eq!(events[0].order_hash, test_state.order_hash.to_bytes());
+                assert_eq!(events[0].affiliate, affiliate);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success();
+                assert_eq!(
+                    get_escrow_src(test_state, &escrow).await.affiliate,
+                    affiliate
+                );
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_create_and_fill_matches_two_step_creation(test_state: &mut TestState) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                create_order(test_state).await;
+                let (two_step_escrow, two_step_escrow_ata) = create_escrow(test_state).await;
+
+                // Another salt gives a distinct order, so both paths can run in one test
+                test_state.test_arguments.salt += 1;
+                test_state.order_hash = get_order_hash(test_state);
+                let (order, order_ata) = get_order_addresses(test_state);
+                let (escrow, escrow_ata) = get_escrow_addresses(test_state);
+                let transaction =
+                    get_create_and_fill_tx(test_state, &order, &order_ata, &escrow, &escrow_ata);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success();
+
+                let two_step_escrow_src = get_escrow_src(test_state, &two_step_escrow).await;
+                let escrow_src = get_escrow_src(test_state, &escrow).await;
+                assert_eq!(escrow_src.order_hash, test_state.order_hash.to_bytes());
+                // Apart from the order hash, both paths produce the same escrow
+                assert_eq!(
+                    EscrowSrc {
+                        order_hash: escrow_src.order_hash,
+                        ..two_step_escrow_src
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                    escrow_src.try_to_vec().unwrap()
+                );
+                let two_step_balance =
+                    get_token_balance(&mut test_state.context, &two_step_escrow_ata).await;
+                assert_eq!(
+                    get_token_balance(&mut test_state.context, &escrow_ata).await,
+                    two_step_balance
+                );
+
+                // A single fill consumes the order, as with the two-step path
+                let acc_lookup_result = test_state.client.get_account(order).await.unwrap();
+                assert!(acc_lookup_result.is_none());
+                let acc_lookup_result = test_state.client.get_account(order_ata).await.unwrap();
+                assert!(acc_lookup_result.is_none());
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_create_and_fill_fails_with_mismatched_order_hash(
+                test_state: &mut TestState,
+            ) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                // The order hash of other terms than those passed along with it
+                test_state.test_arguments.salt += 1;
+                test_state.order_hash = get_order_hash(test_state);
+                test_state.test_arguments.salt -= 1;
+                let (order, order_ata) = get_order_addresses(test_state);
+                let (escrow, escrow_ata) = get_escrow_addresses(test_state);
+                let transaction =
+                    get_create_and_fill_tx(test_state, &order, &order_ata, &escrow, &escrow_ata);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(ErrorCode::ConstraintSeeds.into()));
+
+                let acc_lookup_result = test_state.client.get_account(order).await.unwrap();
+                assert!(acc_lookup_result.is_none());
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_fails_with_insufficient_order_balance(
+                test_state: &mut TestState,
+            ) {
+                let (_, order_ata) = create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                set_token_balance(
+                    &mut test_state.context,
+                    &order_ata,
+                    test_state.test_arguments.escrow_amount - 1,
+                )
+                .await;
+
+                let (escrow, _, transaction) = create_escrow_data(test_state);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::InsufficientOrderBalance.into(),
+                    ));
+
+                let acc_lookup_result = test_state.client.get_account(escrow).await.unwrap();
+                assert!(acc_lookup_result.is_none());
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_before_fill_deadline(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                test_state.test_arguments.fill_deadline = test_state.init_timestamp;
+
+                create_escrow(test_state).await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_fails_after_fill_deadline(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                test_state.test_arguments.fill_deadline = test_state.init_timestamp;
+
+                // The transaction lands after the deadline the resolver signed it with
+                let (escrow, _, transaction) = create_escrow_data(test_state);
+                set_time(&mut test_state.context, test_state.init_timestamp + 1);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::FillDeadlinePassed.into()));
+
+                let acc_lookup_result = test_state.client.get_account(escrow).await.unwrap();
+                assert!(acc_lookup_result.is_none());
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_without_affiliate(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, _) = create_escrow(test_state).await;
+
+                assert_eq!(
+                    get_escrow_src(test_state, &escrow).await.affiliate,
+                    Pubkey::default()
+                );
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_with_pre_existing_escrow_ata(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow_pda, _) = get_escrow_addresses(test_state);
+
+                let _escrow_ata =
+                    <TestState as HasTokenVariant>::Token::initialize_spl_associated_account(
+                        &mut test_state.context,
+                        &test_state.token,
+                        &escrow_pda,
+                    )
+                    .await;
+                common_escrow_tests::test_escrow_creation(test_state).await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_stores_committed_dst_safety_deposit(
+                test_state: &mut TestState,
+            ) {
+                // A value that differs from the src safety deposit and does not fit in u64
+                let dst_safety_deposit = u64::MAX as u128 + DEFAULT_SAFETY_DEPOSIT as u128;
+                test_state.test_arguments.dst_chain_params.safety_deposit = dst_safety_deposit;
+
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, _) = create_escrow(test_state).await;
+
+                let escrow_data = helpers_src::get_escrow_src(test_state, &escrow).await;
+                assert_eq!(escrow_data.dst_safety_deposit, dst_safety_deposit);
+                assert_eq!(
+                    escrow_data.safety_deposit,
+                    test_state.test_arguments.safety_deposit
+                );
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_with_dutch_auction_params(test_state: &mut TestState) {
+                test_state.test_arguments.dutch_auction_data =
+                    cross_chain_escrow_src::AuctionData {
+                        start_time: test_state.init_timestamp - AUCTION_START_OFFSET,
+                        duration: AUCTION_DURATION,
+                        initial_rate_bump: INITIAL_RATE_BUMP.into(),
+                        points_and_time_deltas: vec![
+                            cross_chain_escrow_src::auction::PointAndTimeDelta {
+                                rate_bump: INTERMEDIATE_RATE_BUMP.into(),
+                                time_delta: INTERMEDIATE_TIME_DELTA,
+                            },
+                        ],
+                        rounding: cross_chain_escrow_src::auction::ROUNDING_UP,
+                    };
+
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                common_escrow_tests::test_escrow_creation(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_with_excess_tokens(test_state: &mut TestState) {
+                type S = <TestState as HasTokenVariant>::Token;
+
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (_, order_ata) = create_order(test_state).await;
+                let excess_amount = 1000;
                 // Send excess tokens to the order ATA.
                 S::mint_spl_tokens(
                     &mut test_state.context,
@@ -286,6 +845,33 @@ run_for_tokens!(
                 assert!(order_ata_account.is_none());
             }
 
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_reconcile_escrow_with_excess_tokens(test_state: &mut TestState) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (_, order_ata) = create_order(test_state).await;
+                let excess_amount = 1000;
+                // Send excess tokens to the order ATA, they are moved to the escrow ATA on fill.
+                mint_excess_tokens(test_state, &order_ata, excess_amount).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+
+                let transaction = get_reconcile_escrow_tx(test_state, &escrow, &escrow_ata);
+                let return_data =
+                    common_escrow_tests::simulate_tx_return_data(test_state, transaction).await;
+                let reconciliation =
+                    cross_chain_escrow_src::EscrowReconciliation::try_from_slice(&return_data)
+                        .unwrap();
+
+                assert_eq!(
+                    reconciliation,
+                    cross_chain_escrow_src::EscrowReconciliation {
+                        stored_amount: test_state.test_arguments.escrow_amount,
+                        actual_amount: test_state.test_arguments.escrow_amount + excess_amount,
+                        difference: excess_amount as i128,
+                    }
+                );
+            }
+
             #[test_context(TestState)]
             #[tokio::test]
             async fn test_escrow_creation_fails_with_wrong_dutch_auction_hash(
@@ -302,6 +888,7 @@ run_for_tokens!(
                                 time_delta: INTERMEDIATE_TIME_DELTA,
                             },
                         ],
+                        rounding: cross_chain_escrow_src::auction::ROUNDING_UP,
                     };
 
                 create_order(test_state).await;
@@ -316,6 +903,7 @@ run_for_tokens!(
                                 time_delta: INTERMEDIATE_TIME_DELTA,
                             },
                         ],
+                        rounding: cross_chain_escrow_src::auction::ROUNDING_UP,
                     };
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
                 let (_, _, tx) = create_escrow_data(test_state);
@@ -328,6 +916,39 @@ run_for_tokens!(
                     ));
             }
 
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_with_auction_starting_at_max_lead(
+                test_state: &mut TestState,
+            ) {
+                test_state.test_arguments.dutch_auction_data.start_time =
+                    test_state.init_timestamp + MAX_AUCTION_LEAD;
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                create_escrow(test_state).await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_fails_with_auction_starting_after_max_lead(
+                test_state: &mut TestState,
+            ) {
+                test_state.test_arguments.dutch_auction_data.start_time =
+                    test_state.init_timestamp + MAX_AUCTION_LEAD + 1;
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                let (_, _, tx) = create_escrow_data(test_state);
+                test_state
+                    .client
+                    .process_transaction(tx)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::InvalidAuctionTiming.into(),
+                    ));
+            }
+
             #[test_context(TestState)]
             #[tokio::test]
             async fn test_calculation_of_dutch_auction_params(test_state: &mut TestState) {
@@ -342,6 +963,7 @@ run_for_tokens!(
                                 time_delta: INTERMEDIATE_TIME_DELTA,
                             },
                         ],
+                        rounding: cross_chain_escrow_src::auction::ROUNDING_UP,
                     };
 
                 create_order(test_state).await;
@@ -368,427 +990,613 @@ run_for_tokens!(
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_escrow_creation_fails_with_empty_order_account(
-                test_state: &mut TestState,
-            ) {
-                // Create an escrow account without existing order account.
+            async fn test_preview_dst_amount_matches_escrow_dst_amount(test_state: &mut TestState) {
+                test_state.test_arguments.dutch_auction_data =
+                    cross_chain_escrow_src::AuctionData {
+                        start_time: test_state.init_timestamp - AUCTION_START_OFFSET,
+                        duration: AUCTION_DURATION,
+                        initial_rate_bump: INITIAL_RATE_BUMP.into(),
+                        points_and_time_deltas: vec![
+                            cross_chain_escrow_src::auction::PointAndTimeDelta {
+                                rate_bump: INTERMEDIATE_RATE_BUMP.into(),
+                                time_delta: INTERMEDIATE_TIME_DELTA,
+                            },
+                        ],
+                        rounding: cross_chain_escrow_src::auction::ROUNDING_UP,
+                    };
+
+                let (order, _) = create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                let (escrow, escrow_ata, tx) = create_escrow_data(test_state);
 
-                test_state
-                    .client
-                    .process_transaction(tx)
-                    .await
-                    .expect_error(ProgramError::Custom(
-                        ErrorCode::AccountNotInitialized.into(),
-                    ));
+                // Project the dst amount to a future moment before the order expires
+                let fill_time = test_state.init_timestamp + DEFAULT_PERIOD_DURATION / 2;
+                let transaction = get_preview_dst_amount_tx(test_state, &order, Some(fill_time));
+                let return_data =
+                    common_escrow_tests::simulate_tx_return_data(test_state, transaction).await;
+                let previewed_dst_amount = U256::from_little_endian(&return_data).0;
 
-                // Check that the order accounts have not been created.
-                let acc_lookup_result = test_state.client.get_account(escrow).await.unwrap();
-                assert!(acc_lookup_result.is_none());
+                set_time(&mut test_state.context, fill_time);
+                let (escrow, _) = create_escrow(test_state).await;
 
-                let acc_lookup_result = test_state.client.get_account(escrow_ata).await.unwrap();
-                assert!(acc_lookup_result.is_none());
+                let escrow_data = get_escrow_src(test_state, &escrow).await;
+                assert_eq!(previewed_dst_amount, escrow_data.dst_amount);
+                // Make sure the rate bump is actually applied at the chosen moment
+                assert_ne!(previewed_dst_amount, test_state.test_arguments.dst_amount);
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_escrow_creation_fails_with_expired_order(test_state: &mut TestState) {
+            async fn test_escrow_creation_with_dst_decimals_shift(test_state: &mut TestState) {
+                test_state.test_arguments.dst_decimals_shift = 3;
                 create_order(test_state).await;
-
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                set_time(
-                    &mut test_state.context,
-                    test_state.test_arguments.expiration_time + 1,
-                );
+                let (escrow, _) = create_escrow(test_state).await;
 
-                let (_, _, transaction) = create_escrow_data(test_state);
+                let escrow_data = get_escrow_src(test_state, &escrow).await;
+                assert_eq!(
+                    U256(escrow_data.dst_amount),
+                    U256(test_state.test_arguments.dst_amount) * 1_000
+                );
+            }
 
-                test_state
-                    .client
-                    .process_transaction(transaction)
-                    .await
-                    .expect_error(ProgramError::Custom(EscrowError::OrderHasExpired.into()));
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_by_allowed_taker(test_state: &mut TestState) {
+                test_state.test_arguments.allowed_taker = test_state.taker_wallet.keypair.pubkey();
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, _) = create_escrow(test_state).await;
+
+                let escrow_data = get_escrow_src(test_state, &escrow).await;
+                assert_eq!(escrow_data.taker, test_state.taker_wallet.keypair.pubkey());
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_escrow_creation_fails_when_escrow_amount_is_too_large(
+            async fn test_escrow_creation_fails_if_taker_is_not_allowed(
                 test_state: &mut TestState,
             ) {
+                // The taker is a whitelisted resolver, but the order is reserved for another one
+                test_state.test_arguments.allowed_taker = Keypair::new().pubkey();
                 create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                test_state.test_arguments.escrow_amount =
-                    test_state.test_arguments.order_amount + 1;
-                let (_, _, transaction) = create_escrow_data(test_state);
+                let (escrow, _, transaction) = create_escrow_data(test_state);
 
                 test_state
                     .client
                     .process_transaction(transaction)
                     .await
-                    .expect_error(ProgramError::Custom(EscrowError::InvalidAmount.into()));
+                    .expect_error(ProgramError::Custom(EscrowError::TakerNotAllowed.into()));
+
+                let acc_lookup_result = test_state.client.get_account(escrow).await.unwrap();
+                assert!(acc_lookup_result.is_none());
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_escrow_creation_fails_witout_resolver_access(test_state: &mut TestState) {
+            async fn test_escrow_creation_with_tighter_timelocks(test_state: &mut TestState) {
+                let durations = [
+                    DEFAULT_PERIOD_DURATION / 2,
+                    DEFAULT_PERIOD_DURATION,
+                    DEFAULT_PERIOD_DURATION * 2,
+                    DEFAULT_PERIOD_DURATION * 4,
+                ];
+                test_state.test_arguments.src_timelock_durations = Some(durations);
                 create_order(test_state).await;
-                // test_state.taker_wallet does not have resolver access
-                let (_, _, transaction) = create_escrow_data(test_state);
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, _) = create_escrow(test_state).await;
+
+                let timelocks =
+                    Timelocks(U256(get_escrow_src(test_state, &escrow).await.timelocks));
+                let deployed_at = timelocks.rescue_start(0).unwrap();
+                let stages = [
+                    Stage::SrcWithdrawal,
+                    Stage::SrcPublicWithdrawal,
+                    Stage::SrcCancellation,
+                    Stage::SrcPublicCancellation,
+                ];
+                for (stage, duration) in stages.into_iter().zip(durations) {
+                    assert_eq!(timelocks.get(stage).unwrap(), deployed_at + duration);
+                }
+                // The dst stages keep the values committed in the order
+                assert_eq!(
+                    timelocks.get(Stage::DstWithdrawal).unwrap(),
+                    deployed_at + DEFAULT_PERIOD_DURATION
+                );
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_fails_with_looser_timelocks(test_state: &mut TestState) {
+                test_state.test_arguments.src_timelock_durations = Some([
+                    DEFAULT_PERIOD_DURATION,
+                    DEFAULT_PERIOD_DURATION * 2,
+                    DEFAULT_PERIOD_DURATION * 3 + 1,
+                    DEFAULT_PERIOD_DURATION * 4,
+                ]);
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, _, transaction) = create_escrow_data(test_state);
+
                 test_state
                     .client
                     .process_transaction(transaction)
                     .await
                     .expect_error(ProgramError::Custom(
-                        ErrorCode::AccountNotInitialized.into(),
+                        EscrowError::TimelocksLooserThanOrder.into(),
                     ));
+
+                let acc_lookup_result = test_state.client.get_account(escrow).await.unwrap();
+                assert!(acc_lookup_result.is_none());
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_escrow_creation_fails_with_incorrect_token(test_state: &mut TestState) {
+            async fn test_escrow_creation_fails_with_non_monotonic_tighter_timelocks(
+                test_state: &mut TestState,
+            ) {
+                // Tightening the withdrawal alone must not move it past the public withdrawal
+                test_state.test_arguments.src_timelock_durations = Some([
+                    DEFAULT_PERIOD_DURATION,
+                    DEFAULT_PERIOD_DURATION,
+                    DEFAULT_PERIOD_DURATION * 3,
+                    DEFAULT_PERIOD_DURATION * 4,
+                ]);
                 create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                test_state.token = <TestState as HasTokenVariant>::Token::deploy_spl_token(
-                    &mut test_state.context,
-                )
-                .await
-                .pubkey();
                 let (_, _, transaction) = create_escrow_data(test_state);
+
                 test_state
                     .client
                     .process_transaction(transaction)
                     .await
                     .expect_error(ProgramError::Custom(
-                        ErrorCode::AccountNotInitialized.into(),
+                        EscrowError::NonMonotonicTimelocks.into(),
                     ));
             }
-        }
 
-        mod test_escrow_withdraw {
-            use super::*;
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_withdraw(test_state: &mut TestState) {
-                create_order(test_state).await;
-                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                let (escrow, escrow_ata) = create_escrow(test_state).await;
-                helpers_src::test_withdraw_escrow(test_state, &escrow, &escrow_ata).await;
+            async fn test_order_creation_fails_with_overflowing_dst_decimals_shift(
+                test_state: &mut TestState,
+            ) {
+                test_state.test_arguments.dst_decimals_shift = i8::MAX;
+                let (order, _, tx) = create_order_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(tx)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::DstAmountOverflow.into()));
+
+                let acc_lookup_result = test_state.client.get_account(order).await.unwrap();
+                assert!(acc_lookup_result.is_none());
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_withdraw_with_excess_tokens(test_state: &mut TestState) {
-                create_order(test_state).await;
-                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                let (escrow, escrow_ata) = create_escrow(test_state).await;
-                let transaction = SrcProgram::get_withdraw_tx(test_state, &escrow, &escrow_ata);
-
-                set_time(
-                    &mut test_state.context,
-                    test_state
-                        .test_arguments
-                        .src_timelocks
-                        .get(Stage::SrcWithdrawal)
-                        .unwrap(),
-                );
+            async fn test_preview_dst_amount_fails_with_wrong_dutch_auction_hash(
+                test_state: &mut TestState,
+            ) {
+                let (order, _) = create_order(test_state).await;
+                test_state.test_arguments.dutch_auction_data.duration += 1;
 
-                let (_, taker_ata) = find_user_ata(test_state);
+                let transaction = get_preview_dst_amount_tx(test_state, &order, None);
 
-                let excess_amount = 1000;
-                // Send excess tokens to the escrow account
-                mint_excess_tokens(test_state, &escrow_ata, excess_amount).await;
                 test_state
-                    .expect_state_change(
-                        transaction,
-                        &[token_change(
-                            taker_ata,
-                            test_state.test_arguments.escrow_amount + excess_amount,
-                        )],
-                    )
-                    .await;
-
-                // Assert escrow was closed
-                assert!(test_state
                     .client
-                    .get_account(escrow)
+                    .process_transaction(transaction)
                     .await
-                    .unwrap()
-                    .is_none());
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::DutchAuctionDataHashMismatch.into(),
+                    ));
+            }
 
-                // Assert escrow_ata was closed
-                assert!(test_state
-                    .client
-                    .get_account(escrow_ata)
-                    .await
-                    .unwrap()
-                    .is_none());
+            // Sets up a dutch auction and a destination amount floor equal to the destination
+            // amount at the initial timestamp, which the decaying rate bump later drops below
+            fn set_dutch_auction_with_min_dst_amount(test_state: &mut TestState) {
+                test_state.test_arguments.dutch_auction_data =
+                    cross_chain_escrow_src::AuctionData {
+                        start_time: test_state.init_timestamp - AUCTION_START_OFFSET,
+                        duration: AUCTION_DURATION,
+                        initial_rate_bump: INITIAL_RATE_BUMP.into(),
+                        points_and_time_deltas: vec![
+                            cross_chain_escrow_src::auction::PointAndTimeDelta {
+                                rate_bump: INTERMEDIATE_RATE_BUMP.into(),
+                                time_delta: INTERMEDIATE_TIME_DELTA,
+                            },
+                        ],
+                        rounding: cross_chain_escrow_src::auction::ROUNDING_UP,
+                    };
+                test_state.test_arguments.min_dst_amount =
+                    U256(test_state.test_arguments.dst_amount)
+                        .checked_mul(U256::from(EXPECTED_MULTIPLIER_NUMERATOR))
+                        .unwrap()
+                        .checked_div(U256::from(EXPECTED_MULTIPLIER_DENOMINATOR))
+                        .unwrap()
+                        .0;
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_withdraw_does_not_work_with_wrong_secret(test_state: &mut TestState) {
+            async fn test_escrow_creation_with_dst_amount_at_minimum(test_state: &mut TestState) {
+                set_dutch_auction_with_min_dst_amount(test_state);
+
                 create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                common_escrow_tests::test_withdraw_does_not_work_with_wrong_secret(test_state).await
+                let (escrow, _) = create_escrow(test_state).await;
+
+                let escrow_data = get_escrow_src(test_state, &escrow).await;
+                assert_eq!(
+                    escrow_data.dst_amount,
+                    test_state.test_arguments.min_dst_amount
+                );
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_withdraw_does_not_work_with_non_recipient(test_state: &mut TestState) {
+            async fn test_escrow_creation_fails_with_dst_amount_below_minimum(
+                test_state: &mut TestState,
+            ) {
+                set_dutch_auction_with_min_dst_amount(test_state);
+
                 create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                common_escrow_tests::test_withdraw_does_not_work_with_non_recipient(test_state)
+
+                // Fill right before the order expires, when the rate bump has decayed further
+                set_time(
+                    &mut test_state.context,
+                    test_state.test_arguments.expiration_time - 1,
+                );
+                let (_, _, transaction) = create_escrow_data(test_state);
+                test_state
+                    .client
+                    .process_transaction(transaction)
                     .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::DstAmountBelowMinimum.into(),
+                    ));
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_withdraw_does_not_work_with_wrong_taker_ata(test_state: &mut TestState) {
+            async fn test_escrow_creation_with_dst_amount_at_resolver_maximum(
+                test_state: &mut TestState,
+            ) {
+                set_dutch_auction_with_min_dst_amount(test_state);
+                // The dst amount at the initial timestamp is the floor set above
+                test_state.test_arguments.max_dst_amount =
+                    Some(test_state.test_arguments.min_dst_amount);
+
                 create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                common_escrow_tests::test_withdraw_does_not_work_with_wrong_taker_ata(test_state)
-                    .await
+                let (escrow, _) = create_escrow(test_state).await;
+
+                let escrow_data = get_escrow_src(test_state, &escrow).await;
+                assert_eq!(
+                    escrow_data.dst_amount,
+                    test_state.test_arguments.min_dst_amount
+                );
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_withdraw_does_not_work_with_wrong_escrow_ata(test_state: &mut TestState) {
-                let diff_amount = 1;
-                let new_amount = test_state.test_arguments.order_amount + diff_amount;
-                test_state.test_arguments.order_amount = new_amount;
-                create_order(test_state).await;
-                test_state.test_arguments.order_amount -= diff_amount;
+            async fn test_escrow_creation_fails_with_dst_amount_above_resolver_maximum(
+                test_state: &mut TestState,
+            ) {
+                set_dutch_auction_with_min_dst_amount(test_state);
+                // The resolver quoted the order without the auction rate bump
+                test_state.test_arguments.max_dst_amount =
+                    Some(test_state.test_arguments.dst_amount);
+
                 create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                common_escrow_tests::test_withdraw_does_not_work_with_wrong_escrow_ata(
-                    test_state, new_amount,
-                )
-                .await
+
+                let (_, _, transaction) = create_escrow_data(test_state);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::DstAmountAboveMaximum.into(),
+                    ));
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_withdraw_does_not_work_before_withdrawal_start(
+            async fn test_partial_fill_fails_with_dst_amount_below_minimum(
                 test_state: &mut TestState,
             ) {
-                create_order(test_state).await;
+                set_dutch_auction_with_min_dst_amount(test_state);
+
+                create_order_for_partial_fill(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                common_escrow_tests::test_withdraw_does_not_work_before_withdrawal_start(test_state)
+
+                // The floor of a partial fill is the pro-rata share of the order floor
+                let escrow_amount = DEFAULT_ESCROW_AMOUNT / DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE * 3;
+                set_time(
+                    &mut test_state.context,
+                    test_state.test_arguments.expiration_time - 1,
+                );
+                let (_, _, transaction) =
+                    test_escrow_creation_for_partial_fill_data(test_state, escrow_amount).await;
+                test_state
+                    .client
+                    .process_transaction(transaction)
                     .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::DstAmountBelowMinimum.into(),
+                    ));
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_withdraw_does_not_work_after_cancellation_start(
+            async fn test_escrow_creation_fails_with_empty_order_account(
                 test_state: &mut TestState,
             ) {
-                create_order(test_state).await;
+                // Create an escrow account without existing order account.
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                common_escrow_tests::test_withdraw_does_not_work_after_cancellation_start(
-                    test_state,
-                )
-                .await
+                let (escrow, escrow_ata, tx) = create_escrow_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(tx)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        ErrorCode::AccountNotInitialized.into(),
+                    ));
+
+                // Check that the order accounts have not been created.
+                let acc_lookup_result = test_state.client.get_account(escrow).await.unwrap();
+                assert!(acc_lookup_result.is_none());
+
+                let acc_lookup_result = test_state.client.get_account(escrow_ata).await.unwrap();
+                assert!(acc_lookup_result.is_none());
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_withdraw_fails_with_incorrect_token(test_state: &mut TestState) {
+            async fn test_escrow_creation_fails_with_expired_order(test_state: &mut TestState) {
                 create_order(test_state).await;
-                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                let (escrow, escrow_ata) = create_escrow(test_state).await;
 
-                test_state.token = <TestState as HasTokenVariant>::Token::deploy_spl_token(
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                set_time(
                     &mut test_state.context,
-                )
-                .await
-                .pubkey();
+                    test_state.test_arguments.expiration_time + 1,
+                );
 
-                let transaction = SrcProgram::get_withdraw_tx(test_state, &escrow, &escrow_ata);
+                let (_, _, transaction) = create_escrow_data(test_state);
 
                 test_state
                     .client
                     .process_transaction(transaction)
                     .await
-                    .expect_error(ProgramError::Custom(EscrowError::InvalidMint.into()));
+                    .expect_error(ProgramError::Custom(EscrowError::OrderHasExpired.into()));
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_withdraw_fails_if_public_withdrawal_duration_overflows(
+            async fn test_escrow_creation_fails_with_unsupported_token_program(
                 test_state: &mut TestState,
             ) {
-                test_state.test_arguments.src_timelocks =
-                    init_timelocks(0, u32::MAX, 0, 0, 0, 0, 0, 0);
                 create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                let (escrow, escrow_ata) = create_escrow(test_state).await;
-                let transaction = SrcProgram::get_public_withdraw_tx(
+                unlist_token_program(
                     test_state,
-                    &escrow,
-                    &escrow_ata,
-                    &test_state.taker_wallet.keypair,
-                );
+                    &<TestState as HasTokenVariant>::Token::get_token_program_id(),
+                )
+                .await;
+
+                let (_, _, transaction) = create_escrow_data(test_state);
 
                 test_state
                     .client
                     .process_transaction(transaction)
                     .await
-                    .expect_error(ProgramError::ArithmeticOverflow);
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::UnsupportedTokenProgram.into(),
+                    ));
             }
-        }
-
-        mod test_order_public_withdraw {
-            use super::*;
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_public_withdraw_tokens_by_taker(test_state: &mut TestState) {
+            async fn test_escrow_creation_fails_when_escrow_amount_is_too_large(
+                test_state: &mut TestState,
+            ) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                test_state.test_arguments.escrow_amount =
+                    test_state.test_arguments.order_amount + 1;
+                let (_, _, transaction) = create_escrow_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidAmount.into()));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_fails_witout_resolver_access(test_state: &mut TestState) {
+                create_order(test_state).await;
+                // test_state.taker_wallet does not have resolver access
+                let (_, _, transaction) = create_escrow_data(test_state);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        ErrorCode::AccountNotInitialized.into(),
+                    ));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_fails_with_incorrect_token(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                test_state.token = <TestState as HasTokenVariant>::Token::deploy_spl_token(
+                    &mut test_state.context,
+                )
+                .await
+                .pubkey();
+                let (_, _, transaction) = create_escrow_data(test_state);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        ErrorCode::AccountNotInitialized.into(),
+                    ));
+            }
+        }
+
+        mod test_escrow_withdraw {
+            use super::*;
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_withdraw(test_state: &mut TestState) {
                 create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
                 let (escrow, escrow_ata) = create_escrow(test_state).await;
+                helpers_src::test_withdraw_escrow(test_state, &escrow, &escrow_ata).await;
+            }
 
-                let transaction = SrcProgram::get_public_withdraw_tx(
-                    test_state,
-                    &escrow,
-                    &escrow_ata,
-                    &test_state.taker_wallet.keypair,
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_withdraw_with_parts_prefix_in_single_fill_hashlock(
+                test_state: &mut TestState,
+            ) {
+                // Pick a secret whose hash starts with bytes that decode to a valid parts count
+                let mut secret = test_state.secret;
+                let mut hashlock = hashv(&[&secret]);
+                while !(2..=MAX_PARTS).contains(&u16::from_be_bytes([hashlock.0[0], hashlock.0[1]]))
+                {
+                    secret = hashlock.0;
+                    hashlock = hashv(&[&secret]);
+                }
+                test_state.secret = secret;
+                test_state.hashlock = hashlock;
+
+                let (order, _) = create_order(test_state).await;
+                let transaction = get_order_fill_state_tx(test_state, &order);
+                let return_data =
+                    common_escrow_tests::simulate_tx_return_data(test_state, transaction).await;
+                let fill_state =
+                    cross_chain_escrow_src::OrderFillState::try_from_slice(&return_data).unwrap();
+                assert_eq!(fill_state.parts_amount, 0);
+                assert_eq!(fill_state.next_part_index, 0);
+
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+                helpers_src::test_withdraw_escrow(test_state, &escrow, &escrow_ata).await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_withdraw_with_sha256_hashlock(test_state: &mut TestState) {
+                test_state.test_arguments.hash_algo = HASH_ALGO_SHA256;
+                set_sha256_hashlock(test_state);
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+
+                assert_eq!(
+                    get_escrow_src(test_state, &escrow).await.hash_algo,
+                    HASH_ALGO_SHA256
                 );
+                helpers_src::test_withdraw_escrow(test_state, &escrow, &escrow_ata).await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_withdraw_fails_with_sha256_hashlock_in_keccak_mode(
+                test_state: &mut TestState,
+            ) {
+                // The order commits to keccak, so the same secret does not match its sha256 hash
+                set_sha256_hashlock(test_state);
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+                let transaction = SrcProgram::get_withdraw_tx(test_state, &escrow, &escrow_ata);
 
                 set_time(
                     &mut test_state.context,
                     test_state
                         .test_arguments
                         .src_timelocks
-                        .get(Stage::SrcPublicWithdrawal)
+                        .get(Stage::SrcWithdrawal)
                         .unwrap(),
                 );
-
-                let escrow_data_len = DEFAULT_SRC_ESCROW_SIZE;
-
-                let rent_lamports =
-                    get_min_rent_for_size(&mut test_state.client, escrow_data_len).await;
-
-                let token_account_rent = get_min_rent_for_size(
-                    &mut test_state.client,
-                    <TestState as HasTokenVariant>::Token::get_token_account_size(),
-                )
-                .await;
-
-                assert_eq!(
-                    rent_lamports,
-                    test_state.client.get_balance(escrow).await.unwrap()
-                );
-
                 test_state
-                    .expect_state_change(
-                        transaction,
-                        &[
-                            native_change(
-                                test_state.taker_wallet.keypair.pubkey(),
-                                rent_lamports + token_account_rent,
-                            ),
-                            token_change(
-                                test_state.taker_wallet.token_account,
-                                test_state.test_arguments.escrow_amount,
-                            ),
-                        ],
-                    )
-                    .await;
-
-                // Assert accounts were closed
-                assert!(test_state
-                    .client
-                    .get_account(escrow)
-                    .await
-                    .unwrap()
-                    .is_none());
-
-                // Assert escrow_ata was closed
-                assert!(test_state
                     .client
-                    .get_account(escrow_ata)
+                    .process_transaction(transaction)
                     .await
-                    .unwrap()
-                    .is_none());
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidSecret.into()));
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_public_withdraw_tokens_any_resolver(test_state: &mut TestState) {
+            async fn test_withdraw_emits_secret_revealed(test_state: &mut TestState) {
                 create_order(test_state).await;
-                let withdrawer = Keypair::new();
-                prepare_resolvers(
-                    test_state,
-                    &[
-                        test_state.taker_wallet.keypair.pubkey(),
-                        withdrawer.pubkey(),
-                    ],
-                )
-                .await;
-                transfer_lamports(
-                    &mut test_state.context,
-                    WALLET_DEFAULT_LAMPORTS,
-                    &test_state.payer_kp,
-                    &withdrawer.pubkey(),
-                )
-                .await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
                 let (escrow, escrow_ata) = create_escrow(test_state).await;
-
-                let transaction = SrcProgram::get_public_withdraw_tx(
-                    test_state,
-                    &escrow,
-                    &escrow_ata,
-                    &withdrawer,
-                );
+                let transaction = SrcProgram::get_withdraw_tx(test_state, &escrow, &escrow_ata);
 
                 set_time(
                     &mut test_state.context,
                     test_state
                         .test_arguments
                         .src_timelocks
-                        .get(Stage::SrcPublicWithdrawal)
+                        .get(Stage::SrcWithdrawal)
                         .unwrap(),
                 );
 
-                let escrow_data_len = DEFAULT_SRC_ESCROW_SIZE;
-
-                let rent_lamports =
-                    get_min_rent_for_size(&mut test_state.client, escrow_data_len).await;
-
-                let token_account_rent = get_min_rent_for_size(
-                    &mut test_state.client,
-                    <TestState as HasTokenVariant>::Token::get_token_account_size(),
+                let events = common_escrow_tests::simulate_tx_events::<_, _, SecretRevealed>(
+                    test_state,
+                    transaction,
                 )
                 .await;
+                assert_eq!(events.len(), 1);
+                assert_eq!(events[0].order_hash, test_state.order_hash.to_bytes());
+                assert_eq!(events[0].hashlock, test_state.hashlock.to_bytes());
+                assert_eq!(events[0].secret, test_state.secret);
+            }
 
-                assert_eq!(
-                    rent_lamports,
-                    test_state.client.get_balance(escrow).await.unwrap()
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_withdraw_with_excess_tokens(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+                let transaction = SrcProgram::get_withdraw_tx(test_state, &escrow, &escrow_ata);
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .src_timelocks
+                        .get(Stage::SrcWithdrawal)
+                        .unwrap(),
                 );
 
+                let (_, taker_ata) = find_user_ata(test_state);
+
+                let excess_amount = 1000;
+                // Send excess tokens to the escrow account
+                mint_excess_tokens(test_state, &escrow_ata, excess_amount).await;
                 test_state
                     .expect_state_change(
                         transaction,
-                        &[
-                            native_change(
-                                test_state.taker_wallet.keypair.pubkey(),
-                                rent_lamports + token_account_rent
-                                    - test_state.test_arguments.safety_deposit,
-                            ),
-                            native_change(
-                                withdrawer.pubkey(),
-                                test_state.test_arguments.safety_deposit,
-                            ),
-                            token_change(
-                                test_state.taker_wallet.token_account,
-                                test_state.test_arguments.escrow_amount,
-                            ),
-                        ],
+                        &[token_change(
+                            taker_ata,
+                            test_state.test_arguments.escrow_amount + excess_amount,
+                        )],
                     )
                     .await;
 
-                // Assert accounts were closed
+                // Assert escrow was closed
                 assert!(test_state
                     .client
                     .get_account(escrow)
@@ -807,59 +1615,44 @@ run_for_tokens!(
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_public_withdraw_fails_with_wrong_secret(test_state: &mut TestState) {
+            async fn test_withdraw_does_not_work_with_wrong_secret(test_state: &mut TestState) {
                 create_order(test_state).await;
-                prepare_resolvers(
-                    test_state,
-                    &[
-                        test_state.taker_wallet.keypair.pubkey(),
-                        test_state.context.payer.pubkey(),
-                    ],
-                )
-                .await;
-                common_escrow_tests::test_public_withdraw_fails_with_wrong_secret(test_state).await
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                common_escrow_tests::test_withdraw_does_not_work_with_wrong_secret(test_state).await
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_public_withdraw_fails_with_wrong_taker_ata(test_state: &mut TestState) {
+            async fn test_withdraw_with_memo(test_state: &mut TestState) {
                 create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                common_escrow_tests::test_public_withdraw_fails_with_wrong_taker_ata(test_state)
-                    .await
+                common_escrow_tests::test_withdraw_with_memo(test_state).await
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_public_withdraw_fails_with_wrong_escrow_ata(test_state: &mut TestState) {
-                let diff = 1;
-                let new_amount = test_state.test_arguments.order_amount + diff;
-                test_state.test_arguments.order_amount = new_amount;
+            async fn test_withdraw_fails_with_invalid_memo(test_state: &mut TestState) {
                 create_order(test_state).await;
-                test_state.test_arguments.order_amount -= diff;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                common_escrow_tests::test_withdraw_fails_with_invalid_memo(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_withdraw_through_cpi_returns_secret(test_state: &mut TestState) {
                 create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                common_escrow_tests::test_public_withdraw_fails_with_wrong_escrow_ata(
-                    test_state, new_amount,
-                )
-                .await
+                common_escrow_tests::test_withdraw_through_cpi_returns_secret(test_state).await
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_public_withdraw_fails_before_start_of_public_withdraw(
+            async fn test_withdraw_fails_with_insufficient_escrow_balance(
                 test_state: &mut TestState,
             ) {
                 create_order(test_state).await;
-                prepare_resolvers(
-                    test_state,
-                    &[
-                        test_state.taker_wallet.keypair.pubkey(),
-                        test_state.context.payer.pubkey(),
-                    ],
-                )
-                .await;
-                common_escrow_tests::test_public_withdraw_fails_before_start_of_public_withdraw(
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                common_escrow_tests::test_withdraw_fails_with_insufficient_escrow_balance(
                     test_state,
                 )
                 .await
@@ -867,174 +1660,93 @@ run_for_tokens!(
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_public_withdraw_fails_after_cancellation_start(
+            async fn test_withdraw_does_not_work_with_truncated_preimage(
                 test_state: &mut TestState,
             ) {
+                test_state.hashlock = hashv(&[&test_state.secret[..31]]);
                 create_order(test_state).await;
-                prepare_resolvers(
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                common_escrow_tests::test_withdraw_does_not_work_with_non_32_byte_preimage(
                     test_state,
-                    &[
-                        test_state.taker_wallet.keypair.pubkey(),
-                        test_state.context.payer.pubkey(),
-                    ],
                 )
-                .await;
-                common_escrow_tests::test_public_withdraw_fails_after_cancellation_start(test_state)
-                    .await
+                .await
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_public_withdraw_fails_without_resolver_access(
+            async fn test_withdraw_does_not_work_with_extended_preimage(
                 test_state: &mut TestState,
             ) {
+                test_state.hashlock = hashv(&[&test_state.secret, &[0u8]]);
                 create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                let (escrow, escrow_ata) = create_escrow(test_state).await;
-
-                let withdrawer = Keypair::new();
-                // withdrawer does not have resolver access
-                let transaction = SrcProgram::get_public_withdraw_tx(
+                common_escrow_tests::test_withdraw_does_not_work_with_non_32_byte_preimage(
                     test_state,
-                    &escrow,
-                    &escrow_ata,
-                    &withdrawer,
-                );
-
-                test_state
-                    .client
-                    .process_transaction(transaction)
-                    .await
-                    .expect_error(ProgramError::Custom(
-                        ErrorCode::AccountNotInitialized.into(),
-                    ));
-            }
-
-            #[test_context(TestState)]
-            #[tokio::test]
-            async fn test_public_withdraw_fails_with_incorrect_token(test_state: &mut TestState) {
-                create_order(test_state).await;
-                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                let (escrow, escrow_ata) = create_escrow(test_state).await;
-
-                test_state.token = <TestState as HasTokenVariant>::Token::deploy_spl_token(
-                    &mut test_state.context,
                 )
                 .await
-                .pubkey();
-                let taker_kp = test_state.taker_wallet.keypair.insecure_clone();
-
-                let transaction =
-                    SrcProgram::get_public_withdraw_tx(test_state, &escrow, &escrow_ata, &taker_kp);
-
-                test_state
-                    .client
-                    .process_transaction(transaction)
-                    .await
-                    .expect_error(ProgramError::Custom(EscrowError::InvalidMint.into()));
             }
-        }
-
-        mod test_escrow_cancel {
-            use super::*;
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_cancel(test_state: &mut TestState) {
+            async fn test_withdraw_does_not_work_with_non_recipient(test_state: &mut TestState) {
                 create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                let (escrow, escrow_ata) = create_escrow(test_state).await;
-                common_escrow_tests::test_cancel(test_state, &escrow, &escrow_ata).await
+                common_escrow_tests::test_withdraw_does_not_work_with_non_recipient(test_state)
+                    .await
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_cancel_with_excess_tokens(test_state: &mut TestState) {
+            async fn test_withdraw_does_not_work_with_wrong_taker_ata(test_state: &mut TestState) {
                 create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-
-                let (escrow, escrow_ata) = create_escrow(test_state).await;
-                let transaction = SrcProgram::get_cancel_tx(test_state, &escrow, &escrow_ata);
-
-                set_time(
-                    &mut test_state.context,
-                    test_state
-                        .test_arguments
-                        .src_timelocks
-                        .get(Stage::SrcCancellation)
-                        .unwrap(),
-                );
-
-                let (maker_ata, _) = find_user_ata(test_state);
-
-                let excess_amount = 1000;
-                // Send excess tokens to the escrow account
-                mint_excess_tokens(test_state, &escrow_ata, excess_amount).await;
-
-                test_state
-                    .expect_state_change(
-                        transaction,
-                        &[
-                            token_change(
-                                maker_ata,
-                                test_state.test_arguments.escrow_amount + excess_amount,
-                            ),
-                            account_closure(escrow, true),
-                            account_closure(escrow_ata, true),
-                        ],
-                    )
-                    .await;
+                common_escrow_tests::test_withdraw_does_not_work_with_wrong_taker_ata(test_state)
+                    .await
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_cannot_cancel_by_non_maker(test_state: &mut TestState) {
+            async fn test_withdraw_does_not_work_with_wrong_escrow_ata(test_state: &mut TestState) {
+                let diff_amount = 1;
+                let new_amount = test_state.test_arguments.order_amount + diff_amount;
+                test_state.test_arguments.order_amount = new_amount;
                 create_order(test_state).await;
-                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                common_escrow_tests::test_cannot_cancel_by_non_maker(test_state).await
-            }
-
-            #[test_context(TestState)]
-            #[tokio::test]
-            async fn test_cannot_cancel_with_wrong_maker_ata(test_state: &mut TestState) {
+                test_state.test_arguments.order_amount -= diff_amount;
                 create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                common_escrow_tests::test_cannot_cancel_with_wrong_maker_ata(test_state).await
+                common_escrow_tests::test_withdraw_does_not_work_with_wrong_escrow_ata(
+                    test_state, new_amount,
+                )
+                .await
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_cannot_cancel_with_wrong_escrow_ata(test_state: &mut TestState) {
+            async fn test_withdraw_does_not_work_before_withdrawal_start(
+                test_state: &mut TestState,
+            ) {
                 create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                let (escrow, _) = create_escrow(test_state).await;
-
-                test_state.test_arguments.order_amount += 1;
-                test_state.test_arguments.escrow_amount += 1;
-                create_order(test_state).await;
-
-                let (_, escrow_ata_2) = create_escrow(test_state).await;
-
-                let transaction = SrcProgram::get_cancel_tx(test_state, &escrow, &escrow_ata_2);
-
-                test_state
-                    .client
-                    .process_transaction(transaction)
+                common_escrow_tests::test_withdraw_does_not_work_before_withdrawal_start(test_state)
                     .await
-                    .expect_error(ProgramError::Custom(ErrorCode::ConstraintTokenOwner.into()))
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_cannot_cancel_before_cancellation_start(test_state: &mut TestState) {
+            async fn test_withdraw_does_not_work_after_cancellation_start(
+                test_state: &mut TestState,
+            ) {
                 create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                common_escrow_tests::test_cannot_cancel_before_cancellation_start(test_state).await
+                common_escrow_tests::test_withdraw_does_not_work_after_cancellation_start(
+                    test_state,
+                )
+                .await
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_cancel_fails_with_incorrect_token(test_state: &mut TestState) {
+            async fn test_withdraw_fails_with_incorrect_token(test_state: &mut TestState) {
                 create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
                 let (escrow, escrow_ata) = create_escrow(test_state).await;
@@ -1045,7 +1757,7 @@ run_for_tokens!(
                 .await
                 .pubkey();
 
-                let transaction = SrcProgram::get_cancel_tx(test_state, &escrow, &escrow_ata);
+                let transaction = SrcProgram::get_withdraw_tx(test_state, &escrow, &escrow_ata);
 
                 test_state
                     .client
@@ -1053,68 +1765,1034 @@ run_for_tokens!(
                     .await
                     .expect_error(ProgramError::Custom(EscrowError::InvalidMint.into()));
             }
+        }
+
+        mod test_order_public_withdraw {
+            use super::*;
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_cancel_fails_if_cancellation_duration_overflows(
-                test_state: &mut TestState,
-            ) {
-                test_state.test_arguments.src_timelocks =
-                    init_timelocks(0, 0, u32::MAX, 0, 0, 0, 0, 0);
+            async fn test_public_withdraw_tokens_by_taker(test_state: &mut TestState) {
                 create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
                 let (escrow, escrow_ata) = create_escrow(test_state).await;
-                let transaction = SrcProgram::get_cancel_tx(test_state, &escrow, &escrow_ata);
-
-                test_state
-                    .client
-                    .process_transaction(transaction)
-                    .await
-                    .expect_error(ProgramError::ArithmeticOverflow);
-            }
-        }
 
-        mod test_order_cancel {
-            use super::*;
+                let transaction = SrcProgram::get_public_withdraw_tx(
+                    test_state,
+                    &escrow,
+                    &escrow_ata,
+                    &test_state.taker_wallet.keypair,
+                );
 
-            #[test_context(TestState)]
-            #[tokio::test]
-            async fn test_order_cancel(test_state: &mut TestState) {
-                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                helpers_src::test_order_cancel(test_state).await;
-            }
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .src_timelocks
+                        .get(Stage::SrcPublicWithdrawal)
+                        .unwrap(),
+                );
 
-            #[test_context(TestState)]
-            #[tokio::test]
-            async fn test_rescue_tokens_when_order_is_deleted(test_state: &mut TestState) {
-                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                helpers_src::test_rescue_tokens_when_order_is_deleted(test_state).await;
-            }
+                let escrow_data_len = DEFAULT_SRC_ESCROW_SIZE;
 
-            #[test_context(TestState)]
-            #[tokio::test]
-            async fn test_order_cancel_fails_with_incorrect_token(test_state: &mut TestState) {
-                let (order, order_ata) = create_order(test_state).await;
-                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let rent_lamports =
+                    get_min_rent_for_size(&mut test_state.client, escrow_data_len).await;
 
-                test_state.token = <TestState as HasTokenVariant>::Token::deploy_spl_token(
-                    &mut test_state.context,
+                let token_account_rent = get_min_rent_for_size(
+                    &mut test_state.client,
+                    <TestState as HasTokenVariant>::Token::get_token_account_size(),
                 )
-                .await
-                .pubkey();
+                .await;
 
-                let transaction = get_cancel_order_tx(test_state, &order, &order_ata, None);
+                assert_eq!(
+                    rent_lamports,
+                    test_state.client.get_balance(escrow).await.unwrap()
+                );
 
                 test_state
+                    .expect_state_change(
+                        transaction,
+                        &[
+                            native_change(
+                                test_state.taker_wallet.keypair.pubkey(),
+                                rent_lamports + token_account_rent,
+                            ),
+                            token_change(
+                                test_state.taker_wallet.token_account,
+                                test_state.test_arguments.escrow_amount,
+                            ),
+                        ],
+                    )
+                    .await;
+
+                // Assert accounts were closed
+                assert!(test_state
                     .client
-                    .process_transaction(transaction)
+                    .get_account(escrow)
                     .await
-                    .expect_error(ProgramError::Custom(EscrowError::InvalidMint.into()));
-            }
-        }
+                    .unwrap()
+                    .is_none());
 
-        mod test_order_cancel_with_excess_tokens {
-            use super::*;
+                // Assert escrow_ata was closed
+                assert!(test_state
+                    .client
+                    .get_account(escrow_ata)
+                    .await
+                    .unwrap()
+                    .is_none());
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_withdraw_emits_secret_revealed(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+                let transaction = SrcProgram::get_public_withdraw_tx(
+                    test_state,
+                    &escrow,
+                    &escrow_ata,
+                    &test_state.taker_wallet.keypair,
+                );
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .src_timelocks
+                        .get(Stage::SrcPublicWithdrawal)
+                        .unwrap(),
+                );
+
+                let events = common_escrow_tests::simulate_tx_events::<_, _, SecretRevealed>(
+                    test_state,
+                    transaction,
+                )
+                .await;
+                assert_eq!(events.len(), 1);
+                assert_eq!(events[0].order_hash, test_state.order_hash.to_bytes());
+                assert_eq!(events[0].hashlock, test_state.hashlock.to_bytes());
+                assert_eq!(events[0].secret, test_state.secret);
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_withdraw_tokens_any_resolver(test_state: &mut TestState) {
+                create_order(test_state).await;
+                let withdrawer = Keypair::new();
+                prepare_resolvers(
+                    test_state,
+                    &[
+                        test_state.taker_wallet.keypair.pubkey(),
+                        withdrawer.pubkey(),
+                    ],
+                )
+                .await;
+                transfer_lamports(
+                    &mut test_state.context,
+                    WALLET_DEFAULT_LAMPORTS,
+                    &test_state.payer_kp,
+                    &withdrawer.pubkey(),
+                )
+                .await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+
+                let transaction = SrcProgram::get_public_withdraw_tx(
+                    test_state,
+                    &escrow,
+                    &escrow_ata,
+                    &withdrawer,
+                );
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .src_timelocks
+                        .get(Stage::SrcPublicWithdrawal)
+                        .unwrap(),
+                );
+
+                let escrow_data_len = DEFAULT_SRC_ESCROW_SIZE;
+
+                let rent_lamports =
+                    get_min_rent_for_size(&mut test_state.client, escrow_data_len).await;
+
+                let token_account_rent = get_min_rent_for_size(
+                    &mut test_state.client,
+                    <TestState as HasTokenVariant>::Token::get_token_account_size(),
+                )
+                .await;
+
+                assert_eq!(
+                    rent_lamports,
+                    test_state.client.get_balance(escrow).await.unwrap()
+                );
+
+                test_state
+                    .expect_state_change(
+                        transaction,
+                        &[
+                            native_change(
+                                test_state.taker_wallet.keypair.pubkey(),
+                                rent_lamports + token_account_rent
+                                    - test_state.test_arguments.safety_deposit,
+                            ),
+                            native_change(
+                                withdrawer.pubkey(),
+                                test_state.test_arguments.safety_deposit,
+                            ),
+                            token_change(
+                                test_state.taker_wallet.token_account,
+                                test_state.test_arguments.escrow_amount,
+                            ),
+                        ],
+                    )
+                    .await;
+
+                // Assert accounts were closed
+                assert!(test_state
+                    .client
+                    .get_account(escrow)
+                    .await
+                    .unwrap()
+                    .is_none());
+
+                // Assert escrow_ata was closed
+                assert!(test_state
+                    .client
+                    .get_account(escrow_ata)
+                    .await
+                    .unwrap()
+                    .is_none());
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_withdraw_with_safety_deposit_split(test_state: &mut TestState) {
+                create_order(test_state).await;
+                let withdrawer = Keypair::new();
+                prepare_resolvers(
+                    test_state,
+                    &[
+                        test_state.taker_wallet.keypair.pubkey(),
+                        withdrawer.pubkey(),
+                    ],
+                )
+                .await;
+                transfer_lamports(
+                    &mut test_state.context,
+                    WALLET_DEFAULT_LAMPORTS,
+                    &test_state.payer_kp,
+                    &withdrawer.pubkey(),
+                )
+                .await;
+                // 33.33% of the default safety deposit is not a whole number of lamports
+                common_escrow_tests::test_public_withdraw_with_safety_deposit_split(
+                    test_state,
+                    &withdrawer,
+                    3_333,
+                )
+                .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_withdraw_by_rent_recipient_with_split(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                common_escrow_tests::test_public_withdraw_by_rent_recipient_with_split(
+                    test_state, 3_333,
+                )
+                .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_withdraw_with_entire_safety_deposit_to_secondary_recipient(
+                test_state: &mut TestState,
+            ) {
+                create_order(test_state).await;
+                let withdrawer = Keypair::new();
+                prepare_resolvers(
+                    test_state,
+                    &[
+                        test_state.taker_wallet.keypair.pubkey(),
+                        withdrawer.pubkey(),
+                    ],
+                )
+                .await;
+                transfer_lamports(
+                    &mut test_state.context,
+                    WALLET_DEFAULT_LAMPORTS,
+                    &test_state.payer_kp,
+                    &withdrawer.pubkey(),
+                )
+                .await;
+                common_escrow_tests::test_public_withdraw_with_safety_deposit_split(
+                    test_state,
+                    &withdrawer,
+                    0,
+                )
+                .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_withdraw_with_reward_recipient(test_state: &mut TestState) {
+                create_order(test_state).await;
+                let withdrawer = Keypair::new();
+                prepare_resolvers(
+                    test_state,
+                    &[
+                        test_state.taker_wallet.keypair.pubkey(),
+                        withdrawer.pubkey(),
+                    ],
+                )
+                .await;
+                transfer_lamports(
+                    &mut test_state.context,
+                    WALLET_DEFAULT_LAMPORTS,
+                    &test_state.payer_kp,
+                    &withdrawer.pubkey(),
+                )
+                .await;
+                common_escrow_tests::test_public_withdraw_with_reward_recipient(
+                    test_state,
+                    &withdrawer,
+                )
+                .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_withdraw_awards_nothing_at_ramp_start(test_state: &mut TestState) {
+                let withdrawer = Keypair::new();
+                transfer_lamports(
+                    &mut test_state.context,
+                    WALLET_DEFAULT_LAMPORTS,
+                    &test_state.payer_kp,
+                    &withdrawer.pubkey(),
+                )
+                .await;
+                helpers_src::test_public_withdraw_with_ramped_safety_deposit(
+                    test_state,
+                    &withdrawer,
+                    0,
+                )
+                .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_withdraw_awards_part_of_safety_deposit_mid_ramp(
+                test_state: &mut TestState,
+            ) {
+                let withdrawer = Keypair::new();
+                transfer_lamports(
+                    &mut test_state.context,
+                    WALLET_DEFAULT_LAMPORTS,
+                    &test_state.payer_kp,
+                    &withdrawer.pubkey(),
+                )
+                .await;
+                // Halfway through the ramp, half of the safety deposit is awarded
+                helpers_src::test_public_withdraw_with_ramped_safety_deposit(
+                    test_state,
+                    &withdrawer,
+                    DEFAULT_PERIOD_DURATION / 4,
+                )
+                .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_withdraw_awards_entire_safety_deposit_after_ramp(
+                test_state: &mut TestState,
+            ) {
+                let withdrawer = Keypair::new();
+                transfer_lamports(
+                    &mut test_state.context,
+                    WALLET_DEFAULT_LAMPORTS,
+                    &test_state.payer_kp,
+                    &withdrawer.pubkey(),
+                )
+                .await;
+                helpers_src::test_public_withdraw_with_ramped_safety_deposit(
+                    test_state,
+                    &withdrawer,
+                    DEFAULT_PERIOD_DURATION / 2,
+                )
+                .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_withdraw_fails_with_invalid_safety_deposit_split(
+                test_state: &mut TestState,
+            ) {
+                create_order(test_state).await;
+                let withdrawer = Keypair::new();
+                prepare_resolvers(
+                    test_state,
+                    &[
+                        test_state.taker_wallet.keypair.pubkey(),
+                        withdrawer.pubkey(),
+                    ],
+                )
+                .await;
+                transfer_lamports(
+                    &mut test_state.context,
+                    WALLET_DEFAULT_LAMPORTS,
+                    &test_state.payer_kp,
+                    &withdrawer.pubkey(),
+                )
+                .await;
+                common_escrow_tests::test_public_withdraw_fails_with_invalid_safety_deposit_split(
+                    test_state,
+                    &withdrawer,
+                )
+                .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_withdraw_fails_with_wrong_secret(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(
+                    test_state,
+                    &[
+                        test_state.taker_wallet.keypair.pubkey(),
+                        test_state.context.payer.pubkey(),
+                    ],
+                )
+                .await;
+                common_escrow_tests::test_public_withdraw_fails_with_wrong_secret(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_withdraw_fails_with_wrong_taker_ata(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                common_escrow_tests::test_public_withdraw_fails_with_wrong_taker_ata(test_state)
+                    .await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_withdraw_fails_with_wrong_escrow_ata(test_state: &mut TestState) {
+                let diff = 1;
+                let new_amount = test_state.test_arguments.order_amount + diff;
+                test_state.test_arguments.order_amount = new_amount;
+                create_order(test_state).await;
+                test_state.test_arguments.order_amount -= diff;
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                common_escrow_tests::test_public_withdraw_fails_with_wrong_escrow_ata(
+                    test_state, new_amount,
+                )
+                .await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_withdraw_fails_before_start_of_public_withdraw(
+                test_state: &mut TestState,
+            ) {
+                create_order(test_state).await;
+                prepare_resolvers(
+                    test_state,
+                    &[
+                        test_state.taker_wallet.keypair.pubkey(),
+                        test_state.context.payer.pubkey(),
+                    ],
+                )
+                .await;
+                common_escrow_tests::test_public_withdraw_fails_before_start_of_public_withdraw(
+                    test_state,
+                )
+                .await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_withdraw_fails_after_cancellation_start(
+                test_state: &mut TestState,
+            ) {
+                create_order(test_state).await;
+                prepare_resolvers(
+                    test_state,
+                    &[
+                        test_state.taker_wallet.keypair.pubkey(),
+                        test_state.context.payer.pubkey(),
+                    ],
+                )
+                .await;
+                common_escrow_tests::test_public_withdraw_fails_after_cancellation_start(test_state)
+                    .await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_withdraw_fails_without_resolver_access(
+                test_state: &mut TestState,
+            ) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+
+                let withdrawer = Keypair::new();
+                // withdrawer does not have resolver access
+                let transaction = SrcProgram::get_public_withdraw_tx(
+                    test_state,
+                    &escrow,
+                    &escrow_ata,
+                    &withdrawer,
+                );
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        ErrorCode::AccountNotInitialized.into(),
+                    ));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_withdraw_fails_with_incorrect_token(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+
+                test_state.token = <TestState as HasTokenVariant>::Token::deploy_spl_token(
+                    &mut test_state.context,
+                )
+                .await
+                .pubkey();
+                let taker_kp = test_state.taker_wallet.keypair.insecure_clone();
+
+                let transaction =
+                    SrcProgram::get_public_withdraw_tx(test_state, &escrow, &escrow_ata, &taker_kp);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidMint.into()));
+            }
+        }
+
+        mod test_escrow_cancel {
+            use super::*;
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+                common_escrow_tests::test_cancel(test_state, &escrow, &escrow_ata).await
+            }
+
+            // A taker filling its own order holds both roles in the cancellation. The aliased
+            // account receives the rent and the refund once each, as every payout is debited
+            // from the escrow or its ATA
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_by_taker_who_is_maker(test_state: &mut TestState) {
+                test_state.maker_wallet = test_state.taker_wallet.clone();
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+                common_escrow_tests::test_cancel(test_state, &escrow, &escrow_ata).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_emits_escrow_cancelled(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+                let transaction = SrcProgram::get_cancel_tx(test_state, &escrow, &escrow_ata);
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .src_timelocks
+                        .get(Stage::SrcCancellation)
+                        .unwrap(),
+                );
+
+                let events = common_escrow_tests::simulate_tx_events::<_, _, EscrowCancelled>(
+                    test_state,
+                    transaction,
+                )
+                .await;
+                assert_eq!(events.len(), 1);
+                assert_eq!(events[0].order_hash, test_state.order_hash.to_bytes());
+                assert_eq!(events[0].hashlock, test_state.hashlock.to_bytes());
+                assert_eq!(events[0].amount, test_state.test_arguments.escrow_amount);
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_status_follows_timelocks(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                common_escrow_tests::test_escrow_status_follows_timelocks(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_derive_escrow_pda_matches_created_escrow(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                common_escrow_tests::test_derive_escrow_pda_matches_created_escrow(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_rent_matches_escrow_creation(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                common_escrow_tests::test_escrow_rent_matches_escrow_creation(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_with_excess_tokens(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+                let transaction = SrcProgram::get_cancel_tx(test_state, &escrow, &escrow_ata);
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .src_timelocks
+                        .get(Stage::SrcCancellation)
+                        .unwrap(),
+                );
+
+                let (maker_ata, _) = find_user_ata(test_state);
+
+                let excess_amount = 1000;
+                // Send excess tokens to the escrow account
+                mint_excess_tokens(test_state, &escrow_ata, excess_amount).await;
+
+                test_state
+                    .expect_state_change(
+                        transaction,
+                        &[
+                            token_change(
+                                maker_ata,
+                                test_state.test_arguments.escrow_amount + excess_amount,
+                            ),
+                            account_closure(escrow, true),
+                            account_closure(escrow_ata, true),
+                        ],
+                    )
+                    .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_without_maker_ata(test_state: &mut TestState) {
+                type S = <TestState as HasTokenVariant>::Token;
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+
+                // The maker closes their ATA after the escrow is created
+                let (maker_ata, _) = find_user_ata(test_state);
+                let balance = get_token_balance(&mut test_state.context, &maker_ata).await;
+                S::burn_tokens(
+                    &mut test_state.context,
+                    &maker_ata,
+                    &test_state.token,
+                    &test_state.maker_wallet.keypair,
+                    &test_state.payer_kp,
+                    balance,
+                )
+                .await;
+                S::close_ata(
+                    &mut test_state.context,
+                    &maker_ata,
+                    &test_state.maker_wallet.keypair.pubkey(),
+                    &test_state.maker_wallet.keypair.pubkey(),
+                    &test_state.maker_wallet.keypair,
+                )
+                .await;
+
+                let transaction = SrcProgram::get_cancel_tx(test_state, &escrow, &escrow_ata);
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .src_timelocks
+                        .get(Stage::SrcCancellation)
+                        .unwrap(),
+                );
+
+                // The taker pays for the recreated maker ATA out of the escrow ATA rent,
+                // as both token accounts have the same size
+                let escrow_rent =
+                    get_min_rent_for_size(&mut test_state.client, DEFAULT_SRC_ESCROW_SIZE).await;
+
+                test_state
+                    .expect_state_change(
+                        transaction,
+                        &[
+                            native_change(test_state.taker_wallet.keypair.pubkey(), escrow_rent),
+                            account_closure(escrow, true),
+                            account_closure(escrow_ata, true),
+                        ],
+                    )
+                    .await;
+
+                assert_eq!(
+                    get_token_balance(&mut test_state.context, &maker_ata).await,
+                    test_state.test_arguments.escrow_amount
+                );
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cannot_cancel_by_non_maker(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                common_escrow_tests::test_cannot_cancel_by_non_maker(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cannot_cancel_with_wrong_maker_ata(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                common_escrow_tests::test_cannot_cancel_with_wrong_maker_ata(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cannot_cancel_with_wrong_escrow_ata(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, _) = create_escrow(test_state).await;
+
+                test_state.test_arguments.order_amount += 1;
+                test_state.test_arguments.escrow_amount += 1;
+                create_order(test_state).await;
+
+                let (_, escrow_ata_2) = create_escrow(test_state).await;
+
+                let transaction = SrcProgram::get_cancel_tx(test_state, &escrow, &escrow_ata_2);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(ErrorCode::ConstraintTokenOwner.into()))
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cannot_cancel_before_cancellation_start(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                common_escrow_tests::test_cannot_cancel_before_cancellation_start(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_fails_with_insufficient_escrow_balance(
+                test_state: &mut TestState,
+            ) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                common_escrow_tests::test_cancel_fails_with_insufficient_escrow_balance(test_state)
+                    .await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_fails_with_incorrect_token(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+
+                test_state.token = <TestState as HasTokenVariant>::Token::deploy_spl_token(
+                    &mut test_state.context,
+                )
+                .await
+                .pubkey();
+
+                let transaction = SrcProgram::get_cancel_tx(test_state, &escrow, &escrow_ata);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidMint.into()));
+            }
+        }
+
+        mod test_order_cancel {
+            use super::*;
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_cancel(test_state: &mut TestState) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                helpers_src::test_order_cancel(test_state).await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_cancel_emits_order_cancelled(test_state: &mut TestState) {
+                let (order, order_ata) = create_order(test_state).await;
+                let transaction = get_cancel_order_tx(test_state, &order, &order_ata, None);
+
+                let events = common_escrow_tests::simulate_tx_events::<_, _, OrderCancelled>(
+                    test_state,
+                    transaction,
+                )
+                .await;
+                assert_eq!(events.len(), 1);
+                assert_eq!(events[0].order_hash, test_state.order_hash.to_bytes());
+                assert_eq!(events[0].amount, test_state.test_arguments.order_amount);
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_rescue_tokens_when_order_is_deleted(test_state: &mut TestState) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                helpers_src::test_rescue_tokens_when_order_is_deleted(test_state).await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_cancel_fails_with_incorrect_token(test_state: &mut TestState) {
+                let (order, order_ata) = create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                test_state.token = <TestState as HasTokenVariant>::Token::deploy_spl_token(
+                    &mut test_state.context,
+                )
+                .await
+                .pubkey();
+
+                let transaction = get_cancel_order_tx(test_state, &order, &order_ata, None);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidMint.into()));
+            }
+        }
+
+        mod test_order_extend {
+            use super::*;
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_extend_order(test_state: &mut TestState) {
+                let (order, _) = create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                let old_expiration_time = test_state.test_arguments.expiration_time;
+                let transaction = get_extend_order_tx(
+                    test_state,
+                    &order,
+                    old_expiration_time + DEFAULT_PERIOD_DURATION,
+                    &test_state.maker_wallet.keypair,
+                );
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success();
+
+                // The order stays fillable after its original expiration time
+                set_time(&mut test_state.context, old_expiration_time + 1);
+
+                let (_, _, transaction) = create_escrow_data(test_state);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success();
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_extend_order_fails_by_non_creator(test_state: &mut TestState) {
+                let (order, _) = create_order(test_state).await;
+
+                let transaction = get_extend_order_tx(
+                    test_state,
+                    &order,
+                    test_state.test_arguments.expiration_time + DEFAULT_PERIOD_DURATION,
+                    &test_state.taker_wallet.keypair,
+                );
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidAccount.into()));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_extend_order_fails_with_not_later_expiration_time(
+                test_state: &mut TestState,
+            ) {
+                let (order, _) = create_order(test_state).await;
+
+                let transaction = get_extend_order_tx(
+                    test_state,
+                    &order,
+                    test_state.test_arguments.expiration_time,
+                    &test_state.maker_wallet.keypair,
+                );
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::InvalidExpirationTime.into(),
+                    ));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_extend_order_fails_after_expiration(test_state: &mut TestState) {
+                let (order, _) = create_order(test_state).await;
+
+                set_time(
+                    &mut test_state.context,
+                    test_state.test_arguments.expiration_time,
+                );
+
+                let transaction = get_extend_order_tx(
+                    test_state,
+                    &order,
+                    test_state.test_arguments.expiration_time + DEFAULT_PERIOD_DURATION,
+                    &test_state.maker_wallet.keypair,
+                );
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::OrderHasExpired.into()));
+            }
+        }
+
+        mod test_order_cancellation_auction_update {
+            use super::*;
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_update_cancellation_auction(test_state: &mut TestState) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (order, order_ata) = create_order(test_state).await;
+
+                let new_max_premium = test_state.test_arguments.max_cancellation_premium * 2;
+                let new_duration = test_state.test_arguments.cancellation_auction_duration / 2;
+                let transaction = get_update_cancellation_auction_tx(
+                    test_state,
+                    new_max_premium,
+                    new_duration,
+                    &test_state.maker_wallet.keypair,
+                );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success();
+
+                // The raised premium is reached at the end of the shortened auction
+                test_state.test_arguments.reward_limit = new_max_premium;
+                let transaction =
+                    get_cancel_order_by_resolver_tx(test_state, &order, &order_ata, None);
+
+                set_time(
+                    &mut test_state.context,
+                    test_state.test_arguments.expiration_time + new_duration,
+                );
+                test_state
+                    .expect_state_change(
+                        transaction,
+                        &[
+                            native_change(
+                                test_state.taker_wallet.keypair.pubkey(),
+                                new_max_premium,
+                            ),
+                            account_closure(order, true),
+                            account_closure(order_ata, true),
+                        ],
+                    )
+                    .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_update_cancellation_auction_fails_with_premium_above_order_balance(
+                test_state: &mut TestState,
+            ) {
+                let (_, order_ata) = create_order(test_state).await;
+
+                let order_ata_lamports = test_state.client.get_balance(order_ata).await.unwrap();
+                let transaction = get_update_cancellation_auction_tx(
+                    test_state,
+                    order_ata_lamports + 1,
+                    test_state.test_arguments.cancellation_auction_duration,
+                    &test_state.maker_wallet.keypair,
+                );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::InvalidCancellationFee.into(),
+                    ));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_update_cancellation_auction_fails_by_non_creator(
+                test_state: &mut TestState,
+            ) {
+                create_order(test_state).await;
+
+                let transaction = get_update_cancellation_auction_tx(
+                    test_state,
+                    test_state.test_arguments.max_cancellation_premium,
+                    test_state.test_arguments.cancellation_auction_duration,
+                    &test_state.taker_wallet.keypair,
+                );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidAccount.into()));
+            }
+        }
+
+        mod test_order_cancel_with_excess_tokens {
+            use super::*;
 
             #[test_context(TestState)]
             #[tokio::test]
@@ -1122,255 +2800,1945 @@ run_for_tokens!(
                 let (order, order_ata) = create_order(test_state).await;
                 let transaction = get_cancel_order_tx(test_state, &order, &order_ata, None);
 
-                let (maker_ata, _) = find_user_ata(test_state);
+                let (maker_ata, _) = find_user_ata(test_state);
+
+                let excess_amount = 1000;
+                // Send excess tokens to the order account
+                mint_excess_tokens(test_state, &order_ata, excess_amount).await;
+
+                let balance_changes: Vec<StateChange> = vec![
+                    token_change(
+                        maker_ata,
+                        test_state.test_arguments.order_amount + excess_amount,
+                    ),
+                    account_closure(order, true),
+                    account_closure(order_ata, true),
+                ];
+
+                test_state
+                    .expect_state_change(transaction, &balance_changes)
+                    .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_cancel_by_resolver(test_state: &mut TestState) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (order, order_ata) = create_order(test_state).await;
+                let transaction =
+                    get_cancel_order_by_resolver_tx(test_state, &order, &order_ata, None);
+
+                let excess_amount = 1000;
+                mint_excess_tokens(test_state, &order_ata, excess_amount).await;
+
+                set_time(
+                    &mut test_state.context,
+                    test_state.test_arguments.expiration_time
+                        + test_state.test_arguments.cancellation_auction_duration,
+                );
+
+                let token_account_rent = get_min_rent_for_size(
+                    &mut test_state.client,
+                    <TestState as HasTokenVariant>::Token::get_token_account_size(),
+                )
+                .await;
+                let order_rent =
+                    get_min_rent_for_size(&mut test_state.client, DEFAULT_ORDER_SIZE).await;
+                let resolver_premium = test_state.test_arguments.max_cancellation_premium;
+                let (maker_ata, _) = find_user_ata(test_state);
+
+                // The maker gets every token of the order ATA, while the premium only comes out
+                // of the lamports
+                let balance_changes: Vec<StateChange> = vec![
+                    token_change(
+                        maker_ata,
+                        test_state.test_arguments.order_amount + excess_amount,
+                    ),
+                    native_change(
+                        test_state.maker_wallet.keypair.pubkey(),
+                        token_account_rent + order_rent - resolver_premium,
+                    ),
+                    native_change(test_state.taker_wallet.keypair.pubkey(), resolver_premium),
+                    account_closure(order, true),
+                    account_closure(order_ata, true),
+                ];
+
+                test_state
+                    .expect_state_change(transaction, &balance_changes)
+                    .await;
+            }
+        }
+
+        mod test_order_cancel_by_resolver {
+            use super::*;
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_by_resolver_for_free_at_the_auction_start(
+                test_state: &mut TestState,
+            ) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                helpers_src::test_cancel_by_resolver_for_free_at_the_auction_start(test_state)
+                    .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_by_resolver_emits_order_cancelled_by_resolver(
+                test_state: &mut TestState,
+            ) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (order, order_ata) = create_order(test_state).await;
+                let transaction =
+                    get_cancel_order_by_resolver_tx(test_state, &order, &order_ata, None);
+
+                // The premium has reached its maximum at the end of the auction
+                set_time(
+                    &mut test_state.context,
+                    test_state.test_arguments.expiration_time
+                        + test_state.test_arguments.cancellation_auction_duration,
+                );
+
+                let events = common_escrow_tests::simulate_tx_events::<
+                    _,
+                    _,
+                    OrderCancelledByResolver,
+                >(test_state, transaction)
+                .await;
+                assert_eq!(events.len(), 1);
+                assert_eq!(events[0].order_hash, test_state.order_hash.to_bytes());
+                assert_eq!(events[0].amount, test_state.test_arguments.order_amount);
+                assert_eq!(events[0].resolver, test_state.taker_wallet.keypair.pubkey());
+                assert_eq!(
+                    events[0].premium,
+                    test_state.test_arguments.max_cancellation_premium
+                );
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_by_resolver_fails_with_reward_limit_above_order_balance(
+                test_state: &mut TestState,
+            ) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (order, order_ata) = create_order(test_state).await;
+
+                let order_ata_lamports = test_state.client.get_balance(order_ata).await.unwrap();
+                test_state.test_arguments.reward_limit = order_ata_lamports + 1;
+                let transaction =
+                    get_cancel_order_by_resolver_tx(test_state, &order, &order_ata, None);
+
+                set_time(
+                    &mut test_state.context,
+                    test_state.test_arguments.expiration_time
+                        + test_state.test_arguments.cancellation_auction_duration,
+                );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::PremiumExceedsBalance.into(),
+                    ));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_by_resolver_fails_if_premium_exceeds_order_balance(
+                test_state: &mut TestState,
+            ) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (order, order_ata) = create_order(test_state).await;
+
+                // Emulate an order ATA holding less than the premium the order was created with
+                let mut order_ata_account = test_state
+                    .client
+                    .get_account(order_ata)
+                    .await
+                    .unwrap()
+                    .unwrap();
+                order_ata_account.lamports = test_state.test_arguments.max_cancellation_premium - 1;
+                test_state
+                    .context
+                    .set_account(&order_ata, &order_ata_account.into());
+
+                test_state.test_arguments.reward_limit =
+                    test_state.test_arguments.max_cancellation_premium;
+                let transaction =
+                    get_cancel_order_by_resolver_tx(test_state, &order, &order_ata, None);
+
+                set_time(
+                    &mut test_state.context,
+                    test_state.test_arguments.expiration_time
+                        + test_state.test_arguments.cancellation_auction_duration,
+                );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::PremiumExceedsBalance.into(),
+                    ));
+            }
+
+            // Checks that cancellation by resolver does not invoke the system program
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_by_resolver_with_zero_maker_amount(test_state: &mut TestState) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                let token_account_rent = get_min_rent_for_size(
+                    &mut test_state.client,
+                    <TestState as HasTokenVariant>::Token::get_token_account_size(),
+                )
+                .await;
+
+                // By setting these two test arguments below to token_account_rent and current time to
+                // more than (test_state.test_arguments.expiration_time +
+                // auction_duration)
+                // we ensure that the make_amount will evaluate to zero.
+                test_state.test_arguments.max_cancellation_premium = token_account_rent;
+                test_state.test_arguments.reward_limit = token_account_rent;
+
+                let (order, order_ata) = create_order(test_state).await;
+                let transaction =
+                    get_cancel_order_by_resolver_tx(test_state, &order, &order_ata, None);
+
+                set_time(
+                    &mut test_state.context,
+                    test_state.test_arguments.expiration_time
+                        + test_state.test_arguments.cancellation_auction_duration
+                        + 1,
+                );
+
+                let result = test_state
+                    .client
+                    .simulate_transaction(transaction)
+                    .await
+                    .expect("Simulation RPC failed");
+
+                // Extract the simulation details
+                let sim_details = result
+                    .simulation_details
+                    .expect("Simulation details not found");
+
+                // Check it does not contain system program invocation for the transfer transaction
+                assert!(!sim_details
+                    .logs
+                    .contains(&"Program 11111111111111111111111111111111 invoke [1]".to_string()));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_by_resolver_for_free_at_the_auction_start_with_excess_tokens(
+                test_state: &mut TestState,
+            ) {
+                let (order, order_ata) = create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let transaction =
+                    get_cancel_order_by_resolver_tx(test_state, &order, &order_ata, None);
+
+                set_time(
+                    &mut test_state.context,
+                    test_state.test_arguments.expiration_time,
+                );
+
+                let (maker_ata, _) = find_user_ata(test_state);
+
+                let excess_amount = 1000;
+                // Send excess tokens to the order account
+                mint_excess_tokens(test_state, &order_ata, excess_amount).await;
+
+                let balance_changes: Vec<StateChange> = vec![
+                    token_change(
+                        maker_ata,
+                        test_state.test_arguments.order_amount + excess_amount,
+                    ),
+                    account_closure(order, true),
+                    account_closure(order_ata, true),
+                ];
+
+                test_state
+                    .expect_state_change(transaction, &balance_changes)
+                    .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_by_resolver_to_maker_lamport_recipient(
+                test_state: &mut TestState,
+            ) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                helpers_src::test_cancel_by_resolver_to_maker_lamport_recipient(test_state).await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_by_resolver_fails_with_lamport_recipient_not_approved_by_maker(
+                test_state: &mut TestState,
+            ) {
+                test_state.test_arguments.maker_lamport_recipient = Some(Keypair::new().pubkey());
+                let (order, order_ata) = create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                set_time(
+                    &mut test_state.context,
+                    test_state.test_arguments.expiration_time,
+                );
+
+                // Drop the maker signature so that the recipient is chosen by the resolver alone
+                let mut instruction =
+                    get_cancel_order_by_resolver_ix(test_state, &order, &order_ata, None);
+                instruction.accounts[2].is_signer = false;
+                let transaction = Transaction::new_signed_with_payer(
+                    &[instruction],
+                    Some(&test_state.payer_kp.pubkey()),
+                    &[&test_state.payer_kp, &test_state.taker_wallet.keypair],
+                    test_state.context.last_blockhash,
+                );
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::UnauthorizedLamportRecipient.into(),
+                    ));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_by_resolver_at_different_points(test_state: &mut TestState) {
+                helpers_src::test_cancel_by_resolver_at_different_points(test_state, false, None)
+                    .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_by_resolver_after_auction(test_state: &mut TestState) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                helpers_src::test_cancel_by_resolver_after_auction(test_state).await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_by_resolver_reward_less_then_auction_calculated(
+                test_state: &mut TestState,
+            ) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                helpers_src::test_cancel_by_resolver_reward_less_then_auction_calculated(
+                    test_state,
+                )
+                .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_by_resolver_pays_from_order_with_reward_below_premium(
+                test_state: &mut TestState,
+            ) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                helpers_src::test_cancel_by_resolver_pays_from_order(test_state, false).await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_by_resolver_pays_from_order_with_reward_above_premium(
+                test_state: &mut TestState,
+            ) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                helpers_src::test_cancel_by_resolver_pays_from_order(test_state, true).await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_preview_cancellation_premium_matches_resolver_reward(
+                test_state: &mut TestState,
+            ) {
+                let (order, order_ata) = create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                // Project the premium to a moment in the middle of the cancellation auction
+                let cancellation_time = test_state.test_arguments.expiration_time
+                    + test_state.test_arguments.cancellation_auction_duration / 2;
+                let transaction = get_preview_cancellation_premium_tx(
+                    test_state,
+                    &order,
+                    Some(cancellation_time),
+                );
+                let return_data =
+                    common_escrow_tests::simulate_tx_return_data(test_state, transaction).await;
+                let previewed_premium = u64::from_le_bytes(return_data.try_into().unwrap());
+                assert!(previewed_premium > 0);
+
+                set_time(&mut test_state.context, cancellation_time);
+                let transaction =
+                    get_cancel_order_by_resolver_tx(test_state, &order, &order_ata, None);
+
+                test_state
+                    .expect_state_change(
+                        transaction,
+                        &[native_change(
+                            test_state.taker_wallet.keypair.pubkey(),
+                            previewed_premium,
+                        )],
+                    )
+                    .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_by_resolver_is_forbidden_with_zero_premium(
+                test_state: &mut TestState,
+            ) {
+                test_state.test_arguments.max_cancellation_premium = 0;
+                let (order, order_ata) = create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                set_time(
+                    &mut test_state.context,
+                    test_state.test_arguments.expiration_time
+                        + test_state.test_arguments.cancellation_auction_duration
+                        + 1,
+                );
+
+                let transaction =
+                    get_cancel_order_by_resolver_tx(test_state, &order, &order_ata, None);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::CancelOrderByResolverIsForbidden.into(),
+                    ));
+
+                // The maker can still cancel the order
+                let transaction = get_cancel_order_tx(test_state, &order, &order_ata, None);
+                let (maker_ata, _) = find_user_ata(test_state);
+                test_state
+                    .expect_state_change(
+                        transaction,
+                        &[
+                            token_change(maker_ata, test_state.test_arguments.order_amount),
+                            account_closure(order, true),
+                            account_closure(order_ata, true),
+                        ],
+                    )
+                    .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_by_resolver_fails_if_order_is_not_expired(
+                test_state: &mut TestState,
+            ) {
+                let (order, order_ata) = create_order(test_state).await;
+
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let transaction =
+                    get_cancel_order_by_resolver_tx(test_state, &order, &order_ata, None);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::OrderNotExpired.into()));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_by_resolver_fails_with_incorrect_token(
+                test_state: &mut TestState,
+            ) {
+                let (order, order_ata) = create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                test_state.token = <TestState as HasTokenVariant>::Token::deploy_spl_token(
+                    &mut test_state.context,
+                )
+                .await
+                .pubkey();
+
+                let transaction =
+                    get_cancel_order_by_resolver_tx(test_state, &order, &order_ata, None);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidMint.into()));
+            }
+        }
+
+        mod test_order_public_cancel {
+            use super::*;
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_cancel_by_taker(test_state: &mut TestState) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                create_order(test_state).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+                test_public_cancel_escrow(
+                    test_state,
+                    &escrow,
+                    &escrow_ata,
+                    &test_state.taker_wallet.keypair.insecure_clone(),
+                )
+                .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_cancel_by_any_account(test_state: &mut TestState) {
+                let canceller = Keypair::new();
+                transfer_lamports(
+                    &mut test_state.context,
+                    WALLET_DEFAULT_LAMPORTS,
+                    &test_state.payer_kp,
+                    &canceller.pubkey(),
+                )
+                .await;
+
+                prepare_resolvers(
+                    test_state,
+                    &[test_state.taker_wallet.keypair.pubkey(), canceller.pubkey()],
+                )
+                .await;
+
+                create_order(test_state).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+                test_public_cancel_escrow(test_state, &escrow, &escrow_ata, &canceller).await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_cancel_emits_escrow_public_cancelled(test_state: &mut TestState) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                create_order(test_state).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+                let canceller = test_state.taker_wallet.keypair.insecure_clone();
+                let transaction =
+                    create_public_escrow_cancel_tx(test_state, &escrow, &escrow_ata, &canceller);
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .src_timelocks
+                        .get(Stage::SrcPublicCancellation)
+                        .unwrap(),
+                );
+
+                let events =
+                    common_escrow_tests::simulate_tx_events::<_, _, EscrowPublicCancelled>(
+                        test_state,
+                        transaction,
+                    )
+                    .await;
+                assert_eq!(events.len(), 1);
+                assert_eq!(events[0].order_hash, test_state.order_hash.to_bytes());
+                assert_eq!(events[0].hashlock, test_state.hashlock.to_bytes());
+                assert_eq!(events[0].amount, test_state.test_arguments.escrow_amount);
+                assert_eq!(events[0].canceller, canceller.pubkey());
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_cancel_settlement_matches_balance_changes(
+                test_state: &mut TestState,
+            ) {
+                let canceller = Keypair::new();
+                transfer_lamports(
+                    &mut test_state.context,
+                    WALLET_DEFAULT_LAMPORTS,
+                    &test_state.payer_kp,
+                    &canceller.pubkey(),
+                )
+                .await;
+                prepare_resolvers(
+                    test_state,
+                    &[test_state.taker_wallet.keypair.pubkey(), canceller.pubkey()],
+                )
+                .await;
+                create_order(test_state).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+                let transaction =
+                    create_public_escrow_cancel_tx(test_state, &escrow, &escrow_ata, &canceller);
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .src_timelocks
+                        .get(Stage::SrcPublicCancellation)
+                        .unwrap(),
+                );
+
+                let events = common_escrow_tests::simulate_tx_events::<_, _, PublicCancelSettled>(
+                    test_state,
+                    transaction.clone(),
+                )
+                .await;
+                assert_eq!(events.len(), 1);
+                let event = &events[0];
+                assert_eq!(event.order_hash, test_state.order_hash.to_bytes());
+                assert_eq!(event.taker, test_state.taker_wallet.keypair.pubkey());
+                assert_eq!(event.maker, test_state.maker_wallet.keypair.pubkey());
+                assert_eq!(event.payer, canceller.pubkey());
+                assert_eq!(
+                    event.deposit_to_payer,
+                    test_state.test_arguments.safety_deposit
+                );
+
+                let (maker_ata, _) = find_user_ata(test_state);
+                test_state
+                    .expect_state_change(
+                        transaction,
+                        &[
+                            token_change(maker_ata, test_state.test_arguments.escrow_amount),
+                            native_change(canceller.pubkey(), event.deposit_to_payer),
+                            native_change(
+                                test_state.taker_wallet.keypair.pubkey(),
+                                event.rent_to_taker,
+                            ),
+                            account_closure(escrow, true),
+                            account_closure(escrow_ata, true),
+                        ],
+                    )
+                    .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cannot_public_cancel_before_public_cancellation_start(
+                test_state: &mut TestState,
+            ) {
+                create_order(test_state).await;
+                prepare_resolvers(
+                    test_state,
+                    &[
+                        test_state.taker_wallet.keypair.pubkey(),
+                        test_state.payer_kp.pubkey(),
+                    ],
+                )
+                .await;
+
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+                let transaction = create_public_escrow_cancel_tx(
+                    test_state,
+                    &escrow,
+                    &escrow_ata,
+                    &test_state.payer_kp,
+                );
+
+                set_time(
+                    &mut test_state.context,
+                    test_state
+                        .test_arguments
+                        .src_timelocks
+                        .get(Stage::SrcCancellation)
+                        .unwrap(),
+                );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidTime.into()))
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_public_cancel_fails_with_incorrect_token(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+
+                test_state.token = <TestState as HasTokenVariant>::Token::deploy_spl_token(
+                    &mut test_state.context,
+                )
+                .await
+                .pubkey();
+                let taker_kp = test_state.taker_wallet.keypair.insecure_clone();
+
+                let transaction =
+                    create_public_escrow_cancel_tx(test_state, &escrow, &escrow_ata, &taker_kp);
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidMint.into()));
+            }
+        }
+
+        mod test_order_rescue_funds_for_order {
+            use super::*;
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_rescue_all_tokens_from_order_and_close_ata(test_state: &mut TestState) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                helpers_src::test_rescue_all_tokens_from_order_and_close_ata(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_rescue_all_tokens_from_order_with_rent_recipient(
+                test_state: &mut TestState,
+            ) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                helpers_src::test_rescue_all_tokens_from_order_with_rent_recipient(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_rescue_part_of_tokens_from_order_and_not_close_ata(
+                test_state: &mut TestState,
+            ) {
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                helpers_src::test_rescue_part_of_tokens_from_order_and_not_close_ata(test_state)
+                    .await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cannot_rescue_funds_from_order_before_rescue_delay_pass(
+                test_state: &mut TestState,
+            ) {
+                type S = <TestState as HasTokenVariant>::Token;
+
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (order, _) = create_order(test_state).await;
+
+                let token_to_rescue = S::deploy_spl_token(&mut test_state.context).await.pubkey();
+                let order_ata = S::initialize_spl_associated_account(
+                    &mut test_state.context,
+                    &token_to_rescue,
+                    &order,
+                )
+                .await;
+                let taker_ata = S::initialize_spl_associated_account(
+                    &mut test_state.context,
+                    &token_to_rescue,
+                    &test_state.taker_wallet.keypair.pubkey(),
+                )
+                .await;
+
+                S::mint_spl_tokens(
+                    &mut test_state.context,
+                    &token_to_rescue,
+                    &order_ata,
+                    &test_state.payer_kp.pubkey(),
+                    &test_state.payer_kp,
+                    test_state.test_arguments.rescue_amount,
+                )
+                .await;
+
+                let transaction = get_rescue_funds_from_order_tx(
+                    test_state,
+                    &order,
+                    &order_ata,
+                    &token_to_rescue,
+                    &taker_ata,
+                );
+
+                set_time(
+                    &mut test_state.context,
+                    test_state.init_timestamp + common::constants::RESCUE_DELAY - 100,
+                );
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidRescueStart.into()));
+            }
+
+            // #[test_context(TestState)]
+            // #[tokio::test]
+            // async fn test_cannot_rescue_funds_from_order_by_non_recipient(test_state: &mut TestState) { // TODO: return after implement whitelist
+            //     helpers_src::test_cannot_rescue_funds_from_order_by_non_recipient(test_state).await
+            // }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cannot_rescue_funds_from_order_with_wrong_taker_ata(
+                test_state: &mut TestState,
+            ) {
+                type S = <TestState as HasTokenVariant>::Token;
+
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (order, _) = create_order(test_state).await;
+
+                let token_to_rescue = S::deploy_spl_token(&mut test_state.context).await.pubkey();
+                let order_ata = S::initialize_spl_associated_account(
+                    &mut test_state.context,
+                    &token_to_rescue,
+                    &order,
+                )
+                .await;
+
+                S::mint_spl_tokens(
+                    &mut test_state.context,
+                    &token_to_rescue,
+                    &order_ata,
+                    &test_state.payer_kp.pubkey(),
+                    &test_state.payer_kp,
+                    test_state.test_arguments.rescue_amount,
+                )
+                .await;
+
+                let wrong_taker_ata = S::initialize_spl_associated_account(
+                    &mut test_state.context,
+                    &token_to_rescue,
+                    &test_state.maker_wallet.keypair.pubkey(),
+                )
+                .await;
+
+                let transaction = get_rescue_funds_from_order_tx(
+                    test_state,
+                    &order,
+                    &order_ata,
+                    &token_to_rescue,
+                    &wrong_taker_ata,
+                );
+
+                set_time(
+                    &mut test_state.context,
+                    test_state.init_timestamp + common::constants::RESCUE_DELAY + 100,
+                );
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(ErrorCode::ConstraintTokenOwner.into()))
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cannot_rescue_funds_with_wrong_hash_order(test_state: &mut TestState) {
+                type S = <TestState as HasTokenVariant>::Token;
+
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (order, _) = create_order(test_state).await;
+
+                let token_to_rescue = S::deploy_spl_token(&mut test_state.context).await.pubkey();
+                let order_ata = S::initialize_spl_associated_account(
+                    &mut test_state.context,
+                    &token_to_rescue,
+                    &order,
+                )
+                .await;
+
+                S::mint_spl_tokens(
+                    &mut test_state.context,
+                    &token_to_rescue,
+                    &order_ata,
+                    &test_state.payer_kp.pubkey(),
+                    &test_state.payer_kp,
+                    test_state.test_arguments.rescue_amount,
+                )
+                .await;
+
+                let taker_ata = S::initialize_spl_associated_account(
+                    &mut test_state.context,
+                    &token_to_rescue,
+                    &test_state.taker_wallet.keypair.pubkey(),
+                )
+                .await;
+
+                test_state.test_arguments.order_amount += 1; // Change order amount to make hash different
+                let transaction = get_rescue_funds_from_order_tx(
+                    test_state,
+                    &order,
+                    &order_ata,
+                    &token_to_rescue,
+                    &taker_ata,
+                );
+
+                set_time(
+                    &mut test_state.context,
+                    test_state.init_timestamp + common::constants::RESCUE_DELAY + 100,
+                );
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(ErrorCode::ConstraintSeeds.into()))
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cannot_rescue_funds_from_order_with_wrong_order_ata(
+                test_state: &mut TestState,
+            ) {
+                type S = <TestState as HasTokenVariant>::Token;
+
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                let (order, order_ata) = create_order(test_state).await;
+
+                let token_to_rescue = S::deploy_spl_token(&mut test_state.context).await.pubkey();
+                let taker_ata = S::initialize_spl_associated_account(
+                    &mut test_state.context,
+                    &token_to_rescue,
+                    &test_state.taker_wallet.keypair.pubkey(),
+                )
+                .await;
+
+                let transaction = get_rescue_funds_from_order_tx(
+                    test_state,
+                    &order,
+                    &order_ata, // Use order ata for order mint, but not for token to rescue
+                    &token_to_rescue,
+                    &taker_ata,
+                );
+
+                set_time(
+                    &mut test_state.context,
+                    test_state.init_timestamp + common::constants::RESCUE_DELAY + 100,
+                );
+
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(ErrorCode::ConstraintAssociated.into()))
+            }
+        }
+
+        mod test_order_rescue_funds_for_escrow {
+            use super::*;
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_rescue_all_tokens_and_close_ata(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(
+                    test_state,
+                    &[
+                        test_state.taker_wallet.keypair.pubkey(),
+                        test_state.maker_wallet.keypair.pubkey(),
+                    ],
+                )
+                .await;
+                common_escrow_tests::test_rescue_all_tokens_and_close_ata(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_rescue_fails_with_frozen_escrow_ata(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(
+                    test_state,
+                    &[
+                        test_state.taker_wallet.keypair.pubkey(),
+                        test_state.maker_wallet.keypair.pubkey(),
+                    ],
+                )
+                .await;
+                common_escrow_tests::test_rescue_fails_with_frozen_escrow_ata(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_rescue_all_tokens_with_rent_recipient(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(
+                    test_state,
+                    &[
+                        test_state.taker_wallet.keypair.pubkey(),
+                        test_state.maker_wallet.keypair.pubkey(),
+                    ],
+                )
+                .await;
+                common_escrow_tests::test_rescue_all_tokens_with_rent_recipient(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_rescue_fails_with_non_system_rent_recipient(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(
+                    test_state,
+                    &[
+                        test_state.taker_wallet.keypair.pubkey(),
+                        test_state.maker_wallet.keypair.pubkey(),
+                    ],
+                )
+                .await;
+                common_escrow_tests::test_rescue_fails_with_non_system_rent_recipient(test_state)
+                    .await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_rescue_part_of_tokens_and_not_close_ata(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(
+                    test_state,
+                    &[
+                        test_state.taker_wallet.keypair.pubkey(),
+                        test_state.maker_wallet.keypair.pubkey(),
+                    ],
+                )
+                .await;
+                common_escrow_tests::test_rescue_part_of_tokens_and_not_close_ata(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_rescue_tokens_when_escrow_is_deleted(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(
+                    test_state,
+                    &[
+                        test_state.taker_wallet.keypair.pubkey(),
+                        test_state.maker_wallet.keypair.pubkey(),
+                    ],
+                )
+                .await;
+                common_escrow_tests::test_rescue_tokens_when_escrow_is_deleted(test_state).await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cannot_rescue_funds_before_rescue_delay_pass(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(
+                    test_state,
+                    &[
+                        test_state.taker_wallet.keypair.pubkey(),
+                        test_state.maker_wallet.keypair.pubkey(),
+                    ],
+                )
+                .await;
+                common_escrow_tests::test_cannot_rescue_funds_before_rescue_delay_pass(test_state)
+                    .await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cannot_rescue_funds_by_non_recipient(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(
+                    test_state,
+                    &[
+                        test_state.taker_wallet.keypair.pubkey(),
+                        test_state.maker_wallet.keypair.pubkey(),
+                    ],
+                )
+                .await;
+                common_escrow_tests::test_cannot_rescue_funds_by_non_recipient(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cannot_rescue_funds_with_wrong_taker_ata(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(
+                    test_state,
+                    &[
+                        test_state.taker_wallet.keypair.pubkey(),
+                        test_state.maker_wallet.keypair.pubkey(),
+                    ],
+                )
+                .await;
+                common_escrow_tests::test_cannot_rescue_funds_with_wrong_taker_ata(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cannot_rescue_funds_with_wrong_order_ata(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(
+                    test_state,
+                    &[
+                        test_state.taker_wallet.keypair.pubkey(),
+                        test_state.maker_wallet.keypair.pubkey(),
+                    ],
+                )
+                .await;
+                common_escrow_tests::test_cannot_rescue_funds_with_wrong_escrow_ata(test_state)
+                    .await
+            }
+        }
+
+        mod test_order_creation_cost {
+            use super::*;
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_creation_tx_cost(test_state: &mut TestState) {
+                common_escrow_tests::test_escrow_creation_tx_cost(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_creation_compute_units(test_state: &mut TestState) {
+                helpers_src::test_order_creation_tx_cost(test_state).await
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_simple_compute_units(test_state: &mut TestState) {
+                // NOTE: To actually see the output from this test, use the `--show-output` flag
+                // `cargo test test_escrow_creation_simple_compute_units -- --show-output`
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                let (escrow, escrow_ata, transaction) = create_escrow_data(test_state);
+                let general_units =
+                    common_escrow_tests::measure_tx_compute_units(test_state, transaction).await;
+                let transaction = get_create_escrow_simple_tx(test_state, &escrow, &escrow_ata);
+                let simple_units =
+                    common_escrow_tests::measure_tx_compute_units(test_state, transaction).await;
+                println!(
+                    "CU cost for create escrow: {}, simple: {}",
+                    general_units, simple_units
+                );
+
+                assert!(simple_units < general_units);
+            }
+        }
+
+        mod test_order_creation_by_delegate {
+            use super::*;
+
+            // Creates a delegate with lamports to pay for the order creation
+            async fn create_funded_delegate(test_state: &mut TestState) -> Keypair {
+                let delegate = Keypair::new();
+                transfer_lamports(
+                    &mut test_state.context,
+                    WALLET_DEFAULT_LAMPORTS,
+                    &test_state.payer_kp,
+                    &delegate.pubkey(),
+                )
+                .await;
+                delegate
+            }
+
+            fn get_create_order_by_delegate_tx(
+                test_state: &mut TestState,
+                delegate: &Keypair,
+            ) -> Transaction {
+                test_state.order_hash = get_order_hash(test_state);
+                let (order, order_ata) = get_order_addresses(test_state);
+                let instruction =
+                    get_create_order_ix(test_state, &order, &order_ata, Some(&delegate.pubkey()));
+                // The maker does not sign, the delegate funds the order and pays for its accounts
+                Transaction::new_signed_with_payer(
+                    &[instruction],
+                    Some(&test_state.payer_kp.pubkey()),
+                    &[&test_state.context.payer, delegate],
+                    test_state.context.last_blockhash,
+                )
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_creation_by_delegate(test_state: &mut TestState) {
+                let delegate = create_funded_delegate(test_state).await;
+                let amount = test_state.test_arguments.order_amount;
+                approve_maker_delegate(test_state, &delegate.pubkey(), amount).await;
+
+                let maker_ata = test_state.maker_wallet.token_account;
+                let maker_balance = get_token_balance(&mut test_state.context, &maker_ata).await;
+                let maker = test_state.maker_wallet.keypair.pubkey();
+                let maker_lamports = test_state.client.get_balance(maker).await.unwrap();
+                let transaction = get_create_order_by_delegate_tx(test_state, &delegate);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success();
+
+                // The delegate pays for the order accounts
+                assert_eq!(
+                    test_state.client.get_balance(maker).await.unwrap(),
+                    maker_lamports
+                );
+
+                let (_, order_ata) = get_order_addresses(test_state);
+                assert_eq!(
+                    get_token_balance(&mut test_state.context, &order_ata).await,
+                    amount
+                );
+                assert_eq!(
+                    get_token_balance(&mut test_state.context, &maker_ata).await,
+                    maker_balance - amount
+                );
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_creation_by_delegate_fails_with_insufficient_allowance(
+                test_state: &mut TestState,
+            ) {
+                let delegate = create_funded_delegate(test_state).await;
+                let amount = test_state.test_arguments.order_amount;
+                approve_maker_delegate(test_state, &delegate.pubkey(), amount - 1).await;
+
+                let transaction = get_create_order_by_delegate_tx(test_state, &delegate);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidDelegate.into()));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_creation_by_delegate_fails_if_not_approved(
+                test_state: &mut TestState,
+            ) {
+                let delegate = create_funded_delegate(test_state).await;
+
+                let transaction = get_create_order_by_delegate_tx(test_state, &delegate);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidDelegate.into()));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_creation_fails_without_creator_signature_or_delegate(
+                test_state: &mut TestState,
+            ) {
+                test_state.order_hash = get_order_hash(test_state);
+                let (order, order_ata) = get_order_addresses(test_state);
+                let mut instruction = get_create_order_ix(test_state, &order, &order_ata, None);
+                instruction.accounts[0].is_signer = false;
+                instruction.accounts[1] = AccountMeta::new(test_state.payer_kp.pubkey(), true);
+                let transaction = Transaction::new_signed_with_payer(
+                    &[instruction],
+                    Some(&test_state.payer_kp.pubkey()),
+                    &[&test_state.context.payer],
+                    test_state.context.last_blockhash,
+                );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(ErrorCode::AccountNotSigner.into()));
+            }
+        }
+
+        mod test_order_create_idempotent {
+            use super::*;
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_create_idempotent_creates_order(test_state: &mut TestState) {
+                create_order_idempotent(test_state).await.expect_success();
+
+                let (order, order_ata) = get_order_addresses(test_state);
+                assert!(test_state
+                    .client
+                    .get_account(order)
+                    .await
+                    .unwrap()
+                    .is_some());
+                assert_eq!(
+                    get_token_balance(&mut test_state.context, &order_ata).await,
+                    test_state.test_arguments.order_amount
+                );
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_create_idempotent_retry_with_same_params(test_state: &mut TestState) {
+                create_order_idempotent(test_state).await.expect_success();
+
+                let maker_ata = test_state.maker_wallet.token_account;
+                let maker_balance = get_token_balance(&mut test_state.context, &maker_ata).await;
+                // Update the last blockhash to execute the next identical transaction
+                test_state.context.last_blockhash =
+                    test_state.context.get_new_latest_blockhash().await.unwrap();
+                create_order_idempotent(test_state).await.expect_success();
+
+                // The retry leaves the order funded once
+                let (_, order_ata) = get_order_addresses(test_state);
+                assert_eq!(
+                    get_token_balance(&mut test_state.context, &order_ata).await,
+                    test_state.test_arguments.order_amount
+                );
+                assert_eq!(
+                    get_token_balance(&mut test_state.context, &maker_ata).await,
+                    maker_balance
+                );
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_create_idempotent_retry_fails_with_different_params(
+                test_state: &mut TestState,
+            ) {
+                create_order_idempotent(test_state).await.expect_success();
+
+                // The expiration time is not part of the order hash, so the retry targets the same order
+                test_state.test_arguments.expiration_time += 1;
+                create_order_idempotent(test_state)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::OrderParamsMismatch.into(),
+                    ));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_create_idempotent_retry_fails_after_ownership_transfer(
+                test_state: &mut TestState,
+            ) {
+                create_order_idempotent(test_state).await.expect_success();
+
+                let (order, _) = get_order_addresses(test_state);
+                let transaction = get_transfer_order_ownership_tx(
+                    test_state,
+                    &order,
+                    &Pubkey::new_unique(),
+                    &test_state.maker_wallet.keypair,
+                );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success();
+
+                // The previous creator no longer controls the order, even with matching params
+                test_state.context.last_blockhash =
+                    test_state.context.get_new_latest_blockhash().await.unwrap();
+                create_order_idempotent(test_state)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidAccount.into()));
+            }
+        }
+
+        mod test_order_ownership {
+            use super::*;
+
+            async fn transfer_to_new_creator(test_state: &mut TestState, order: &Pubkey) -> Wallet {
+                let new_creator = create_test_wallet(test_state).await;
+
+                let transaction = get_transfer_order_ownership_tx(
+                    test_state,
+                    order,
+                    &new_creator.keypair.pubkey(),
+                    &test_state.maker_wallet.keypair,
+                );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success();
+
+                new_creator
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_new_creator_controls_order(test_state: &mut TestState) {
+                let (order, order_ata) = create_order(test_state).await;
+                let new_creator = transfer_to_new_creator(test_state, &order).await;
+
+                let transaction = get_extend_order_tx(
+                    test_state,
+                    &order,
+                    test_state.test_arguments.expiration_time + DEFAULT_PERIOD_DURATION,
+                    &new_creator.keypair,
+                );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success();
+
+                // The unfilled amount of a cancelled order goes back to the new creator
+                let new_creator_ata = new_creator.token_account;
+                test_state.maker_wallet = new_creator;
+                let transaction = get_cancel_order_tx(test_state, &order, &order_ata, None);
+                test_state
+                    .expect_state_change(
+                        transaction,
+                        &[token_change(
+                            new_creator_ata,
+                            test_state.test_arguments.order_amount,
+                        )],
+                    )
+                    .await;
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_previous_creator_loses_control(test_state: &mut TestState) {
+                let (order, order_ata) = create_order(test_state).await;
+
+                // The maker controls the order before the transfer
+                let transaction = get_extend_order_tx(
+                    test_state,
+                    &order,
+                    test_state.test_arguments.expiration_time + DEFAULT_PERIOD_DURATION,
+                    &test_state.maker_wallet.keypair,
+                );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success();
+
+                transfer_to_new_creator(test_state, &order).await;
+
+                let transaction = get_extend_order_tx(
+                    test_state,
+                    &order,
+                    test_state.test_arguments.expiration_time + 2 * DEFAULT_PERIOD_DURATION,
+                    &test_state.maker_wallet.keypair,
+                );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidAccount.into()));
+
+                let transaction = get_cancel_order_tx(test_state, &order, &order_ata, None);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidAccount.into()));
+
+                // Nor can the previous creator hand the order over again
+                let transaction = get_transfer_order_ownership_tx(
+                    test_state,
+                    &order,
+                    &test_state.maker_wallet.keypair.pubkey(),
+                    &test_state.maker_wallet.keypair,
+                );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidAccount.into()));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_transfer_order_ownership_fails_by_non_creator(
+                test_state: &mut TestState,
+            ) {
+                let (order, _) = create_order(test_state).await;
+
+                let transaction = get_transfer_order_ownership_tx(
+                    test_state,
+                    &order,
+                    &test_state.taker_wallet.keypair.pubkey(),
+                    &test_state.taker_wallet.keypair,
+                );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidAccount.into()));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_transfer_order_ownership_fails_to_default_pubkey(
+                test_state: &mut TestState,
+            ) {
+                let (order, _) = create_order(test_state).await;
+
+                let transaction = get_transfer_order_ownership_tx(
+                    test_state,
+                    &order,
+                    &Pubkey::default(),
+                    &test_state.maker_wallet.keypair,
+                );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidAccount.into()));
+            }
+        }
+
+        mod test_simple_fills {
+            use super::*;
+
+            const SIMPLE_PARTS_AMOUNT: u16 = 3;
+
+            // Fills the tranche by a new whitelisted taker, as a taker can hold a single escrow per fill
+            // amount of an order with simple fills
+            async fn fill_tranche(test_state: &mut TestState, amount: u64) {
+                test_state.taker_wallet = create_test_wallet(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                test_state.test_arguments.escrow_amount = amount;
+                let (_, escrow_ata) = create_escrow(test_state).await;
+                assert_eq!(
+                    get_token_balance(&mut test_state.context, &escrow_ata).await,
+                    amount
+                );
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_fill_order_in_equal_tranches(test_state: &mut TestState) {
+                test_state.test_arguments.order_amount = DEFAULT_ESCROW_AMOUNT + 1;
+                test_state.test_arguments.simple_parts_amount = SIMPLE_PARTS_AMOUNT;
+                let (order, _) = create_order(test_state).await;
+
+                let tranche = test_state.test_arguments.order_amount / SIMPLE_PARTS_AMOUNT as u64;
+                fill_tranche(test_state, tranche).await;
+                fill_tranche(test_state, tranche).await;
+                assert!(test_state
+                    .client
+                    .get_account(order)
+                    .await
+                    .unwrap()
+                    .is_some());
+
+                // The last fill takes the remainder of the division along with its tranche
+                let last_tranche = test_state.test_arguments.order_amount - 2 * tranche;
+                fill_tranche(test_state, last_tranche).await;
+                assert!(test_state
+                    .client
+                    .get_account(order)
+                    .await
+                    .unwrap()
+                    .is_none());
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_simple_fill_fails_with_amount_other_than_tranche(
+                test_state: &mut TestState,
+            ) {
+                test_state.test_arguments.simple_parts_amount = SIMPLE_PARTS_AMOUNT;
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+                let tranche = test_state.test_arguments.order_amount / SIMPLE_PARTS_AMOUNT as u64;
+                test_state.test_arguments.escrow_amount = tranche + 1;
+                let (_, _, transaction) = create_escrow_data(test_state);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidAmount.into()));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_creation_fails_with_simple_and_multiple_fills(
+                test_state: &mut TestState,
+            ) {
+                test_state.test_arguments.simple_parts_amount = SIMPLE_PARTS_AMOUNT;
+                test_state.test_arguments.allow_multiple_fills = true;
+
+                let (_, _, transaction) = create_order_data(test_state);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::InconsistentFillMode.into(),
+                    ));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_creation_fails_with_single_simple_part(test_state: &mut TestState) {
+                test_state.test_arguments.simple_parts_amount = 1;
+
+                let (_, _, transaction) = create_order_data(test_state);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidPartsAmount.into()));
+            }
+        }
+
+        mod test_blocked_mint {
+            use super::*;
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_order_creation_fails_with_blocked_mint(test_state: &mut TestState) {
+                block_mint(test_state, test_state.token).await;
+
+                let (_, _, transaction) = create_order_data(test_state);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::MintBlocked.into()));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_fails_with_blocked_mint(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                block_mint(test_state, test_state.token).await;
+
+                let (_, _, transaction) = create_escrow_data(test_state);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::MintBlocked.into()));
+            }
+
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_with_other_mint_blocked(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                block_mint(test_state, Pubkey::new_unique()).await;
 
-                let excess_amount = 1000;
-                // Send excess tokens to the order account
-                mint_excess_tokens(test_state, &order_ata, excess_amount).await;
+                common_escrow_tests::test_escrow_creation(test_state).await;
+            }
 
-                let balance_changes: Vec<StateChange> = vec![
-                    token_change(
-                        maker_ata,
-                        test_state.test_arguments.order_amount + excess_amount,
-                    ),
-                    account_closure(order, true),
-                    account_closure(order_ata, true),
-                ];
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_unblock_mint_restores_escrow_creation(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                block_mint(test_state, test_state.token).await;
+                unblock_mint(test_state, test_state.token).await;
 
-                test_state
-                    .expect_state_change(transaction, &balance_changes)
-                    .await;
+                common_escrow_tests::test_escrow_creation(test_state).await;
             }
         }
 
-        mod test_order_cancel_by_resolver {
+        mod test_escrow_creation_with_maker_cosign {
             use super::*;
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_cancel_by_resolver_for_free_at_the_auction_start(
-                test_state: &mut TestState,
-            ) {
+            async fn test_escrow_creation_with_maker_cosign(test_state: &mut TestState) {
+                test_state.test_arguments.require_maker_cosign = true;
+                create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                helpers_src::test_cancel_by_resolver_for_free_at_the_auction_start(test_state)
-                    .await;
+
+                let (escrow, escrow_ata, transaction) = create_escrow_data(test_state);
+                let transaction = get_cosigned_create_escrow_tx(test_state, &transaction);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success();
+
+                assert_eq!(
+                    get_token_balance(&mut test_state.context, &escrow_ata).await,
+                    test_state.test_arguments.escrow_amount
+                );
+                assert!(test_state
+                    .client
+                    .get_account(escrow)
+                    .await
+                    .unwrap()
+                    .is_some());
             }
 
-            // Checks the maker_amount transfer transaction is skipped if the maker_amount is zero
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_cancel_by_resolver_with_zero_maker_amount(test_state: &mut TestState) {
+            async fn test_escrow_creation_fails_without_maker_cosign(test_state: &mut TestState) {
+                test_state.test_arguments.require_maker_cosign = true;
+                create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
 
-                let token_account_rent = get_min_rent_for_size(
-                    &mut test_state.client,
-                    <TestState as HasTokenVariant>::Token::get_token_account_size(),
-                )
-                .await;
+                let (_, _, transaction) = create_escrow_data(test_state);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::MakerCosignRequired.into(),
+                    ));
+            }
+        }
 
-                // By setting these two test arguments below to token_account_rent and current time to
-                // more than (test_state.test_arguments.expiration_time +
-                // auction_duration)
-                // we ensure that the make_amount will evaluate to zero.
-                test_state.test_arguments.max_cancellation_premium = token_account_rent;
-                test_state.test_arguments.reward_limit = token_account_rent;
+        mod test_escrow_creation_through_cpi {
+            use super::*;
 
-                let (order, order_ata) = create_order(test_state).await;
-                let transaction =
-                    get_cancel_order_by_resolver_tx(test_state, &order, &order_ata, None);
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_through_cpi_without_top_level_requirement(
+                test_state: &mut TestState,
+            ) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
 
-                set_time(
-                    &mut test_state.context,
-                    test_state.test_arguments.expiration_time
-                        + test_state.test_arguments.cancellation_auction_duration
-                        + 1,
+                let (_, _, transaction) = create_escrow_data(test_state);
+                let transaction = wrap_in_cpi(
+                    test_state,
+                    &transaction,
+                    &[&test_state.context.payer, &test_state.taker_wallet.keypair],
                 );
-
-                let result = test_state
+                test_state
                     .client
-                    .simulate_transaction(transaction)
+                    .process_transaction(transaction)
                     .await
-                    .expect("Simulation RPC failed");
+                    .expect_success();
+            }
 
-                // Extract the simulation details
-                let sim_details = result
-                    .simulation_details
-                    .expect("Simulation details not found");
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_creation_through_cpi_fails_with_top_level_requirement(
+                test_state: &mut TestState,
+            ) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                set_require_top_level(test_state, true).await;
 
-                // Check it does not contain system program invocation for the transfer transaction
-                assert!(!sim_details
-                    .logs
-                    .contains(&"Program 11111111111111111111111111111111 invoke [1]".to_string()));
+                let (_, _, transaction) = create_escrow_data(test_state);
+                let transaction = wrap_in_cpi(
+                    test_state,
+                    &transaction,
+                    &[&test_state.context.payer, &test_state.taker_wallet.keypair],
+                );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::CpiNotAllowed.into()));
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_cancel_by_resolver_for_free_at_the_auction_start_with_excess_tokens(
+            async fn test_escrow_creation_at_top_level_with_top_level_requirement(
                 test_state: &mut TestState,
             ) {
-                let (order, order_ata) = create_order(test_state).await;
+                create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                let transaction =
-                    get_cancel_order_by_resolver_tx(test_state, &order, &order_ata, None);
+                set_require_top_level(test_state, true).await;
 
-                set_time(
-                    &mut test_state.context,
-                    test_state.test_arguments.expiration_time,
-                );
+                common_escrow_tests::test_escrow_creation(test_state).await;
+            }
+        }
 
-                let (maker_ata, _) = find_user_ata(test_state);
+        mod test_escrow_withdraw_with_slot_guard {
+            use super::*;
 
-                let excess_amount = 1000;
-                // Send excess tokens to the order account
-                mint_excess_tokens(test_state, &order_ata, excess_amount).await;
+            const MIN_WITHDRAWAL_SLOTS: u32 = 10;
 
-                let balance_changes: Vec<StateChange> = vec![
-                    token_change(
-                        maker_ata,
-                        test_state.test_arguments.order_amount + excess_amount,
-                    ),
-                    account_closure(order, true),
-                    account_closure(order_ata, true),
-                ];
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_withdraw_fails_before_min_slots(test_state: &mut TestState) {
+                test_state.test_arguments.min_withdrawal_slots = MIN_WITHDRAWAL_SLOTS;
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
 
+                // The withdrawal is open by time, but not enough slots have passed since the escrow
+                // creation
+                let withdrawal_start = test_state
+                    .test_arguments
+                    .src_timelocks
+                    .get(Stage::SrcWithdrawal)
+                    .unwrap();
+                set_time_and_slot(
+                    &mut test_state.context,
+                    withdrawal_start,
+                    MIN_WITHDRAWAL_SLOTS as u64 - 1,
+                );
+                let transaction = SrcProgram::get_withdraw_tx(test_state, &escrow, &escrow_ata);
                 test_state
-                    .expect_state_change(transaction, &balance_changes)
-                    .await;
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(
+                        EscrowError::WithdrawalSlotsNotElapsed.into(),
+                    ));
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_cancel_by_resolver_at_different_points(test_state: &mut TestState) {
-                helpers_src::test_cancel_by_resolver_at_different_points(test_state, false, None)
+            async fn test_withdraw_after_min_slots(test_state: &mut TestState) {
+                test_state.test_arguments.min_withdrawal_slots = MIN_WITHDRAWAL_SLOTS;
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+
+                let withdrawal_start = test_state
+                    .test_arguments
+                    .src_timelocks
+                    .get(Stage::SrcWithdrawal)
+                    .unwrap();
+                set_time_and_slot(
+                    &mut test_state.context,
+                    withdrawal_start,
+                    MIN_WITHDRAWAL_SLOTS as u64,
+                );
+                let transaction = SrcProgram::get_withdraw_tx(test_state, &escrow, &escrow_ata);
+                let (_, taker_ata) = find_user_ata(test_state);
+                test_state
+                    .expect_state_change(
+                        transaction,
+                        &[
+                            token_change(taker_ata, test_state.test_arguments.escrow_amount),
+                            account_closure(escrow, true),
+                            account_closure(escrow_ata, true),
+                        ],
+                    )
                     .await;
             }
+        }
 
-            #[test_context(TestState)]
-            #[tokio::test]
-            async fn test_cancel_by_resolver_after_auction(test_state: &mut TestState) {
+        mod test_escrow_withdrawal_grace {
+            use super::*;
+
+            const WITHDRAWAL_GRACE: u32 = DEFAULT_PERIOD_DURATION / 2;
+
+            // Creates an escrow of an order with the withdrawal grace and moves to `offset` seconds past
+            // the src cancellation start
+            async fn create_escrow_with_grace(
+                test_state: &mut TestState,
+                offset: u32,
+            ) -> (Pubkey, Pubkey) {
+                test_state.test_arguments.withdrawal_grace = WITHDRAWAL_GRACE;
+                create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                helpers_src::test_cancel_by_resolver_after_auction(test_state).await;
+                let escrows = create_escrow(test_state).await;
+
+                let cancellation_start = test_state
+                    .test_arguments
+                    .src_timelocks
+                    .get(Stage::SrcCancellation)
+                    .unwrap();
+                set_time(&mut test_state.context, cancellation_start + offset);
+                escrows
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_cancel_by_resolver_reward_less_then_auction_calculated(
-                test_state: &mut TestState,
-            ) {
-                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                helpers_src::test_cancel_by_resolver_reward_less_then_auction_calculated(
-                    test_state,
-                )
-                .await;
+            async fn test_withdraw_at_end_of_grace(test_state: &mut TestState) {
+                let (escrow, escrow_ata) =
+                    create_escrow_with_grace(test_state, WITHDRAWAL_GRACE - 1).await;
+
+                let transaction = SrcProgram::get_withdraw_tx(test_state, &escrow, &escrow_ata);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success();
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_cancel_by_resolver_fails_if_order_is_not_expired(
-                test_state: &mut TestState,
-            ) {
-                let (order, order_ata) = create_order(test_state).await;
-
-                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                let transaction =
-                    get_cancel_order_by_resolver_tx(test_state, &order, &order_ata, None);
+            async fn test_withdraw_fails_after_grace(test_state: &mut TestState) {
+                let (escrow, escrow_ata) =
+                    create_escrow_with_grace(test_state, WITHDRAWAL_GRACE).await;
 
+                let transaction = SrcProgram::get_withdraw_tx(test_state, &escrow, &escrow_ata);
                 test_state
                     .client
                     .process_transaction(transaction)
                     .await
-                    .expect_error(ProgramError::Custom(EscrowError::OrderNotExpired.into()));
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidTime.into()));
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_cancel_by_resolver_fails_with_incorrect_token(
-                test_state: &mut TestState,
-            ) {
-                let (order, order_ata) = create_order(test_state).await;
-                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+            async fn test_cancel_fails_during_grace(test_state: &mut TestState) {
+                let (escrow, escrow_ata) =
+                    create_escrow_with_grace(test_state, WITHDRAWAL_GRACE - 1).await;
 
-                test_state.token = <TestState as HasTokenVariant>::Token::deploy_spl_token(
-                    &mut test_state.context,
-                )
-                .await
-                .pubkey();
+                let transaction = SrcProgram::get_cancel_tx(test_state, &escrow, &escrow_ata);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidTime.into()));
+            }
 
-                let transaction =
-                    get_cancel_order_by_resolver_tx(test_state, &order, &order_ata, None);
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_cancel_after_grace(test_state: &mut TestState) {
+                let (escrow, escrow_ata) =
+                    create_escrow_with_grace(test_state, WITHDRAWAL_GRACE).await;
 
+                let transaction = SrcProgram::get_cancel_tx(test_state, &escrow, &escrow_ata);
                 test_state
                     .client
                     .process_transaction(transaction)
                     .await
-                    .expect_error(ProgramError::Custom(EscrowError::InvalidMint.into()));
+                    .expect_success();
             }
         }
 
-        mod test_order_public_cancel {
+        mod test_escrow_reveal_secret {
             use super::*;
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_public_cancel_by_taker(test_state: &mut TestState) {
-                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+            async fn test_reveal_secret_then_withdraw(test_state: &mut TestState) {
                 create_order(test_state).await;
-                let (escrow, escrow_ata) = create_escrow(test_state).await;
-                test_public_cancel_escrow(
-                    test_state,
-                    &escrow,
-                    &escrow_ata,
-                    &test_state.taker_wallet.keypair.insecure_clone(),
-                )
-                .await;
-            }
-
-            #[test_context(TestState)]
-            #[tokio::test]
-            async fn test_public_cancel_by_any_account(test_state: &mut TestState) {
-                let canceller = Keypair::new();
-                transfer_lamports(
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
+                set_time(
                     &mut test_state.context,
-                    WALLET_DEFAULT_LAMPORTS,
-                    &test_state.payer_kp,
-                    &canceller.pubkey(),
-                )
-                .await;
+                    test_state
+                        .test_arguments
+                        .src_timelocks
+                        .get(Stage::SrcWithdrawal)
+                        .unwrap(),
+                );
 
-                prepare_resolvers(
+                let transaction = get_reveal_secret_tx(test_state, &escrow, test_state.secret);
+                let events = common_escrow_tests::simulate_tx_events::<_, _, SecretRevealed>(
                     test_state,
-                    &[test_state.taker_wallet.keypair.pubkey(), canceller.pubkey()],
+                    transaction,
                 )
                 .await;
+                assert_eq!(events.len(), 1);
+                assert_eq!(events[0].order_hash, test_state.order_hash.to_bytes());
+                assert_eq!(events[0].hashlock, test_state.hashlock.to_bytes());
+                assert_eq!(events[0].secret, test_state.secret);
 
-                create_order(test_state).await;
-                let (escrow, escrow_ata) = create_escrow(test_state).await;
-                test_public_cancel_escrow(test_state, &escrow, &escrow_ata, &canceller).await;
+                let transaction = get_reveal_secret_tx(test_state, &escrow, test_state.secret);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success();
+
+                // The escrow keeps its funds until the withdrawal with the same secret
+                assert_eq!(
+                    get_token_balance(&mut test_state.context, &escrow_ata).await,
+                    test_state.test_arguments.escrow_amount
+                );
+                helpers_src::test_withdraw_escrow(test_state, &escrow, &escrow_ata).await;
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_cannot_public_cancel_before_public_cancellation_start(
-                test_state: &mut TestState,
-            ) {
+            async fn test_reveal_secret_fails_before_withdrawal_window(test_state: &mut TestState) {
                 create_order(test_state).await;
-                prepare_resolvers(
-                    test_state,
-                    &[
-                        test_state.taker_wallet.keypair.pubkey(),
-                        test_state.payer_kp.pubkey(),
-                    ],
-                )
-                .await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, _) = create_escrow(test_state).await;
 
-                let (escrow, escrow_ata) = create_escrow(test_state).await;
-                let transaction = create_public_escrow_cancel_tx(
-                    test_state,
-                    &escrow,
-                    &escrow_ata,
-                    &test_state.payer_kp,
-                );
+                let transaction = get_reveal_secret_tx(test_state, &escrow, test_state.secret);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidTime.into()));
+            }
 
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_reveal_secret_fails_after_withdrawal_window(test_state: &mut TestState) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, _) = create_escrow(test_state).await;
                 set_time(
                     &mut test_state.context,
                     test_state
@@ -1379,386 +4747,1094 @@ run_for_tokens!(
                         .get(Stage::SrcCancellation)
                         .unwrap(),
                 );
+
+                let transaction = get_reveal_secret_tx(test_state, &escrow, test_state.secret);
                 test_state
                     .client
                     .process_transaction(transaction)
                     .await
-                    .expect_error(ProgramError::Custom(EscrowError::InvalidTime.into()))
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidTime.into()));
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_public_cancel_fails_with_incorrect_token(test_state: &mut TestState) {
+            async fn test_reveal_secret_fails_with_wrong_secret(test_state: &mut TestState) {
                 create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                let (escrow, escrow_ata) = create_escrow(test_state).await;
-
-                test_state.token = <TestState as HasTokenVariant>::Token::deploy_spl_token(
+                let (escrow, _) = create_escrow(test_state).await;
+                set_time(
                     &mut test_state.context,
-                )
-                .await
-                .pubkey();
-                let taker_kp = test_state.taker_wallet.keypair.insecure_clone();
-
-                let transaction =
-                    create_public_escrow_cancel_tx(test_state, &escrow, &escrow_ata, &taker_kp);
+                    test_state
+                        .test_arguments
+                        .src_timelocks
+                        .get(Stage::SrcWithdrawal)
+                        .unwrap(),
+                );
 
+                let transaction = get_reveal_secret_tx(test_state, &escrow, [0; 32]);
                 test_state
                     .client
                     .process_transaction(transaction)
                     .await
-                    .expect_error(ProgramError::Custom(EscrowError::InvalidMint.into()));
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidSecret.into()));
             }
         }
 
-        mod test_order_rescue_funds_for_order {
+        mod test_escrow_sweep_dust {
             use super::*;
 
-            #[test_context(TestState)]
-            #[tokio::test]
-            async fn test_rescue_all_tokens_from_order_and_close_ata(test_state: &mut TestState) {
-                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                helpers_src::test_rescue_all_tokens_from_order_and_close_ata(test_state).await
-            }
-
-            #[test_context(TestState)]
-            #[tokio::test]
-            async fn test_rescue_part_of_tokens_from_order_and_not_close_ata(
-                test_state: &mut TestState,
-            ) {
-                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                helpers_src::test_rescue_part_of_tokens_from_order_and_not_close_ata(test_state)
-                    .await
-            }
+            const DUST: u64 = 1000;
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_cannot_rescue_funds_from_order_before_rescue_delay_pass(
-                test_state: &mut TestState,
-            ) {
-                type S = <TestState as HasTokenVariant>::Token;
-
-                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                let (order, _) = create_order(test_state).await;
+            async fn test_sweep_dust_from_closed_escrow(test_state: &mut TestState) {
+                let (escrow, _) = get_escrow_addresses(test_state);
+                test_state.context.set_account(
+                    &escrow,
+                    &AccountSharedData::new(DUST, 0, &system_program::ID),
+                );
 
-                let token_to_rescue = S::deploy_spl_token(&mut test_state.context).await.pubkey();
-                let order_ata = S::initialize_spl_associated_account(
-                    &mut test_state.context,
-                    &token_to_rescue,
-                    &order,
-                )
-                .await;
-                let taker_ata = S::initialize_spl_associated_account(
-                    &mut test_state.context,
-                    &token_to_rescue,
-                    &test_state.taker_wallet.keypair.pubkey(),
-                )
-                .await;
+                let treasury = test_state.authority_whitelist_kp.pubkey();
+                let treasury_balance = test_state.client.get_balance(treasury).await.unwrap();
 
-                S::mint_spl_tokens(
-                    &mut test_state.context,
-                    &token_to_rescue,
-                    &order_ata,
-                    &test_state.payer_kp.pubkey(),
-                    &test_state.payer_kp,
-                    test_state.test_arguments.rescue_amount,
-                )
-                .await;
+                let transaction = get_sweep_dust_tx(test_state, &escrow, &treasury);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success();
 
-                let transaction = get_rescue_funds_from_order_tx(
-                    test_state,
-                    &order,
-                    &order_ata,
-                    &token_to_rescue,
-                    &taker_ata,
+                assert_eq!(
+                    test_state.client.get_balance(treasury).await.unwrap(),
+                    treasury_balance + DUST
                 );
+                assert_eq!(test_state.client.get_balance(escrow).await.unwrap(), 0);
+            }
 
-                set_time(
-                    &mut test_state.context,
-                    test_state.init_timestamp + common::constants::RESCUE_DELAY - 100,
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_sweep_dust_fails_above_rent_exempt_reserve(test_state: &mut TestState) {
+                let (escrow, _) = get_escrow_addresses(test_state);
+                test_state.context.set_account(
+                    &escrow,
+                    &AccountSharedData::new(WALLET_DEFAULT_LAMPORTS, 0, &system_program::ID),
                 );
 
+                let treasury = test_state.authority_whitelist_kp.pubkey();
+                let transaction = get_sweep_dust_tx(test_state, &escrow, &treasury);
                 test_state
                     .client
                     .process_transaction(transaction)
                     .await
-                    .expect_error(ProgramError::Custom(EscrowError::InvalidRescueStart.into()));
+                    .expect_error(ProgramError::Custom(EscrowError::InvalidDustAmount.into()));
             }
 
-            // #[test_context(TestState)]
-            // #[tokio::test]
-            // async fn test_cannot_rescue_funds_from_order_by_non_recipient(test_state: &mut TestState) { // TODO: return after implement whitelist
-            //     helpers_src::test_cannot_rescue_funds_from_order_by_non_recipient(test_state).await
-            // }
-
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_cannot_rescue_funds_from_order_with_wrong_taker_ata(
-                test_state: &mut TestState,
-            ) {
-                type S = <TestState as HasTokenVariant>::Token;
-
+            async fn test_sweep_dust_fails_for_active_escrow(test_state: &mut TestState) {
+                create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                let (order, _) = create_order(test_state).await;
-
-                let token_to_rescue = S::deploy_spl_token(&mut test_state.context).await.pubkey();
-                let order_ata = S::initialize_spl_associated_account(
-                    &mut test_state.context,
-                    &token_to_rescue,
-                    &order,
-                )
-                .await;
-
-                S::mint_spl_tokens(
-                    &mut test_state.context,
-                    &token_to_rescue,
-                    &order_ata,
-                    &test_state.payer_kp.pubkey(),
-                    &test_state.payer_kp,
-                    test_state.test_arguments.rescue_amount,
-                )
-                .await;
-
-                let wrong_taker_ata = S::initialize_spl_associated_account(
-                    &mut test_state.context,
-                    &token_to_rescue,
-                    &test_state.maker_wallet.keypair.pubkey(),
-                )
-                .await;
-
-                let transaction = get_rescue_funds_from_order_tx(
-                    test_state,
-                    &order,
-                    &order_ata,
-                    &token_to_rescue,
-                    &wrong_taker_ata,
-                );
-
-                set_time(
-                    &mut test_state.context,
-                    test_state.init_timestamp + common::constants::RESCUE_DELAY + 100,
-                );
+                let (escrow, _) = create_escrow(test_state).await;
 
+                let treasury = test_state.authority_whitelist_kp.pubkey();
+                let transaction = get_sweep_dust_tx(test_state, &escrow, &treasury);
                 test_state
                     .client
                     .process_transaction(transaction)
                     .await
-                    .expect_error(ProgramError::Custom(ErrorCode::ConstraintTokenOwner.into()))
+                    .expect_error(ProgramError::Custom(EscrowError::EscrowNotClosed.into()));
             }
+        }
+
+        mod test_tvl_stats {
+            use super::*;
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_cannot_rescue_funds_with_wrong_hash_order(test_state: &mut TestState) {
-                type S = <TestState as HasTokenVariant>::Token;
-
+            async fn test_tvl_follows_escrows_until_resolved(test_state: &mut TestState) {
+                enable_tvl_tracking(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
-                let (order, _) = create_order(test_state).await;
+                let amount = test_state.test_arguments.escrow_amount;
 
-                let token_to_rescue = S::deploy_spl_token(&mut test_state.context).await.pubkey();
-                let order_ata = S::initialize_spl_associated_account(
-                    &mut test_state.context,
-                    &token_to_rescue,
-                    &order,
-                )
-                .await;
+                create_order(test_state).await;
+                let (escrow_to_withdraw, escrow_ata_to_withdraw) = create_escrow(test_state).await;
+                assert_eq!(get_tvl_total_locked(test_state).await, amount);
 
-                S::mint_spl_tokens(
-                    &mut test_state.context,
-                    &token_to_rescue,
-                    &order_ata,
-                    &test_state.payer_kp.pubkey(),
-                    &test_state.payer_kp,
-                    test_state.test_arguments.rescue_amount,
-                )
-                .await;
+                // A second order, whose escrow is cancelled instead of withdrawn
+                test_state.test_arguments.salt += 1;
+                create_order(test_state).await;
+                let (escrow_to_cancel, escrow_ata_to_cancel) = create_escrow(test_state).await;
+                assert_eq!(get_tvl_total_locked(test_state).await, 2 * amount);
 
-                let taker_ata = S::initialize_spl_associated_account(
+                set_time(
                     &mut test_state.context,
-                    &token_to_rescue,
-                    &test_state.taker_wallet.keypair.pubkey(),
-                )
-                .await;
-
-                test_state.test_arguments.order_amount += 1; // Change order amount to make hash different
-                let transaction = get_rescue_funds_from_order_tx(
+                    test_state
+                        .test_arguments
+                        .src_timelocks
+                        .get(Stage::SrcWithdrawal)
+                        .unwrap(),
+                );
+                let transaction = SrcProgram::get_withdraw_tx(
                     test_state,
-                    &order,
-                    &order_ata,
-                    &token_to_rescue,
-                    &taker_ata,
+                    &escrow_to_withdraw,
+                    &escrow_ata_to_withdraw,
                 );
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_success();
+                assert_eq!(get_tvl_total_locked(test_state).await, amount);
 
                 set_time(
                     &mut test_state.context,
-                    test_state.init_timestamp + common::constants::RESCUE_DELAY + 100,
+                    test_state
+                        .test_arguments
+                        .src_timelocks
+                        .get(Stage::SrcCancellation)
+                        .unwrap(),
                 );
-
+                let transaction =
+                    SrcProgram::get_cancel_tx(test_state, &escrow_to_cancel, &escrow_ata_to_cancel);
                 test_state
                     .client
                     .process_transaction(transaction)
                     .await
-                    .expect_error(ProgramError::Custom(ErrorCode::ConstraintSeeds.into()))
+                    .expect_success();
+                assert_eq!(get_tvl_total_locked(test_state).await, 0);
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_cannot_rescue_funds_from_order_with_wrong_order_ata(
+            async fn test_escrow_creation_fails_without_tvl_stats_when_tracked(
                 test_state: &mut TestState,
             ) {
-                type S = <TestState as HasTokenVariant>::Token;
-
+                enable_tvl_tracking(test_state).await;
+                test_state.test_arguments.pass_tvl_stats = false;
+                create_order(test_state).await;
                 prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
 
-                let (order, order_ata) = create_order(test_state).await;
-
-                let token_to_rescue = S::deploy_spl_token(&mut test_state.context).await.pubkey();
-                let taker_ata = S::initialize_spl_associated_account(
-                    &mut test_state.context,
-                    &token_to_rescue,
-                    &test_state.taker_wallet.keypair.pubkey(),
-                )
-                .await;
+                let (_, _, transaction) = create_escrow_data(test_state);
+                test_state
+                    .client
+                    .process_transaction(transaction)
+                    .await
+                    .expect_error(ProgramError::Custom(EscrowError::MissingTvlStats.into()));
+            }
 
-                let transaction = get_rescue_funds_from_order_tx(
-                    test_state,
-                    &order,
-                    &order_ata, // Use order ata for order mint, but not for token to rescue
-                    &token_to_rescue,
-                    &taker_ata,
-                );
+            #[test_context(TestState)]
+            #[tokio::test]
+            async fn test_escrow_created_before_tracking_is_not_deducted(
+                test_state: &mut TestState,
+            ) {
+                create_order(test_state).await;
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, escrow_ata) = create_escrow(test_state).await;
 
+                enable_tvl_tracking(test_state).await;
                 set_time(
                     &mut test_state.context,
-                    test_state.init_timestamp + common::constants::RESCUE_DELAY + 100,
+                    test_state
+                        .test_arguments
+                        .src_timelocks
+                        .get(Stage::SrcWithdrawal)
+                        .unwrap(),
                 );
-
+                let transaction = SrcProgram::get_withdraw_tx(test_state, &escrow, &escrow_ata);
                 test_state
                     .client
                     .process_transaction(transaction)
                     .await
-                    .expect_error(ProgramError::Custom(ErrorCode::ConstraintAssociated.into()))
+                    .expect_success();
+                assert_eq!(get_tvl_total_locked(test_state).await, 0);
             }
         }
 
-        mod test_order_rescue_funds_for_escrow {
+        mod test_decode_timelocks {
             use super::*;
 
-            #[test_context(TestState)]
-            #[tokio::test]
-            async fn test_rescue_all_tokens_and_close_ata(test_state: &mut TestState) {
-                create_order(test_state).await;
-                prepare_resolvers(
-                    test_state,
-                    &[
-                        test_state.taker_wallet.keypair.pubkey(),
-                        test_state.maker_wallet.keypair.pubkey(),
-                    ],
-                )
-                .await;
-                common_escrow_tests::test_rescue_all_tokens_and_close_ata(test_state).await
+            async fn decode_timelocks(test_state: &mut TestState, deployed_at: u32) -> [u32; 8] {
+                let timelocks = test_state.test_arguments.src_timelocks.get_timelocks();
+                let transaction = get_decode_timelocks_tx(test_state, timelocks, deployed_at);
+                let mut return_data =
+                    common_escrow_tests::simulate_tx_return_data(test_state, transaction).await;
+                // Trailing zero bytes of the return data are trimmed
+                return_data.resize(32, 0);
+                <[u32; 8]>::try_from_slice(&return_data).unwrap()
+            }
+
+            async fn get_escrow_status(
+                test_state: &mut TestState,
+                escrow: &Pubkey,
+                time: u32,
+            ) -> EscrowStatus {
+                set_time(&mut test_state.context, time);
+                let transaction = SrcProgram::get_escrow_status_tx(test_state, escrow);
+                let mut return_data =
+                    common_escrow_tests::simulate_tx_return_data(test_state, transaction).await;
+                return_data.resize(4, 0);
+                EscrowStatus::try_from_slice(&return_data).unwrap()
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_rescue_part_of_tokens_and_not_close_ata(test_state: &mut TestState) {
-                create_order(test_state).await;
-                prepare_resolvers(
-                    test_state,
-                    &[
-                        test_state.taker_wallet.keypair.pubkey(),
-                        test_state.maker_wallet.keypair.pubkey(),
-                    ],
-                )
-                .await;
-                common_escrow_tests::test_rescue_part_of_tokens_and_not_close_ata(test_state).await
+            async fn test_decode_timelocks_returns_stage_starts(test_state: &mut TestState) {
+                let timelocks = test_state.test_arguments.src_timelocks;
+                let deployed_at = test_state.init_timestamp;
+                let stage_starts = decode_timelocks(test_state, deployed_at).await;
+
+                for (stage, start) in [
+                    Stage::SrcWithdrawal,
+                    Stage::SrcPublicWithdrawal,
+                    Stage::SrcCancellation,
+                    Stage::SrcPublicCancellation,
+                    Stage::DstWithdrawal,
+                    Stage::DstPublicWithdrawal,
+                    Stage::DstCancellation,
+                    Stage::DstPublicCancellation,
+                ]
+                .into_iter()
+                .zip(stage_starts)
+                {
+                    assert_eq!(start, timelocks.get(stage).unwrap());
+                }
+
+                // The deployment time is replaced rather than taken from the timelocks
+                let later_stage_starts = decode_timelocks(test_state, deployed_at + 1).await;
+                for (later_start, start) in later_stage_starts.into_iter().zip(stage_starts) {
+                    assert_eq!(later_start, start + 1);
+                }
             }
 
             #[test_context(TestState)]
             #[tokio::test]
-            async fn test_rescue_tokens_when_escrow_is_deleted(test_state: &mut TestState) {
+            async fn test_decode_timelocks_matches_escrow_windows(test_state: &mut TestState) {
                 create_order(test_state).await;
-                prepare_resolvers(
-                    test_state,
+                prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+                let (escrow, _) = create_escrow(test_state).await;
+
+                let deployed_at = test_state.init_timestamp;
+                let stage_starts = decode_timelocks(test_state, deployed_at).await;
+                let withdrawal_start = stage_starts[Stage::SrcWithdrawal as usize];
+                let public_withdrawal_start = stage_starts[Stage::SrcPublicWithdrawal as usize];
+                let cancellation_start = stage_starts[Stage::SrcCancellation as usize];
+                let public_cancellation_start = stage_starts[Stage::SrcPublicCancellation as usize];
+
+                let status = get_escrow_status(test_state, &escrow, withdrawal_start - 1).await;
+                assert!(!status.withdrawable);
+                let status = get_escrow_status(test_state, &escrow, withdrawal_start).await;
+                assert!(status.withdrawable);
+
+                let status =
+                    get_escrow_status(test_state, &escrow, public_withdrawal_start - 1).await;
+                assert!(!status.public_withdrawable);
+                let status = get_escrow_status(test_state, &escrow, public_withdrawal_start).await;
+                assert!(status.public_withdrawable);
+
+                let status = get_escrow_status(test_state, &escrow, cancellation_start - 1).await;
+                assert!(status.withdrawable && !status.cancellable);
+                let status = get_escrow_status(test_state, &escrow, cancellation_start).await;
+                assert!(!status.withdrawable && status.cancellable);
+
+                let status =
+                    get_escrow_status(test_state, &escrow, public_cancellation_start - 1).await;
+                assert!(!status.public_cancellable);
+                let status =
+                    get_escrow_status(test_state, &escrow, public_cancellation_start).await;
+                assert!(status.public_cancellable);
+            }
+        }
+    }
+);
+
+// Transfer fees and permanent delegates are Token-2022 extensions
+mod test_token_2022_extensions {
+    use super::*;
+
+    type TestState = TestStateBase<SrcProgram, Token2022>;
+
+    mod test_transfer_fee {
+        use super::*;
+
+        const FEE_BPS: u16 = DEFAULT_TRANSFER_FEE_BASIS_POINTS;
+
+        // Creates an order and fills it entirely, checking that both transfers are charged the fee
+        // and that the order ATA holding the withheld fee can still be closed
+        async fn create_escrow_with_transfer_fee(test_state: &mut TestState) -> (Pubkey, Pubkey) {
+            use_transfer_fee_token(test_state, FEE_BPS).await;
+            let (order, order_ata) = create_order(test_state).await;
+
+            let order_amount = test_state.test_arguments.order_amount;
+            let order_ata_amount = order_amount - get_transfer_fee(order_amount, FEE_BPS);
+            assert_eq!(
+                order_ata_amount,
+                get_token_balance(&mut test_state.context, &order_ata).await
+            );
+
+            prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+            let (escrow, escrow_ata, transaction) = create_escrow_data(test_state);
+
+            let order_rent =
+                get_min_rent_for_size(&mut test_state.client, DEFAULT_ORDER_SIZE).await;
+            let token_account_rent = get_min_rent_for_size(
+                &mut test_state.client,
+                get_transfer_fee_token_account_size(),
+            )
+            .await;
+            let withheld_fees =
+                get_mint_withheld_fees(&mut test_state.context, &test_state.token).await;
+
+            harvest_withheld_fees(&mut test_state.context, &test_state.token, &[&order_ata]).await;
+            test_state
+                .expect_state_change(
+                    transaction,
                     &[
-                        test_state.taker_wallet.keypair.pubkey(),
-                        test_state.maker_wallet.keypair.pubkey(),
+                        native_change(
+                            test_state.maker_wallet.keypair.pubkey(),
+                            order_rent + token_account_rent,
+                        ),
+                        account_closure(order, true),
+                        account_closure(order_ata, true),
                     ],
                 )
                 .await;
-                common_escrow_tests::test_rescue_tokens_when_escrow_is_deleted(test_state).await;
-            }
 
-            #[test_context(TestState)]
-            #[tokio::test]
-            async fn test_cannot_rescue_funds_before_rescue_delay_pass(test_state: &mut TestState) {
-                create_order(test_state).await;
-                prepare_resolvers(
-                    test_state,
+            // The fee withheld on order creation is harvested before closing the order ATA
+            assert_eq!(
+                withheld_fees + get_transfer_fee(order_amount, FEE_BPS),
+                get_mint_withheld_fees(&mut test_state.context, &test_state.token).await
+            );
+            assert_eq!(
+                order_ata_amount - get_transfer_fee(order_ata_amount, FEE_BPS),
+                get_token_balance(&mut test_state.context, &escrow_ata).await
+            );
+            assert_eq!(
+                token_account_rent,
+                test_state.client.get_balance(escrow_ata).await.unwrap()
+            );
+
+            (escrow, escrow_ata)
+        }
+
+        #[test_context(TestState)]
+        #[tokio::test]
+        async fn test_withdraw_with_transfer_fee(test_state: &mut TestState) {
+            let (escrow, escrow_ata) = create_escrow_with_transfer_fee(test_state).await;
+
+            set_time(
+                &mut test_state.context,
+                test_state
+                    .test_arguments
+                    .src_timelocks
+                    .get(Stage::SrcWithdrawal)
+                    .unwrap(),
+            );
+            let transaction = SrcProgram::get_withdraw_tx(test_state, &escrow, &escrow_ata);
+
+            let escrow_rent =
+                get_min_rent_for_size(&mut test_state.client, DEFAULT_SRC_ESCROW_SIZE).await;
+            let token_account_rent = get_min_rent_for_size(
+                &mut test_state.client,
+                get_transfer_fee_token_account_size(),
+            )
+            .await;
+            let escrow_ata_amount = get_token_balance(&mut test_state.context, &escrow_ata).await;
+            let withheld_fees =
+                get_mint_withheld_fees(&mut test_state.context, &test_state.token).await;
+
+            let (_, taker_ata) = find_user_ata(test_state);
+
+            // The taker receives the entire escrow ATA balance net of the fee of the last transfer
+            harvest_withheld_fees(&mut test_state.context, &test_state.token, &[&escrow_ata]).await;
+            test_state
+                .expect_state_change(
+                    transaction,
                     &[
-                        test_state.taker_wallet.keypair.pubkey(),
-                        test_state.maker_wallet.keypair.pubkey(),
+                        native_change(
+                            test_state.taker_wallet.keypair.pubkey(),
+                            escrow_rent + token_account_rent,
+                        ),
+                        token_change(
+                            taker_ata,
+                            escrow_ata_amount - get_transfer_fee(escrow_ata_amount, FEE_BPS),
+                        ),
+                        account_closure(escrow, true),
+                        account_closure(escrow_ata, true),
                     ],
                 )
                 .await;
-                common_escrow_tests::test_cannot_rescue_funds_before_rescue_delay_pass(test_state)
-                    .await
-            }
 
-            #[test_context(TestState)]
-            #[tokio::test]
-            async fn test_cannot_rescue_funds_by_non_recipient(test_state: &mut TestState) {
-                create_order(test_state).await;
-                prepare_resolvers(
-                    test_state,
+            // The fee withheld when funding the escrow is harvested before closing the escrow ATA
+            let order_amount = test_state.test_arguments.order_amount;
+            let escrow_funding_amount = order_amount - get_transfer_fee(order_amount, FEE_BPS);
+            assert_eq!(
+                withheld_fees + get_transfer_fee(escrow_funding_amount, FEE_BPS),
+                get_mint_withheld_fees(&mut test_state.context, &test_state.token).await
+            );
+        }
+
+        #[test_context(TestState)]
+        #[tokio::test]
+        async fn test_public_withdraw_with_transfer_fee_and_max_safety_deposit(
+            test_state: &mut TestState,
+        ) {
+            // The safety deposit is bounded by the escrow rent only, the larger token account of
+            // a transfer fee mint must not affect it
+            let escrow_rent =
+                get_min_rent_for_size(&mut test_state.client, DEFAULT_SRC_ESCROW_SIZE).await;
+            test_state.test_arguments.safety_deposit = escrow_rent;
+            let (escrow, escrow_ata) = create_escrow_with_transfer_fee(test_state).await;
+
+            let withdrawer = Keypair::new();
+            prepare_resolvers(test_state, &[withdrawer.pubkey()]).await;
+            transfer_lamports(
+                &mut test_state.context,
+                WALLET_DEFAULT_LAMPORTS,
+                &test_state.payer_kp,
+                &withdrawer.pubkey(),
+            )
+            .await;
+
+            set_time(
+                &mut test_state.context,
+                test_state
+                    .test_arguments
+                    .src_timelocks
+                    .get(Stage::SrcPublicWithdrawal)
+                    .unwrap(),
+            );
+            let transaction =
+                SrcProgram::get_public_withdraw_tx(test_state, &escrow, &escrow_ata, &withdrawer);
+
+            let token_account_rent = get_min_rent_for_size(
+                &mut test_state.client,
+                get_transfer_fee_token_account_size(),
+            )
+            .await;
+            let escrow_ata_amount = get_token_balance(&mut test_state.context, &escrow_ata).await;
+
+            let (_, taker_ata) = find_user_ata(test_state);
+
+            harvest_withheld_fees(&mut test_state.context, &test_state.token, &[&escrow_ata]).await;
+            test_state
+                .expect_state_change(
+                    transaction,
                     &[
-                        test_state.taker_wallet.keypair.pubkey(),
-                        test_state.maker_wallet.keypair.pubkey(),
+                        native_change(withdrawer.pubkey(), escrow_rent),
+                        native_change(test_state.taker_wallet.keypair.pubkey(), token_account_rent),
+                        token_change(
+                            taker_ata,
+                            escrow_ata_amount - get_transfer_fee(escrow_ata_amount, FEE_BPS),
+                        ),
+                        account_closure(escrow, true),
+                        account_closure(escrow_ata, true),
                     ],
                 )
                 .await;
-                common_escrow_tests::test_cannot_rescue_funds_by_non_recipient(test_state).await
-            }
+        }
 
-            #[test_context(TestState)]
-            #[tokio::test]
-            async fn test_cannot_rescue_funds_with_wrong_taker_ata(test_state: &mut TestState) {
-                create_order(test_state).await;
-                prepare_resolvers(
-                    test_state,
+        #[test_context(TestState)]
+        #[tokio::test]
+        async fn test_cancel_escrow_with_transfer_fee(test_state: &mut TestState) {
+            let (escrow, escrow_ata) = create_escrow_with_transfer_fee(test_state).await;
+
+            set_time(
+                &mut test_state.context,
+                test_state
+                    .test_arguments
+                    .src_timelocks
+                    .get(Stage::SrcCancellation)
+                    .unwrap(),
+            );
+            let transaction = SrcProgram::get_cancel_tx(test_state, &escrow, &escrow_ata);
+
+            let escrow_rent =
+                get_min_rent_for_size(&mut test_state.client, DEFAULT_SRC_ESCROW_SIZE).await;
+            let token_account_rent = get_min_rent_for_size(
+                &mut test_state.client,
+                get_transfer_fee_token_account_size(),
+            )
+            .await;
+            let escrow_ata_amount = get_token_balance(&mut test_state.context, &escrow_ata).await;
+
+            let (maker_ata, _) = find_user_ata(test_state);
+
+            harvest_withheld_fees(&mut test_state.context, &test_state.token, &[&escrow_ata]).await;
+            test_state
+                .expect_state_change(
+                    transaction,
                     &[
-                        test_state.taker_wallet.keypair.pubkey(),
-                        test_state.maker_wallet.keypair.pubkey(),
+                        native_change(
+                            test_state.taker_wallet.keypair.pubkey(),
+                            escrow_rent + token_account_rent,
+                        ),
+                        token_change(
+                            maker_ata,
+                            escrow_ata_amount - get_transfer_fee(escrow_ata_amount, FEE_BPS),
+                        ),
+                        account_closure(escrow, true),
+                        account_closure(escrow_ata, true),
                     ],
                 )
                 .await;
-                common_escrow_tests::test_cannot_rescue_funds_with_wrong_taker_ata(test_state).await
-            }
+        }
 
-            #[test_context(TestState)]
-            #[tokio::test]
-            async fn test_cannot_rescue_funds_with_wrong_order_ata(test_state: &mut TestState) {
-                create_order(test_state).await;
-                prepare_resolvers(
-                    test_state,
+        #[test_context(TestState)]
+        #[tokio::test]
+        async fn test_cancel_escrow_fails_with_underfunded_escrow_with_transfer_fee(
+            test_state: &mut TestState,
+        ) {
+            let (escrow, escrow_ata) = create_escrow_with_transfer_fee(test_state).await;
+
+            // The escrow ATA holds less than the filled amount net of the fee it was funded with
+            let escrow_ata_amount = get_token_balance(&mut test_state.context, &escrow_ata).await;
+            set_token_balance(&mut test_state.context, &escrow_ata, escrow_ata_amount - 1).await;
+
+            set_time(
+                &mut test_state.context,
+                test_state
+                    .test_arguments
+                    .src_timelocks
+                    .get(Stage::SrcCancellation)
+                    .unwrap(),
+            );
+            let transaction = SrcProgram::get_cancel_tx(test_state, &escrow, &escrow_ata);
+            test_state
+                .client
+                .process_transaction(transaction)
+                .await
+                .expect_error(ProgramError::Custom(
+                    EscrowError::InsufficientEscrowBalance.into(),
+                ));
+        }
+
+        #[test_context(TestState)]
+        #[tokio::test]
+        async fn test_cancel_order_with_transfer_fee(test_state: &mut TestState) {
+            use_transfer_fee_token(test_state, FEE_BPS).await;
+            let (order, order_ata) = create_order(test_state).await;
+
+            let transaction = get_cancel_order_tx(test_state, &order, &order_ata, None);
+
+            let order_rent =
+                get_min_rent_for_size(&mut test_state.client, DEFAULT_ORDER_SIZE).await;
+            let token_account_rent = get_min_rent_for_size(
+                &mut test_state.client,
+                get_transfer_fee_token_account_size(),
+            )
+            .await;
+            let order_ata_amount = get_token_balance(&mut test_state.context, &order_ata).await;
+
+            let (maker_ata, _) = find_user_ata(test_state);
+
+            harvest_withheld_fees(&mut test_state.context, &test_state.token, &[&order_ata]).await;
+            test_state
+                .expect_state_change(
+                    transaction,
                     &[
-                        test_state.taker_wallet.keypair.pubkey(),
-                        test_state.maker_wallet.keypair.pubkey(),
+                        native_change(
+                            test_state.maker_wallet.keypair.pubkey(),
+                            order_rent + token_account_rent,
+                        ),
+                        token_change(
+                            maker_ata,
+                            order_ata_amount - get_transfer_fee(order_ata_amount, FEE_BPS),
+                        ),
+                        account_closure(order, true),
+                        account_closure(order_ata, true),
                     ],
                 )
                 .await;
-                common_escrow_tests::test_cannot_rescue_funds_with_wrong_escrow_ata(test_state)
+        }
+
+        #[test_context(TestState)]
+        #[tokio::test]
+        async fn test_cancel_order_fails_with_withheld_fees(test_state: &mut TestState) {
+            use_transfer_fee_token(test_state, FEE_BPS).await;
+            let (order, order_ata) = create_order(test_state).await;
+
+            let transaction = get_cancel_order_tx(test_state, &order, &order_ata, None);
+            test_state
+                .client
+                .process_transaction(transaction)
+                .await
+                .expect_error(ProgramError::Custom(
+                    EscrowError::WithheldFeesNotHarvested.into(),
+                ));
+        }
+
+        #[test_context(TestState)]
+        #[tokio::test]
+        async fn test_partial_fills_with_transfer_fee(test_state: &mut TestState) {
+            use_transfer_fee_token(test_state, FEE_BPS).await;
+            let (order, order_ata) = create_order_for_partial_fill(test_state).await;
+            prepare_resolvers(test_state, &[test_state.taker_wallet.keypair.pubkey()]).await;
+
+            let order_amount = test_state.test_arguments.order_amount;
+            let mut order_ata_amount = order_amount - get_transfer_fee(order_amount, FEE_BPS);
+            let mut remaining_amount = order_amount;
+
+            // Fill three quarters of the order, then most of the last quarter, then the rest
+            let part_amount = order_amount / DEFAULT_PARTS_AMOUNT_FOR_MULTIPLE;
+            let fill_amounts = [part_amount * 3, part_amount - 1000, 1000];
+            for fill_amount in fill_amounts {
+                let (_, escrow_ata, transaction) =
+                    test_escrow_creation_for_partial_fill_data(test_state, fill_amount).await;
+                if fill_amount == remaining_amount {
+                    harvest_withheld_fees(
+                        &mut test_state.context,
+                        &test_state.token,
+                        &[&order_ata],
+                    )
+                    .await;
+                }
+                test_state
+                    .client
+                    .process_transaction(transaction)
                     .await
+                    .expect_success();
+
+                // Every fill bears its pro-rata share of the fee withheld on order creation,
+                // so the fill before the last one does not run out of tokens
+                let transferred_amount = if fill_amount == remaining_amount {
+                    order_ata_amount
+                } else {
+                    fill_amount * order_ata_amount / remaining_amount
+                };
+                assert_eq!(
+                    transferred_amount - get_transfer_fee(transferred_amount, FEE_BPS),
+                    get_token_balance(&mut test_state.context, &escrow_ata).await
+                );
+
+                order_ata_amount -= transferred_amount;
+                remaining_amount -= fill_amount;
+                test_state.test_arguments.order_remaining_amount = remaining_amount;
             }
+
+            assert!(test_state
+                .client
+                .get_account(order)
+                .await
+                .unwrap()
+                .is_none());
+            assert!(test_state
+                .client
+                .get_account(order_ata)
+                .await
+                .unwrap()
+                .is_none());
         }
+    }
 
-        mod test_order_creation_cost {
-            use super::*;
+    mod test_permanent_delegate {
+        use super::*;
 
-            #[test_context(TestState)]
-            #[tokio::test]
-            async fn test_order_creation_tx_cost(test_state: &mut TestState) {
-                common_escrow_tests::test_escrow_creation_tx_cost(test_state).await
-            }
+        #[test_context(TestState)]
+        #[tokio::test]
+        async fn test_order_creation_fails_with_permanent_delegate(test_state: &mut TestState) {
+            let delegate = Keypair::new();
+            use_permanent_delegate_token(test_state, &delegate.pubkey()).await;
+
+            let (_, _, transaction) = create_order_data(test_state);
+            test_state
+                .client
+                .process_transaction(transaction)
+                .await
+                .expect_error(ProgramError::Custom(
+                    EscrowError::UnsupportedMintExtension.into(),
+                ));
         }
     }
-);
+}
+
+mod test_auction_data {
+    use common::error::EscrowError;
+    use cross_chain_escrow_src::auction::{
+        AuctionData, PointAndTimeDelta, MAX_AUCTION_DURATION, MAX_AUCTION_LEAD, ROUNDING_NEAREST,
+        ROUNDING_UP,
+    };
+
+    const AUCTION_DURATION: u32 = 1000;
+
+    fn get_auction_data(points: &[(u32, u16)]) -> AuctionData {
+        AuctionData {
+            start_time: 0,
+            duration: AUCTION_DURATION,
+            initial_rate_bump: 1_000_000.into(),
+            points_and_time_deltas: points
+                .iter()
+                .map(|&(rate_bump, time_delta)| PointAndTimeDelta {
+                    rate_bump: rate_bump.into(),
+                    time_delta,
+                })
+                .collect(),
+            rounding: ROUNDING_UP,
+        }
+    }
+
+    #[test]
+    fn test_validate_auction_without_points() {
+        assert!(get_auction_data(&[]).validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_decreasing_auction_curve() {
+        let auction_data = get_auction_data(&[(900_000, 500), (900_000, 250), (0, 250)]);
+        assert!(auction_data.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_fails_with_out_of_order_points() {
+        // A zero time delta places the point at the same time as the previous one
+        let auction_data = get_auction_data(&[(900_000, 500), (800_000, 0)]);
+        assert_eq!(
+            auction_data.validate().unwrap_err(),
+            EscrowError::InvalidAuctionCurve.into()
+        );
+    }
+
+    #[test]
+    fn test_validate_fails_with_deltas_overflowing_duration() {
+        let auction_data = get_auction_data(&[(900_000, 500), (800_000, 501)]);
+        assert_eq!(
+            auction_data.validate().unwrap_err(),
+            EscrowError::InvalidAuctionCurve.into()
+        );
+
+        let auction_data = get_auction_data(&[(900_000, u16::MAX), (800_000, u16::MAX)]);
+        assert_eq!(
+            auction_data.validate().unwrap_err(),
+            EscrowError::InvalidAuctionCurve.into()
+        );
+    }
+
+    #[test]
+    fn test_validate_fails_with_unknown_rounding() {
+        let mut auction_data = get_auction_data(&[]);
+        auction_data.rounding = ROUNDING_NEAREST + 1;
+        assert_eq!(
+            auction_data.validate().unwrap_err(),
+            EscrowError::InvalidRounding.into()
+        );
+    }
+
+    #[test]
+    fn test_validate_fails_with_increasing_rate_bump() {
+        let auction_data = get_auction_data(&[(900_000, 500), (950_000, 250)]);
+        assert_eq!(
+            auction_data.validate().unwrap_err(),
+            EscrowError::InvalidAuctionCurve.into()
+        );
+    }
+
+    #[test]
+    fn test_validate_timing_at_boundaries() {
+        let now = 1_000_000;
+        let mut auction_data = get_auction_data(&[]);
+
+        auction_data.start_time = now + MAX_AUCTION_LEAD;
+        auction_data.duration = MAX_AUCTION_DURATION;
+        assert!(auction_data.validate_timing(now).is_ok());
+
+        auction_data.start_time = now + MAX_AUCTION_LEAD + 1;
+        assert_eq!(
+            auction_data.validate_timing(now).unwrap_err(),
+            EscrowError::InvalidAuctionTiming.into()
+        );
+
+        auction_data.start_time = now;
+        auction_data.duration = MAX_AUCTION_DURATION + 1;
+        assert_eq!(
+            auction_data.validate_timing(now).unwrap_err(),
+            EscrowError::InvalidAuctionTiming.into()
+        );
+    }
+
+    #[test]
+    fn test_validate_timing_with_max_start_time() {
+        let mut auction_data = get_auction_data(&[]);
+        auction_data.start_time = u32::MAX;
+        assert!(auction_data
+            .validate_timing(u32::MAX - MAX_AUCTION_LEAD)
+            .is_ok());
+    }
+}
+
+mod test_dst_amount {
+    use common::error::EscrowError;
+    use cross_chain_escrow_src::{
+        auction::{AuctionData, ROUNDING_DOWN, ROUNDING_NEAREST, ROUNDING_UP},
+        get_dst_amount, get_pro_rata_dst_amount, scale_dst_amount,
+    };
+
+    const DST_AMOUNT: [u64; 4] = [1_234_567, 0, 0, 0];
+    const MAX_DST_AMOUNT: [u64; 4] = [u64::MAX; 4];
+
+    fn get_auction_data() -> AuctionData {
+        get_auction_data_with_rounding(ROUNDING_UP)
+    }
+
+    fn get_auction_data_with_rounding(rounding: u8) -> AuctionData {
+        AuctionData {
+            start_time: 0,
+            duration: 1000,
+            initial_rate_bump: 1_000_000.into(),
+            points_and_time_deltas: vec![],
+            rounding,
+        }
+    }
+
+    #[test]
+    fn test_scale_dst_amount_without_shift() {
+        assert_eq!(scale_dst_amount(DST_AMOUNT, 0).unwrap(), DST_AMOUNT);
+    }
+
+    #[test]
+    fn test_scale_dst_amount_with_positive_shift() {
+        assert_eq!(
+            scale_dst_amount(DST_AMOUNT, 3).unwrap(),
+            [1_234_567_000, 0, 0, 0]
+        );
+
+        // Results above u64 are carried into the next limb
+        assert_eq!(
+            scale_dst_amount([u64::MAX, 0, 0, 0], 1).unwrap(),
+            [u64::MAX - 9, 9, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_scale_dst_amount_with_negative_shift_rounds_up() {
+        assert_eq!(scale_dst_amount(DST_AMOUNT, -3).unwrap(), [1_235, 0, 0, 0]);
+        assert_eq!(
+            scale_dst_amount([1_234_000, 0, 0, 0], -3).unwrap(),
+            [1_234, 0, 0, 0]
+        );
+        assert_eq!(scale_dst_amount(DST_AMOUNT, -10).unwrap(), [1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_scale_dst_amount_fails_with_overflowing_shift() {
+        assert_eq!(
+            scale_dst_amount([0, 0, 0, u64::MAX], 1).unwrap_err(),
+            EscrowError::DstAmountOverflow.into()
+        );
+
+        // 10^78 does not fit into U256 regardless of the amount
+        assert_eq!(
+            scale_dst_amount(DST_AMOUNT, 78).unwrap_err(),
+            EscrowError::DstAmountOverflow.into()
+        );
+        assert_eq!(
+            scale_dst_amount(DST_AMOUNT, -78).unwrap_err(),
+            EscrowError::DstAmountOverflow.into()
+        );
+    }
+
+    #[test]
+    fn test_pro_rata_dst_amount_rounds_up() {
+        assert_eq!(
+            get_pro_rata_dst_amount(DST_AMOUNT, 1, 1000).unwrap(),
+            [1_235, 0, 0, 0]
+        );
+
+        // The intermediate product exceeds the amount but still fits into U256
+        let dst_amount = [u64::MAX, u64::MAX, u64::MAX, 0];
+        assert_eq!(
+            get_pro_rata_dst_amount(dst_amount, u64::MAX, u64::MAX).unwrap(),
+            dst_amount
+        );
+    }
+
+    #[test]
+    fn test_pro_rata_dst_amount_fails_with_overflowing_fill() {
+        assert_eq!(
+            get_pro_rata_dst_amount(MAX_DST_AMOUNT, u64::MAX - 1, u64::MAX).unwrap_err(),
+            EscrowError::DstAmountOverflow.into()
+        );
+    }
+
+    #[test]
+    fn test_dst_amount_with_rate_bump() {
+        // The initial rate bump of 1_000_000 increases the amount by 10%, rounded up
+        assert_eq!(
+            get_dst_amount(DST_AMOUNT, &get_auction_data(), 0).unwrap(),
+            [1_358_024, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_dst_amount_rounds_down() {
+        // 1_234_567 * 1.1 = 1_358_023.7
+        assert_eq!(
+            get_dst_amount(
+                DST_AMOUNT,
+                &get_auction_data_with_rounding(ROUNDING_DOWN),
+                0
+            )
+            .unwrap(),
+            [1_358_023, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_dst_amount_rounds_to_nearest() {
+        let auction_data = get_auction_data_with_rounding(ROUNDING_NEAREST);
+        // 1_234_567 * 1.1 = 1_358_023.7
+        assert_eq!(
+            get_dst_amount(DST_AMOUNT, &auction_data, 0).unwrap(),
+            [1_358_024, 0, 0, 0]
+        );
+        // 1_234_561 * 1.1 = 1_358_017.1
+        assert_eq!(
+            get_dst_amount([1_234_561, 0, 0, 0], &auction_data, 0).unwrap(),
+            [1_358_017, 0, 0, 0]
+        );
+        // 1_234_565 * 1.1 = 1_358_021.5, halves are rounded up
+        assert_eq!(
+            get_dst_amount([1_234_565, 0, 0, 0], &auction_data, 0).unwrap(),
+            [1_358_022, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_dst_amount_modes_agree_on_exact_results() {
+        // 1_234_560 * 1.1 = 1_358_016
+        for rounding in [ROUNDING_UP, ROUNDING_DOWN, ROUNDING_NEAREST] {
+            assert_eq!(
+                get_dst_amount(
+                    [1_234_560, 0, 0, 0],
+                    &get_auction_data_with_rounding(rounding),
+                    0
+                )
+                .unwrap(),
+                [1_358_016, 0, 0, 0]
+            );
+        }
+    }
+
+    #[test]
+    fn test_dst_amount_fails_with_unknown_rounding() {
+        assert_eq!(
+            get_dst_amount(
+                DST_AMOUNT,
+                &get_auction_data_with_rounding(ROUNDING_NEAREST + 1),
+                0
+            )
+            .unwrap_err(),
+            EscrowError::InvalidRounding.into()
+        );
+    }
+
+    #[test]
+    fn test_dst_amount_fails_with_overflowing_rate_bump() {
+        assert_eq!(
+            get_dst_amount(MAX_DST_AMOUNT, &get_auction_data(), 0).unwrap_err(),
+            EscrowError::DstAmountOverflow.into()
+        );
+    }
+}
+
+mod test_order_hash {
+    use common::constants::HASH_ALGO_SHA256;
+    use cross_chain_escrow_src::{
+        derive_salted_order_hash, get_order_hash, DstChainParams, OrderArgs,
+    };
+    use solana_program::pubkey::Pubkey;
+
+    // Order PDAs are derived from the order hash, so its encoding must never change unintentionally.
+    // The expected value below corresponds to the encoding without the order expiration time,
+    // which was excluded so that orders can be extended without changing their PDA, with the
+    // minimum destination amount following the destination amount, with the public withdrawal
+    // premium duration following the cancellation auction duration, with the minimum fill amount
+    // following the multiple fills flag, with the hashlock algorithm following the salt, with
+    // the dst decimals shift following the hashlock algorithm, with the allowed taker following
+    // the dst decimals shift, with the simple parts amount following the allowed taker, with the
+    // withdrawal grace following the simple parts amount, with the maker cosign flag following
+    // the withdrawal grace, with the minimum withdrawal slots following the maker cosign flag and
+    // with the dst token and safety deposit hashed along with the hash of the other parameters.
+    const SALT: u64 = 0xFACE8D00DEADBEEF;
+
+    const EXPECTED_ORDER_HASH: [u8; 32] = [
+        253, 6, 59, 184, 68, 113, 121, 74, 162, 87, 119, 229, 33, 172, 128, 37, 91, 249, 251, 105,
+        53, 72, 50, 120, 144, 27, 157, 145, 209, 221, 26, 215,
+    ];
+
+    fn get_maker() -> Pubkey {
+        Pubkey::new_from_array([0x22; 32])
+    }
+
+    fn get_token() -> Pubkey {
+        Pubkey::new_from_array([0x33; 32])
+    }
+
+    fn get_order_args() -> OrderArgs {
+        OrderArgs {
+            hashlock: [0x11; 32],
+            amount: 100_000,
+            safety_deposit: 25,
+            timelocks: [1, 0x0102_0304_0506_0708, u64::MAX, 0xDEAD_BEEF],
+            // Not part of the order hash
+            expiration_time: 0,
+            asset_is_native: true,
+            dst_amount: [1_000, 2, 0, 0x8000_0000_0000_0001],
+            min_dst_amount: [900, 1, 0, 0x4000_0000_0000_0000],
+            dutch_auction_data_hash: [0x44; 32],
+            max_cancellation_premium: 50_000,
+            cancellation_auction_duration: 100,
+            public_withdrawal_premium_duration: 200,
+            allow_multiple_fills: true,
+            min_fill_amount: 10_000,
+            salt: SALT,
+            hash_algo: HASH_ALGO_SHA256,
+            dst_decimals_shift: -3,
+            allowed_taker: Pubkey::new_from_array([0x55; 32]),
+            simple_parts_amount: 0,
+            withdrawal_grace: 0,
+            require_maker_cosign: false,
+            min_withdrawal_slots: 0,
+            dst_chain_params: DstChainParams {
+                chain_id: 0,
+                maker_address: [0; 32],
+                token: [0x66; 32],
+                safety_deposit: 1_000_000_000,
+            },
+        }
+    }
+
+    #[test]
+    fn test_order_hash_matches_hardcoded_value() {
+        let order_hash = get_order_hash(get_maker(), get_token(), &get_order_args());
+
+        assert_eq!(order_hash, EXPECTED_ORDER_HASH);
+    }
+
+    fn get_order_address(order_hash: &[u8; 32]) -> Pubkey {
+        Pubkey::find_program_address(&[b"order", order_hash], &cross_chain_escrow_src::id()).0
+    }
+
+    #[test]
+    fn test_salted_order_hash_matches_order_hash() {
+        let args = OrderArgs {
+            salt: 0,
+            ..get_order_args()
+        };
+        assert_eq!(
+            derive_salted_order_hash(get_maker(), get_token(), &args, SALT),
+            EXPECTED_ORDER_HASH
+        );
+    }
+
+    #[test]
+    fn test_incremented_salt_yields_distinct_order() {
+        let args = get_order_args();
+        let order_hash = derive_salted_order_hash(get_maker(), get_token(), &args, SALT);
+        let next_order_hash = derive_salted_order_hash(get_maker(), get_token(), &args, SALT + 1);
+
+        assert_ne!(order_hash, next_order_hash);
+        assert_ne!(
+            get_order_address(&order_hash),
+            get_order_address(&next_order_hash)
+        );
+    }
+}
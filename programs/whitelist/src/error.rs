@@ -4,4 +4,10 @@ use anchor_lang::error_code;
 pub enum WhitelistError {
     #[msg("Unauthorized")]
     Unauthorized,
+    #[msg("Too many token programs")]
+    TooManyTokenPrograms,
+    #[msg("Invalid resolver access")]
+    InvalidResolverAccess,
+    #[msg("Resolver rate limited")]
+    ResolverRateLimited,
 }
@@ -1,4 +1,6 @@
 use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+use anchor_spl::{token::ID as SPL_TOKEN_PROGRAM_ID, token_2022::ID as TOKEN_2022_PROGRAM_ID};
 use common::constants::DISCRIMINATOR_BYTES;
 
 pub mod error;
@@ -8,6 +10,22 @@ declare_id!("CShaLBTQn6xbwq9behWTZuDuYY7APeWvXchsHkTw3DcZ");
 
 pub const WHITELIST_STATE_SEED: &[u8] = b"whitelist_state";
 pub const RESOLVER_ACCESS_SEED: &[u8] = b"resolver_access";
+pub const CONFIG_SEED: &[u8] = b"config";
+pub const FILL_AUTHORITY_SEED: &[u8] = b"fill_authority";
+pub const BLOCKED_MINT_SEED: &[u8] = b"blocked_mint";
+
+/// Program allowed to record resolver fills, through its PDA derived from `FILL_AUTHORITY_SEED`
+pub const ESCROW_SRC_PROGRAM_ID: Pubkey = pubkey!("2g4JDRMD7G3dK1PHmCnDAycKzd6e5sdhxqGBbs264zwz");
+
+/// Duration of the window over which the fill volume of a resolver is limited, in seconds
+pub const FILL_WINDOW_DURATION: i64 = 60 * 60;
+
+/// Maximum number of token programs that can be stored in the config
+pub const MAX_SUPPORTED_TOKEN_PROGRAMS: usize = 8;
+
+/// Current layout version of the resolver access account, accounts created before
+/// versioning was introduced are on version 0
+pub const RESOLVER_ACCESS_VERSION: u8 = 3;
 
 /// Program for managing whitelisted users for the Fusion Swap
 #[program]
@@ -21,9 +39,22 @@ pub mod whitelist {
         Ok(())
     }
 
-    /// Registers a new user to the whitelist
-    pub fn register(ctx: Context<Register>, _user: Pubkey) -> Result<()> {
-        ctx.accounts.resolver_access.bump = ctx.bumps.resolver_access;
+    /// Registers a new user to the whitelist, optionally with metadata for monitoring
+    pub fn register(
+        ctx: Context<Register>,
+        user: Pubkey,
+        metadata: Option<[u8; 32]>,
+    ) -> Result<()> {
+        let metadata = metadata.unwrap_or_default();
+        ctx.accounts.resolver_access.set_inner(ResolverAccess {
+            bump: ctx.bumps.resolver_access,
+            version: RESOLVER_ACCESS_VERSION,
+            window_start: 0,
+            window_volume: 0,
+            max_window_volume: 0,
+            metadata,
+        });
+        emit!(ResolverAdded { user, metadata });
         Ok(())
     }
 
@@ -35,9 +66,179 @@ pub mod whitelist {
     /// Sets the new whitelist authority
     pub fn set_authority(ctx: Context<SetAuthority>, new_authority: Pubkey) -> Result<()> {
         let whitelist_state = &mut ctx.accounts.whitelist_state;
+        emit!(AuthorityRotated {
+            previous_authority: whitelist_state.authority,
+            new_authority,
+        });
         whitelist_state.authority = new_authority;
         Ok(())
     }
+
+    /// Reallocates a resolver access account created with an older layout to the current one,
+    /// preserving its bump and zero-initializing the fields added since
+    pub fn migrate_resolver_access(
+        ctx: Context<MigrateResolverAccess>,
+        _user: Pubkey,
+    ) -> Result<()> {
+        let resolver_access = &ctx.accounts.resolver_access;
+        let new_len = DISCRIMINATOR_BYTES + ResolverAccess::INIT_SPACE;
+
+        // The old layout can't be deserialized as the current one, so the bump and version are
+        // read directly
+        let (bump, version) = {
+            let data = resolver_access.try_borrow_data()?;
+            require!(
+                data.len() > DISCRIMINATOR_BYTES && data.starts_with(ResolverAccess::DISCRIMINATOR),
+                WhitelistError::InvalidResolverAccess
+            );
+            (
+                data[DISCRIMINATOR_BYTES],
+                data.get(DISCRIMINATOR_BYTES + 1).copied().unwrap_or(0),
+            )
+        };
+
+        // Accounts on the current layout are left as is to keep their fill limits
+        if resolver_access.data_len() >= new_len {
+            return Ok(());
+        }
+
+        let rent_shortfall = Rent::get()?
+            .minimum_balance(new_len)
+            .saturating_sub(resolver_access.lamports());
+        if rent_shortfall > 0 {
+            transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.authority.to_account_info(),
+                        to: resolver_access.to_account_info(),
+                    },
+                ),
+                rent_shortfall,
+            )?;
+        }
+
+        resolver_access.resize(new_len)?;
+        let mut migrated = if version == 2 {
+            // Version 2 only lacks the trailing metadata, which the resize zero-extended, so its
+            // fill limits are kept
+            ResolverAccess::try_deserialize(&mut &resolver_access.try_borrow_data()?[..])?
+        } else {
+            ResolverAccess {
+                bump,
+                version,
+                window_start: 0,
+                window_volume: 0,
+                max_window_volume: 0,
+                metadata: [0; 32],
+            }
+        };
+        migrated.version = RESOLVER_ACCESS_VERSION;
+        migrated.try_serialize(&mut &mut resolver_access.try_borrow_mut_data()?[..])?;
+
+        Ok(())
+    }
+
+    /// Sets the maximum volume the user can lock into escrows within a fill window,
+    /// zero disables the limit
+    pub fn set_max_window_volume(
+        ctx: Context<SetMaxWindowVolume>,
+        _user: Pubkey,
+        max_window_volume: u64,
+    ) -> Result<()> {
+        ctx.accounts.resolver_access.max_window_volume = max_window_volume;
+        Ok(())
+    }
+
+    /// Adds the amount of a fill to the volume of the user in the current fill window,
+    /// failing if it exceeds the maximum window volume
+    pub fn record_fill(ctx: Context<RecordFill>, _user: Pubkey, amount: u64) -> Result<()> {
+        let resolver_access = &mut ctx.accounts.resolver_access;
+        if resolver_access.max_window_volume == 0 {
+            return Ok(());
+        }
+
+        let now = Clock::get()?.unix_timestamp;
+        let window_end = resolver_access
+            .window_start
+            .saturating_add(FILL_WINDOW_DURATION);
+        if now >= window_end {
+            resolver_access.window_start = now;
+            resolver_access.window_volume = 0;
+        }
+
+        let window_volume = resolver_access
+            .window_volume
+            .checked_add(amount)
+            .ok_or(WhitelistError::ResolverRateLimited)?;
+        require!(
+            window_volume <= resolver_access.max_window_volume,
+            WhitelistError::ResolverRateLimited
+        );
+        resolver_access.window_volume = window_volume;
+
+        Ok(())
+    }
+
+    /// Returns whether the user is whitelisted, without failing if they are not
+    pub fn is_whitelisted(ctx: Context<IsWhitelisted>, _user: Pubkey) -> Result<bool> {
+        let resolver_access = &ctx.accounts.resolver_access;
+        Ok(resolver_access.owner == &crate::ID
+            && ResolverAccess::try_deserialize(&mut &resolver_access.data.borrow()[..]).is_ok())
+    }
+
+    /// Initializes the config with SPL Token and Token-2022 as supported token programs
+    pub fn initialize_config(ctx: Context<InitializeConfig>) -> Result<()> {
+        ctx.accounts.config.set_inner(Config {
+            supported_token_programs: vec![SPL_TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID],
+            bump: ctx.bumps.config,
+            require_top_level: false,
+            track_tvl: false,
+        });
+        Ok(())
+    }
+
+    /// Replaces the list of token programs honored by the escrow programs
+    pub fn set_supported_token_programs(
+        ctx: Context<SetSupportedTokenPrograms>,
+        token_programs: Vec<Pubkey>,
+    ) -> Result<()> {
+        require!(
+            token_programs.len() <= MAX_SUPPORTED_TOKEN_PROGRAMS,
+            WhitelistError::TooManyTokenPrograms
+        );
+        ctx.accounts.config.supported_token_programs = token_programs;
+        Ok(())
+    }
+
+    /// Blocks new orders and escrows for the mint on the src escrow program, e.g. during a
+    /// token incident, without pausing the other mints
+    pub fn block_mint(ctx: Context<BlockMint>, _mint: Pubkey) -> Result<()> {
+        ctx.accounts.blocked_mint.bump = ctx.bumps.blocked_mint;
+        Ok(())
+    }
+
+    /// Lifts the block of the mint, returning the rent to the authority
+    pub fn unblock_mint(_ctx: Context<UnblockMint>, _mint: Pubkey) -> Result<()> {
+        Ok(())
+    }
+
+    /// Toggles whether the src escrow program only accepts escrow creation as a top-level
+    /// instruction, rejecting it when invoked through another program
+    pub fn set_require_top_level(
+        ctx: Context<SetRequireTopLevel>,
+        require_top_level: bool,
+    ) -> Result<()> {
+        ctx.accounts.config.require_top_level = require_top_level;
+        Ok(())
+    }
+
+    /// Toggles whether the escrow programs count the value locked in new escrows per mint. While
+    /// enabled, escrow creation must pass the tvl stats account of the mint
+    pub fn set_track_tvl(ctx: Context<SetTrackTvl>, track_tvl: bool) -> Result<()> {
+        ctx.accounts.config.track_tvl = track_tvl;
+        Ok(())
+    }
 }
 
 #[derive(Accounts)]
@@ -108,6 +309,83 @@ pub struct Deregister<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+#[instruction(user: Pubkey)]
+pub struct MigrateResolverAccess<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+      seeds = [WHITELIST_STATE_SEED],
+      bump,
+      // Ensures only the whitelist authority can migrate resolver access accounts
+      constraint = whitelist_state.authority == authority.key() @ WhitelistError::Unauthorized
+    )]
+    pub whitelist_state: Account<'info, WhitelistState>,
+
+    /// CHECK: The account may be on an older layout, so it is validated in the instruction
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [RESOLVER_ACCESS_SEED, user.key().as_ref()],
+        bump,
+    )]
+    pub resolver_access: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(user: Pubkey)]
+pub struct SetMaxWindowVolume<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+      seeds = [WHITELIST_STATE_SEED],
+      bump,
+      // Ensures only the whitelist authority can change the fill limits of users
+      constraint = whitelist_state.authority == authority.key() @ WhitelistError::Unauthorized
+    )]
+    pub whitelist_state: Account<'info, WhitelistState>,
+
+    #[account(
+        mut,
+        seeds = [RESOLVER_ACCESS_SEED, user.key().as_ref()],
+        bump = resolver_access.bump,
+    )]
+    pub resolver_access: Account<'info, ResolverAccess>,
+}
+
+#[derive(Accounts)]
+#[instruction(user: Pubkey)]
+pub struct RecordFill<'info> {
+    /// Ensures fills can only be recorded by the escrow program creating them
+    #[account(
+        seeds = [FILL_AUTHORITY_SEED],
+        bump,
+        seeds::program = ESCROW_SRC_PROGRAM_ID,
+    )]
+    pub fill_authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [RESOLVER_ACCESS_SEED, user.key().as_ref()],
+        bump = resolver_access.bump,
+    )]
+    pub resolver_access: Account<'info, ResolverAccess>,
+}
+
+#[derive(Accounts)]
+#[instruction(user: Pubkey)]
+pub struct IsWhitelisted<'info> {
+    /// CHECK: The account does not exist if the user is not whitelisted
+    #[account(
+        seeds = [RESOLVER_ACCESS_SEED, user.key().as_ref()],
+        bump,
+    )]
+    pub resolver_access: UncheckedAccount<'info>,
+}
+
 #[derive(Accounts)]
 pub struct SetAuthority<'info> {
     #[account(mut)]
@@ -122,6 +400,142 @@ pub struct SetAuthority<'info> {
     pub whitelist_state: Account<'info, WhitelistState>,
 }
 
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+      seeds = [WHITELIST_STATE_SEED],
+      bump,
+      // Ensures only the whitelist authority can initialize the config
+      constraint = whitelist_state.authority == authority.key() @ WhitelistError::Unauthorized
+    )]
+    pub whitelist_state: Account<'info, WhitelistState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = DISCRIMINATOR_BYTES + Config::INIT_SPACE,
+        seeds = [CONFIG_SEED],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetSupportedTokenPrograms<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+      seeds = [WHITELIST_STATE_SEED],
+      bump,
+      // Ensures only the whitelist authority can change the supported token programs
+      constraint = whitelist_state.authority == authority.key() @ WhitelistError::Unauthorized
+    )]
+    pub whitelist_state: Account<'info, WhitelistState>,
+
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+#[instruction(mint: Pubkey)]
+pub struct BlockMint<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+      seeds = [WHITELIST_STATE_SEED],
+      bump,
+      // Ensures only the whitelist authority can block mints
+      constraint = whitelist_state.authority == authority.key() @ WhitelistError::Unauthorized
+    )]
+    pub whitelist_state: Account<'info, WhitelistState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = DISCRIMINATOR_BYTES + BlockedMint::INIT_SPACE,
+        seeds = [BLOCKED_MINT_SEED, mint.key().as_ref()],
+        bump,
+    )]
+    pub blocked_mint: Account<'info, BlockedMint>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(mint: Pubkey)]
+pub struct UnblockMint<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+      seeds = [WHITELIST_STATE_SEED],
+      bump,
+      // Ensures only the whitelist authority can unblock mints
+      constraint = whitelist_state.authority == authority.key() @ WhitelistError::Unauthorized
+    )]
+    pub whitelist_state: Account<'info, WhitelistState>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [BLOCKED_MINT_SEED, mint.key().as_ref()],
+        bump = blocked_mint.bump,
+    )]
+    pub blocked_mint: Account<'info, BlockedMint>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetRequireTopLevel<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+      seeds = [WHITELIST_STATE_SEED],
+      bump,
+      // Ensures only the whitelist authority can toggle the CPI guard
+      constraint = whitelist_state.authority == authority.key() @ WhitelistError::Unauthorized
+    )]
+    pub whitelist_state: Account<'info, WhitelistState>,
+
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+pub struct SetTrackTvl<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+      seeds = [WHITELIST_STATE_SEED],
+      bump,
+      // Ensures only the whitelist authority can toggle the tvl tracking
+      constraint = whitelist_state.authority == authority.key() @ WhitelistError::Unauthorized
+    )]
+    pub whitelist_state: Account<'info, WhitelistState>,
+
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+}
+
 #[account]
 #[derive(InitSpace)]
 pub struct WhitelistState {
@@ -132,4 +546,49 @@ pub struct WhitelistState {
 #[derive(InitSpace)]
 pub struct ResolverAccess {
     pub bump: u8,
+    /// Layout version, see `RESOLVER_ACCESS_VERSION`
+    pub version: u8,
+    /// Start of the current fill window
+    pub window_start: i64,
+    /// Volume filled by the user within the current fill window
+    pub window_volume: u64,
+    /// Maximum volume the user can fill within a window, zero if unlimited
+    pub max_window_volume: u64,
+    /// Opaque metadata attached on registration, e.g. a hash of the resolver's name and
+    /// contact, all zeros if none
+    pub metadata: [u8; 32],
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Config {
+    #[max_len(MAX_SUPPORTED_TOKEN_PROGRAMS)]
+    pub supported_token_programs: Vec<Pubkey>,
+    pub bump: u8,
+    /// Rejects escrow creation invoked through CPI. The flags are kept last so that configs
+    /// created before a flag existed read it from their unused space as disabled
+    pub require_top_level: bool,
+    /// Counts the value locked in escrows per mint, see `set_track_tvl`
+    pub track_tvl: bool,
+}
+
+/// Marks a mint as blocked, see `block_mint`
+#[account]
+#[derive(InitSpace)]
+pub struct BlockedMint {
+    pub bump: u8,
+}
+
+/// Emitted when a user is registered to the whitelist
+#[event]
+pub struct ResolverAdded {
+    pub user: Pubkey,
+    pub metadata: [u8; 32],
+}
+
+/// Emitted when the whitelist authority is handed over, e.g. after a key compromise
+#[event]
+pub struct AuthorityRotated {
+    pub previous_authority: Pubkey,
+    pub new_authority: Pubkey,
 }
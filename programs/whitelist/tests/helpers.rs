@@ -1,15 +1,17 @@
 use anchor_lang::prelude::AccountMeta;
-use anchor_lang::InstructionData;
+use anchor_lang::{Discriminator, InstructionData};
+use common::constants::DISCRIMINATOR_BYTES;
 use common_tests::{
     helpers::*,
     whitelist::{
-        get_program_whitelist_spec, get_whitelist_access_address, get_whitelist_state_address,
+        get_config_address, get_program_whitelist_spec, get_whitelist_access_address,
+        get_whitelist_state_address,
     },
 };
 use solana_program::{instruction::Instruction, pubkey::Pubkey};
 
 use solana_sdk::{
-    signature::Keypair, signer::Signer, system_program::ID as system_program_id,
+    account::Account, signature::Keypair, signer::Signer, system_program::ID as system_program_id,
     transaction::Transaction,
 };
 
@@ -138,7 +140,8 @@ pub fn register_deregister_data(
 
 pub async fn register(test_state: &TestState) -> Pubkey {
     let instruction_data = InstructionData::data(&whitelist::instruction::Register {
-        _user: test_state.whitelisted_kp.pubkey(),
+        user: test_state.whitelisted_kp.pubkey(),
+        metadata: None,
     });
 
     let (whitelist_access, tx) = register_deregister_data(test_state, instruction_data);
@@ -164,6 +167,90 @@ pub async fn deregister(test_state: &TestState) -> Pubkey {
     whitelist_access
 }
 
+pub async fn is_whitelisted(test_state: &mut TestState, user: &Pubkey) -> bool {
+    let (_, program_id) = get_whitelist_state_address();
+    let (whitelist_access, _) = get_whitelist_access_address(user);
+    let instruction_data =
+        InstructionData::data(&whitelist::instruction::IsWhitelisted { _user: *user });
+
+    let instruction: Instruction = Instruction {
+        program_id,
+        accounts: vec![AccountMeta::new_readonly(whitelist_access, false)],
+        data: instruction_data,
+    };
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.someone_kp.pubkey()),
+        &[&test_state.someone_kp],
+        test_state.context.last_blockhash,
+    );
+
+    let sim_details = test_state
+        .client
+        .simulate_transaction(transaction)
+        .await
+        .expect("Simulation RPC failed")
+        .simulation_details
+        .expect("Simulation details not found");
+    // Trailing zero bytes of the return data are trimmed, so `false` may come back empty
+    let return_data = sim_details
+        .return_data
+        .map(|return_data| return_data.data)
+        .unwrap_or_default();
+    return_data.first() == Some(&1)
+}
+
+pub fn migrate_resolver_access_data(
+    test_state: &TestState,
+    user: &Pubkey,
+    authority: &Keypair,
+) -> Transaction {
+    let (whitelist_state, program_id) = get_whitelist_state_address();
+    let (whitelist_access, _) = get_whitelist_access_address(user);
+    let instruction_data =
+        InstructionData::data(&whitelist::instruction::MigrateResolverAccess { _user: *user });
+
+    let instruction: Instruction = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(authority.pubkey(), true),
+            AccountMeta::new_readonly(whitelist_state, false),
+            AccountMeta::new(whitelist_access, false),
+            AccountMeta::new_readonly(system_program_id, false),
+        ],
+        data: instruction_data,
+    };
+    Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&authority.pubkey()),
+        &[authority],
+        test_state.context.last_blockhash,
+    )
+}
+
+// Creates a resolver access account for the user with the layout preceding versioning,
+// which only stored the bump
+pub async fn set_unversioned_resolver_access(test_state: &mut TestState, user: &Pubkey) -> Pubkey {
+    let (_, program_id) = get_whitelist_state_address();
+    let (whitelist_access, bump) = get_whitelist_access_address(user);
+
+    let mut data = whitelist::ResolverAccess::DISCRIMINATOR.to_vec();
+    data.push(bump);
+    let lamports = get_min_rent_for_size(&mut test_state.client, DISCRIMINATOR_BYTES + 1).await;
+    test_state.context.set_account(
+        &whitelist_access,
+        &Account {
+            lamports,
+            data,
+            owner: program_id,
+            executable: false,
+            rent_epoch: 0,
+        }
+        .into(),
+    );
+    whitelist_access
+}
+
 pub fn set_authority_data(test_state: &TestState) -> (Pubkey, Transaction) {
     let (whitelist_state, program_id) = get_whitelist_state_address();
     let instruction_data = InstructionData::data(&whitelist::instruction::SetAuthority {
@@ -196,3 +283,66 @@ pub async fn set_authority(test_state: &TestState) -> Pubkey {
         .expect_success();
     whitelist_state
 }
+
+pub fn init_config_data(test_state: &TestState) -> (Pubkey, Transaction) {
+    let (whitelist_state, program_id) = get_whitelist_state_address();
+    let config = get_config_address();
+    let instruction_data = InstructionData::data(&whitelist::instruction::InitializeConfig {});
+
+    let instruction: Instruction = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(test_state.authority_kp.pubkey(), true),
+            AccountMeta::new_readonly(whitelist_state, false),
+            AccountMeta::new(config, false),
+            AccountMeta::new_readonly(system_program_id, false),
+        ],
+        data: instruction_data,
+    };
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.authority_kp.pubkey()),
+        &[&test_state.authority_kp],
+        test_state.context.last_blockhash,
+    );
+    (config, transaction)
+}
+
+pub async fn init_config(test_state: &TestState) -> Pubkey {
+    let (config, tx) = init_config_data(test_state);
+    test_state
+        .client
+        .process_transaction(tx)
+        .await
+        .expect_success();
+    config
+}
+
+pub fn set_supported_token_programs_data(
+    test_state: &TestState,
+    token_programs: Vec<Pubkey>,
+) -> (Pubkey, Transaction) {
+    let (whitelist_state, program_id) = get_whitelist_state_address();
+    let config = get_config_address();
+    let instruction_data =
+        InstructionData::data(&whitelist::instruction::SetSupportedTokenPrograms {
+            token_programs,
+        });
+
+    let instruction: Instruction = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(test_state.authority_kp.pubkey(), true),
+            AccountMeta::new_readonly(whitelist_state, false),
+            AccountMeta::new(config, false),
+        ],
+        data: instruction_data,
+    };
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&test_state.authority_kp.pubkey()),
+        &[&test_state.authority_kp],
+        test_state.context.last_blockhash,
+    );
+    (config, transaction)
+}
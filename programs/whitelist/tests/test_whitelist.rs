@@ -1,22 +1,29 @@
 use crate::helpers::{
-    deregister, init_whitelist, register, register_deregister_data, set_authority,
-    set_authority_data, TestState,
+    deregister, init_config, init_config_data, init_whitelist, is_whitelisted,
+    migrate_resolver_access_data, register, register_deregister_data, set_authority,
+    set_authority_data, set_supported_token_programs_data, set_unversioned_resolver_access,
+    TestState,
+};
+use anchor_lang::{
+    prelude::ProgramError, AccountDeserialize, AccountSerialize, InstructionData, Space,
 };
-use anchor_lang::{prelude::ProgramError, AccountDeserialize, InstructionData, Space};
 use common::constants::DISCRIMINATOR_BYTES;
 use common_tests::helpers::*;
+use common_tests::tests as common_escrow_tests;
 use common_tests::whitelist::get_whitelist_access_address;
+use solana_program::pubkey::Pubkey;
 use solana_program_test::tokio;
-use solana_sdk::signer::Signer;
+use solana_sdk::{signature::Keypair, signer::Signer};
 
 use test_context::test_context;
 pub mod helpers;
 use whitelist::{self, error::WhitelistError};
 
 mod test_whitelist {
-
     use super::*;
 
+    const MAX_WINDOW_VOLUME: u64 = 1_000_000;
+
     #[test_context(TestState)]
     #[tokio::test]
     async fn test_init_whitelist(test_state: &mut TestState) {
@@ -95,6 +102,48 @@ mod test_whitelist {
             )
             .unwrap();
         assert_eq!(resolver_access.bump, canonical_bump);
+        assert_eq!(resolver_access.version, whitelist::RESOLVER_ACCESS_VERSION);
+        assert_eq!(resolver_access.metadata, [0; 32]);
+    }
+
+    #[test_context(TestState)]
+    #[tokio::test]
+    async fn test_register_with_metadata(test_state: &mut TestState) {
+        init_whitelist(test_state).await;
+        let user = test_state.whitelisted_kp.pubkey();
+        let metadata = [7; 32];
+
+        let instruction_data = InstructionData::data(&whitelist::instruction::Register {
+            user,
+            metadata: Some(metadata),
+        });
+        let (whitelist_access_address, tx) = register_deregister_data(test_state, instruction_data);
+        let events = common_escrow_tests::simulate_client_tx_events::<whitelist::ResolverAdded>(
+            &mut test_state.client,
+            tx.clone(),
+        )
+        .await;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].user, user);
+        assert_eq!(events[0].metadata, metadata);
+        test_state
+            .client
+            .process_transaction(tx)
+            .await
+            .expect_success();
+
+        let whitelist_access_account = test_state
+            .client
+            .get_account(whitelist_access_address)
+            .await
+            .unwrap()
+            .unwrap();
+        let resolver_access: whitelist::ResolverAccess =
+            whitelist::ResolverAccess::try_deserialize(
+                &mut whitelist_access_account.data.as_slice(),
+            )
+            .unwrap();
+        assert_eq!(resolver_access.metadata, metadata);
     }
 
     #[test_context(TestState)]
@@ -106,7 +155,8 @@ mod test_whitelist {
         // Update the last blockhash to execute the next identical transaction
         test_state.context.last_blockhash = test_state.client.get_latest_blockhash().await.unwrap();
         let instruction_data = InstructionData::data(&whitelist::instruction::Register {
-            _user: test_state.whitelisted_kp.pubkey(),
+            user: test_state.whitelisted_kp.pubkey(),
+            metadata: None,
         });
         let (_, tx) = register_deregister_data(test_state, instruction_data);
         test_state
@@ -137,6 +187,39 @@ mod test_whitelist {
         assert_eq!(whitelist_state.authority, test_state.someone_kp.pubkey());
     }
 
+    #[test_context(TestState)]
+    #[tokio::test]
+    async fn test_previous_authority_cannot_rotate_again(test_state: &mut TestState) {
+        init_whitelist(test_state).await;
+
+        let (_, tx) = set_authority_data(test_state);
+        let events = common_escrow_tests::simulate_client_tx_events::<whitelist::AuthorityRotated>(
+            &mut test_state.client,
+            tx.clone(),
+        )
+        .await;
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            events[0].previous_authority,
+            test_state.authority_kp.pubkey()
+        );
+        assert_eq!(events[0].new_authority, test_state.someone_kp.pubkey());
+        test_state
+            .client
+            .process_transaction(tx)
+            .await
+            .expect_success();
+
+        // Rotating again to another key must be signed by the new authority
+        test_state.someone_kp = Keypair::new();
+        let (_, tx) = set_authority_data(test_state);
+        test_state
+            .client
+            .process_transaction(tx)
+            .await
+            .expect_error(ProgramError::Custom(WhitelistError::Unauthorized.into()));
+    }
+
     #[test_context(TestState)]
     #[tokio::test]
     async fn test_new_authority_register_deregister(test_state: &mut TestState) {
@@ -173,7 +256,8 @@ mod test_whitelist {
         init_whitelist(test_state).await;
 
         let instruction_data = InstructionData::data(&whitelist::instruction::Register {
-            _user: test_state.whitelisted_kp.pubkey(),
+            user: test_state.whitelisted_kp.pubkey(),
+            metadata: None,
         });
 
         test_state.authority_kp = test_state.someone_kp.insecure_clone();
@@ -213,7 +297,8 @@ mod test_whitelist {
         set_authority(test_state).await;
 
         let instruction_data = InstructionData::data(&whitelist::instruction::Register {
-            _user: test_state.whitelisted_kp.pubkey(),
+            user: test_state.whitelisted_kp.pubkey(),
+            metadata: None,
         });
         let (_, tx) = register_deregister_data(test_state, instruction_data);
 
@@ -255,4 +340,228 @@ mod test_whitelist {
             .await
             .expect_error(ProgramError::Custom(WhitelistError::Unauthorized.into()));
     }
+
+    #[test_context(TestState)]
+    #[tokio::test]
+    async fn test_init_config(test_state: &mut TestState) {
+        init_whitelist(test_state).await;
+        let config = init_config(test_state).await;
+
+        let config_account = test_state
+            .client
+            .get_account(config)
+            .await
+            .unwrap()
+            .unwrap();
+        let config: whitelist::Config =
+            whitelist::Config::try_deserialize(&mut config_account.data.as_slice()).unwrap();
+        assert_eq!(
+            config.supported_token_programs,
+            vec![anchor_spl::token::ID, anchor_spl::token_2022::ID]
+        );
+    }
+
+    #[test_context(TestState)]
+    #[tokio::test]
+    async fn test_init_config_wrong_authority(test_state: &mut TestState) {
+        init_whitelist(test_state).await;
+
+        test_state.authority_kp = test_state.someone_kp.insecure_clone();
+        let (_, tx) = init_config_data(test_state);
+
+        test_state
+            .client
+            .process_transaction(tx)
+            .await
+            .expect_error(ProgramError::Custom(WhitelistError::Unauthorized.into()));
+    }
+
+    #[test_context(TestState)]
+    #[tokio::test]
+    async fn test_set_supported_token_programs(test_state: &mut TestState) {
+        init_whitelist(test_state).await;
+        init_config(test_state).await;
+
+        let (config, tx) =
+            set_supported_token_programs_data(test_state, vec![anchor_spl::token::ID]);
+        test_state
+            .client
+            .process_transaction(tx)
+            .await
+            .expect_success();
+
+        let config_account = test_state
+            .client
+            .get_account(config)
+            .await
+            .unwrap()
+            .unwrap();
+        let config: whitelist::Config =
+            whitelist::Config::try_deserialize(&mut config_account.data.as_slice()).unwrap();
+        assert_eq!(config.supported_token_programs, vec![anchor_spl::token::ID]);
+    }
+
+    #[test_context(TestState)]
+    #[tokio::test]
+    async fn test_set_supported_token_programs_wrong_authority(test_state: &mut TestState) {
+        init_whitelist(test_state).await;
+        init_config(test_state).await;
+
+        test_state.authority_kp = test_state.someone_kp.insecure_clone();
+        let (_, tx) = set_supported_token_programs_data(test_state, vec![]);
+
+        test_state
+            .client
+            .process_transaction(tx)
+            .await
+            .expect_error(ProgramError::Custom(WhitelistError::Unauthorized.into()));
+    }
+
+    #[test_context(TestState)]
+    #[tokio::test]
+    async fn test_set_supported_token_programs_fails_with_too_many_programs(
+        test_state: &mut TestState,
+    ) {
+        init_whitelist(test_state).await;
+        init_config(test_state).await;
+
+        let token_programs = (0..=whitelist::MAX_SUPPORTED_TOKEN_PROGRAMS)
+            .map(|_| Pubkey::new_unique())
+            .collect();
+        let (_, tx) = set_supported_token_programs_data(test_state, token_programs);
+
+        test_state
+            .client
+            .process_transaction(tx)
+            .await
+            .expect_error(ProgramError::Custom(
+                WhitelistError::TooManyTokenPrograms.into(),
+            ));
+    }
+
+    #[test_context(TestState)]
+    #[tokio::test]
+    async fn test_is_whitelisted(test_state: &mut TestState) {
+        init_whitelist(test_state).await;
+        register(test_state).await;
+
+        let whitelisted = test_state.whitelisted_kp.pubkey();
+        let someone = test_state.someone_kp.pubkey();
+        assert!(is_whitelisted(test_state, &whitelisted).await);
+        assert!(!is_whitelisted(test_state, &someone).await);
+
+        deregister(test_state).await;
+        assert!(!is_whitelisted(test_state, &whitelisted).await);
+    }
+
+    #[test_context(TestState)]
+    #[tokio::test]
+    async fn test_migrate_resolver_access(test_state: &mut TestState) {
+        init_whitelist(test_state).await;
+        let user = test_state.whitelisted_kp.pubkey();
+        let (_, canonical_bump) = get_whitelist_access_address(&user);
+        let whitelist_access_address = set_unversioned_resolver_access(test_state, &user).await;
+
+        // The old layout is not recognized until the account is migrated
+        assert!(!is_whitelisted(test_state, &user).await);
+
+        let tx = migrate_resolver_access_data(test_state, &user, &test_state.authority_kp);
+        test_state
+            .client
+            .process_transaction(tx)
+            .await
+            .expect_success();
+
+        let whitelist_data_len = DISCRIMINATOR_BYTES + whitelist::ResolverAccess::INIT_SPACE;
+        let rent_lamports = get_min_rent_for_size(&mut test_state.client, whitelist_data_len).await;
+        let whitelist_access_account = test_state
+            .client
+            .get_account(whitelist_access_address)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(whitelist_access_account.data.len(), whitelist_data_len);
+        assert_eq!(whitelist_access_account.lamports, rent_lamports);
+
+        let resolver_access: whitelist::ResolverAccess =
+            whitelist::ResolverAccess::try_deserialize(
+                &mut whitelist_access_account.data.as_slice(),
+            )
+            .unwrap();
+        assert_eq!(resolver_access.bump, canonical_bump);
+        assert_eq!(resolver_access.version, whitelist::RESOLVER_ACCESS_VERSION);
+        // A migrated resolver starts with a fresh fill window and no fill limit
+        assert_eq!(resolver_access.window_start, 0);
+        assert_eq!(resolver_access.window_volume, 0);
+        assert_eq!(resolver_access.max_window_volume, 0);
+        assert_eq!(resolver_access.metadata, [0; 32]);
+        assert!(is_whitelisted(test_state, &user).await);
+    }
+
+    #[test_context(TestState)]
+    #[tokio::test]
+    async fn test_migrate_resolver_access_keeps_fill_limit(test_state: &mut TestState) {
+        init_whitelist(test_state).await;
+        let user = test_state.whitelisted_kp.pubkey();
+        let whitelist_access_address = register(test_state).await;
+
+        // Rewrite the account in the version 2 layout, which has no trailing metadata
+        let mut whitelist_access_account = test_state
+            .client
+            .get_account(whitelist_access_address)
+            .await
+            .unwrap()
+            .unwrap();
+        let mut resolver_access: whitelist::ResolverAccess =
+            whitelist::ResolverAccess::try_deserialize(
+                &mut whitelist_access_account.data.as_slice(),
+            )
+            .unwrap();
+        resolver_access.version = 2;
+        resolver_access.max_window_volume = MAX_WINDOW_VOLUME;
+        let mut data = Vec::new();
+        resolver_access.try_serialize(&mut data).unwrap();
+        data.truncate(data.len() - 32);
+        whitelist_access_account.data = data;
+        test_state
+            .context
+            .set_account(&whitelist_access_address, &whitelist_access_account.into());
+
+        let tx = migrate_resolver_access_data(test_state, &user, &test_state.authority_kp);
+        test_state
+            .client
+            .process_transaction(tx)
+            .await
+            .expect_success();
+
+        let whitelist_access_account = test_state
+            .client
+            .get_account(whitelist_access_address)
+            .await
+            .unwrap()
+            .unwrap();
+        let resolver_access: whitelist::ResolverAccess =
+            whitelist::ResolverAccess::try_deserialize(
+                &mut whitelist_access_account.data.as_slice(),
+            )
+            .unwrap();
+        assert_eq!(resolver_access.version, whitelist::RESOLVER_ACCESS_VERSION);
+        assert_eq!(resolver_access.max_window_volume, MAX_WINDOW_VOLUME);
+        assert_eq!(resolver_access.metadata, [0; 32]);
+    }
+
+    #[test_context(TestState)]
+    #[tokio::test]
+    async fn test_migrate_resolver_access_wrong_authority(test_state: &mut TestState) {
+        init_whitelist(test_state).await;
+        let user = test_state.whitelisted_kp.pubkey();
+        set_unversioned_resolver_access(test_state, &user).await;
+
+        let tx = migrate_resolver_access_data(test_state, &user, &test_state.someone_kp);
+        test_state
+            .client
+            .process_transaction(tx)
+            .await
+            .expect_error(ProgramError::Custom(WhitelistError::Unauthorized.into()));
+    }
 }